@@ -0,0 +1,373 @@
+//! boxes.rs — Box tree generation: turns the styled tree into the boxes layout works on.
+//! `display: none` subtrees generate nothing, `display: contents` elements are replaced by
+//! their children, blocks inside inlines split the inline, and inline content sharing a block
+//! container with blocks is wrapped in anonymous block boxes (CSS 2.1 §9.2.1.1). Missing table
+//! boxes around rows and cells are generated as anonymous table objects (CSS 2.1 §17.2.1).
+//! Boxes in vertical content get their styles in the coordinates they are laid out in.
+
+use crate::browser::dom::NodeType as DomNodeType;
+use crate::browser::engine::{Display, ElementData, Float, Node, NodeType, Style, WritingMode};
+use crate::browser::style::{inherited_style, StyledNode};
+use crate::browser::writing::reorient_tree;
+use std::sync::Arc;
+
+/// Build the box tree for a styled subtree; None if it generates no boxes
+pub fn build_box_tree(styled: &StyledNode) -> Option<Node> {
+    let mut boxes = generate_boxes(styled);
+    let mut root = match boxes.len() {
+        0 => return None,
+        1 if boxes[0].is_block_level() => boxes.pop()?,
+        // A `contents` or inline root still needs a block container to lay out in
+        _ => block_container(
+            NodeType::Anonymous,
+            anonymous_style(&styled.style, Display::Block),
+            boxes,
+        ),
+    };
+    // Boxes in vertical content are styled in the coordinates they are laid out in
+    reorient_tree(&mut root, WritingMode::HorizontalTb);
+    Some(root)
+}
+
+/// The boxes a styled node contributes to its parent's children
+fn generate_boxes(styled: &StyledNode) -> Vec<Node> {
+    let el = match &styled.node_type {
+        DomNodeType::Element(el) => el,
+        DomNodeType::Text(text) => {
+            return vec![Node {
+                children: vec![],
+                node_type: NodeType::Text(text.clone()),
+                style: styled.style.clone(),
+                dom: styled.dom.clone(),
+            }];
+        }
+        DomNodeType::Comment(_) => return vec![],
+    };
+
+    // The computed style is shared with the styled tree and only copied when a box changes it
+    let mut style = styled.style.clone();
+    // Logical `float` and `clear` values take their side from the box's direction
+    let (float, clear) = (style.float.resolve(style.direction), style.clear.resolve(style.direction));
+    if float != style.float || clear != style.clear {
+        let style = Arc::make_mut(&mut style);
+        style.float = float;
+        style.clear = clear;
+    }
+    // Absolutely positioned boxes do not float, and both are always block containers (CSS 2.1 §9.7)
+    if style.position.is_out_of_flow() && style.float != Float::None {
+        Arc::make_mut(&mut style).float = Float::None;
+    }
+    let blockified = style.float != Float::None || style.position.is_out_of_flow();
+    if blockified {
+        blockify(&mut style);
+    }
+
+    let mut children = Vec::new();
+    for child in &styled.children {
+        let mut boxes = generate_boxes(child);
+        // An element with `display: contents` has no box of its own, so the text of its
+        // generated content stands for it when hit testing
+        if style.display == Display::Contents && child.pseudo.is_some() {
+            boxes.iter_mut().for_each(|generated| link_generated_text(generated, styled));
+        }
+        children.extend(boxes);
+    }
+    let children = match style.display {
+        // Replaced elements show their image; `<video>` and `<canvas>` fallback content is not rendered
+        _ if matches!(el.tag_name.as_str(), "img" | "video" | "canvas") => vec![],
+        Display::Table | Display::InlineTable => table_children(children, &style),
+        _ if style.display.is_row_group() => row_group_children(children, &style),
+        Display::TableRow => row_children(children, &style),
+        Display::TableColumn => vec![],
+        Display::TableColumnGroup => children
+            .into_iter()
+            .filter(|c| c.style.display == Display::TableColumn)
+            .collect(),
+        _ => anonymous_tables(children, &style),
+    };
+    let mut attributes: Vec<(String, String)> = el.attrs.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+    attributes.sort();
+    let node_type = NodeType::Element(ElementData {
+        tag_name: el.tag_name.clone(),
+        attributes,
+    });
+
+    let mut node = match style.display {
+        Display::None => return vec![],
+        Display::Contents => return children,
+        Display::Inline => return split_inline(node_type, styled, children),
+        Display::Block
+        | Display::ListItem
+        | Display::InlineBlock
+        | Display::FlowRoot
+        | Display::TableCell
+        | Display::TableCaption => block_container(node_type, style, children),
+        Display::Flex | Display::InlineFlex | Display::Grid | Display::InlineGrid => {
+            item_container(node_type, style, children)
+        }
+        Display::Table
+        | Display::InlineTable
+        | Display::TableRowGroup
+        | Display::TableHeaderGroup
+        | Display::TableFooterGroup
+        | Display::TableRow
+        | Display::TableColumn
+        | Display::TableColumnGroup => Node {
+            children,
+            node_type,
+            style,
+            dom: None,
+        },
+    };
+    node.dom = styled.dom.clone();
+    vec![node]
+}
+
+/// Point the text boxes of generated content at the element that generated it
+fn link_generated_text(node: &mut Node, element: &StyledNode) {
+    if matches!(node.node_type, NodeType::Text(_)) && node.dom.is_none() {
+        node.dom = element.dom.clone();
+    }
+    for child in &mut node.children {
+        link_generated_text(child, element);
+    }
+}
+
+/// A block container's children must be all block-level or all inline-level: wrap each run
+/// of inline-level boxes between blocks in an anonymous block
+fn block_container(node_type: NodeType, style: Arc<Style>, children: Vec<Node>) -> Node {
+    let has_blocks = children.iter().any(|c| c.is_block_level());
+    if !has_blocks {
+        return Node {
+            children,
+            node_type,
+            style,
+            dom: None,
+        };
+    }
+
+    let mut wrapped = Vec::new();
+    let mut run: Vec<Node> = Vec::new();
+    for child in children {
+        if child.is_block_level() || child.is_outside_marker() {
+            flush_anonymous(&mut wrapped, &mut run, &style);
+            wrapped.push(child);
+        } else {
+            run.push(child);
+        }
+    }
+    flush_anonymous(&mut wrapped, &mut run, &style);
+
+    Node {
+        children: wrapped,
+        node_type,
+        style,
+        dom: None,
+    }
+}
+
+/// Every child of a flex or grid container is an item: elements are blockified and each run
+/// of text is wrapped in an anonymous block (CSS Flexbox §4, CSS Grid §6)
+fn item_container(node_type: NodeType, style: Arc<Style>, children: Vec<Node>) -> Node {
+    let mut items = Vec::new();
+    let mut run: Vec<Node> = Vec::new();
+    for mut child in children {
+        if matches!(child.node_type, NodeType::Text(_)) {
+            run.push(child);
+        } else {
+            flush_anonymous(&mut items, &mut run, &style);
+            blockify(&mut child.style);
+            items.push(child);
+        }
+    }
+    flush_anonymous(&mut items, &mut run, &style);
+
+    Node {
+        children: items,
+        node_type,
+        style,
+        dom: None,
+    }
+}
+
+/// Turn an inline-level display into its block-level equivalent
+fn blockify(style: &mut Arc<Style>) {
+    let display = match style.display {
+        Display::Inline | Display::InlineBlock => Display::Block,
+        Display::InlineFlex => Display::Flex,
+        Display::InlineGrid => Display::Grid,
+        Display::InlineTable => Display::Table,
+        _ if style.display.is_table_part() => Display::Block,
+        _ => return,
+    };
+    Arc::make_mut(style).display = display;
+}
+
+fn is_table_part(node: &Node) -> bool {
+    !matches!(node.node_type, NodeType::Text(_)) && node.style.display.is_table_part()
+}
+
+/// A table holds captions, columns, row groups and rows; anything else goes in anonymous rows
+fn table_children(children: Vec<Node>, table: &Style) -> Vec<Node> {
+    let mut out = Vec::new();
+    let mut run = Vec::new();
+    for child in children {
+        if is_table_part(&child) && child.style.display != Display::TableCell {
+            flush_row(&mut out, &mut run, table);
+            out.push(child);
+        } else {
+            run.push(child);
+        }
+    }
+    flush_row(&mut out, &mut run, table);
+    out
+}
+
+/// A row group holds rows; anything else goes in anonymous rows
+fn row_group_children(children: Vec<Node>, group: &Style) -> Vec<Node> {
+    let mut out = Vec::new();
+    let mut run = Vec::new();
+    for child in children {
+        if is_table_part(&child) && child.style.display == Display::TableRow {
+            flush_row(&mut out, &mut run, group);
+            out.push(child);
+        } else {
+            run.push(child);
+        }
+    }
+    flush_row(&mut out, &mut run, group);
+    out
+}
+
+/// A row holds cells; runs of anything else are wrapped in anonymous cells
+fn row_children(children: Vec<Node>, row: &Style) -> Vec<Node> {
+    let mut out = Vec::new();
+    let mut run: Vec<Node> = Vec::new();
+    for child in children {
+        if is_table_part(&child) && child.style.display == Display::TableCell {
+            flush_cell(&mut out, &mut run, row);
+            out.push(child);
+        } else {
+            run.push(child);
+        }
+    }
+    flush_cell(&mut out, &mut run, row);
+    out
+}
+
+fn flush_row(out: &mut Vec<Node>, run: &mut Vec<Node>, parent: &Style) {
+    let run = std::mem::take(run);
+    if run.iter().all(|c| c.is_collapsible_whitespace()) {
+        return;
+    }
+    let style = anonymous_style(parent, Display::TableRow);
+    out.push(Node {
+        children: row_children(run, &style),
+        node_type: NodeType::Anonymous,
+        style,
+        dom: None,
+    });
+}
+
+fn flush_cell(out: &mut Vec<Node>, run: &mut Vec<Node>, parent: &Style) {
+    let run = std::mem::take(run);
+    if run.iter().all(|c| c.is_collapsible_whitespace()) {
+        return;
+    }
+    out.push(block_container(
+        NodeType::Anonymous,
+        anonymous_style(parent, Display::TableCell),
+        run,
+    ));
+}
+
+/// Rows, cells and other table parts outside a table are wrapped in an anonymous table
+fn anonymous_tables(children: Vec<Node>, parent: &Style) -> Vec<Node> {
+    if !children.iter().any(is_table_part) {
+        return children;
+    }
+    let mut out = Vec::new();
+    let mut run = Vec::new();
+    for child in children {
+        if is_table_part(&child) || (!run.is_empty() && child.is_collapsible_whitespace()) {
+            run.push(child);
+            continue;
+        }
+        flush_table(&mut out, &mut run, parent);
+        out.push(child);
+    }
+    flush_table(&mut out, &mut run, parent);
+    out
+}
+
+fn flush_table(out: &mut Vec<Node>, run: &mut Vec<Node>, parent: &Style) {
+    if run.is_empty() {
+        return;
+    }
+    // Inside an inline the table is inline-level too
+    let display = if parent.display == Display::Inline {
+        Display::InlineTable
+    } else {
+        Display::Table
+    };
+    let style = anonymous_style(parent, display);
+    out.push(Node {
+        children: table_children(std::mem::take(run), &style),
+        node_type: NodeType::Anonymous,
+        style,
+        dom: None,
+    });
+}
+
+/// Wrap pending inline-level boxes in an anonymous block, unless they are only whitespace
+fn flush_anonymous(out: &mut Vec<Node>, run: &mut Vec<Node>, parent: &Style) {
+    let run = std::mem::take(run);
+    if run.iter().all(|c| c.is_collapsible_whitespace()) {
+        return;
+    }
+    out.push(Node {
+        children: run,
+        node_type: NodeType::Anonymous,
+        style: anonymous_style(parent, Display::Block),
+        dom: None,
+    });
+}
+
+/// An inline containing blocks is split around them (CSS 2.1 §9.2.1.1); each part keeps the
+/// inline's style and the blocks become its siblings
+fn split_inline(node_type: NodeType, styled: &StyledNode, children: Vec<Node>) -> Vec<Node> {
+    let part = |children: Vec<Node>| Node {
+        children,
+        node_type: node_type.clone(),
+        style: styled.style.clone(),
+        dom: styled.dom.clone(),
+    };
+    if !children.iter().any(|c| c.is_block_level()) {
+        return vec![part(children)];
+    }
+
+    let mut parts = Vec::new();
+    let mut run = Vec::new();
+    for child in children {
+        if child.is_block_level() {
+            if !run.is_empty() {
+                parts.push(part(std::mem::take(&mut run)));
+            }
+            parts.push(child);
+        } else {
+            run.push(child);
+        }
+    }
+    if !run.is_empty() {
+        parts.push(part(run));
+    }
+    parts
+}
+
+/// Anonymous boxes inherit from the box they are created in
+fn anonymous_style(parent: &Style, display: Display) -> Arc<Style> {
+    Arc::new(Style {
+        display,
+        ..inherited_style(Some(parent))
+    })
+}
@@ -0,0 +1,876 @@
+//! css.rs — A small CSS stylesheet parser.
+//! Turns stylesheet text into rules (selectors + declarations) that the style system cascades.
+
+use crate::browser::dom::{AttrMap, ElementData, ElementState, Node, NodeType};
+use std::fmt;
+use std::rc::Rc;
+
+/// A parsed stylesheet
+#[derive(Debug, Clone, Default)]
+pub struct Stylesheet {
+    pub rules: Vec<Rule>,
+    pub font_faces: Vec<FontFaceRule>,
+    pub pages: Vec<PageRule>,
+}
+
+/// An `@font-face { ... }` block; its descriptors are interpreted by `fonts`
+#[derive(Debug, Clone)]
+pub struct FontFaceRule {
+    pub declarations: Vec<Declaration>,
+    pub line: usize,
+}
+
+/// An `@page :first { ... }` block; its descriptors are interpreted by `paginate`
+#[derive(Debug, Clone)]
+pub struct PageRule {
+    /// The page selector (`:first`, a page name, ...); empty for every page
+    pub selector: String,
+    pub declarations: Vec<Declaration>,
+    pub line: usize,
+}
+
+/// A style rule: `h1, p.note { color: red }`
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub selectors: Vec<Selector>,
+    pub declarations: Vec<Declaration>,
+    /// 1-based line of the rule in its stylesheet
+    pub line: usize,
+}
+
+/// A single `name: value` pair
+#[derive(Debug, Clone, PartialEq)]
+pub struct Declaration {
+    pub name: String,
+    pub value: String,
+}
+
+/// Pseudo-elements that generate boxes not present in the DOM
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PseudoElement {
+    Before,
+    After,
+    Marker,
+}
+
+/// A complex selector: compound selectors joined by combinators.
+/// The rightmost compound (the subject) is last.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selector {
+    pub compounds: Vec<CompoundSelector>,
+    /// The combinator between each compound and the next one (one fewer than `compounds`)
+    pub combinators: Vec<Combinator>,
+    pub pseudo_element: Option<PseudoElement>,
+}
+
+/// How two compounds of a selector relate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Combinator {
+    /// `a b`: b is anywhere inside a
+    Descendant,
+    /// `a > b`: b is a child of a
+    Child,
+}
+
+/// A compound selector such as `div#main.note[title]`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompoundSelector {
+    pub tag: Option<String>,
+    pub id: Option<String>,
+    pub classes: Vec<String>,
+    pub attrs: Vec<AttrSelector>,
+    /// Dynamic state required by `:hover`, `:active`, `:focus`
+    pub state: ElementState,
+}
+
+/// An attribute selector: `[name]`, or `[name op value]` with an optional `i` flag
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttrSelector {
+    /// Lowercased attribute name
+    pub name: String,
+    pub op: AttrOp,
+    pub value: String,
+    /// `[name=value i]`: the value is compared ignoring ASCII case
+    pub ignore_case: bool,
+}
+
+/// The comparison an attribute selector makes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttrOp {
+    /// `[name]`
+    Exists,
+    /// `[name=value]`
+    Equals,
+    /// `[name~=value]`: one of its whitespace-separated words
+    Includes,
+    /// `[name|=value]`: the value, or the value followed by `-`
+    DashMatch,
+    /// `[name^=value]`
+    Prefix,
+    /// `[name$=value]`
+    Suffix,
+    /// `[name*=value]`
+    Substring,
+}
+
+/// Selector specificity (ids, classes/attributes, tags)
+pub type Specificity = (usize, usize, usize);
+
+/// Something selectors can be matched against: a live DOM node, or a node of a
+/// thread-safe snapshot when styling in parallel
+pub trait MatchTarget: Sized {
+    /// Run `f` on the element's data and state; false if this is not an element
+    fn match_element(&self, f: &dyn Fn(&ElementData, ElementState) -> bool) -> bool;
+
+    /// The parent node, if any
+    fn parent_node(&self) -> Option<Self>;
+}
+
+impl MatchTarget for Rc<Node> {
+    fn match_element(&self, f: &dyn Fn(&ElementData, ElementState) -> bool) -> bool {
+        match &*self.node_type() {
+            NodeType::Element(el) => f(el, self.state()),
+            _ => false,
+        }
+    }
+
+    fn parent_node(&self) -> Option<Self> {
+        self.parent()
+    }
+}
+
+impl Selector {
+    /// Compute the specificity used to order rules in the cascade
+    pub fn specificity(&self) -> Specificity {
+        let mut spec = (0, 0, 0);
+        for c in &self.compounds {
+            spec.0 += c.id.is_some() as usize;
+            spec.1 += c.classes.len() + c.attrs.len() + c.state.0.count_ones() as usize;
+            spec.2 += c.tag.is_some() as usize;
+        }
+        spec.2 += self.pseudo_element.is_some() as usize;
+        spec
+    }
+
+    /// Returns true if the selector's subject matches `node`
+    pub fn matches<T: MatchTarget>(&self, node: &T) -> bool {
+        let Some(subject) = self.compounds.last() else {
+            return false;
+        };
+        node.match_element(&|el, state| subject.matches_element(el, state))
+            && self.matches_ancestors(self.compounds.len() - 1, node)
+    }
+
+    /// Returns true if the compounds before `index` match around `node`, which matched
+    /// compound `index`. Matching runs right to left and backtracks, so `a > b c` also finds
+    /// a `b` child of an `a` further up than the nearest `b`.
+    fn matches_ancestors<T: MatchTarget>(&self, index: usize, node: &T) -> bool {
+        if index == 0 {
+            return true;
+        }
+        let compound = &self.compounds[index - 1];
+        let matches = |ancestor: &T| {
+            ancestor.match_element(&|el, state| compound.matches_element(el, state))
+                && self.matches_ancestors(index - 1, ancestor)
+        };
+        match self.combinators[index - 1] {
+            Combinator::Child => node.parent_node().is_some_and(|parent| matches(&parent)),
+            Combinator::Descendant => {
+                let mut current = node.parent_node();
+                while let Some(ancestor) = current {
+                    if matches(&ancestor) {
+                        return true;
+                    }
+                    current = ancestor.parent_node();
+                }
+                false
+            }
+        }
+    }
+}
+
+impl fmt::Display for AttrSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = match self.op {
+            AttrOp::Exists => return write!(f, "[{}]", self.name),
+            AttrOp::Equals => "=",
+            AttrOp::Includes => "~=",
+            AttrOp::DashMatch => "|=",
+            AttrOp::Prefix => "^=",
+            AttrOp::Suffix => "$=",
+            AttrOp::Substring => "*=",
+        };
+        let flag = if self.ignore_case { " i" } else { "" };
+        write!(f, "[{}{}{:?}{}]", self.name, op, self.value, flag)
+    }
+}
+
+impl AttrSelector {
+    /// Returns true if an element with these attributes matches
+    pub fn matches(&self, attrs: &AttrMap) -> bool {
+        let Some(actual) = attrs.get(&self.name) else {
+            return false;
+        };
+        let (actual, value) = if self.ignore_case {
+            (actual.to_ascii_lowercase(), self.value.to_ascii_lowercase())
+        } else {
+            (actual.clone(), self.value.clone())
+        };
+        match self.op {
+            AttrOp::Exists => true,
+            AttrOp::Equals => actual == value,
+            AttrOp::Includes => !value.is_empty() && actual.split_whitespace().any(|word| word == value),
+            AttrOp::DashMatch => actual == value || actual.strip_prefix(&value).is_some_and(|rest| rest.starts_with('-')),
+            // An empty value matches nothing
+            AttrOp::Prefix => !value.is_empty() && actual.starts_with(&value),
+            AttrOp::Suffix => !value.is_empty() && actual.ends_with(&value),
+            AttrOp::Substring => !value.is_empty() && actual.contains(&value),
+        }
+    }
+}
+
+/// Serializes back to selector text, e.g. `ul li.done:hover::before`
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, compound) in self.compounds.iter().enumerate() {
+            match i.checked_sub(1).map(|i| self.combinators[i]) {
+                Some(Combinator::Descendant) => write!(f, " ")?,
+                Some(Combinator::Child) => write!(f, " > ")?,
+                None => {}
+            }
+            write!(f, "{}", compound)?;
+        }
+        match self.pseudo_element {
+            Some(PseudoElement::Before) => write!(f, "::before"),
+            Some(PseudoElement::After) => write!(f, "::after"),
+            Some(PseudoElement::Marker) => write!(f, "::marker"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for CompoundSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let universal = self.id.is_none() && self.classes.is_empty() && self.attrs.is_empty() && self.state.0 == 0;
+        match &self.tag {
+            Some(tag) => write!(f, "{}", tag)?,
+            None if universal => write!(f, "*")?,
+            None => {}
+        }
+        if let Some(id) = &self.id {
+            write!(f, "#{}", id)?;
+        }
+        for class in &self.classes {
+            write!(f, ".{}", class)?;
+        }
+        for attr in &self.attrs {
+            write!(f, "{}", attr)?;
+        }
+        for (state, name) in [
+            (ElementState::HOVER, "hover"),
+            (ElementState::ACTIVE, "active"),
+            (ElementState::FOCUS, "focus"),
+        ] {
+            if self.state.contains(state) {
+                write!(f, ":{}", name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl CompoundSelector {
+    /// Returns true if this compound matches a single element
+    pub fn matches(&self, node: &Node) -> bool {
+        match &*node.node_type() {
+            NodeType::Element(el) => self.matches_element(el, node.state()),
+            _ => false,
+        }
+    }
+
+    /// Returns true if this compound matches an element with the given data and state
+    pub fn matches_element(&self, el: &ElementData, state: ElementState) -> bool {
+        if !state.contains(self.state) {
+            return false;
+        }
+
+        if let Some(tag) = &self.tag {
+            if *tag != el.tag_name {
+                return false;
+            }
+        }
+        if let Some(id) = &self.id {
+            if el.attrs.get("id") != Some(id) {
+                return false;
+            }
+        }
+
+        let classes = el.attrs.get("class").map(String::as_str).unwrap_or("");
+        if !self
+            .classes
+            .iter()
+            .all(|c| classes.split_whitespace().any(|have| have == c))
+        {
+            return false;
+        }
+
+        self.attrs.iter().all(|attr| attr.matches(&el.attrs))
+    }
+}
+
+/// Parse stylesheet text into a `Stylesheet`
+pub fn parse_stylesheet(input: &str) -> Stylesheet {
+    let mut parser = CSSParser::new(input);
+    parser.parse_stylesheet()
+}
+
+/// Parse a declaration block body (`color: red; margin: 4px`), as found in `style=""`
+pub fn parse_declarations(input: &str) -> Vec<Declaration> {
+    split_top_level(input, ';')
+        .into_iter()
+        .filter_map(|decl| {
+            let (name, value) = decl.split_once(':')?;
+            let name = name.trim().to_lowercase();
+            let value = value.trim();
+            if name.is_empty() || value.is_empty() {
+                return None;
+            }
+            Some(Declaration {
+                name,
+                value: value.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Split on `sep`, ignoring separators inside quotes or parentheses
+pub fn split_top_level(input: &str, sep: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut quote: Option<char> = None;
+    let mut start = 0;
+
+    for (i, c) in input.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => depth = depth.saturating_sub(1),
+            (None, c) if c == sep && depth == 0 => {
+                parts.push(&input[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&input[start..]);
+    parts
+}
+
+/// Block text with the nested at-rules in it (e.g. `@top-center { ... }` in `@page`) removed
+fn without_nested_blocks(body: &str) -> String {
+    let mut out = String::new();
+    let mut depth = 0usize;
+    let mut nested = false;
+    for c in body.chars() {
+        match c {
+            '@' if depth == 0 => nested = true,
+            '{' if nested => depth += 1,
+            '}' if nested => {
+                depth = depth.saturating_sub(1);
+                nested = depth > 0;
+            }
+            _ if !nested => out.push(c),
+            _ => {}
+        }
+    }
+    out
+}
+
+/// Strip matching quotes from a CSS string token
+pub fn unquote(token: &str) -> Option<String> {
+    let quote = token.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let inner = token.strip_prefix(quote)?.strip_suffix(quote)?;
+    Some(inner.replace(&format!("\\{}", quote), &quote.to_string()))
+}
+
+#[derive(Debug)]
+struct CSSParser {
+    pos: usize,
+    input: String,
+}
+
+impl CSSParser {
+    fn new(input: &str) -> Self {
+        Self {
+            pos: 0,
+            input: input.to_string(),
+        }
+    }
+
+    fn parse_stylesheet(&mut self) -> Stylesheet {
+        let mut sheet = Stylesheet::default();
+        loop {
+            self.consume_whitespace_and_comments();
+            if self.eof() {
+                break;
+            }
+            if self.starts_with("@") {
+                self.parse_at_rule(&mut sheet);
+                continue;
+            }
+            if let Some(rule) = self.parse_rule() {
+                sheet.rules.push(rule);
+            }
+        }
+        sheet
+    }
+
+    fn parse_at_rule(&mut self, sheet: &mut Stylesheet) {
+        let line = self.line();
+        let name = self.input[self.pos + 1..]
+            .split(|c: char| !(c.is_alphanumeric() || c == '-'))
+            .next()
+            .unwrap_or("")
+            .to_lowercase();
+
+        match name.as_str() {
+            "font-face" => {
+                self.consume_while(|c| c != '{');
+                if self.eof() {
+                    return;
+                }
+                let body = self.consume_block();
+                sheet.font_faces.push(FontFaceRule {
+                    declarations: parse_declarations(&body),
+                    line,
+                });
+            }
+            "page" => {
+                let prelude = self.consume_while(|c| c != '{');
+                if self.eof() {
+                    return;
+                }
+                let body = self.consume_block();
+                sheet.pages.push(PageRule {
+                    selector: prelude["@page".len()..].trim().to_string(),
+                    declarations: parse_declarations(&without_nested_blocks(&body)),
+                    line,
+                });
+            }
+            _ => self.skip_at_rule(),
+        }
+    }
+
+    fn parse_rule(&mut self) -> Option<Rule> {
+        let line = self.line();
+        let prelude = self.consume_while(|c| c != '{');
+        if self.eof() {
+            return None;
+        }
+        let body = self.consume_block();
+
+        let selectors: Vec<Selector> = split_top_level(&prelude, ',')
+            .into_iter()
+            .filter_map(parse_selector)
+            .collect();
+        if selectors.is_empty() {
+            return None;
+        }
+
+        Some(Rule {
+            selectors,
+            declarations: parse_declarations(&body),
+            line,
+        })
+    }
+
+    /// Skip an at-rule we do not understand (either `@x ...;` or `@x ... { ... }`)
+    fn skip_at_rule(&mut self) {
+        self.consume_while(|c| c != ';' && c != '{');
+        if self.starts_with("{") {
+            self.consume_block();
+        } else if !self.eof() {
+            self.consume_char();
+        }
+    }
+
+    /// Consume a `{ ... }` block (with nesting) and return its inner text. Braces inside
+    /// strings (`content: "}"`) and comments do not count.
+    fn consume_block(&mut self) -> String {
+        assert!(self.consume_char() == '{');
+        let mut depth = 1;
+        let mut body = String::new();
+        let mut quote = None;
+        while !self.eof() {
+            if quote.is_none() && self.starts_with("/*") {
+                match self.input[self.pos..].find("*/") {
+                    Some(end) => self.pos += end + 2,
+                    None => self.pos = self.input.len(),
+                }
+                continue;
+            }
+            let c = self.consume_char();
+            match (quote, c) {
+                (Some(_), '\\') | (None, '\\') if !self.eof() => {
+                    body.push(c);
+                    body.push(self.consume_char());
+                    continue;
+                }
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), _) => {}
+                (None, '"' | '\'') => quote = Some(c),
+                (None, '{') => depth += 1,
+                (None, '}') => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+            body.push(c);
+        }
+        body
+    }
+
+    fn consume_whitespace_and_comments(&mut self) {
+        loop {
+            self.consume_while(|c| c.is_whitespace());
+            if self.starts_with("/*") {
+                match self.input[self.pos..].find("*/") {
+                    Some(end) => self.pos += end + 2,
+                    None => self.pos = self.input.len(),
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn line(&self) -> usize {
+        self.input[..self.pos].matches('\n').count() + 1
+    }
+
+    fn consume_char(&mut self) -> char {
+        let c = self.current_char();
+        self.pos += c.len_utf8();
+        c
+    }
+
+    fn current_char(&self) -> char {
+        self.input[self.pos..].chars().next().unwrap_or('\0')
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        self.input[self.pos..].starts_with(s)
+    }
+
+    fn consume_while<F>(&mut self, test: F) -> String
+    where
+        F: Fn(char) -> bool,
+    {
+        let mut result = String::new();
+        while !self.eof() && test(self.current_char()) {
+            result.push(self.consume_char());
+        }
+        result
+    }
+
+    fn eof(&self) -> bool {
+        self.pos >= self.input.len()
+    }
+}
+
+/// Parse one selector from a selector list (e.g. `ul > li.item::before`). Selectors with
+/// sibling combinators (`+`, `~`) are not supported and never match.
+fn parse_selector(text: &str) -> Option<Selector> {
+    let mut compounds = Vec::new();
+    let mut combinators = Vec::new();
+    let mut pseudo_element = None;
+    let mut pending = None;
+
+    for token in selector_tokens(text.trim())? {
+        match token {
+            ">" if pending.is_none() && !compounds.is_empty() => pending = Some(Combinator::Child),
+            ">" | "+" | "~" => return None,
+            _ => {
+                // Pseudo-elements may only appear on the subject
+                if pseudo_element.is_some() {
+                    return None;
+                }
+                if !compounds.is_empty() {
+                    combinators.push(pending.take().unwrap_or(Combinator::Descendant));
+                }
+                let (compound, pseudo) = parse_compound(token)?;
+                compounds.push(compound);
+                pseudo_element = pseudo;
+            }
+        }
+    }
+    if compounds.is_empty() || pending.is_some() {
+        return None;
+    }
+
+    Some(Selector {
+        compounds,
+        combinators,
+        pseudo_element,
+    })
+}
+
+/// Split a selector into compounds and combinators (`>`, `+`, `~`). Whitespace inside
+/// attribute selectors (`[title="a b"]`) does not split.
+fn selector_tokens(text: &str) -> Option<Vec<&str>> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    let mut bracket = false;
+    let mut quote: Option<char> = None;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') if bracket => quote = Some(c),
+            (None, '[') => {
+                bracket = true;
+                start.get_or_insert(i);
+            }
+            (None, ']') => bracket = false,
+            (None, _) if bracket => {}
+            (None, '>' | '+' | '~') => {
+                if let Some(from) = start.take() {
+                    tokens.push(&text[from..i]);
+                }
+                tokens.push(&text[i..i + 1]);
+            }
+            (None, c) if c.is_whitespace() => {
+                if let Some(from) = start.take() {
+                    tokens.push(&text[from..i]);
+                }
+            }
+            _ => {
+                start.get_or_insert(i);
+            }
+        }
+    }
+    if bracket || quote.is_some() {
+        return None;
+    }
+    if let Some(from) = start {
+        tokens.push(&text[from..]);
+    }
+    Some(tokens)
+}
+
+/// Parse a compound like `li.item#x[title]::marker`
+fn parse_compound(text: &str) -> Option<(CompoundSelector, Option<PseudoElement>)> {
+    let mut compound = CompoundSelector::default();
+    let mut pseudo = None;
+
+    // The pseudo-classes start at the first `:` outside an attribute selector
+    let mut bracket = false;
+    let colon = text.char_indices().find_map(|(i, c)| {
+        match c {
+            '[' => bracket = true,
+            ']' => bracket = false,
+            ':' if !bracket => return Some(i),
+            _ => {}
+        }
+        None
+    });
+    let (body, pseudo_text) = match colon {
+        Some(i) => (&text[..i], &text[i..]),
+        None => (text, ""),
+    };
+
+    // `:hover:focus::before` — pseudo-classes, then at most one pseudo-element
+    for name in pseudo_text.split(':').filter(|n| !n.is_empty()) {
+        if pseudo.is_some() {
+            return None;
+        }
+        match name.to_lowercase().as_str() {
+            "before" => pseudo = Some(PseudoElement::Before),
+            "after" => pseudo = Some(PseudoElement::After),
+            "marker" => pseudo = Some(PseudoElement::Marker),
+            "hover" => compound.state.0 |= ElementState::HOVER.0,
+            "active" => compound.state.0 |= ElementState::ACTIVE.0,
+            "focus" => compound.state.0 |= ElementState::FOCUS.0,
+            // Unsupported pseudo-classes/elements never match
+            _ => return None,
+        }
+    }
+
+    let mut chars = body.char_indices().peekable();
+    let ident_end = |s: &str, from: usize| s[from..].find(['.', '#', '[']).map_or(s.len(), |i| from + i);
+
+    while let Some((i, c)) = chars.next() {
+        let end = match c {
+            '*' => continue,
+            '.' | '#' => {
+                let end = ident_end(body, i + 1);
+                let name = body[i + 1..end].to_string();
+                if name.is_empty() {
+                    return None;
+                }
+                if c == '.' {
+                    compound.classes.push(name);
+                } else {
+                    compound.id = Some(name);
+                }
+                end
+            }
+            '[' => {
+                let end = attr_end(body, i)?;
+                compound.attrs.push(parse_attr_selector(&body[i + 1..end])?);
+                end + 1
+            }
+            _ => {
+                let end = ident_end(body, i);
+                compound.tag = Some(body[i..end].to_lowercase());
+                end
+            }
+        };
+        while chars.next_if(|(j, _)| *j < end).is_some() {}
+    }
+
+    Some((compound, pseudo))
+}
+
+/// Index of the `]` closing the attribute selector opened at `open`, skipping quoted values
+fn attr_end(text: &str, open: usize) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in text[open..].char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, ']') => return Some(open + i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parse the inside of `[...]`: `title`, `type=text`, `lang|="en"`, `href^='http' i`
+fn parse_attr_selector(text: &str) -> Option<AttrSelector> {
+    let text = text.trim();
+    let Some(eq) = text.find('=') else {
+        let name = text.to_lowercase();
+        let valid = !name.is_empty() && !name.contains(char::is_whitespace);
+        return valid.then_some(AttrSelector {
+            name,
+            op: AttrOp::Exists,
+            value: String::new(),
+            ignore_case: false,
+        });
+    };
+
+    let (op, name_end) = match text[..eq].chars().last() {
+        Some('~') => (AttrOp::Includes, eq - 1),
+        Some('|') => (AttrOp::DashMatch, eq - 1),
+        Some('^') => (AttrOp::Prefix, eq - 1),
+        Some('$') => (AttrOp::Suffix, eq - 1),
+        Some('*') => (AttrOp::Substring, eq - 1),
+        _ => (AttrOp::Equals, eq),
+    };
+    let name = text[..name_end].trim().to_lowercase();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return None;
+    }
+
+    let rest = text[eq + 1..].trim();
+    let (value, flags) = match rest.chars().next() {
+        Some(q @ ('"' | '\'')) => {
+            let close = rest[1..].find(q)? + 1;
+            (rest[1..close].to_string(), rest[close + 1..].trim())
+        }
+        _ => match rest.split_once(char::is_whitespace) {
+            Some((value, flags)) => (value.to_string(), flags.trim()),
+            None => (rest.to_string(), ""),
+        },
+    };
+    let ignore_case = match flags.to_ascii_lowercase().as_str() {
+        "" | "s" => false,
+        "i" => true,
+        _ => return None,
+    };
+
+    Some(AttrSelector {
+        name,
+        op,
+        value,
+        ignore_case,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::browser::parser::parse_html;
+
+    /// The first element named `tag` in document order
+    fn find(node: &Rc<Node>, tag: &str) -> Option<Rc<Node>> {
+        if node.tag_name().is_some_and(|name| &*name == tag) {
+            return Some(node.clone());
+        }
+        node.children().iter().find_map(|child| find(child, tag))
+    }
+
+    fn matches(selector: &str, html: &str, tag: &str) -> bool {
+        let sheet = parse_stylesheet(&format!("{} {{ color: red }}", selector));
+        // Parents are weak links, so the document must outlive the match
+        let document = parse_html(html);
+        let node = find(&document, tag).unwrap();
+        sheet.rules.iter().flat_map(|rule| &rule.selectors).any(|s| s.matches(&node))
+    }
+
+    #[test]
+    fn braces_in_strings_do_not_end_the_block() {
+        let sheet = parse_stylesheet(r#"p::before { content: "}"; color: red } div { color: blue }"#);
+        assert_eq!(sheet.rules.len(), 2);
+        assert_eq!(sheet.rules[0].declarations.len(), 2);
+        assert_eq!(sheet.rules[0].declarations[0].value, r#""}""#);
+        assert_eq!(sheet.rules[1].selectors[0].compounds[0].tag.as_deref(), Some("div"));
+    }
+
+    #[test]
+    fn attribute_selectors_compare_values() {
+        let html = r#"<form><input type="text" lang="en-US" class="a b"></form>"#;
+        assert!(matches("[type=text]", html, "input"));
+        assert!(matches(r#"input[type="text"]"#, html, "input"));
+        assert!(!matches("[type=password]", html, "input"));
+        assert!(matches("[TYPE=TEXT i]", html, "input"));
+        assert!(matches("[lang|=en]", html, "input"));
+        assert!(matches("[class~=b]", html, "input"));
+        assert!(matches("[lang^=en][lang$=US][lang*=n-U]", html, "input"));
+        assert!(!matches("[lang^='']", html, "input"));
+    }
+
+    #[test]
+    fn child_combinator() {
+        let html = "<div><section><p>x</p></section></div>";
+        assert!(matches("section > p", html, "p"));
+        assert!(!matches("div > p", html, "p"));
+        assert!(matches("div p", html, "p"));
+        assert!(matches("div>section>p", html, "p"));
+        // Backtracks past the nearest `section` ancestor
+        let nested = "<div><section><article><section><p>x</p></section></article></section></div>";
+        assert!(matches("div > section p", nested, "p"));
+    }
+
+    #[test]
+    fn unsupported_selectors_are_dropped() {
+        let sheet = parse_stylesheet("a + b, p, > q, r > { color: red }");
+        let selectors = &sheet.rules[0].selectors;
+        assert_eq!(selectors.len(), 1);
+        assert_eq!(selectors[0].compounds[0].tag.as_deref(), Some("p"));
+    }
+
+    #[test]
+    fn commas_inside_attribute_values_do_not_split() {
+        let sheet = parse_stylesheet(r#"a[title="x, y"], a[href^="http:"] { color: red }"#);
+        let values: Vec<&str> = sheet.rules[0].selectors.iter().map(|s| s.compounds[0].attrs[0].value.as_str()).collect();
+        assert_eq!(values, ["x, y", "http:"]);
+    }
+}
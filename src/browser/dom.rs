@@ -1,7 +1,8 @@
 //! dom.rs — Hardened, secure DOM representation
 
-use std::cell::{Ref, RefCell, RefMut};
+use std::cell::{Cell, Ref, RefCell, RefMut};
 use std::collections::HashMap;
+use std::ops::BitOr;
 use std::rc::{Rc, Weak};
 
 /// Map of element attributes (e.g., class="x")
@@ -22,24 +23,123 @@ pub enum NodeType {
     Comment(String),
 }
 
+/// Reasons a node's computed style is out of date (bit set)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StyleDirty(pub u8);
+
+impl StyleDirty {
+    pub const NONE: StyleDirty = StyleDirty(0);
+    /// An attribute other than `class` changed
+    pub const ATTRIBUTE: StyleDirty = StyleDirty(1 << 0);
+    /// The `class` attribute changed
+    pub const CLASS: StyleDirty = StyleDirty(1 << 1);
+    /// A child was inserted or removed
+    pub const CHILDREN: StyleDirty = StyleDirty(1 << 2);
+    /// Hover/active/focus state changed
+    pub const STATE: StyleDirty = StyleDirty(1 << 3);
+    /// Some descendant is dirty (set on ancestors so restyle can find it)
+    pub const DESCENDANT: StyleDirty = StyleDirty(1 << 4);
+    /// A text node's text changed
+    pub const TEXT: StyleDirty = StyleDirty(1 << 5);
+
+    /// Returns true if any bit of `other` is set
+    pub fn intersects(self, other: StyleDirty) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    /// Returns true if the node itself must be restyled
+    pub fn needs_restyle(self) -> bool {
+        self.intersects(Self::ATTRIBUTE | Self::CLASS | Self::CHILDREN | Self::STATE)
+    }
+}
+
+impl BitOr for StyleDirty {
+    type Output = StyleDirty;
+
+    fn bitor(self, rhs: StyleDirty) -> StyleDirty {
+        StyleDirty(self.0 | rhs.0)
+    }
+}
+
+/// Reasons a node's layout is out of date (bit set)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LayoutDirty(pub u8);
+
+impl LayoutDirty {
+    pub const NONE: LayoutDirty = LayoutDirty(0);
+    /// A text node's text changed
+    pub const TEXT: LayoutDirty = LayoutDirty(1 << 0);
+    /// The element's size changed from outside the style system (e.g. an image finished loading)
+    pub const SIZE: LayoutDirty = LayoutDirty(1 << 1);
+    /// The computed style was recomputed
+    pub const STYLE: LayoutDirty = LayoutDirty(1 << 2);
+    /// Some descendant is dirty (set on ancestors so relayout can find it)
+    pub const DESCENDANT: LayoutDirty = LayoutDirty(1 << 3);
+
+    /// Returns true if any bit of `other` is set
+    pub fn intersects(self, other: LayoutDirty) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    /// Returns true if the node's subtree cannot reuse its previous layout
+    pub fn needs_relayout(self) -> bool {
+        self != Self::NONE
+    }
+}
+
+impl BitOr for LayoutDirty {
+    type Output = LayoutDirty;
+
+    fn bitor(self, rhs: LayoutDirty) -> LayoutDirty {
+        LayoutDirty(self.0 | rhs.0)
+    }
+}
+
+/// Dynamic element state matched by `:hover`, `:active` and `:focus` (bit set)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ElementState(pub u8);
+
+impl ElementState {
+    pub const NONE: ElementState = ElementState(0);
+    pub const HOVER: ElementState = ElementState(1 << 0);
+    pub const ACTIVE: ElementState = ElementState(1 << 1);
+    pub const FOCUS: ElementState = ElementState(1 << 2);
+
+    /// Returns true if every bit of `other` is set
+    pub fn contains(self, other: ElementState) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
 /// A DOM node with references to children and parent
 #[derive(Debug)]
 pub struct Node {
-    pub node_type: NodeType,
+    node_type: RefCell<NodeType>,
     children: RefCell<Vec<Rc<Node>>>,
     parent: RefCell<Option<Weak<Node>>>,
+    state: Cell<ElementState>,
+    style_dirty: Cell<StyleDirty>,
+    layout_dirty: Cell<LayoutDirty>,
 }
 
 impl Node {
     /// Create a new node with the specified type
     pub fn new(node_type: NodeType) -> Rc<Node> {
         Rc::new(Node {
-            node_type,
+            node_type: RefCell::new(node_type),
             children: RefCell::new(vec![]),
             parent: RefCell::new(None),
+            state: Cell::new(ElementState::NONE),
+            style_dirty: Cell::new(StyleDirty::NONE),
+            layout_dirty: Cell::new(LayoutDirty::NONE),
         })
     }
 
+    /// Borrow the node's type and data
+    pub fn node_type(&self) -> Ref<'_, NodeType> {
+        self.node_type.borrow()
+    }
+
     /// Append a child node (ensures no cycles)
     pub fn append_child(parent: &Rc<Node>, child: Rc<Node>) {
         if Rc::ptr_eq(parent, &child) {
@@ -57,15 +157,16 @@ impl Node {
 
         child.parent.borrow_mut().replace(Rc::downgrade(parent));
         parent.children.borrow_mut().push(child);
+        parent.mark_style_dirty(StyleDirty::CHILDREN);
     }
 
     /// Get immutable children
-    pub fn children(&self) -> Ref<Vec<Rc<Node>>> {
+    pub fn children(&self) -> Ref<'_, Vec<Rc<Node>>> {
         self.children.borrow()
     }
 
     /// Get mutable access to children (use with caution)
-    pub fn children_mut(&self) -> RefMut<Vec<Rc<Node>>> {
+    pub fn children_mut(&self) -> RefMut<'_, Vec<Rc<Node>>> {
         self.children.borrow_mut()
     }
 
@@ -76,38 +177,40 @@ impl Node {
 
     /// Returns true if this is a text node
     pub fn is_text(&self) -> bool {
-        matches!(self.node_type, NodeType::Text(_))
+        matches!(*self.node_type(), NodeType::Text(_))
     }
 
     /// Returns true if this is an element node
     pub fn is_element(&self) -> bool {
-        matches!(self.node_type, NodeType::Element(_))
+        matches!(*self.node_type(), NodeType::Element(_))
     }
 
     /// Returns true if this is a comment node
     pub fn is_comment(&self) -> bool {
-        matches!(self.node_type, NodeType::Comment(_))
+        matches!(*self.node_type(), NodeType::Comment(_))
     }
 
     /// Return the tag name if this is an element node
-    pub fn tag_name(&self) -> Option<&str> {
-        match &self.node_type {
-            NodeType::Element(el) => Some(&el.tag_name),
+    pub fn tag_name(&self) -> Option<Ref<'_, str>> {
+        Ref::filter_map(self.node_type(), |t| match t {
+            NodeType::Element(el) => Some(el.tag_name.as_str()),
             _ => None,
-        }
+        })
+        .ok()
     }
 
     /// Return the text content if it's a text node
-    pub fn text(&self) -> Option<&str> {
-        match &self.node_type {
-            NodeType::Text(txt) => Some(txt),
+    pub fn text(&self) -> Option<Ref<'_, str>> {
+        Ref::filter_map(self.node_type(), |t| match t {
+            NodeType::Text(txt) => Some(txt.as_str()),
             _ => None,
-        }
+        })
+        .ok()
     }
 
     /// Get an attribute value (case-sensitive)
     pub fn get_attr(&self, name: &str) -> Option<String> {
-        match &self.node_type {
+        match &*self.node_type() {
             NodeType::Element(el) => el.attrs.get(name).cloned(),
             _ => None,
         }
@@ -120,34 +223,137 @@ impl Node {
 
     /// Set or replace an attribute (normalized)
     pub fn set_attr(&self, name: &str, value: &str) {
-        if let NodeType::Element(el) = &mut self.node_type.clone() {
-            let clean_key = name.trim().to_lowercase();
-            let clean_val = value.trim().to_string();
-            let mut new_attrs = el.attrs.clone();
-            new_attrs.insert(clean_key, clean_val);
-            self.replace_element_data(el.tag_name.clone(), new_attrs);
+        let el = match &*self.node_type() {
+            NodeType::Element(el) => el.clone(),
+            _ => return,
+        };
+        let clean_key = name.trim().to_lowercase();
+        let clean_val = value.trim().to_string();
+        if el.attrs.get(&clean_key) == Some(&clean_val) {
+            return;
         }
+
+        let mut new_attrs = el.attrs;
+        new_attrs.insert(clean_key.clone(), clean_val);
+        self.replace_element_data(el.tag_name, new_attrs);
+        self.mark_style_dirty(attr_dirty_reason(&clean_key));
+    }
+
+    /// Replace the text of a text node
+    pub fn set_text(&self, text: &str) {
+        {
+            let mut node_type = self.node_type.borrow_mut();
+            match &mut *node_type {
+                NodeType::Text(old) if old != text => *old = text.to_string(),
+                _ => return,
+            }
+        }
+        self.mark_style_dirty(StyleDirty::TEXT);
+        self.mark_layout_dirty(LayoutDirty::TEXT);
     }
 
     /// Remove an attribute
     pub fn remove_attr(&self, name: &str) {
-        if let NodeType::Element(el) = &mut self.node_type.clone() {
-            let mut new_attrs = el.attrs.clone();
-            new_attrs.remove(name);
-            self.replace_element_data(el.tag_name.clone(), new_attrs);
+        let el = match &*self.node_type() {
+            NodeType::Element(el) => el.clone(),
+            _ => return,
+        };
+        let mut new_attrs = el.attrs;
+        if new_attrs.remove(name).is_some() {
+            self.replace_element_data(el.tag_name, new_attrs);
+            self.mark_style_dirty(attr_dirty_reason(name));
         }
     }
 
     /// Internal: Replace element metadata (to apply attribute changes)
     fn replace_element_data(&self, tag: String, attrs: AttrMap) {
-        if let NodeType::Element(_) = self.node_type {
+        let mut node_type = self.node_type.borrow_mut();
+        if let NodeType::Element(_) = *node_type {
             let new_data = ElementData {
                 tag_name: tag,
                 attrs,
             };
-            self.node_type = NodeType::Element(new_data);
+            *node_type = NodeType::Element(new_data);
+        }
+    }
+
+    /// Current dynamic state (hover, active, focus)
+    pub fn state(&self) -> ElementState {
+        self.state.get()
+    }
+
+    /// Turn a dynamic state on or off (e.g. on mouse enter/leave)
+    pub fn set_state(&self, state: ElementState, on: bool) {
+        let old = self.state.get();
+        let new = if on {
+            ElementState(old.0 | state.0)
+        } else {
+            ElementState(old.0 & !state.0)
+        };
+        if new != old {
+            self.state.set(new);
+            self.mark_style_dirty(StyleDirty::STATE);
+        }
+    }
+
+    /// Why this node needs restyling, if at all
+    pub fn style_dirty(&self) -> StyleDirty {
+        self.style_dirty.get()
+    }
+
+    /// Flag this node for restyle and let its ancestors know a descendant is dirty
+    pub fn mark_style_dirty(&self, reason: StyleDirty) {
+        self.style_dirty.set(self.style_dirty.get() | reason);
+
+        let mut current = self.parent();
+        while let Some(ancestor) = current {
+            let dirty = ancestor.style_dirty.get();
+            if dirty.intersects(StyleDirty::DESCENDANT) {
+                break;
+            }
+            ancestor.style_dirty.set(dirty | StyleDirty::DESCENDANT);
+            current = ancestor.parent();
         }
     }
+
+    /// Called by the style system once the node's style is up to date
+    pub fn clear_style_dirty(&self) {
+        self.style_dirty.set(StyleDirty::NONE);
+    }
+
+    /// Why this node's layout is out of date, if it is
+    pub fn layout_dirty(&self) -> LayoutDirty {
+        self.layout_dirty.get()
+    }
+
+    /// Flag this node for relayout and let its ancestors know a descendant is dirty
+    pub fn mark_layout_dirty(&self, reason: LayoutDirty) {
+        self.layout_dirty.set(self.layout_dirty.get() | reason);
+
+        let mut current = self.parent();
+        while let Some(ancestor) = current {
+            let dirty = ancestor.layout_dirty.get();
+            if dirty.intersects(LayoutDirty::DESCENDANT) {
+                break;
+            }
+            ancestor.layout_dirty.set(dirty | LayoutDirty::DESCENDANT);
+            current = ancestor.parent();
+        }
+    }
+
+    /// Called by layout once the node's boxes are up to date
+    pub fn clear_layout_dirty(&self) {
+        self.layout_dirty.set(LayoutDirty::NONE);
+    }
+}
+
+/// `class` changes are tracked separately from other attributes
+fn attr_dirty_reason(name: &str) -> StyleDirty {
+    if name == "class" {
+        StyleDirty::CLASS
+    } else {
+        StyleDirty::ATTRIBUTE
+    }
 }
 
 /// Construct an element node with tag, attributes, and children
@@ -187,7 +393,7 @@ pub fn print_tree(node: &Rc<Node>, indent: usize) {
         print!("  ");
     }
 
-    match &node.node_type {
+    match &*node.node_type() {
         NodeType::Text(text) => println!("Text: {:?}", text),
         NodeType::Comment(comment) => println!("<!-- {} -->", comment),
         NodeType::Element(el) => {
@@ -206,3 +412,25 @@ pub fn print_tree(node: &Rc<Node>, indent: usize) {
         }
     }
 }
+
+/// A thread-safe copy of a DOM subtree, for styling on worker threads
+#[derive(Debug, Clone)]
+pub struct DomSnapshot {
+    pub node_type: NodeType,
+    pub state: ElementState,
+    pub children: Vec<DomSnapshot>,
+    /// Number of nodes in this subtree, including itself
+    pub size: usize,
+}
+
+/// Copy a DOM subtree into a `DomSnapshot`
+pub fn snapshot(node: &Rc<Node>) -> DomSnapshot {
+    let children: Vec<DomSnapshot> = node.children().iter().map(snapshot).collect();
+    let size = 1 + children.iter().map(|c| c.size).sum::<usize>();
+    DomSnapshot {
+        node_type: node.node_type().clone(),
+        state: node.state(),
+        children,
+        size,
+    }
+}
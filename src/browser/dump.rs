@@ -0,0 +1,191 @@
+//! dump.rs — Layout tree dumps for regression tests.
+//! A layout tree is written as text, one box per line and indented by depth: what kind of box
+//! it is, the DOM node it comes from, its rectangle and what it paints. Line boxes are
+//! numbered within their block and text runs give their baseline, so a test can compare a dump
+//! with an expected one instead of comparing pixels. Numbers are rounded to hundredths so the
+//! output does not change with float noise.
+//!
+//! ```
+//! use rusty_browser::browser::{
+//!     build_box_tree, build_layout_tree, compute_styles, dump_layout, parse_html, parse_stylesheet,
+//! };
+//!
+//! let document = parse_html("<div><p>Hello</p></div>");
+//! let styled = compute_styles(&document, &[parse_stylesheet("div, p { margin: 0; padding: 0 }")]);
+//! let layout = build_layout_tree(&build_box_tree(&styled).unwrap(), 800.0, 600.0);
+//! let dump = dump_layout(&layout);
+//! let lines: Vec<&str> = dump.lines().collect();
+//! assert_eq!(
+//!     lines[..3],
+//!     [
+//!         "box /div at (0,0) size 800x19.2",
+//!         "  box /div/p at (0,0) size 800x19.2",
+//!         "    line 1 at (0,0) size 800x19.2",
+//!     ]
+//! );
+//! // The text's size and baseline depend on the font it is drawn with, as in
+//! // `text /div/p/#text at (0,0.29) size 43.29x18.63 baseline 15.14 "Hello"`
+//! assert!(lines[3].starts_with("      text /div/p/#text at (0,"));
+//! assert!(lines[3].ends_with(r#" "Hello""#));
+//! ```
+
+use crate::browser::dom::{Node as DomNode, NodeType};
+use crate::browser::engine::PointerEvents;
+use crate::browser::renderer::{Color, Layer, LayoutBox};
+use std::fmt::Write;
+use std::rc::Rc;
+
+/// Write a layout tree as text (see the module docs for the format)
+pub fn dump_layout(root: &LayoutBox) -> String {
+    let mut out = String::new();
+    dump_box(&mut out, root, "box", 0);
+    out
+}
+
+/// Where a DOM node sits in its document, as a path of node names from the root:
+/// `/html/body/div[2]/#text`. Siblings with the same name are told apart by their position
+/// among them, counting from 1.
+pub fn node_path(node: &Rc<DomNode>) -> String {
+    let mut steps = Vec::new();
+    let mut current = Some(node.clone());
+    while let Some(node) = current {
+        let parent = node.parent();
+        let name = node_name(&node);
+        let step = match &parent {
+            Some(parent) => {
+                let siblings = parent.children();
+                let same: Vec<&Rc<DomNode>> = siblings.iter().filter(|s| node_name(s) == name).collect();
+                match same.iter().position(|s| Rc::ptr_eq(s, &node)) {
+                    Some(index) if same.len() > 1 => format!("{}[{}]", name, index + 1),
+                    _ => name,
+                }
+            }
+            None => name,
+        };
+        steps.push(step);
+        current = parent;
+    }
+    steps.reverse();
+    format!("/{}", steps.join("/"))
+}
+
+fn node_name(node: &DomNode) -> String {
+    match &*node.node_type() {
+        NodeType::Element(el) => el.tag_name.clone(),
+        NodeType::Text(_) => "#text".to_string(),
+        NodeType::Comment(_) => "#comment".to_string(),
+    }
+}
+
+/// One line for `layout`, then its children. `kind` says what it is; it depends on the parent,
+/// since only the parent knows a child is one of its lines.
+fn dump_box(out: &mut String, layout: &LayoutBox, kind: &str, depth: usize) {
+    let _ = write!(out, "{}{}", "  ".repeat(depth), kind);
+    if let Some(node) = layout.dom.as_ref().and_then(|dom| dom.upgrade()) {
+        let _ = write!(out, " {}", node_path(&node));
+    }
+    let _ = write!(
+        out,
+        " at ({},{}) size {}x{}",
+        num(layout.x),
+        num(layout.y),
+        num(layout.width),
+        num(layout.height)
+    );
+
+    match &layout.layer {
+        Some(Layer { fixed: true, .. }) => out.push_str(" fixed"),
+        Some(Layer { sticky: Some(_), .. }) => out.push_str(" sticky"),
+        Some(_) => out.push_str(" positioned"),
+        None => {}
+    }
+    if let Some(z_index) = layout.layer.as_ref().and_then(|layer| layer.z_index) {
+        let _ = write!(out, " z-index {}", z_index);
+    }
+    if let Some(clip) = &layout.clip {
+        let _ = write!(out, " overflow {}", format!("{:?}", clip.overflow).to_lowercase());
+        if clip.scroll != (0.0, 0.0) {
+            let _ = write!(out, " scrolled ({},{})", num(clip.scroll.0), num(clip.scroll.1));
+        }
+    }
+    if layout.pointer_events == PointerEvents::None {
+        out.push_str(" pointer-events none");
+    }
+    if let Some(background) = layout.background {
+        let _ = write!(out, " background {}", hex(background));
+    }
+    if let Some((color, width)) = layout.border {
+        let _ = write!(out, " border {} {}", num(width), hex(color));
+    }
+    if let Some(image) = &layout.image {
+        let dest = image.dest;
+        let _ = write!(
+            out,
+            " image {}x{} into ({},{}) size {}x{}",
+            image.image.width,
+            image.image.height,
+            num(dest.x),
+            num(dest.y),
+            num(dest.width),
+            num(dest.height)
+        );
+    }
+    if let Some(marker) = &layout.marker {
+        let _ = write!(out, " {}", format!("{:?}", marker.kind).to_lowercase());
+    }
+    if let Some(text) = &layout.text {
+        // A vertical line's baseline is a distance from its right edge
+        let baseline = if text.vertical {
+            layout.x + layout.width - text.baseline
+        } else {
+            layout.y + text.baseline
+        };
+        let _ = write!(out, " baseline {}", num(baseline));
+        if text.vertical {
+            out.push_str(" vertical");
+        }
+        if text.rtl {
+            out.push_str(" rtl");
+        }
+        let _ = write!(out, " {:?}", text.content);
+    }
+    out.push('\n');
+
+    let mut lines = 0;
+    for child in &layout.children {
+        let kind = if child.text.is_some() {
+            "text".to_string()
+        } else if child.marker.is_some() {
+            "marker".to_string()
+        } else if child.image.is_some() {
+            "image".to_string()
+        } else if child.line {
+            lines += 1;
+            format!("line {}", lines)
+        } else if kind.starts_with("line") {
+            // Inline boxes and inline-blocks sitting on a line
+            "inline".to_string()
+        } else if child.dom.is_none() {
+            "anonymous".to_string()
+        } else {
+            "box".to_string()
+        };
+        dump_box(out, child, &kind, depth + 1);
+    }
+}
+
+/// `#rrggbb`, with the alpha as a fourth byte when not opaque
+fn hex(color: Color) -> String {
+    let Color(r, g, b, a) = color;
+    match a {
+        255 => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        _ => format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a),
+    }
+}
+
+/// A number rounded to hundredths, without trailing zeros
+fn num(value: f32) -> String {
+    let rounded = (value * 100.0).round() / 100.0;
+    // `-0` and `0` print the same
+    format!("{}", rounded + 0.0)
+}
@@ -1,6 +1,21 @@
 //! engine.rs — Layout engine: Transforms styled DOM into positioned LayoutBoxes.
 
-use crate::browser::renderer::{LayoutBox, TextNode, Color};
+use crate::browser::dom::Node as DomNode;
+use crate::browser::flex::layout_flex;
+use crate::browser::grid::{grid_content_widths, layout_grid};
+use crate::browser::table::{layout_table, table_content_widths};
+use crate::browser::images::{image_state, DecodedImage, ImageState};
+use crate::browser::lazy::{deferred_height, record_height, suspend_deferral};
+use crate::browser::relayout::{cache_block, cached_block};
+use crate::browser::writing::{
+    current_space, enter_space, reorient_box, reorient_style, set_viewport, viewport,
+};
+use crate::browser::inline::{half_leading, inline_content_widths, layout_inline_run, measure_text, text_node};
+use crate::browser::paginate::Breaks;
+use crate::browser::renderer::{Clip, ImagePaint, Layer, LayoutBox, ListMarker, Sticky, TextNode};
+use std::rc::Weak;
+use std::sync::Arc;
+pub use crate::browser::renderer::Color;
 
 /// Edge values (top, right, bottom, left)
 #[derive(Debug, Clone, Copy, Default)]
@@ -20,6 +35,34 @@ pub struct Rect {
     pub height: f32,
 }
 
+impl Rect {
+    /// This rectangle grown outward by `edge` on every side
+    pub fn expanded_by(self, edge: EdgeSizes) -> Rect {
+        Rect {
+            x: self.x - edge.left,
+            y: self.y - edge.top,
+            width: self.width + edge.left + edge.right,
+            height: self.height + edge.top + edge.bottom,
+        }
+    }
+
+    /// Returns true if the point lies inside this rectangle
+    pub fn contains(self, x: f32, y: f32) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+
+    /// Smallest rectangle covering both
+    pub fn union(self, other: Rect) -> Rect {
+        let (x, y) = (self.x.min(other.x), self.y.min(other.y));
+        Rect {
+            x,
+            y,
+            width: (self.x + self.width).max(other.x + other.width) - x,
+            height: (self.y + self.height).max(other.y + other.height) - y,
+        }
+    }
+}
+
 /// Box dimensions (padding, borders, etc.)
 #[derive(Debug, Clone, Default)]
 pub struct Dimensions {
@@ -29,14 +72,502 @@ pub struct Dimensions {
     pub margin: EdgeSizes,
 }
 
+impl Dimensions {
+    pub fn padding_box(&self) -> Rect {
+        self.content.expanded_by(self.padding)
+    }
+
+    pub fn border_box(&self) -> Rect {
+        self.padding_box().expanded_by(self.border)
+    }
+
+    pub fn margin_box(&self) -> Rect {
+        self.border_box().expanded_by(self.margin)
+    }
+}
+
+/// `width`/`height`: a length, a percentage of the containing block, or `auto`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Length {
+    Auto,
+    Px(f32),
+    Percent(f32),
+}
+
+impl Length {
+    /// Used value in px; None for `auto`, and for percentages of an unknown reference
+    pub fn resolve(self, reference: Option<f32>) -> Option<f32> {
+        match self {
+            Length::Auto => None,
+            Length::Px(px) => Some(px),
+            Length::Percent(pct) => reference.map(|r| r * pct / 100.0),
+        }
+    }
+}
+
 /// Display types supported
 #[derive(Debug, Clone, PartialEq)]
 pub enum Display {
     Block,
     Inline,
+    /// Laid out as a block, placed on a line as a single unit
+    InlineBlock,
+    ListItem,
+    /// A block that establishes a new block formatting context
+    FlowRoot,
+    /// A block-level flex container
+    Flex,
+    /// A flex container placed on a line as a single unit
+    InlineFlex,
+    /// A block-level grid container
+    Grid,
+    /// A grid container placed on a line as a single unit
+    InlineGrid,
+    /// A block-level table
+    Table,
+    /// A table placed on a line as a single unit
+    InlineTable,
+    /// `<tbody>`
+    TableRowGroup,
+    /// `<thead>`: laid out before the other row groups
+    TableHeaderGroup,
+    /// `<tfoot>`: laid out after the other row groups
+    TableFooterGroup,
+    TableRow,
+    TableCell,
+    TableCaption,
+    TableColumn,
+    TableColumnGroup,
+    /// The element generates no box; its children take its place
+    Contents,
+    None,
+}
+
+impl Display {
+    /// The box lays its children out as flex items
+    pub fn is_flex(&self) -> bool {
+        matches!(self, Display::Flex | Display::InlineFlex)
+    }
+
+    /// The box places its children in grid cells
+    pub fn is_grid(&self) -> bool {
+        matches!(self, Display::Grid | Display::InlineGrid)
+    }
+
+    pub fn is_table(&self) -> bool {
+        matches!(self, Display::Table | Display::InlineTable)
+    }
+
+    /// Boxes that only make sense inside a table: rows, cells, captions, columns and their groups
+    pub fn is_table_part(&self) -> bool {
+        matches!(
+            self,
+            Display::TableRowGroup
+                | Display::TableHeaderGroup
+                | Display::TableFooterGroup
+                | Display::TableRow
+                | Display::TableCell
+                | Display::TableCaption
+                | Display::TableColumn
+                | Display::TableColumnGroup
+        )
+    }
+
+    /// `<tbody>`, `<thead>` and `<tfoot>`
+    pub fn is_row_group(&self) -> bool {
+        matches!(
+            self,
+            Display::TableRowGroup | Display::TableHeaderGroup | Display::TableFooterGroup
+        )
+    }
+}
+
+/// One piece of the `content` property of a generated box
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContentItem {
+    /// A literal string: `"→ "`
+    String(String),
+    /// The value of an attribute on the originating element: `attr(title)`
+    Attr(String),
+    /// The innermost value of a counter: `counter(item, upper-roman)`
+    Counter(String, ListStyleType),
+    /// All nested values of a counter joined by a separator: `counters(item, ".")`
+    Counters(String, String, ListStyleType),
+}
+
+/// `list-style-type` (also the counter style used by `counter()`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ListStyleType {
+    Disc,
+    Circle,
+    Square,
+    Decimal,
+    LowerAlpha,
+    UpperAlpha,
+    LowerRoman,
+    UpperRoman,
+    None,
+}
+
+/// `font-style`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FontStyle {
+    Normal,
+    Italic,
+    Oblique,
+}
+
+/// `list-style-position`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ListStylePosition {
+    Outside,
+    Inside,
+}
+
+/// `white-space`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WhiteSpace {
+    Normal,
+    Nowrap,
+    Pre,
+    PreWrap,
+    PreLine,
+}
+
+impl WhiteSpace {
+    /// Runs of spaces and tabs collapse to one space
+    pub fn collapses_spaces(self) -> bool {
+        matches!(self, WhiteSpace::Normal | WhiteSpace::Nowrap | WhiteSpace::PreLine)
+    }
+
+    /// Newlines in the source force line breaks
+    pub fn preserves_newlines(self) -> bool {
+        matches!(self, WhiteSpace::Pre | WhiteSpace::PreWrap | WhiteSpace::PreLine)
+    }
+
+    /// Lines may wrap at soft break opportunities
+    pub fn wraps(self) -> bool {
+        matches!(self, WhiteSpace::Normal | WhiteSpace::PreWrap | WhiteSpace::PreLine)
+    }
+}
+
+/// `text-align`; `start` and `end` follow the direction of the paragraph
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextAlign {
+    Start,
+    End,
+    Left,
+    Right,
+    Center,
+    Justify,
+}
+
+/// `direction`: the base direction of inline content
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Ltr,
+    Rtl,
+}
+
+/// `unicode-bidi`: how an element's text takes part in the Unicode bidi algorithm
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnicodeBidi {
+    Normal,
+    Embed,
+    Isolate,
+    BidiOverride,
+    IsolateOverride,
+    /// The direction is taken from the text itself
+    Plaintext,
+}
+
+/// `writing-mode`: which way lines run and in which direction they stack
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WritingMode {
+    HorizontalTb,
+    /// Lines run top to bottom and stack right to left
+    VerticalRl,
+    /// Lines run top to bottom and stack left to right
+    VerticalLr,
+}
+
+impl WritingMode {
+    pub fn is_vertical(self) -> bool {
+        self != WritingMode::HorizontalTb
+    }
+}
+
+/// `text-orientation`: how glyphs stand in vertical lines
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextOrientation {
+    /// CJK characters upright, others turned sideways
+    Mixed,
+    Upright,
+    Sideways,
+}
+
+/// `break-before`/`break-after`: whether a page break may or must fall beside the box.
+/// Ordered by strength, so the stronger of two adjoining values wins.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum BreakBetween {
+    Auto,
+    Avoid,
+    /// A forced page break (`page`, `left`, `right` and the legacy `always`)
+    Page,
+}
+
+/// `break-inside`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BreakInside {
+    Auto,
+    Avoid,
+}
+
+/// `line-height`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineHeight {
+    Normal,
+    /// A multiple of the element's font size
+    Number(f32),
+    Px(f32),
+}
+
+/// `float`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Float {
+    None,
+    Left,
+    Right,
+    /// The start side of the containing block's inline direction
+    InlineStart,
+    InlineEnd,
+}
+
+impl Float {
+    /// The physical side of a logical value in content of `direction`
+    pub fn resolve(self, direction: Direction) -> Float {
+        match (self, direction) {
+            (Float::InlineStart, Direction::Ltr) | (Float::InlineEnd, Direction::Rtl) => Float::Left,
+            (Float::InlineStart, Direction::Rtl) | (Float::InlineEnd, Direction::Ltr) => Float::Right,
+            (physical, _) => physical,
+        }
+    }
+}
+
+/// `position`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Position {
+    Static,
+    /// Laid out in flow, then shifted by its insets
+    Relative,
+    /// Out of flow, placed in its containing block by its insets
+    Absolute,
+    /// Like `absolute`, against the viewport, and does not scroll with the page
+    Fixed,
+    /// In flow, but kept inside the viewport while its containing block is visible
+    Sticky,
+}
+
+impl Position {
+    /// Absolutely positioned boxes take no space in the flow
+    pub fn is_out_of_flow(self) -> bool {
+        matches!(self, Position::Absolute | Position::Fixed)
+    }
+}
+
+/// `flex-direction`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlexDirection {
+    Row,
+    RowReverse,
+    Column,
+    ColumnReverse,
+}
+
+impl FlexDirection {
+    /// The main axis is horizontal
+    pub fn is_row(self) -> bool {
+        matches!(self, FlexDirection::Row | FlexDirection::RowReverse)
+    }
+
+    pub fn is_reverse(self) -> bool {
+        matches!(self, FlexDirection::RowReverse | FlexDirection::ColumnReverse)
+    }
+}
+
+/// `flex-wrap`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlexWrap {
+    Nowrap,
+    Wrap,
+    WrapReverse,
+}
+
+/// `justify-content` and `align-content`: where free space goes between and around boxes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContentDistribution {
+    FlexStart,
+    FlexEnd,
+    Center,
+    SpaceBetween,
+    SpaceAround,
+    SpaceEvenly,
+    /// Lines grow to share the free space (`align-content` only)
+    Stretch,
+}
+
+/// `align-items` and `align-self`: where an item sits across its flex line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlignItems {
+    Stretch,
+    FlexStart,
+    FlexEnd,
+    Center,
+    Baseline,
+}
+
+/// One end of a grid track's size range
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrackBreadth {
+    Px(f32),
+    Percent(f32),
+    /// A share of the space left after the other tracks (`fr`); only valid as a maximum
+    Fr(f32),
+    Auto,
+    MinContent,
+    MaxContent,
+}
+
+impl TrackBreadth {
+    /// Used value in px for a fixed breadth; percentages need a definite `reference`
+    pub fn fixed(self, reference: Option<f32>) -> Option<f32> {
+        match self {
+            TrackBreadth::Px(px) => Some(px),
+            TrackBreadth::Percent(pct) => reference.map(|r| r * pct / 100.0),
+            _ => None,
+        }
+    }
+}
+
+/// Size of a grid track: `minmax(min, max)`. A single breadth sets both, except `1fr`,
+/// which is `minmax(auto, 1fr)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrackSize {
+    pub min: TrackBreadth,
+    pub max: TrackBreadth,
+}
+
+impl TrackSize {
+    pub const AUTO: TrackSize = TrackSize {
+        min: TrackBreadth::Auto,
+        max: TrackBreadth::Auto,
+    };
+}
+
+/// `grid-auto-flow`: the axis auto-placed items fill first
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GridAutoFlow {
+    Row,
+    Column,
+}
+
+/// One edge of a grid item's placement (`grid-row-start`, `grid-column-end`, ...)
+#[derive(Debug, Clone, PartialEq)]
+pub enum GridLine {
+    Auto,
+    /// A line number; negative numbers count back from the end of the explicit grid
+    Line(i32),
+    /// Span this many tracks from the other edge
+    Span(u32),
+    /// The matching edge of a named area from `grid-template-areas`
+    Area(String),
+}
+
+/// `border-collapse`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BorderCollapse {
+    Separate,
+    Collapse,
+}
+
+/// `table-layout`: size columns from all the cells, or from the first row only
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TableLayout {
+    Auto,
+    Fixed,
+}
+
+/// `caption-side`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CaptionSide {
+    Top,
+    Bottom,
+}
+
+/// `vertical-align` (only table cells use it)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VerticalAlign {
+    Baseline,
+    Top,
+    Middle,
+    Bottom,
+}
+
+/// `object-fit`: how a replaced element's image fills its content box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ObjectFit {
+    Fill,
+    Contain,
+    Cover,
+    None,
+    ScaleDown,
+}
+
+/// `overflow`: what happens to content that does not fit in a box's padding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Overflow {
+    Visible,
+    Hidden,
+    Scroll,
+    Auto,
+}
+
+impl Overflow {
+    /// The user can scroll the box (`hidden` boxes only scroll programmatically)
+    pub fn is_scrollable(self) -> bool {
+        matches!(self, Overflow::Scroll | Overflow::Auto)
+    }
+}
+
+/// `pointer-events`: whether a box can be the target of pointer events
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PointerEvents {
+    Auto,
     None,
 }
 
+/// `clear`: which floats a box must be placed below
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Clear {
+    None,
+    Left,
+    Right,
+    Both,
+    InlineStart,
+    InlineEnd,
+}
+
+impl Clear {
+    /// The physical side of a logical value in content of `direction`
+    pub fn resolve(self, direction: Direction) -> Clear {
+        match (self, direction) {
+            (Clear::InlineStart, Direction::Ltr) | (Clear::InlineEnd, Direction::Rtl) => Clear::Left,
+            (Clear::InlineStart, Direction::Rtl) | (Clear::InlineEnd, Direction::Ltr) => Clear::Right,
+            (physical, _) => physical,
+        }
+    }
+}
+
 /// Style associated with a node
 #[derive(Debug, Clone)]
 pub struct Style {
@@ -44,18 +575,190 @@ pub struct Style {
     pub background: Option<Color>,
     pub border_color: Option<Color>,
     pub border_width: f32,
+    /// Margins in px; `auto` sides are 0 here and flagged below
     pub margin: EdgeSizes,
+    pub margin_left_auto: bool,
+    pub margin_right_auto: bool,
     pub padding: EdgeSizes,
+    pub width: Length,
+    pub height: Length,
+    pub float: Float,
+    pub clear: Clear,
+    pub position: Position,
+    /// Insets of positioned boxes: `top`, `right`, `bottom` and `left`
+    pub top: Length,
+    pub right: Length,
+    pub bottom: Length,
+    pub left: Length,
+    /// `z-index`; None for `auto`
+    pub z_index: Option<i32>,
+    pub flex_direction: FlexDirection,
+    pub flex_wrap: FlexWrap,
+    pub justify_content: ContentDistribution,
+    pub align_content: ContentDistribution,
+    pub align_items: AlignItems,
+    /// `align-self`; None for `auto` (the container's `align-items`)
+    pub align_self: Option<AlignItems>,
+    /// Gaps between flex lines and items in px
+    pub row_gap: f32,
+    pub column_gap: f32,
+    pub flex_grow: f32,
+    pub flex_shrink: f32,
+    pub flex_basis: Length,
+    pub order: i32,
+    /// Explicit grid tracks; empty for `none`
+    pub grid_template_columns: Vec<TrackSize>,
+    pub grid_template_rows: Vec<TrackSize>,
+    /// `grid-template-areas`: the area name of each cell, row by row (None for `.`)
+    pub grid_template_areas: Vec<Vec<Option<String>>>,
+    /// Size of tracks outside the explicit grid
+    pub grid_auto_columns: TrackSize,
+    pub grid_auto_rows: TrackSize,
+    pub grid_auto_flow: GridAutoFlow,
+    pub grid_row_start: GridLine,
+    pub grid_row_end: GridLine,
+    pub grid_column_start: GridLine,
+    pub grid_column_end: GridLine,
+    pub border_collapse: BorderCollapse,
+    /// `border-spacing`: horizontal and vertical space between cells in px
+    pub border_spacing: (f32, f32),
+    pub table_layout: TableLayout,
+    pub caption_side: CaptionSide,
+    pub vertical_align: VerticalAlign,
+    pub object_fit: ObjectFit,
+    pub overflow: Overflow,
+    pub pointer_events: PointerEvents,
+    pub break_before: BreakBetween,
+    pub break_after: BreakBetween,
+    pub break_inside: BreakInside,
     pub font_size: f32,
     pub font_family: String,
+    /// `font-weight` on the 1..=1000 scale (400 = normal, 700 = bold)
+    pub font_weight: u16,
+    pub font_style: FontStyle,
+    /// `font-stretch` as a percentage (100 = normal)
+    pub font_stretch: f32,
     pub color: Color,
+    /// Generated content for `::before`/`::after`/`::marker` (None = `normal`/`none`)
+    pub content: Option<Vec<ContentItem>>,
+    /// `counter-reset` as (name, value) pairs
+    pub counter_reset: Vec<(String, i32)>,
+    /// `counter-increment` as (name, step) pairs
+    pub counter_increment: Vec<(String, i32)>,
+    pub list_style_type: ListStyleType,
+    pub list_style_position: ListStylePosition,
+    pub white_space: WhiteSpace,
+    pub text_align: TextAlign,
+    pub direction: Direction,
+    pub unicode_bidi: UnicodeBidi,
+    pub writing_mode: WritingMode,
+    pub text_orientation: TextOrientation,
+    pub line_height: LineHeight,
+}
+
+impl Style {
+    /// `line-height` in px (`normal` is 1.2 times the font size)
+    pub fn used_line_height(&self) -> f32 {
+        match self.line_height {
+            LineHeight::Normal => self.font_size * 1.2,
+            LineHeight::Number(n) => self.font_size * n,
+            LineHeight::Px(px) => px,
+        }
+    }
+}
+
+/// Initial values for every style property
+pub fn default_style() -> Style {
+    Style {
+        display: Display::Inline,
+        background: None,
+        border_color: None,
+        border_width: 0.0,
+        margin: edges(0.0),
+        margin_left_auto: false,
+        margin_right_auto: false,
+        padding: edges(0.0),
+        width: Length::Auto,
+        height: Length::Auto,
+        float: Float::None,
+        clear: Clear::None,
+        position: Position::Static,
+        top: Length::Auto,
+        right: Length::Auto,
+        bottom: Length::Auto,
+        left: Length::Auto,
+        z_index: None,
+        flex_direction: FlexDirection::Row,
+        flex_wrap: FlexWrap::Nowrap,
+        justify_content: ContentDistribution::FlexStart,
+        align_content: ContentDistribution::Stretch,
+        align_items: AlignItems::Stretch,
+        align_self: None,
+        row_gap: 0.0,
+        column_gap: 0.0,
+        flex_grow: 0.0,
+        flex_shrink: 1.0,
+        flex_basis: Length::Auto,
+        order: 0,
+        grid_template_columns: vec![],
+        grid_template_rows: vec![],
+        grid_template_areas: vec![],
+        grid_auto_columns: TrackSize::AUTO,
+        grid_auto_rows: TrackSize::AUTO,
+        grid_auto_flow: GridAutoFlow::Row,
+        grid_row_start: GridLine::Auto,
+        grid_row_end: GridLine::Auto,
+        grid_column_start: GridLine::Auto,
+        grid_column_end: GridLine::Auto,
+        border_collapse: BorderCollapse::Separate,
+        border_spacing: (0.0, 0.0),
+        table_layout: TableLayout::Auto,
+        caption_side: CaptionSide::Top,
+        vertical_align: VerticalAlign::Baseline,
+        object_fit: ObjectFit::Fill,
+        overflow: Overflow::Visible,
+        pointer_events: PointerEvents::Auto,
+        break_before: BreakBetween::Auto,
+        break_after: BreakBetween::Auto,
+        break_inside: BreakInside::Auto,
+        font_size: 16.0,
+        font_family: "Arial".into(),
+        font_weight: 400,
+        font_style: FontStyle::Normal,
+        font_stretch: 100.0,
+        color: Color(0, 0, 0, 255),
+        content: None,
+        counter_reset: vec![],
+        counter_increment: vec![],
+        list_style_type: ListStyleType::Disc,
+        list_style_position: ListStylePosition::Outside,
+        white_space: WhiteSpace::Normal,
+        text_align: TextAlign::Start,
+        direction: Direction::Ltr,
+        unicode_bidi: UnicodeBidi::Normal,
+        writing_mode: WritingMode::HorizontalTb,
+        text_orientation: TextOrientation::Mixed,
+        line_height: LineHeight::Normal,
+    }
+}
+
+/// Edge sizes with the same value on every side
+pub fn edges(value: f32) -> EdgeSizes {
+    EdgeSizes {
+        top: value,
+        right: value,
+        bottom: value,
+        left: value,
+    }
 }
 
-/// DOM element types
+/// Box types
 #[derive(Debug, Clone)]
 pub enum NodeType {
     Text(String),
     Element(ElementData),
+    /// A block box with no element, wrapping inline content next to blocks
+    Anonymous,
 }
 
 #[derive(Debug, Clone)]
@@ -64,45 +767,1164 @@ pub struct ElementData {
     pub attributes: Vec<(String, String)>,
 }
 
+/// What a replaced element (`<img>`, `<video>`, `<canvas>`) shows in place of children
+#[derive(Debug, Clone)]
+pub enum Replaced {
+    Image(DecodedImage),
+    /// An image that failed to load, shown as its alt text
+    Alt(String),
+    /// Nothing to paint yet, at the element's default size: a `<canvas>`, or a `<video>` without a poster
+    Blank,
+    /// An image that hasn't been fetched, sized by its `width` and `height` until it arrives
+    Pending,
+}
+
+impl Replaced {
+    /// Intrinsic width, height and aspect ratio (width / height) in the element's style
+    fn intrinsic_size(&self, style: &Style) -> (f32, f32, Option<f32>) {
+        match self {
+            Replaced::Image(image) => {
+                let (width, height) = (image.width as f32, image.height as f32);
+                (width, height, (height > 0.0).then_some(width / height))
+            }
+            Replaced::Alt(alt) if alt.is_empty() => (0.0, 0.0, None),
+            Replaced::Pending => (0.0, 0.0, None),
+            Replaced::Alt(alt) => (measure_text(alt, style), style.used_line_height(), None),
+            Replaced::Blank => (300.0, 150.0, Some(2.0)),
+        }
+    }
+}
+
 /// Renderable DOM node (styled and ready for layout)
 #[derive(Debug, Clone)]
 pub struct Node {
     pub children: Vec<Node>,
     pub node_type: NodeType,
-    pub style: Style,
+    pub style: Arc<Style>,
+    /// The DOM node the box was generated from; None for generated boxes
+    pub dom: Option<Weak<DomNode>>,
+}
+
+impl Node {
+    /// Block-level boxes stack vertically in their parent's block formatting context
+    pub fn is_block_level(&self) -> bool {
+        !matches!(self.node_type, NodeType::Text(_))
+            && !self.is_floated()
+            && !self.is_out_of_flow()
+            && matches!(
+                self.style.display,
+                Display::Block
+                    | Display::ListItem
+                    | Display::FlowRoot
+                    | Display::Flex
+                    | Display::Grid
+                    | Display::Table
+            )
+    }
+
+    /// Floats are taken out of flow and shifted to one side of their line
+    pub fn is_floated(&self) -> bool {
+        !matches!(self.node_type, NodeType::Text(_)) && self.style.float != Float::None
+    }
+
+    /// Absolutely positioned boxes are placed in their containing block, outside the flow
+    pub fn is_out_of_flow(&self) -> bool {
+        !matches!(self.node_type, NodeType::Text(_)) && self.style.position.is_out_of_flow()
+    }
+
+    /// The box lays out its content in a block formatting context of its own
+    fn establishes_context(&self) -> bool {
+        self.is_floated()
+            || self.is_out_of_flow()
+            || self.style.display.is_flex()
+            || self.style.display.is_grid()
+            || self.style.display.is_table()
+            || self.style.overflow != Overflow::Visible
+            || matches!(
+                self.style.display,
+                Display::InlineBlock | Display::FlowRoot | Display::TableCell | Display::TableCaption
+            )
+    }
+
+    /// An outside `::marker` hangs beside its list item instead of taking part in its flow
+    pub fn is_outside_marker(&self) -> bool {
+        matches!(&self.node_type, NodeType::Element(el) if el.tag_name == "::marker")
+            && self.style.list_style_position == ListStylePosition::Outside
+    }
+
+    /// Whitespace-only text between blocks renders nothing (unless `white-space` preserves it)
+    pub fn is_collapsible_whitespace(&self) -> bool {
+        matches!(&self.node_type, NodeType::Text(t) if t.trim().is_empty())
+            && self.style.white_space.collapses_spaces()
+    }
+
+    /// Value of an element attribute
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        match &self.node_type {
+            NodeType::Element(el) => el.attributes.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str()),
+            _ => None,
+        }
+    }
+
+    /// `<img>`, `<video>` and `<canvas>` show external content instead of their children
+    pub fn is_replaced(&self) -> bool {
+        matches!(&self.node_type, NodeType::Element(el) if matches!(el.tag_name.as_str(), "img" | "video" | "canvas"))
+    }
+
+    /// The content of a replaced element, with its image if that has been loaded
+    pub fn replaced(&self) -> Option<Replaced> {
+        let NodeType::Element(el) = &self.node_type else {
+            return None;
+        };
+        let image = |src: Option<&str>| src.map_or(ImageState::Failed, image_state);
+        match el.tag_name.as_str() {
+            "img" => Some(match image(self.attribute("src")) {
+                ImageState::Loaded(image) => Replaced::Image(image),
+                ImageState::Pending => Replaced::Pending,
+                ImageState::Failed => Replaced::Alt(self.attribute("alt").unwrap_or_default().to_string()),
+            }),
+            "video" => Some(match image(self.attribute("poster")) {
+                ImageState::Loaded(image) => Replaced::Image(image),
+                ImageState::Pending | ImageState::Failed => Replaced::Blank,
+            }),
+            "canvas" => Some(Replaced::Blank),
+            _ => None,
+        }
+    }
+
+    /// Children that take part in this box's flow
+    fn in_flow_children(&self) -> impl Iterator<Item = &Node> {
+        self.children
+            .iter()
+            .filter(|c| {
+                c.style.display != Display::None
+                    && !c.is_outside_marker()
+                    && !c.is_collapsible_whitespace()
+                    && !c.is_out_of_flow()
+            })
+    }
+}
+
+/// Adjoining vertical margins waiting to collapse (CSS 2.1 §8.3.1): the collapsed margin is
+/// the largest positive margin plus the most negative one.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CollapsedMargin {
+    positive: f32,
+    negative: f32,
 }
 
-pub fn build_layout_tree(node: &Node, container_width: f32) -> LayoutBox {
+impl CollapsedMargin {
+    fn of(margin: f32) -> Self {
+        CollapsedMargin::default().join(margin)
+    }
+
+    fn join(self, margin: f32) -> Self {
+        CollapsedMargin {
+            positive: self.positive.max(margin),
+            negative: self.negative.min(margin),
+        }
+    }
+
+    fn merge(self, other: CollapsedMargin) -> Self {
+        CollapsedMargin {
+            positive: self.positive.max(other.positive),
+            negative: self.negative.min(other.negative),
+        }
+    }
+
+    fn resolve(self) -> f32 {
+        self.positive + self.negative
+    }
+}
+
+/// Floats placed so far in one block formatting context, as margin boxes
+#[derive(Debug, Default)]
+pub struct FloatContext {
+    left: Vec<Rect>,
+    right: Vec<Rect>,
+}
+
+impl FloatContext {
+    fn all(&self) -> impl Iterator<Item = &Rect> {
+        self.left.iter().chain(&self.right)
+    }
+
+    /// Number of floats placed so far
+    fn len(&self) -> usize {
+        self.left.len() + self.right.len()
+    }
+
+    /// The part of `left..right` that no float covers anywhere between `y` and `y + height`
+    pub fn band(&self, y: f32, height: f32, left: f32, right: f32) -> (f32, f32) {
+        let band_left = self
+            .left
+            .iter()
+            .filter(|r| overlaps(r, y, height))
+            .map(|r| r.x + r.width)
+            .fold(left, f32::max);
+        let band_right = self
+            .right
+            .iter()
+            .filter(|r| overlaps(r, y, height))
+            .map(|r| r.x)
+            .fold(right, f32::min);
+        (band_left, band_right)
+    }
+
+    /// The first float bottom below `y` among the floats between `y` and `y + height`:
+    /// the next position where the band can get wider
+    pub fn next_bottom(&self, y: f32, height: f32) -> Option<f32> {
+        self.all()
+            .filter(|r| overlaps(r, y, height))
+            .map(|r| r.y + r.height)
+            .reduce(f32::min)
+    }
+
+    /// Bottom of the lowest float `clear` moves a box below; None if there is none
+    pub fn clearance(&self, clear: Clear) -> Option<f32> {
+        let (left, right) = match clear {
+            Clear::None => (false, false),
+            Clear::Left => (true, false),
+            Clear::Right => (false, true),
+            Clear::Both => (true, true),
+            // Resolved to a side when the box tree is built; clearing both is the safe fallback
+            Clear::InlineStart | Clear::InlineEnd => (true, true),
+        };
+        self.left
+            .iter()
+            .filter(|_| left)
+            .chain(self.right.iter().filter(|_| right))
+            .map(|r| r.y + r.height)
+            .reduce(f32::max)
+    }
+
+    /// Bottom of the lowest float
+    pub fn bottom(&self) -> Option<f32> {
+        self.all().map(|r| r.y + r.height).reduce(f32::max)
+    }
+
+    /// Place a float's `width` × `height` margin box as high as possible at or below `y`, and
+    /// as far to its side of `left..right` as it goes (CSS 2.1 §9.5.1). Returns its top left corner.
+    pub fn place(&mut self, style: &Style, width: f32, height: f32, y: f32, left: f32, right: f32) -> (f32, f32) {
+        // Not above an earlier float, nor beside the floats it clears
+        let mut y = self.all().map(|r| r.y).fold(y, f32::max);
+        if let Some(clear_y) = self.clearance(style.clear) {
+            y = y.max(clear_y);
+        }
+        let (band_left, band_right) = loop {
+            let (band_left, band_right) = self.band(y, height, left, right);
+            if width <= band_right - band_left {
+                break (band_left, band_right);
+            }
+            match self.next_bottom(y, height) {
+                Some(bottom) => y = bottom,
+                // Wider than the container: it overflows on the far side
+                None => break (band_left, band_right),
+            }
+        };
+
+        let rect = Rect {
+            x: if style.float == Float::Right { band_right - width } else { band_left },
+            y,
+            width,
+            height,
+        };
+        if style.float == Float::Right {
+            self.right.push(rect);
+        } else {
+            self.left.push(rect);
+        }
+        (rect.x, rect.y)
+    }
+}
+
+/// A float overlaps the band from `y` to `y + height` (a band of no height still has a top line)
+fn overlaps(rect: &Rect, y: f32, height: f32) -> bool {
+    rect.height > 0.0 && rect.y < y + height.max(0.01) && rect.y + rect.height > y
+}
+
+/// Lay out a box tree in a viewport of `viewport_width` × `viewport_height`, which is also the
+/// containing block of fixed boxes and of absolute boxes with no positioned ancestor
+pub fn build_layout_tree(node: &Node, viewport_width: f32, viewport_height: f32) -> LayoutBox {
     let mut root_dimensions = Dimensions::default();
-    root_dimensions.content.width = container_width;
+    root_dimensions.content.width = viewport_width;
+    root_dimensions.content.height = viewport_height;
+    set_viewport(viewport_width, viewport_height);
+
+    // The root establishes a formatting context: its margins never collapse with its children
+    let BlockLayout {
+        mut layout,
+        positioned,
+        ..
+    } = build_layout_box(node, &root_dimensions, None, node.style.margin.top, None);
+    layout_positioned_boxes(positioned, root_dimensions.content, true, &mut layout.children);
+    layout
+}
 
-    build_layout_box(node, root_dimensions, 0.0, 0.0)
+/// A laid out block-level box
+struct BlockLayout<'n> {
+    layout: LayoutBox,
+    /// Margins adjoining the box's bottom edge: its own bottom margin, plus its last child's
+    /// when the two collapse
+    bottom_margin: CollapsedMargin,
+    /// The box has no height, content, border or padding, so its margins collapse through it
+    collapsed_through: bool,
+    /// Absolutely positioned descendants still looking for their containing block
+    positioned: Vec<Positioned<'n>>,
 }
 
-fn build_layout_box(node: &Node, container: Dimensions, offset_x: f32, mut offset_y: f32) -> LayoutBox {
-    let style = normalize_style(node.style.clone());
+/// An absolutely positioned box waiting for its containing block to be laid out, with the
+/// position it would have had in the flow (its static position)
+pub struct Positioned<'n> {
+    pub node: &'n Node,
+    pub x: f32,
+    pub y: f32,
+}
+
+/// Stand-in for a block lazy layout leaves unlaid: its border box at the reserved height
+fn deferred_block<'n>(node: &Node, container: &Dimensions, y: f32, height: f32) -> BlockLayout<'n> {
+    let style = normalize_style(&node.style);
+    let d = block_width(&style, container);
+    BlockLayout {
+        layout: LayoutBox {
+            x: container.content.x + d.margin.left,
+            y,
+            width: d.border_box().width,
+            height,
+            background: style.background,
+            dom: node.dom.clone(),
+            pointer_events: style.pointer_events,
+            ..LayoutBox::empty()
+        },
+        bottom_margin: CollapsedMargin::of(d.margin.bottom),
+        collapsed_through: false,
+        positioned: vec![],
+    }
+}
+
+/// Lay out a block-level box whose border edge starts at `y`. The caller has already resolved
+/// the margins above it, including any collapsing in from its first child (see `top_margin`).
+/// `floats` is the block formatting context the box takes part in, or None if the box
+/// establishes a new one; such boxes never collapse margins with their children.
+fn build_layout_box<'n>(
+    node: &'n Node,
+    container: &Dimensions,
+    container_height: Option<f32>,
+    y: f32,
+    mut floats: Option<&mut FloatContext>,
+) -> BlockLayout<'n> {
+    // Earlier boxes are only valid if no float from outside reaches into the block
+    let clear_of_floats = floats.as_ref().is_none_or(|f| f.clearance(Clear::Both).is_none_or(|c| c <= y));
+    if clear_of_floats {
+        if let Some((layout, bottom_margin, collapsed_through)) = cached_block(node, container, container_height, y) {
+            return BlockLayout {
+                layout,
+                bottom_margin,
+                collapsed_through,
+                positioned: vec![],
+            };
+        }
+    }
+
+    let floats_before = floats.as_deref().map(FloatContext::len);
+    let result = layout_block(
+        node,
+        normalize_style(&node.style),
+        container,
+        container_height,
+        y,
+        floats.as_deref_mut(),
+    );
+    // Boxes still waiting for a containing block outside, and floats placed in the outer
+    // context, cannot be replayed from the cache
+    let floats_added = floats.as_deref().map(FloatContext::len) != floats_before;
+    if clear_of_floats && !floats_added && result.positioned.is_empty() {
+        let (margin, through) = (result.bottom_margin, result.collapsed_through);
+        cache_block(node, container, container_height, y, &result.layout, margin, through);
+    }
+    result
+}
 
+/// `build_layout_box` with the style to use in place of the node's own
+fn layout_block<'n>(
+    node: &'n Node,
+    style: Arc<Style>,
+    container: &Dimensions,
+    container_height: Option<f32>,
+    y: f32,
+    floats: Option<&mut FloatContext>,
+) -> BlockLayout<'n> {
     if style.display == Display::None {
-        return LayoutBox::empty();
+        return BlockLayout {
+            layout: LayoutBox::empty(),
+            bottom_margin: CollapsedMargin::default(),
+            collapsed_through: true,
+            positioned: vec![],
+        };
     }
 
-    let x = offset_x + style.margin.left + style.border_width + style.padding.left;
-    let mut y = offset_y + style.margin.top + style.border_width + style.padding.top;
+    // A box in another writing mode lays out its content in coordinates of its own
+    if style.writing_mode != current_space() {
+        return layout_orthogonal(node, style, container, container_height, y);
+    }
+
+    // Content that does not move with the page scroll is laid out in full
+    let _eager = (style.overflow != Overflow::Visible || matches!(style.position, Position::Fixed | Position::Sticky))
+        .then(suspend_deferral);
+
+    // A replaced element is as large as its content, or its `width`/`height` scaled to match
+    let replaced = node.replaced().map(|content| {
+        let size = replaced_size(&content, &style, Some(container.content.width), container_height);
+        (content, size)
+    });
+    let mut d = match &replaced {
+        Some((_, (width, _))) => block_width(&Style { width: Length::Px(*width), ..(*style).clone() }, container),
+        None => block_width(&style, container),
+    };
+    d.content.x = container.content.x + d.margin.left + d.border.left + d.padding.left;
+    d.content.y = y + d.border.top + d.padding.top;
+
+    let new_context = floats.is_none();
+    let mut own_floats = FloatContext::default();
+    let floats = floats.unwrap_or(&mut own_floats);
+
+    if let Some((content, (_, height))) = replaced {
+        d.content.height = height;
+        let boxes = vec![replaced_box(content, &style, &d)];
+        let (layout, positioned) = finish_block(node, &style, &d, boxes, vec![], container, container_height);
+        return BlockLayout {
+            layout,
+            bottom_margin: CollapsedMargin::of(d.margin.bottom),
+            collapsed_through: false,
+            positioned,
+        };
+    }
 
-    let width = container.width
-        - (style.margin.left + style.margin.right + style.padding.left + style.padding.right + 2.0 * style.border_width)
-        .max(0.0);
+    let explicit_height = style.height.resolve(container_height);
+    if style.display.is_flex() || style.display.is_grid() || style.display.is_table() {
+        // Flex, grid and table containers never collapse margins with their contents
+        let (boxes, height, positioned) = if style.display.is_flex() {
+            let flex = layout_flex(node, &style, &d, explicit_height);
+            (flex.boxes, explicit_height.unwrap_or(flex.height), flex.positioned)
+        } else if style.display.is_grid() {
+            let grid = layout_grid(node, &style, &d, explicit_height);
+            (grid.boxes, explicit_height.unwrap_or(grid.height), grid.positioned)
+        } else {
+            // A table's height is at least what its rows need; its captions widen its margins
+            let table = layout_table(node, &style, &mut d, explicit_height);
+            (table.boxes, table.height, table.positioned)
+        };
+        d.content.height = height.max(0.0);
+        let (layout, positioned) = finish_block(node, &style, &d, boxes, positioned, container, container_height);
+        return BlockLayout {
+            layout,
+            bottom_margin: CollapsedMargin::of(d.margin.bottom),
+            collapsed_through: false,
+            positioned,
+        };
+    }
 
-    let mut height = 0.0;
-    let mut children = vec![];
+    let collapse_top = !new_context && d.border.top == 0.0 && d.padding.top == 0.0;
+    let collapse_bottom = !new_context && d.border.bottom == 0.0 && d.padding.bottom == 0.0 && explicit_height.is_none();
 
+    let mut flow = BlockFlow::new(&d, &style, explicit_height, collapse_top, floats);
     for child in &node.children {
-        
+        if child.style.display == Display::None || child.is_collapsible_whitespace() {
+            continue;
+        }
+        if child.is_outside_marker() {
+            flow.boxes.push(outside_marker_box(child, d.content));
+        } else if child.is_out_of_flow() {
+            flow.defer_positioned(child);
+        } else if child.is_block_level() {
+            flow.place_block(child);
+        } else {
+            flow.inline_run.push(child);
+        }
+    }
+    flow.flush_inline_run();
+    let trailing = flow.resolve_pending(CollapsedMargin::default());
+    let BlockFlow {
+        cursor,
+        pending,
+        has_content,
+        boxes,
+        floats,
+        positioned,
+        ..
+    } = flow;
+
+    // Without a bottom border or padding, the last child's bottom margin leaves through ours
+    let (mut auto_height, bottom_margin) = if collapse_bottom {
+        (cursor - d.content.y, pending.join(style.margin.bottom))
+    } else {
+        (cursor + trailing - d.content.y, CollapsedMargin::of(style.margin.bottom))
+    };
+    // A formatting context root grows to contain its floats (CSS 2.1 §10.6.7)
+    if let Some(bottom) = floats.bottom().filter(|_| new_context) {
+        auto_height = auto_height.max(bottom - d.content.y);
+    }
+    d.content.height = explicit_height.unwrap_or(auto_height).max(0.0);
+    let collapsed_through = collapse_top && collapse_bottom && d.content.height == 0.0 && !has_content;
+
+    let (layout, positioned) = finish_block(node, &style, &d, boxes, positioned, container, container_height);
+    BlockLayout {
+        layout,
+        bottom_margin,
+        collapsed_through,
+        positioned,
+    }
+}
+
+/// Used content width and height of a replaced element (CSS 2.1 §10.3.2, §10.6.2): an auto
+/// dimension follows the other through the aspect ratio, or is the intrinsic one
+fn replaced_size(
+    content: &Replaced,
+    style: &Style,
+    container_width: Option<f32>,
+    container_height: Option<f32>,
+) -> (f32, f32) {
+    let (intrinsic_width, intrinsic_height, ratio) = content.intrinsic_size(style);
+    let width = style.width.resolve(container_width);
+    let height = style.height.resolve(container_height);
+    let (width, height) = match (width, height) {
+        (Some(width), Some(height)) => (width, height),
+        (Some(width), None) => (width, ratio.map_or(intrinsic_height, |ratio| width / ratio)),
+        (None, Some(height)) => (ratio.map_or(intrinsic_width, |ratio| height * ratio), height),
+        (None, None) => (intrinsic_width, intrinsic_height),
+    };
+    (width.max(0.0), height.max(0.0))
+}
+
+/// The box filling a replaced element's content box: its image placed by `object-fit`, or
+/// its alt text inside a thin frame
+fn replaced_box(content: Replaced, style: &Style, d: &Dimensions) -> LayoutBox {
+    let area = d.content;
+    let mut layout = LayoutBox {
+        x: area.x,
+        y: area.y,
+        width: area.width,
+        height: area.height,
+        ..LayoutBox::empty()
+    };
+    match content {
+        Replaced::Image(image) => {
+            let dest = object_fit(style.object_fit, (image.width as f32, image.height as f32), area);
+            layout.image = Some(ImagePaint { image, dest });
+        }
+        Replaced::Alt(alt) if !alt.is_empty() => {
+            layout.border = Some((Color(160, 160, 160, 255), 1.0));
+            layout.text = Some(TextNode {
+                baseline: half_leading(style).0,
+                ..text_node(alt, style)
+            });
+        }
+        Replaced::Alt(_) | Replaced::Blank | Replaced::Pending => {}
+    }
+    layout
+}
+
+/// Where an image of `size` is drawn in `area` for `object-fit`, centered
+fn object_fit(fit: ObjectFit, size: (f32, f32), area: Rect) -> Rect {
+    if fit == ObjectFit::Fill || size.0 <= 0.0 || size.1 <= 0.0 {
+        return area;
     }
+    let contain = (area.width / size.0).min(area.height / size.1);
+    let scale = match fit {
+        ObjectFit::Contain => contain,
+        ObjectFit::Cover => (area.width / size.0).max(area.height / size.1),
+        ObjectFit::ScaleDown => contain.min(1.0),
+        _ => 1.0,
+    };
+    let (width, height) = (size.0 * scale, size.1 * scale);
+    Rect {
+        x: area.x + (area.width - width) / 2.0,
+        y: area.y + (area.height - height) / 2.0,
+        width,
+        height,
+    }
+}
+
+/// Build the box of a block whose size and children are laid out. Absolutely positioned
+/// descendants are placed in it if it is their containing block, and returned otherwise.
+fn finish_block<'n>(
+    node: &Node,
+    style: &Style,
+    d: &Dimensions,
+    mut boxes: Vec<LayoutBox>,
+    positioned: Vec<Positioned<'n>>,
+    container: &Dimensions,
+    container_height: Option<f32>,
+) -> (LayoutBox, Vec<Positioned<'n>>) {
+    // Sticky children stay inside this box, whose height is only known now
+    for child in &mut boxes {
+        if let Some(sticky) = child.layer.as_mut().and_then(|layer| layer.sticky.as_mut()) {
+            sticky.bounds = d.content;
+        }
+    }
+    // A positioned box is the containing block of its absolute descendants (CSS 2.1 §10.1)
+    let positioned = if style.position == Position::Static {
+        positioned
+    } else {
+        layout_positioned_boxes(positioned, d.padding_box(), false, &mut boxes)
+    };
+
+    // A box that does not show its overflow clips it to its padding box (CSS 2.1 §11.1.1)
+    let clip = (style.overflow != Overflow::Visible).then(|| {
+        let rect = d.padding_box();
+        Clip {
+            rect,
+            content: boxes.iter().fold(rect, |area, child| area.union(child.overflow_extent())),
+            overflow: style.overflow,
+            scroll: (0.0, 0.0),
+        }
+    });
+    let border_box = d.border_box();
+    let layout = LayoutBox {
+        x: border_box.x,
+        y: border_box.y,
+        width: border_box.width,
+        height: border_box.height,
+        background: style.background,
+        border: style
+            .border_color
+            .filter(|_| style.border_width > 0.0)
+            .map(|color| (color, style.border_width)),
+        layer: layer(style, container, container_height),
+        clip,
+        dom: node.dom.clone(),
+        pointer_events: style.pointer_events,
+        breaks: Breaks::of(style),
+        children: boxes,
+        ..LayoutBox::empty()
+    };
+    (layout, positioned)
+}
+
+/// Painting layer of a positioned box
+fn layer(style: &Style, container: &Dimensions, container_height: Option<f32>) -> Option<Layer> {
+    let width = Some(container.content.width);
+    match style.position {
+        Position::Static => None,
+        position => Some(Layer {
+            z_index: style.z_index,
+            fixed: position == Position::Fixed,
+            sticky: (position == Position::Sticky).then(|| Sticky {
+                top: style.top.resolve(container_height),
+                right: style.right.resolve(width),
+                bottom: style.bottom.resolve(container_height),
+                left: style.left.resolve(width),
+                bounds: container.content,
+            }),
+        }),
+    }
+}
+
+/// How far `position: relative` shifts a box from where it was laid out (CSS 2.1 §9.4.3).
+/// `left` wins over `right` and `top` over `bottom`.
+pub fn relative_offset(style: &Style, container_width: f32, container_height: Option<f32>) -> (f32, f32) {
+    if style.position != Position::Relative {
+        return (0.0, 0.0);
+    }
+    let dx = match (style.left.resolve(Some(container_width)), style.right.resolve(Some(container_width))) {
+        (Some(left), _) => left,
+        (None, Some(right)) => -right,
+        (None, None) => 0.0,
+    };
+    let dy = match (style.top.resolve(container_height), style.bottom.resolve(container_height)) {
+        (Some(top), _) => top,
+        (None, Some(bottom)) => -bottom,
+        (None, None) => 0.0,
+    };
+    (dx, dy)
+}
+
+/// Lay out the positioned boxes whose containing block is `block` (a padding box) into
+/// `children`. Fixed boxes are left to the viewport unless `with_fixed`; the rest are returned.
+fn layout_positioned_boxes<'n>(
+    positioned: Vec<Positioned<'n>>,
+    block: Rect,
+    with_fixed: bool,
+    children: &mut Vec<LayoutBox>,
+) -> Vec<Positioned<'n>> {
+    let mut rest = Vec::new();
+    for entry in positioned {
+        if with_fixed || entry.node.style.position != Position::Fixed {
+            children.push(layout_absolute(entry.node, block, entry.x, entry.y));
+        } else {
+            rest.push(entry);
+        }
+    }
+    rest
+}
+
+/// Lay out an absolutely positioned box in its containing block (CSS 2.1 §10.3.7, §10.6.4).
+/// Insets place it against the containing block's edges; with both `auto` on an axis it stays
+/// at its static position (`x`, `y`). With both insets set, an `auto` width or height stretches
+/// between them; otherwise an `auto` width shrinks to fit.
+fn layout_absolute(node: &Node, block: Rect, x: f32, y: f32) -> LayoutBox {
+    let style = &node.style;
+    let left = style.left.resolve(Some(block.width));
+    let right = style.right.resolve(Some(block.width));
+    let top = style.top.resolve(Some(block.height));
+    let bottom = style.bottom.resolve(Some(block.height));
+
+    let mut container = Dimensions::default();
+    container.content.width = match (style.width, left, right) {
+        // Percentages resolve against the containing block; the margin absorbs the rest
+        (Length::Px(_) | Length::Percent(_), _, _) => block.width,
+        (Length::Auto, Some(left), Some(right)) => (block.width - left - right).max(0.0),
+        (Length::Auto, left, right) => shrink_to_fit(node, block.width - left.or(right).unwrap_or(0.0)),
+    };
+    let BlockLayout {
+        mut layout,
+        positioned,
+        ..
+    } = build_layout_box(node, &container, Some(block.height), style.margin.top, None);
+    layout.translate(style.margin.left - layout.x, 0.0);
+    if let (Length::Auto, Some(top), Some(bottom)) = (style.height, top, bottom) {
+        layout.height = (block.height - top - bottom - style.margin.top - style.margin.bottom).max(0.0);
+    }
+    // Fixed descendants of an absolute box are placed against it too
+    layout_positioned_boxes(positioned, padding_box(&layout, style), true, &mut layout.children);
+
+    let width = layout.width + style.margin.left + style.margin.right;
+    let height = layout.height + style.margin.top + style.margin.bottom;
+    let dx = match (left, right) {
+        (Some(left), _) => block.x + left,
+        (None, Some(right)) => block.x + block.width - right - width,
+        (None, None) => x,
+    };
+    let dy = match (top, bottom) {
+        (Some(top), _) => block.y + top,
+        (None, Some(bottom)) => block.y + block.height - bottom - height,
+        (None, None) => y,
+    };
+    layout.translate(dx, dy);
+    layout
 }
 
-fn normalize(mut style: Style) -> Style {
-    style.border_width = style.border_width.max(0.0);
-    style.font_size = style.font_size.max(1.0);
+/// Children of one block container, stacked top to bottom
+struct BlockFlow<'a, 'n> {
+    container: &'a Dimensions,
+    /// Style of the container, for `text-align` and the line-height strut of its lines
+    style: &'a Style,
+    container_height: Option<f32>,
+    /// Bottom border edge of the last in-flow box
+    cursor: f32,
+    /// Margins below the last in-flow box that have not been resolved yet
+    pending: CollapsedMargin,
+    /// The next block is the first child and its top margin collapses with the parent's
+    collapse_with_parent: bool,
+    /// Margins of leading children that collapsed through into the parent's top margin. They
+    /// already moved the container, so they take part in collapsing but add no more space.
+    applied: CollapsedMargin,
+    /// Consecutive inline-level children (and floats among them), laid out together as lines
+    inline_run: Vec<&'n Node>,
+    has_content: bool,
+    boxes: Vec<LayoutBox>,
+    /// Floats of the block formatting context the container takes part in
+    floats: &'a mut FloatContext,
+    /// Absolutely positioned descendants, for the nearest positioned ancestor to lay out
+    positioned: Vec<Positioned<'n>>,
+}
+
+impl<'a, 'n> BlockFlow<'a, 'n> {
+    fn new(
+        container: &'a Dimensions,
+        style: &'a Style,
+        container_height: Option<f32>,
+        collapse_top: bool,
+        floats: &'a mut FloatContext,
+    ) -> Self {
+        BlockFlow {
+            container,
+            style,
+            container_height,
+            cursor: container.content.y,
+            pending: CollapsedMargin::default(),
+            collapse_with_parent: collapse_top,
+            applied: CollapsedMargin::default(),
+            inline_run: vec![],
+            has_content: false,
+            boxes: vec![],
+            floats,
+            positioned: vec![],
+        }
+    }
+
+    /// An absolutely positioned child keeps the position it would have had in the flow until
+    /// its containing block is laid out; among inline content that is its place on the line
+    fn defer_positioned(&mut self, child: &'n Node) {
+        if !self.inline_run.is_empty() {
+            self.inline_run.push(child);
+            return;
+        }
+        self.positioned.push(Positioned {
+            node: child,
+            x: self.container.content.x,
+            y: self.cursor + self.resolve_pending(CollapsedMargin::default()),
+        });
+    }
+
+    /// Space between the cursor and a box whose top margin is `top`, after collapsing it with
+    /// the pending margins
+    fn resolve_pending(&self, top: CollapsedMargin) -> f32 {
+        let applied = self.applied.resolve();
+        self.pending.merge(top).merge(self.applied).resolve() - applied
+    }
+
+    fn place_block(&mut self, child: &'n Node) {
+        self.flush_inline_run();
+
+        // A first child's top margin was already applied when the parent was positioned
+        let top = top_margin(child);
+        let first = std::mem::take(&mut self.collapse_with_parent);
+        let offset = if first { 0.0 } else { self.resolve_pending(top) };
+
+        // Clearance moves the border edge below the floats it names (CSS 2.1 §9.5.2)
+        let mut y = self.cursor + offset;
+        let mut cleared = false;
+        if let Some(clear_y) = self.floats.clearance(child.style.clear).filter(|c| *c > y) {
+            y = clear_y;
+            cleared = true;
+        }
+
+        // Lazy layout leaves blocks far from the viewport unlaid (see `lazy`)
+        let deferred = deferred_height(child, y, self.container.content.width);
+        let mut result = if let Some(height) = deferred {
+            deferred_block(child, self.container, y, height)
+        } else if child.establishes_context() || child.style.writing_mode != self.style.writing_mode {
+            // A new formatting context may not overlap floats, so it is laid out beside them
+            let content = &self.container.content;
+            let (left, right) = self.floats.band(y, 0.0, content.x, content.x + content.width);
+            let mut beside = self.container.clone();
+            beside.content.x = left;
+            beside.content.width = (right - left).max(0.0);
+            build_layout_box(child, &beside, self.container_height, y, None)
+        } else {
+            build_layout_box(child, self.container, self.container_height, y, Some(&mut *self.floats))
+        };
+        if deferred.is_none() {
+            record_height(child, result.layout.height);
+        }
+        if result.collapsed_through && !cleared {
+            // Its top and bottom margins adjoin and collapse with the ones around it
+            self.pending = self.pending.merge(top).merge(result.bottom_margin);
+            if first {
+                self.applied = self.applied.merge(top);
+            }
+        } else {
+            self.cursor = result.layout.y + result.layout.height;
+            self.pending = result.bottom_margin;
+            self.applied = CollapsedMargin::default();
+            self.has_content = true;
+        }
+
+        let (dx, dy) = relative_offset(&child.style, self.container.content.width, self.container_height);
+        result.layout.translate(dx, dy);
+        self.positioned.extend(result.positioned);
+        self.boxes.push(result.layout);
+    }
+
+    /// Line boxes separate the margins above them from those below; floats alone do not
+    fn flush_inline_run(&mut self) {
+        if self.inline_run.is_empty() {
+            return;
+        }
+        let run = std::mem::take(&mut self.inline_run);
+        let y = self.cursor + self.resolve_pending(CollapsedMargin::default());
+
+        let content = &self.container.content;
+        let inline = layout_inline_run(&run, self.style, content.x, y, content.width, self.floats);
+        self.boxes.extend(inline.floats);
+        self.positioned.extend(inline.positioned);
+        if inline.lines.is_empty() {
+            return;
+        }
+        self.cursor = y + inline.height;
+        self.pending = CollapsedMargin::default();
+        self.applied = CollapsedMargin::default();
+        self.collapse_with_parent = false;
+        self.has_content = true;
+        self.boxes.extend(inline.lines);
+    }
+}
+
+/// The margins adjoining a block's top border edge: its own top margin and, when it has no top
+/// border or padding, those of its first in-flow block child (recursively)
+fn top_margin(node: &Node) -> CollapsedMargin {
+    let style = &node.style;
+    let margin = CollapsedMargin::of(style.margin.top);
+    if style.border_width > 0.0 || style.padding.top > 0.0 {
+        return margin;
+    }
+    match node.in_flow_children().next() {
+        Some(first) if first.is_block_level() && first.style.writing_mode == style.writing_mode => {
+            margin.merge(top_margin(first))
+        }
+        _ => margin,
+    }
+}
+
+/// Used width and horizontal margins of a block in normal flow (CSS 2.1 §10.3.3)
+fn block_width(style: &Style, container: &Dimensions) -> Dimensions {
+    let mut d = Dimensions {
+        padding: style.padding,
+        border: edges(style.border_width),
+        margin: style.margin,
+        ..Dimensions::default()
+    };
+    let available = container.content.width;
+    let chrome = d.padding.left + d.padding.right + d.border.left + d.border.right;
+
+    match style.width.resolve(Some(available)) {
+        // `auto` margins become 0 and the width fills the rest
+        None => d.content.width = (available - d.margin.left - d.margin.right - chrome).max(0.0),
+        Some(width) => {
+            d.content.width = width.max(0.0);
+            let underflow = available - d.content.width - chrome - d.margin.left - d.margin.right;
+            if underflow < 0.0 {
+                // Overconstrained: `auto` margins are 0 and the right margin absorbs the difference
+                d.margin.right += underflow;
+            } else {
+                match (style.margin_left_auto, style.margin_right_auto) {
+                    (true, true) => {
+                        d.margin.left = underflow / 2.0;
+                        d.margin.right = underflow / 2.0;
+                    }
+                    (true, false) => d.margin.left = underflow,
+                    (false, _) => d.margin.right += underflow,
+                }
+            }
+        }
+    }
+    d
+}
+
+/// Lay out an `inline-block` with its margin box at the origin, at its shrink-to-fit width
+/// (CSS 2.1 §10.3.9). The inline layout moves it onto its line.
+/// Absolutely positioned descendants with no positioned ancestor inside are placed against it.
+pub fn layout_atomic_inline(node: &Node, available: f32) -> LayoutBox {
+    let style = &node.style;
+
+    let mut container = Dimensions::default();
+    container.content.width = match style.width.resolve(Some(available)) {
+        Some(_) => available,
+        // An auto width fills the container, so size the container to the used width
+        None => shrink_to_fit(node, available),
+    };
+    let BlockLayout {
+        mut layout,
+        positioned,
+        ..
+    } = build_layout_box(node, &container, None, style.margin.top, None);
+    layout_positioned_boxes(positioned, padding_box(&layout, style), true, &mut layout.children);
+    // `auto` margins take no space here, so keep the margin box at the origin
+    layout.translate(style.margin.left - layout.x, 0.0);
+    layout
+}
+
+/// Lay out a block whose writing mode is not the one layout is working in: in the logical
+/// coordinates of its own mode, with its boxes then mapped back. Its inline size is the
+/// container's extent along its inline axis; across the container's block axis that is the
+/// container's height, or the viewport's if unknown (CSS Writing Modes 3 §7.3). It establishes
+/// a formatting context and places absolute descendants with no positioned ancestor inside.
+fn layout_orthogonal<'n>(
+    node: &Node,
+    style: Arc<Style>,
+    container: &Dimensions,
+    container_height: Option<f32>,
+    y: f32,
+) -> BlockLayout<'n> {
+    let space = current_space();
+    let mode = style.writing_mode;
+    let (inline_size, block_size) = if mode.is_vertical() == space.is_vertical() {
+        (container.content.width, container_height)
+    } else {
+        let (viewport_width, viewport_height) = viewport();
+        let across = if mode.is_vertical() { viewport_height } else { viewport_width };
+        (container_height.unwrap_or(across), Some(container.content.width))
+    };
+
+    // Its descendants are already styled in its coordinates (see `reorient_tree`)
+    let own = Arc::new(reorient_style(&style, space, mode));
+    let mut inner = Dimensions::default();
+    inner.content.width = inline_size;
+    let mut layout = {
+        let _space = enter_space(mode);
+        // Its lines do not follow the page's scroll axis, so it is laid out in full
+        let _eager = suspend_deferral();
+        let BlockLayout {
+            mut layout,
+            positioned,
+            ..
+        } = layout_block(node, own.clone(), &inner, block_size, own.margin.top, None);
+        layout_positioned_boxes(positioned, padding_box(&layout, &own), true, &mut layout.children);
+        layout
+    };
+
+    // Map its margin box into the container's coordinates, with the top border edge at `y`
+    let frame = (inline_size, layout.y + layout.height + own.margin.bottom);
+    reorient_box(&mut layout, mode, space, frame);
+    layout.translate(container.content.x, y - style.margin.top);
+    BlockLayout {
+        layout,
+        bottom_margin: CollapsedMargin::of(style.margin.bottom),
+        collapsed_through: false,
+        positioned: vec![],
+    }
+}
+
+/// Lay out a flex or grid item at a used border-box `width` and, if given, `height` in place of its own
+/// `width`/`height`, with its border box at the origin. Absolutely positioned descendants
+/// with no positioned ancestor inside are returned, relative to the same origin.
+pub fn layout_sized<'n>(node: &'n Node, width: f32, height: Option<f32>) -> (LayoutBox, Vec<Positioned<'n>>) {
+    let mut shared = normalize_style(&node.style);
+    let style = Arc::make_mut(&mut shared);
+    let chrome_x = style.padding.left + style.padding.right + 2.0 * style.border_width;
+    style.width = Length::Px((width - chrome_x).max(0.0));
+    if let Some(height) = height {
+        let chrome_y = style.padding.top + style.padding.bottom + 2.0 * style.border_width;
+        style.height = Length::Px((height - chrome_y).max(0.0));
+    }
+
+    let mut container = Dimensions::default();
+    container.content.width = width + style.margin.left + style.margin.right;
+    let BlockLayout {
+        mut layout,
+        mut positioned,
+        ..
+    } = layout_block(node, shared, &container, None, 0.0, None);
+    let (dx, dy) = (-layout.x, -layout.y);
+    layout.translate(dx, dy);
+    for entry in &mut positioned {
+        entry.x += dx;
+        entry.y += dy;
+    }
+    (layout, positioned)
+}
+
+/// Padding box of a laid out block
+fn padding_box(layout: &LayoutBox, style: &Style) -> Rect {
+    let border_box = Rect {
+        x: layout.x,
+        y: layout.y,
+        width: layout.width,
+        height: layout.height,
+    };
+    border_box.expanded_by(edges(-style.border_width))
+}
+
+/// Shrink-to-fit margin box width in `available` space: the max-content width, unless that
+/// overflows, but never below the min-content width (CSS 2.1 §10.3.5)
+fn shrink_to_fit(node: &Node, available: f32) -> f32 {
+    let chrome = horizontal_chrome(&node.style);
+    let (min, max) = content_widths(node);
+    max.min((available - chrome).max(min)) + chrome
+}
+
+/// Min-content and max-content widths of a box including its margins, borders and padding
+pub fn outer_widths(node: &Node) -> (f32, f32) {
+    let style = &node.style;
+    let chrome = horizontal_chrome(style);
+    match style.width {
+        Length::Px(width) => (width + chrome, width + chrome),
+        _ => {
+            let (min, max) = content_widths(node);
+            (min + chrome, max + chrome)
+        }
+    }
+}
+
+/// Min-content and max-content widths of a box's content
+fn content_widths(node: &Node) -> (f32, f32) {
+    let children: Vec<&Node> = node.in_flow_children().collect();
+    let style = &node.style;
+    if let Some(content) = node.replaced() {
+        // Percentages of the (unknown) container count as `auto`
+        let (width, _) = replaced_size(&content, style, None, None);
+        (width, width)
+    } else if style.display.is_grid() {
+        grid_content_widths(node)
+    } else if style.display.is_table() {
+        table_content_widths(node)
+    } else if style.display.is_flex() && style.flex_direction.is_row() {
+        // Items sit side by side, but may wrap or shrink down to their own minimum
+        let gaps = style.column_gap * children.len().saturating_sub(1) as f32;
+        children
+            .iter()
+            .map(|c| outer_widths(c))
+            .fold((0.0, gaps), |(min, max), (c_min, c_max)| (min.max(c_min), max + c_max))
+    } else if style.display.is_flex() || children.iter().any(|c| c.is_block_level()) {
+        children
+            .iter()
+            .map(|c| outer_widths(c))
+            .fold((0.0, 0.0), |(min, max), (c_min, c_max)| (min.max(c_min), max.max(c_max)))
+    } else {
+        inline_content_widths(&children)
+    }
+}
+
+/// Horizontal margins, borders and padding
+fn horizontal_chrome(style: &Style) -> f32 {
+    style.margin.left + style.margin.right + style.padding.left + style.padding.right + 2.0 * style.border_width
+}
+
+fn normalize_style(style: &Arc<Style>) -> Arc<Style> {
+    if style.border_width >= 0.0 && style.font_size >= 1.0 {
+        return style.clone();
+    }
+    let mut style = style.clone();
+    let fixed = Arc::make_mut(&mut style);
+    fixed.border_width = fixed.border_width.max(0.0);
+    fixed.font_size = fixed.font_size.max(1.0);
     style
-}
\ No newline at end of file
+}
+
+/// Lay out an outside `::marker`: a box hanging in the gutter beside its list item's first line,
+/// on the left or, for right-to-left items, on the right of the item's `content` box.
+/// Default bullets are painted as shapes so they don't depend on the font having the glyph.
+pub fn outside_marker_box(marker: &Node, content: Rect) -> LayoutBox {
+    let style = &marker.style;
+    let text = marker
+        .children
+        .iter()
+        .find_map(|child| match &child.node_type {
+            NodeType::Text(t) => Some(t.clone()),
+            _ => None,
+        })
+        .unwrap_or_default();
+
+    let line_height = style.used_line_height();
+    let width = measure_text(&text, style);
+    let is_bullet = matches!(
+        style.list_style_type,
+        ListStyleType::Disc | ListStyleType::Circle | ListStyleType::Square
+    );
+
+    let mut marker_box = LayoutBox {
+        x: match style.direction {
+            Direction::Ltr => content.x - width,
+            Direction::Rtl => content.x + content.width,
+        },
+        y: content.y,
+        width,
+        height: line_height,
+        ..LayoutBox::empty()
+    };
+    if is_bullet && style.content.is_none() {
+        marker_box.marker = Some(ListMarker {
+            kind: style.list_style_type,
+            color: style.color,
+            font_size: style.font_size,
+        });
+    } else {
+        // Centre the text's line box on the marker box like any other line
+        marker_box.text = Some(TextNode {
+            baseline: half_leading(style).0,
+            ..text_node(text, style)
+        });
+    }
+    marker_box
+}
@@ -0,0 +1,408 @@
+//! flex.rs — Flex layout (CSS Flexible Box Layout, simplified).
+//! Items are sized along the main axis from their flex basis and flex factors, broken into
+//! lines when the container wraps, then aligned across each line and the lines across the
+//! container. Auto margins are only resolved along a row's main axis.
+
+use crate::browser::engine::{
+    layout_sized, outer_widths, relative_offset, AlignItems, ContentDistribution, Dimensions, Display, FlexWrap,
+    Length, Node, Positioned, Style,
+};
+use crate::browser::inline::first_baseline;
+use crate::browser::renderer::LayoutBox;
+use std::ops::Range;
+
+/// Laid out items of a flex container
+pub struct FlexLayout<'n> {
+    /// Item boxes in `order`, placed in the container's content box
+    pub boxes: Vec<LayoutBox>,
+    /// Content height the items need, used when the container's `height` is `auto`
+    pub height: f32,
+    /// Absolutely positioned children and descendants still looking for their containing block
+    pub positioned: Vec<Positioned<'n>>,
+}
+
+/// A flex item while it is being sized. Main sizes are border-box sizes.
+struct Item<'n> {
+    node: &'n Node,
+    align: AlignItems,
+    /// Flex base size
+    base: f32,
+    /// Base size clamped to the minimum
+    hypothetical: f32,
+    min: f32,
+    /// Margins along the main axis (`auto` counts as 0)
+    margin_main: f32,
+    margin_cross: f32,
+    /// Resolved main size
+    target: f32,
+    frozen: bool,
+    /// Border-box width of an item in a column, chosen before its height is known
+    cross_width: f32,
+    layout: LayoutBox,
+    positioned: Vec<Positioned<'n>>,
+    /// Cross size of the margin box
+    outer_cross: f32,
+    /// Distance from the margin box top to the first baseline
+    baseline: f32,
+}
+
+impl<'n> Item<'n> {
+    fn new(node: &'n Node, container: &Style, d: &Dimensions, main_size: Option<f32>) -> Self {
+        let style = &node.style;
+        let row = container.flex_direction.is_row();
+        let align = style.align_self.unwrap_or(container.align_items);
+        let chrome_x = style.padding.left + style.padding.right + 2.0 * style.border_width;
+        let chrome_y = style.padding.top + style.padding.bottom + 2.0 * style.border_width;
+        let margin_x = style.margin.left + style.margin.right;
+        let margin_y = style.margin.top + style.margin.bottom;
+        let (min_content, max_content) = outer_widths(node);
+
+        let (base, min, cross_width) = if row {
+            let specified = style.width.resolve(Some(d.content.width)).map(|w| w + chrome_x);
+            let base = match style.flex_basis.resolve(main_size) {
+                Some(basis) => basis + chrome_x,
+                None => specified.unwrap_or(max_content - margin_x),
+            };
+            // An item won't shrink below its min-content width, unless its width is smaller
+            let min = (min_content - margin_x).min(specified.unwrap_or(f32::INFINITY));
+            (base, min.max(chrome_x), 0.0)
+        } else {
+            let cross_width = match style.width.resolve(Some(d.content.width)) {
+                Some(width) => width + chrome_x,
+                None if align == AlignItems::Stretch => d.content.width - margin_x,
+                None => max_content.min(d.content.width).max(min_content) - margin_x,
+            }
+            .max(0.0);
+            let base = match style.flex_basis.resolve(main_size).or(style.height.resolve(main_size)) {
+                Some(height) => height + chrome_y,
+                None => layout_sized(node, cross_width, None).0.height,
+            };
+            (base, chrome_y, cross_width)
+        };
+
+        Item {
+            node,
+            align,
+            base,
+            hypothetical: base.max(min),
+            min,
+            margin_main: if row { margin_x } else { margin_y },
+            margin_cross: if row { margin_y } else { margin_x },
+            target: base.max(min),
+            frozen: false,
+            cross_width,
+            layout: LayoutBox::empty(),
+            positioned: vec![],
+            outer_cross: 0.0,
+            baseline: 0.0,
+        }
+    }
+
+    /// Lay the item out at its main size and `cross` border-box size, if given
+    fn lay_out(&mut self, row: bool, cross: Option<f32>) {
+        let (layout, positioned) = if row {
+            layout_sized(self.node, self.target, cross)
+        } else {
+            layout_sized(self.node, cross.unwrap_or(self.cross_width), Some(self.target))
+        };
+        let margin = &self.node.style.margin;
+        self.outer_cross = self.margin_cross + if row { layout.height } else { layout.width };
+        // Boxes without text are aligned by their bottom margin edge
+        self.baseline = margin.top + first_baseline(&layout).unwrap_or(layout.height);
+        self.layout = layout;
+        self.positioned = positioned;
+    }
+}
+
+/// Lay out the children of a flex container whose content box is `d.content`. `height` is the
+/// container's used height, or None if it depends on the items.
+pub fn layout_flex<'n>(node: &'n Node, style: &Style, d: &Dimensions, height: Option<f32>) -> FlexLayout<'n> {
+    let row = style.flex_direction.is_row();
+    let (main_size, cross_size) = if row {
+        (Some(d.content.width), height)
+    } else {
+        (height, Some(d.content.width))
+    };
+    let (main_gap, cross_gap) = if row {
+        (style.column_gap, style.row_gap)
+    } else {
+        (style.row_gap, style.column_gap)
+    };
+
+    // Absolutely positioned children are not items; their static position is the content origin
+    let mut positioned = Vec::new();
+    let mut children = Vec::new();
+    for child in &node.children {
+        if child.style.display == Display::None {
+            continue;
+        }
+        if child.is_out_of_flow() {
+            positioned.push(Positioned {
+                node: child,
+                x: d.content.x,
+                y: d.content.y,
+            });
+        } else {
+            children.push(child);
+        }
+    }
+    children.sort_by_key(|c| c.style.order);
+    let mut items: Vec<Item> = children.into_iter().map(|c| Item::new(c, style, d, main_size)).collect();
+    if items.is_empty() {
+        return FlexLayout {
+            boxes: vec![],
+            height: 0.0,
+            positioned,
+        };
+    }
+
+    let wrap = style.flex_wrap != FlexWrap::Nowrap;
+    let lines = break_lines(&items, main_size.filter(|_| wrap), main_gap);
+    for line in &lines {
+        resolve_flexible_lengths(&mut items[line.clone()], main_size, main_gap);
+    }
+    for item in &mut items {
+        item.lay_out(row, None);
+    }
+    // Without a definite height, a column is as long as its longest line
+    let main_total = main_size.unwrap_or_else(|| {
+        lines
+            .iter()
+            .map(|line| main_extent(&items[line.clone()], main_gap))
+            .fold(0.0, f32::max)
+    });
+
+    // Cross size of each line: its tallest item, with baseline-aligned items lined up
+    let mut line_cross: Vec<f32> = lines
+        .iter()
+        .map(|line| {
+            let (mut above, mut below, mut max) = (0.0f32, 0.0f32, 0.0f32);
+            for item in &items[line.clone()] {
+                if row && item.align == AlignItems::Baseline {
+                    above = above.max(item.baseline);
+                    below = below.max(item.outer_cross - item.baseline);
+                } else {
+                    max = max.max(item.outer_cross);
+                }
+            }
+            max.max(above + below)
+        })
+        .collect();
+    // A single-line container's line fills its cross size
+    if let (false, Some(cross)) = (wrap, cross_size) {
+        line_cross[0] = cross;
+    }
+
+    let line_count = lines.len() as f32;
+    let cross_gaps = cross_gap * (line_count - 1.0);
+    let free = cross_size.map_or(0.0, |cross| cross - line_cross.iter().sum::<f32>() - cross_gaps);
+    if wrap && style.align_content == ContentDistribution::Stretch && free > 0.0 {
+        for cross in &mut line_cross {
+            *cross += free / line_count;
+        }
+    }
+    let lines_used = line_cross.iter().sum::<f32>() + cross_gaps;
+    let cross_total = cross_size.unwrap_or(lines_used);
+    let (mut line_start, line_between) = if wrap {
+        distribute(style.align_content, cross_total - lines_used, lines.len())
+    } else {
+        (0.0, 0.0)
+    };
+
+    let reverse = style.flex_direction.is_reverse();
+    for (line, &cross) in lines.iter().zip(&line_cross) {
+        let line_items = &mut items[line.clone()];
+        let free = main_total - main_extent(line_items, main_gap);
+        let auto_margins: usize = line_items
+            .iter()
+            .filter(|_| row)
+            .map(|i| i.node.style.margin_left_auto as usize + i.node.style.margin_right_auto as usize)
+            .sum();
+        // Auto margins absorb positive free space before `justify-content` sees it
+        let (mut main, between, auto_share) = if auto_margins > 0 && free > 0.0 {
+            (0.0, 0.0, free / auto_margins as f32)
+        } else {
+            let (start, between) = distribute(style.justify_content, free, line_items.len());
+            (start, between, 0.0)
+        };
+        let max_baseline = line_items
+            .iter()
+            .filter(|i| row && i.align == AlignItems::Baseline)
+            .map(|i| i.baseline)
+            .fold(0.0, f32::max);
+
+        for item in line_items.iter_mut() {
+            let node = item.node;
+            let item_style = &node.style;
+            if row && item_style.margin_left_auto {
+                main += auto_share;
+            }
+            let outer_main = item.target + item.margin_main;
+            let main_pos = if reverse { main_total - main - outer_main } else { main };
+            main += outer_main + main_gap + between;
+            if row && item_style.margin_right_auto {
+                main += auto_share;
+            }
+
+            let cross_auto = if row {
+                item_style.height == Length::Auto
+            } else {
+                item_style.width == Length::Auto
+            };
+            if item.align == AlignItems::Stretch && cross_auto {
+                let stretched = (cross - item.margin_cross).max(0.0);
+                if (stretched + item.margin_cross - item.outer_cross).abs() > 0.01 {
+                    item.lay_out(row, Some(stretched));
+                }
+            }
+            let offset = match item.align {
+                AlignItems::FlexStart | AlignItems::Stretch => 0.0,
+                AlignItems::FlexEnd => cross - item.outer_cross,
+                AlignItems::Center => (cross - item.outer_cross) / 2.0,
+                AlignItems::Baseline if row => max_baseline - item.baseline,
+                AlignItems::Baseline => 0.0,
+            };
+            let mut cross_pos = line_start + offset;
+            // `wrap-reverse` stacks lines from the cross end and swaps start and end within them
+            if style.flex_wrap == FlexWrap::WrapReverse {
+                cross_pos = cross_total - cross_pos - item.outer_cross;
+            }
+
+            let (x, y) = if row { (main_pos, cross_pos) } else { (cross_pos, main_pos) };
+            let (rx, ry) = relative_offset(item_style, d.content.width, height);
+            let dx = d.content.x + x + item_style.margin.left + rx;
+            let dy = d.content.y + y + item_style.margin.top + ry;
+            item.layout.translate(dx, dy);
+            for entry in &mut item.positioned {
+                entry.x += dx;
+                entry.y += dy;
+            }
+        }
+        line_start += cross + cross_gap + line_between;
+    }
+
+    let mut boxes = Vec::with_capacity(items.len());
+    for item in items {
+        boxes.push(item.layout);
+        positioned.extend(item.positioned);
+    }
+    FlexLayout {
+        boxes,
+        height: if row { cross_total } else { main_total },
+        positioned,
+    }
+}
+
+/// Split items into lines no longer than `limit` along the main axis; one line if None
+fn break_lines(items: &[Item], limit: Option<f32>, gap: f32) -> Vec<Range<usize>> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    let mut used = 0.0;
+    for (i, item) in items.iter().enumerate() {
+        let outer = item.hypothetical + item.margin_main;
+        if let Some(limit) = limit {
+            if i > start && used + gap + outer > limit {
+                lines.push(start..i);
+                start = i;
+            }
+        }
+        used = if i > start { used + gap + outer } else { outer };
+    }
+    lines.push(start..items.len());
+    lines
+}
+
+/// Margin-box length of a line of items along the main axis, with gaps
+fn main_extent(items: &[Item], gap: f32) -> f32 {
+    let gaps = gap * items.len().saturating_sub(1) as f32;
+    items.iter().map(|i| i.target + i.margin_main).sum::<f32>() + gaps
+}
+
+/// Grow or shrink the items of one line to fill `available` (CSS Flexbox §9.7). Items that
+/// would go below their minimum are frozen there and the rest is shared out again.
+fn resolve_flexible_lengths(items: &mut [Item], available: Option<f32>, gap: f32) {
+    for item in items.iter_mut() {
+        item.target = item.hypothetical;
+    }
+    let Some(available) = available else {
+        return;
+    };
+    let gaps = gap * items.len().saturating_sub(1) as f32;
+    let available = available - gaps - items.iter().map(|i| i.margin_main).sum::<f32>();
+    let growing = items.iter().map(|i| i.hypothetical).sum::<f32>() < available;
+    let factor = |item: &Item| {
+        if growing {
+            item.node.style.flex_grow
+        } else {
+            item.node.style.flex_shrink
+        }
+    };
+
+    for item in items.iter_mut() {
+        item.frozen = factor(item) == 0.0 || (!growing && item.base < item.hypothetical);
+    }
+    let remaining = |items: &[Item]| {
+        available
+            - items
+                .iter()
+                .map(|i| if i.frozen { i.target } else { i.base })
+                .sum::<f32>()
+    };
+    let initial_free = remaining(items);
+
+    while items.iter().any(|i| !i.frozen) {
+        let mut free = remaining(items);
+        let factors: f32 = items.iter().filter(|i| !i.frozen).map(factor).sum();
+        // Factors summing below 1 only take that fraction of the free space
+        if factors < 1.0 && (initial_free * factors).abs() < free.abs() {
+            free = initial_free * factors;
+        }
+        let scaled: f32 = items
+            .iter()
+            .filter(|i| !i.frozen)
+            .map(|i| i.node.style.flex_shrink * i.base)
+            .sum();
+
+        let mut violation = 0.0;
+        let mut sizes = vec![0.0; items.len()];
+        for (item, unclamped) in items.iter_mut().zip(&mut sizes).filter(|(i, _)| !i.frozen) {
+            *unclamped = if growing {
+                item.base + free * item.node.style.flex_grow / factors
+            } else if scaled > 0.0 {
+                item.base + free * item.node.style.flex_shrink * item.base / scaled
+            } else {
+                item.base
+            };
+            item.target = unclamped.max(item.min);
+            violation += item.target - *unclamped;
+        }
+        // Freeze everything when nothing was clamped, otherwise only the clamped items
+        let mut froze = false;
+        for (item, unclamped) in items.iter_mut().zip(&sizes).filter(|(i, _)| !i.frozen) {
+            if violation <= 0.0 || item.target > *unclamped {
+                item.frozen = true;
+                froze = true;
+            }
+        }
+        // Sizes that are not numbers clamp nothing; stop rather than loop forever
+        if !froze {
+            items.iter_mut().for_each(|item| item.frozen = true);
+        }
+    }
+}
+
+/// Offset of the first box and extra space between boxes for `count` boxes sharing `free`
+/// space. Distributions that would overflow fall back to start or center alignment.
+fn distribute(mode: ContentDistribution, free: f32, count: usize) -> (f32, f32) {
+    let n = count as f32;
+    match mode {
+        ContentDistribution::FlexStart | ContentDistribution::Stretch => (0.0, 0.0),
+        ContentDistribution::FlexEnd => (free, 0.0),
+        ContentDistribution::Center => (free / 2.0, 0.0),
+        ContentDistribution::SpaceBetween if free > 0.0 && count > 1 => (0.0, free / (n - 1.0)),
+        ContentDistribution::SpaceBetween => (0.0, 0.0),
+        ContentDistribution::SpaceAround if free > 0.0 => (free / n / 2.0, free / n),
+        ContentDistribution::SpaceEvenly if free > 0.0 => (free / (n + 1.0), free / (n + 1.0)),
+        ContentDistribution::SpaceAround | ContentDistribution::SpaceEvenly => (free / 2.0, 0.0),
+    }
+}
@@ -0,0 +1,439 @@
+//! fonts.rs — Web fonts: `@font-face` descriptors, loading, and CSS font matching.
+
+use crate::browser::css::{split_top_level, unquote, FontFaceRule, Stylesheet};
+use crate::browser::engine::{FontStyle, Style};
+use crate::browser::style::{parse_font_stretch, parse_font_style, parse_font_weight};
+use crate::browser::woff::decode_font;
+use crate::net::{fetch_resource, RequestType};
+use ab_glyph::{Font, FontVec};
+use font_kit::handle::Handle;
+use font_kit::source::SystemSource;
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use url::Url;
+
+/// Where a face's data comes from
+#[derive(Debug, Clone, PartialEq)]
+pub enum FontSource {
+    /// `url(...) format(...)`, already resolved against the stylesheet URL
+    Url { url: String, format: Option<String> },
+    /// `local(...)`: an installed font by PostScript name
+    Local(String),
+}
+
+/// A font face declared with `@font-face`
+#[derive(Debug, Clone)]
+pub struct FontFace {
+    pub family: String,
+    pub sources: Vec<FontSource>,
+    /// Supported weight range (`font-weight: 100 900` for variable fonts)
+    pub weight: (u16, u16),
+    pub style: FontStyle,
+    /// Supported `font-stretch` range in percent
+    pub stretch: (f32, f32),
+    /// Codepoints covered by the face (empty = everything)
+    pub unicode_range: Vec<RangeInclusive<u32>>,
+}
+
+impl FontFace {
+    /// Interpret the descriptors of an `@font-face` rule; None without a family or source
+    pub fn from_rule(rule: &FontFaceRule, base_url: &str) -> Option<Self> {
+        let mut face = FontFace {
+            family: String::new(),
+            sources: vec![],
+            weight: (400, 400),
+            style: FontStyle::Normal,
+            stretch: (100.0, 100.0),
+            unicode_range: vec![],
+        };
+
+        for decl in &rule.declarations {
+            let value = decl.value.trim();
+            match decl.name.as_str() {
+                "font-family" => face.family = unquote(value).unwrap_or_else(|| value.to_string()),
+                "src" => face.sources = parse_sources(value, base_url),
+                "font-weight" => {
+                    let weights: Vec<u16> = value
+                        .split_whitespace()
+                        .filter_map(|w| parse_font_weight(w, 400))
+                        .collect();
+                    if let (Some(&lo), Some(&hi)) = (weights.first(), weights.last()) {
+                        face.weight = (lo.min(hi), lo.max(hi));
+                    }
+                }
+                "font-style" => {
+                    if let Some(style) = parse_font_style(value) {
+                        face.style = style;
+                    }
+                }
+                "font-stretch" => {
+                    let stretches: Vec<f32> = value.split_whitespace().filter_map(parse_font_stretch).collect();
+                    if let (Some(&lo), Some(&hi)) = (stretches.first(), stretches.last()) {
+                        face.stretch = (lo.min(hi), lo.max(hi));
+                    }
+                }
+                "unicode-range" => face.unicode_range = parse_unicode_range(value),
+                _ => {}
+            }
+        }
+
+        if face.family.is_empty() || face.sources.is_empty() {
+            return None;
+        }
+        Some(face)
+    }
+
+    /// Returns true if the face's `unicode-range` includes `ch`
+    pub fn covers(&self, ch: char) -> bool {
+        self.unicode_range.is_empty() || self.unicode_range.iter().any(|r| r.contains(&(ch as u32)))
+    }
+}
+
+/// A loaded font: parsed for outlines and metrics, with its raw data kept for shaping
+#[derive(Clone)]
+pub struct LoadedFont {
+    pub font: Arc<FontVec>,
+    /// Face index within a font collection
+    pub index: u32,
+}
+
+impl LoadedFont {
+    pub fn from_vec(data: Vec<u8>, index: u32) -> Option<Self> {
+        let font = FontVec::try_from_vec_and_index(data, index).ok()?;
+        Some(LoadedFont {
+            font: Arc::new(font),
+            index,
+        })
+    }
+
+    /// The font file's bytes
+    pub fn data(&self) -> &[u8] {
+        self.font.as_slice()
+    }
+
+    /// Factor from font units to px at `size` px per em
+    pub fn scale(&self, size: f32) -> f32 {
+        size / self.font.units_per_em().unwrap_or(1000.0)
+    }
+
+    /// Returns true if both are the same loaded face
+    pub fn same(&self, other: &LoadedFont) -> bool {
+        Arc::ptr_eq(&self.font, &other.font)
+    }
+}
+
+/// The font properties text asks for
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontQuery {
+    /// `font-family` list in priority order
+    pub families: Vec<String>,
+    pub weight: u16,
+    pub style: FontStyle,
+    pub stretch: f32,
+}
+
+impl FontQuery {
+    pub fn new(font_family: &str, weight: u16, style: FontStyle, stretch: f32) -> Self {
+        let families = split_top_level(font_family, ',')
+            .into_iter()
+            .map(str::trim)
+            .filter(|f| !f.is_empty())
+            .map(|f| unquote(f).unwrap_or_else(|| f.to_string()))
+            .collect();
+        FontQuery {
+            families,
+            weight,
+            style,
+            stretch,
+        }
+    }
+
+    pub fn from_style(style: &Style) -> Self {
+        Self::new(&style.font_family, style.font_weight, style.font_style, style.font_stretch)
+    }
+}
+
+/// Registry of web font faces with CSS font matching and lazy loading
+#[derive(Default)]
+pub struct FontMatcher {
+    faces: Vec<FontFace>,
+    /// Loaded faces by index; failed loads are cached as None so they are not refetched
+    loaded: HashMap<usize, Option<LoadedFont>>,
+}
+
+impl FontMatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the `@font-face` rules of a stylesheet fetched from `base_url`
+    pub fn add_stylesheet(&mut self, sheet: &Stylesheet, base_url: &str) {
+        self.faces
+            .extend(sheet.font_faces.iter().filter_map(|rule| FontFace::from_rule(rule, base_url)));
+    }
+
+    pub fn faces(&self) -> &[FontFace] {
+        &self.faces
+    }
+
+    /// Returns true if any web face is declared for `family`
+    pub fn has_family(&self, family: &str) -> bool {
+        self.faces.iter().any(|f| f.family.eq_ignore_ascii_case(family))
+    }
+
+    /// Pick the face of `family` to draw `ch` with (CSS Fonts 4 §5.2).
+    /// Faces are narrowed by stretch, then style, then weight; among faces with identical
+    /// descriptors the last declared one whose `unicode-range` covers `ch` wins.
+    pub fn select(&self, family: &str, query: &FontQuery, ch: char) -> Option<usize> {
+        let candidates: Vec<usize> = (0..self.faces.len())
+            .filter(|&i| self.faces[i].family.eq_ignore_ascii_case(family))
+            .collect();
+
+        let key = |face: &FontFace| {
+            (
+                stretch_distance(query.stretch, face.stretch),
+                style_rank(query.style, face.style),
+                weight_distance(query.weight, face.weight),
+            )
+        };
+        let best = candidates
+            .iter()
+            .map(|&i| key(&self.faces[i]))
+            .min_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))?;
+
+        candidates
+            .into_iter()
+            .rev()
+            .find(|&i| key(&self.faces[i]) == best && self.faces[i].covers(ch))
+    }
+
+    /// Load a face (once), trying its sources in order
+    pub fn load(&mut self, index: usize) -> Option<LoadedFont> {
+        if let Some(font) = self.loaded.get(&index) {
+            return font.clone();
+        }
+
+        let face = self.faces.get(index)?;
+        let font = face.sources.iter().find_map(|source| match load_source(source) {
+            Ok(font) => Some(font),
+            Err(e) => {
+                log::warn!("Font source {:?} for '{}' failed: {:?}", source, face.family, e);
+                None
+            }
+        });
+
+        self.loaded.insert(index, font.clone());
+        font
+    }
+}
+
+/// Errors while loading a font face
+#[derive(Debug)]
+pub enum FontLoadError {
+    Fetch(String),
+    Decode(String),
+    NotInstalled(String),
+}
+
+/// Fetch or look up one source and turn it into a usable font
+fn load_source(source: &FontSource) -> Result<LoadedFont, FontLoadError> {
+    let bytes = match source {
+        FontSource::Url { url, .. } => {
+            let result = fetch_resource(url, RequestType::Font)
+                .map_err(|e| FontLoadError::Fetch(format!("{:?}", e)))?;
+            decode_font(&result.data).map_err(|e| FontLoadError::Decode(format!("{:?}", e)))?
+        }
+        FontSource::Local(name) => {
+            let handle = SystemSource::new()
+                .select_by_postscript_name(name)
+                .map_err(|_| FontLoadError::NotInstalled(name.clone()))?;
+            return font_from_handle(&handle).ok_or_else(|| FontLoadError::NotInstalled(name.clone()));
+        }
+    };
+
+    LoadedFont::from_vec(bytes, 0).ok_or_else(|| FontLoadError::Decode("unreadable font data".into()))
+}
+
+/// Read an installed font from a font-kit handle
+pub fn font_from_handle(handle: &Handle) -> Option<LoadedFont> {
+    let (bytes, index) = match handle {
+        Handle::Memory { bytes, font_index } => (bytes.to_vec(), *font_index),
+        Handle::Path { path, font_index } => (std::fs::read(path).ok()?, *font_index),
+    };
+    LoadedFont::from_vec(bytes, index)
+}
+
+/// Parses the `src` descriptor: `url(a.woff2) format("woff2"), local(Foo)`.
+/// Sources in formats we cannot decode are dropped.
+fn parse_sources(value: &str, base_url: &str) -> Vec<FontSource> {
+    let base = Url::parse(base_url).ok();
+    let mut sources = Vec::new();
+
+    for entry in split_top_level(value, ',') {
+        let mut url = None;
+        let mut format = None;
+        let mut local = None;
+
+        for token in split_top_level(entry.trim(), ' ').into_iter().filter(|t| !t.is_empty()) {
+            let Some((func, arg)) = token.strip_suffix(')').and_then(|t| t.split_once('(')) else {
+                continue;
+            };
+            let arg = arg.trim();
+            let arg = unquote(arg).unwrap_or_else(|| arg.to_string());
+            match func.to_lowercase().as_str() {
+                "url" => url = Some(arg),
+                "format" => format = Some(arg.to_lowercase()),
+                "local" => local = Some(arg),
+                _ => {}
+            }
+        }
+
+        if let Some(name) = local {
+            sources.push(FontSource::Local(name));
+        } else if let Some(url) = url {
+            let supported = matches!(
+                format.as_deref(),
+                None | Some("woff2") | Some("woff") | Some("truetype") | Some("opentype")
+            );
+            let resolved = match &base {
+                Some(base) => base.join(&url).map(|u| u.to_string()).unwrap_or(url),
+                None => url,
+            };
+            if supported {
+                sources.push(FontSource::Url { url: resolved, format });
+            }
+        }
+    }
+    sources
+}
+
+/// Parses `unicode-range`: `U+0025-00FF, U+4??, U+131`
+fn parse_unicode_range(value: &str) -> Vec<RangeInclusive<u32>> {
+    value
+        .split(',')
+        .filter_map(|part| {
+            let part = part.trim();
+            let hex = part.strip_prefix("U+").or_else(|| part.strip_prefix("u+"))?;
+            if let Some((start, end)) = hex.split_once('-') {
+                let start = u32::from_str_radix(start, 16).ok()?;
+                let end = u32::from_str_radix(end, 16).ok()?;
+                Some(start..=end)
+            } else if hex.contains('?') {
+                let start = u32::from_str_radix(&hex.replace('?', "0"), 16).ok()?;
+                let end = u32::from_str_radix(&hex.replace('?', "F"), 16).ok()?;
+                Some(start..=end)
+            } else {
+                let cp = u32::from_str_radix(hex, 16).ok()?;
+                Some(cp..=cp)
+            }
+        })
+        .collect()
+}
+
+/// Stretch preference: exact, then narrower (for <= 100%) or wider (for > 100%), then the other way
+fn stretch_distance(desired: f32, range: (f32, f32)) -> (u8, f32) {
+    if desired >= range.0 && desired <= range.1 {
+        return (0, 0.0);
+    }
+    let nearest = if range.1 < desired { range.1 } else { range.0 };
+    let narrower = nearest < desired;
+    let preferred = if desired <= 100.0 { narrower } else { !narrower };
+    (if preferred { 1 } else { 2 }, (nearest - desired).abs())
+}
+
+/// Style preference order for each requested style
+fn style_rank(desired: FontStyle, available: FontStyle) -> u8 {
+    use FontStyle::*;
+    let order = match desired {
+        Italic => [Italic, Oblique, Normal],
+        Oblique => [Oblique, Italic, Normal],
+        Normal => [Normal, Oblique, Italic],
+    };
+    order.iter().position(|s| *s == available).unwrap_or(order.len()) as u8
+}
+
+/// Weight preference: exact; 400–500 looks up to 500 first, then lighter, then heavier;
+/// lighter weights look lighter first and heavier weights look heavier first.
+fn weight_distance(desired: u16, range: (u16, u16)) -> (u8, u16) {
+    if desired >= range.0 && desired <= range.1 {
+        return (0, 0);
+    }
+    let nearest = if range.1 < desired { range.1 } else { range.0 };
+    let distance = nearest.abs_diff(desired);
+    let tier = match desired {
+        400..=500 if nearest > desired && nearest <= 500 => 1,
+        400..=500 if nearest < desired => 2,
+        400..=500 => 3,
+        0..=399 if nearest < desired => 1,
+        0..=399 => 2,
+        _ if nearest > desired => 1,
+        _ => 2,
+    };
+    (tier, distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::browser::css::parse_stylesheet;
+
+    fn matcher(css: &str) -> FontMatcher {
+        let mut fonts = FontMatcher::new();
+        fonts.add_stylesheet(&parse_stylesheet(css), "https://example.com/css/site.css");
+        fonts
+    }
+
+    fn query(weight: u16, style: FontStyle, stretch: f32) -> FontQuery {
+        FontQuery::new("Body", weight, style, stretch)
+    }
+
+    #[test]
+    fn descriptors_and_sources_are_parsed() {
+        let fonts = matcher(
+            r#"@font-face { font-family: "Body"; src: local(Body Bold), url(../f/body.woff2) format("woff2"),
+               url(body.eot) format("embedded-opentype"); font-weight: 700 300; font-stretch: 75% condensed;
+               unicode-range: U+0-7F, U+4?? }
+               @font-face { font-family: Nameless }"#,
+        );
+        assert_eq!(fonts.faces().len(), 1);
+        let face = &fonts.faces()[0];
+        assert_eq!(face.family, "Body");
+        assert_eq!(
+            face.sources,
+            [
+                FontSource::Local("Body Bold".into()),
+                FontSource::Url { url: "https://example.com/f/body.woff2".into(), format: Some("woff2".into()) },
+            ]
+        );
+        assert_eq!(face.weight, (300, 700));
+        assert_eq!(face.stretch, (75.0, 75.0));
+        assert!(face.covers('a') && face.covers('\u{4ff}') && !face.covers('é'));
+    }
+
+    #[test]
+    fn faces_are_matched_by_stretch_then_style_then_weight() {
+        let fonts = matcher(
+            "@font-face { font-family: Body; src: local(a); font-weight: 300 }
+             @font-face { font-family: Body; src: local(b); font-weight: 700 }
+             @font-face { font-family: Body; src: local(c); font-style: italic; font-weight: 400 }
+             @font-face { font-family: Body; src: local(d); font-stretch: 50% }",
+        );
+        assert_eq!(fonts.select("Body", &query(400, FontStyle::Normal, 100.0), 'a'), Some(0));
+        assert_eq!(fonts.select("body", &query(600, FontStyle::Normal, 100.0), 'a'), Some(1));
+        assert_eq!(fonts.select("Body", &query(700, FontStyle::Oblique, 100.0), 'a'), Some(2));
+        assert_eq!(fonts.select("Body", &query(400, FontStyle::Normal, 62.5), 'a'), Some(3));
+        assert_eq!(fonts.select("Other", &query(400, FontStyle::Normal, 100.0), 'a'), None);
+    }
+
+    #[test]
+    fn unicode_range_picks_among_identical_faces() {
+        let fonts = matcher(
+            "@font-face { font-family: Body; src: local(latin) }
+             @font-face { font-family: Body; src: local(greek); unicode-range: U+370-3FF }",
+        );
+        let q = query(400, FontStyle::Normal, 100.0);
+        assert_eq!(fonts.select("Body", &q, 'λ'), Some(1));
+        assert_eq!(fonts.select("Body", &q, 'a'), Some(0));
+    }
+}
@@ -0,0 +1,598 @@
+//! grid.rs — Grid layout (CSS Grid Layout, simplified).
+//! Items are placed by line numbers, named areas or auto-placement, tracks are sized from
+//! their track sizes and the items in them, then each item is laid out in its grid area.
+//! Line names, `dense` packing and subgrids are not supported.
+
+use crate::browser::engine::{
+    layout_sized, outer_widths, relative_offset, AlignItems, Dimensions, Display, GridAutoFlow, GridLine, Length, Node,
+    Positioned, Style, TrackBreadth, TrackSize,
+};
+use crate::browser::renderer::LayoutBox;
+use std::ops::Range;
+
+/// Laid out items of a grid container
+pub struct GridLayout<'n> {
+    /// Item boxes in `order`, placed in the container's content box
+    pub boxes: Vec<LayoutBox>,
+    /// Content height the rows need, used when the container's `height` is `auto`
+    pub height: f32,
+    /// Absolutely positioned children and descendants still looking for their containing block
+    pub positioned: Vec<Positioned<'n>>,
+}
+
+/// An item and the tracks its grid area covers
+struct GridItem<'n> {
+    node: &'n Node,
+    rows: Range<usize>,
+    columns: Range<usize>,
+}
+
+/// Items placed on a grid of `rows` × `columns` tracks
+struct Grid<'n> {
+    items: Vec<GridItem<'n>>,
+    rows: usize,
+    columns: usize,
+}
+
+/// Lines past this, on either side, are clamped to it, so a stray `grid-row: 99999` can't
+/// make the grid allocate that many tracks
+const MAX_LINE: usize = 10000;
+
+/// An item's position on one axis before auto-placement: its start track, if definite
+#[derive(Debug, Clone, Copy)]
+struct Placement {
+    start: Option<usize>,
+    span: usize,
+}
+
+/// The size an item needs from the tracks it spans
+struct Contribution {
+    span: Range<usize>,
+    min: f32,
+    max: f32,
+}
+
+/// Lay out the children of a grid container whose content box is `d.content`. `height` is the
+/// container's used height, or None if it depends on the items.
+pub fn layout_grid<'n>(node: &'n Node, style: &Style, d: &Dimensions, height: Option<f32>) -> GridLayout<'n> {
+    // Absolutely positioned children are not items; their static position is the content origin
+    let mut positioned: Vec<Positioned<'n>> = node
+        .children
+        .iter()
+        .filter(|c| c.style.display != Display::None && c.is_out_of_flow())
+        .map(|child| Positioned {
+            node: child,
+            x: d.content.x,
+            y: d.content.y,
+        })
+        .collect();
+    let grid = place_items(style, &grid_items(node));
+
+    let columns = tracks(&style.grid_template_columns, style.grid_auto_columns, grid.columns);
+    let contributions: Vec<Contribution> = grid
+        .items
+        .iter()
+        .map(|item| {
+            let (min, max) = outer_widths(item.node);
+            Contribution {
+                span: item.columns.clone(),
+                min,
+                max,
+            }
+        })
+        .collect();
+    let column_sizes = size_tracks(&columns, Some(d.content.width), style.column_gap, &contributions);
+    let column_offsets = offsets(&column_sizes, style.column_gap);
+
+    // Rows are sized from the items' heights at their column widths
+    let mut layouts: Vec<(LayoutBox, Vec<Positioned<'n>>)> = grid
+        .items
+        .iter()
+        .map(|item| {
+            let area = span_size(&column_offsets, &column_sizes, &item.columns);
+            layout_sized(item.node, item_width(item.node, area), None)
+        })
+        .collect();
+    let rows = tracks(&style.grid_template_rows, style.grid_auto_rows, grid.rows);
+    let contributions: Vec<Contribution> = grid
+        .items
+        .iter()
+        .zip(&layouts)
+        .map(|(item, (layout, _))| {
+            let margin = &item.node.style.margin;
+            let outer = layout.height + margin.top + margin.bottom;
+            Contribution {
+                span: item.rows.clone(),
+                min: outer,
+                max: outer,
+            }
+        })
+        .collect();
+    let row_sizes = size_tracks(&rows, height, style.row_gap, &contributions);
+    let row_offsets = offsets(&row_sizes, style.row_gap);
+
+    let mut boxes = Vec::with_capacity(grid.items.len());
+    for (item, (layout, item_positioned)) in grid.items.iter().zip(&mut layouts) {
+        let item_style = &item.node.style;
+        let area_width = span_size(&column_offsets, &column_sizes, &item.columns);
+        let area_height = span_size(&row_offsets, &row_sizes, &item.rows);
+        let margin = &item_style.margin;
+
+        let align = item_style.align_self.unwrap_or(style.align_items);
+        let stretched = (area_height - margin.top - margin.bottom).max(0.0);
+        if align == AlignItems::Stretch && item_style.height == Length::Auto && (layout.height - stretched).abs() > 0.01
+        {
+            (*layout, *item_positioned) = layout_sized(item.node, layout.width, Some(stretched));
+        }
+        let free_y = area_height - layout.height - margin.top - margin.bottom;
+        let offset_y = match align {
+            AlignItems::FlexStart | AlignItems::Stretch | AlignItems::Baseline => 0.0,
+            AlignItems::FlexEnd => free_y,
+            AlignItems::Center => free_y / 2.0,
+        };
+        // Auto margins take the free space in the column
+        let free_x = area_width - layout.width - margin.left - margin.right;
+        let offset_x = match (item_style.margin_left_auto, item_style.margin_right_auto) {
+            (true, true) => free_x / 2.0,
+            (true, false) => free_x,
+            _ => 0.0,
+        };
+
+        let (rx, ry) = relative_offset(item_style, d.content.width, height);
+        let dx = d.content.x + column_offsets[item.columns.start] + margin.left + offset_x + rx;
+        let dy = d.content.y + row_offsets[item.rows.start] + margin.top + offset_y + ry;
+        layout.translate(dx, dy);
+        for entry in item_positioned.iter_mut() {
+            entry.x += dx;
+            entry.y += dy;
+        }
+    }
+    for (layout, item_positioned) in layouts {
+        boxes.push(layout);
+        positioned.extend(item_positioned);
+    }
+
+    let gaps = style.row_gap * row_sizes.len().saturating_sub(1) as f32;
+    GridLayout {
+        boxes,
+        height: row_sizes.iter().sum::<f32>() + gaps,
+        positioned,
+    }
+}
+
+/// Min-content and max-content widths of a grid container's content: its columns sized
+/// for the narrowest and the widest contributions of the items
+pub fn grid_content_widths(node: &Node) -> (f32, f32) {
+    let style = &node.style;
+    let grid = place_items(style, &grid_items(node));
+    let columns = tracks(&style.grid_template_columns, style.grid_auto_columns, grid.columns);
+    let widths: Vec<(f32, f32)> = grid.items.iter().map(|item| outer_widths(item.node)).collect();
+    let gaps = style.column_gap * columns.len().saturating_sub(1) as f32;
+
+    let total = |pick: fn((f32, f32)) -> f32| {
+        let contributions: Vec<Contribution> = grid
+            .items
+            .iter()
+            .zip(&widths)
+            .map(|(item, &width)| Contribution {
+                span: item.columns.clone(),
+                min: width.0,
+                max: pick(width),
+            })
+            .collect();
+        size_tracks(&columns, None, style.column_gap, &contributions)
+            .iter()
+            .sum::<f32>()
+            + gaps
+    };
+    (total(|(min, _)| min), total(|(_, max)| max))
+}
+
+/// In-flow children of a grid container, in `order`
+fn grid_items(node: &Node) -> Vec<&Node> {
+    let mut items: Vec<&Node> = node
+        .children
+        .iter()
+        .filter(|c| c.style.display != Display::None && !c.is_out_of_flow())
+        .collect();
+    items.sort_by_key(|c| c.style.order);
+    items
+}
+
+/// Track sizes for `count` tracks: the explicit ones, then implicit `auto` tracks
+fn tracks(template: &[TrackSize], auto: TrackSize, count: usize) -> Vec<TrackSize> {
+    (0..count).map(|i| template.get(i).copied().unwrap_or(auto)).collect()
+}
+
+/// Start offset of each track
+fn offsets(sizes: &[f32], gap: f32) -> Vec<f32> {
+    let mut offset = 0.0;
+    sizes
+        .iter()
+        .map(|size| {
+            let start = offset;
+            offset += size + gap;
+            start
+        })
+        .collect()
+}
+
+/// Length of a run of tracks, including the gaps between them
+fn span_size(offsets: &[f32], sizes: &[f32], span: &Range<usize>) -> f32 {
+    offsets[span.end - 1] + sizes[span.end - 1] - offsets[span.start]
+}
+
+/// Border-box width of an item in a grid area: `auto` stretches across it unless a horizontal
+/// margin is `auto`, in which case the item shrinks to fit
+fn item_width(node: &Node, area: f32) -> f32 {
+    let style = &node.style;
+    let chrome = style.padding.left + style.padding.right + 2.0 * style.border_width;
+    let margins = style.margin.left + style.margin.right;
+    match style.width.resolve(Some(area)) {
+        Some(width) => width + chrome,
+        None if style.margin_left_auto || style.margin_right_auto => {
+            let (min, max) = outer_widths(node);
+            max.min(area).max(min) - margins
+        }
+        None => area - margins,
+    }
+    .max(0.0)
+}
+
+/// Track ranges (rows, columns) of a named area: the cells carrying its name
+fn find_area(areas: &[Vec<Option<String>>], name: &str) -> Option<(Range<usize>, Range<usize>)> {
+    let mut found: Option<(Range<usize>, Range<usize>)> = None;
+    for (r, row) in areas.iter().enumerate() {
+        for (c, cell) in row.iter().enumerate() {
+            if cell.as_deref() == Some(name) {
+                found = Some(match found {
+                    None => (r..r + 1, c..c + 1),
+                    Some((rows, columns)) => (
+                        rows.start.min(r)..rows.end.max(r + 1),
+                        columns.start.min(c)..columns.end.max(c + 1),
+                    ),
+                });
+            }
+        }
+    }
+    found
+}
+
+/// Resolve one axis of an item's placement (CSS Grid §8.3). Line numbers are 1-based and
+/// negative ones count back from the end of the explicit grid's `explicit` tracks. Lines
+/// before the start of the grid are clamped to it, and areas end by [`MAX_LINE`].
+fn resolve_placement(
+    start: &GridLine,
+    end: &GridLine,
+    explicit: usize,
+    area: impl Fn(&str) -> Option<Range<usize>>,
+) -> Placement {
+    let line = |line: &GridLine, is_end: bool| match line {
+        GridLine::Line(n) if *n > 0 => Some(*n as usize - 1),
+        GridLine::Line(n) => Some((explicit as i32 + 1 + n).max(0) as usize),
+        GridLine::Area(name) => area(name).map(|tracks| if is_end { tracks.end } else { tracks.start }),
+        _ => None,
+    };
+    let span = |line: &GridLine| match line {
+        GridLine::Span(n) => *n as usize,
+        _ => 1,
+    };
+    let placement = match (line(start, false), line(end, true)) {
+        (Some(s), Some(e)) if e > s => Placement {
+            start: Some(s),
+            span: e - s,
+        },
+        (Some(s), Some(e)) if e < s => Placement {
+            start: Some(e),
+            span: s - e,
+        },
+        (Some(s), _) => Placement {
+            start: Some(s),
+            span: if matches!(end, GridLine::Span(_)) { span(end) } else { 1 },
+        },
+        (None, Some(e)) => {
+            let s = e.saturating_sub(span(start));
+            Placement {
+                start: Some(s),
+                span: (e - s).max(1),
+            }
+        }
+        // With both edges spans, the end one is ignored
+        (None, None) => Placement {
+            start: None,
+            span: if matches!(start, GridLine::Span(_)) {
+                span(start)
+            } else {
+                span(end)
+            },
+        },
+    };
+    let start = placement.start.map(|s| s.min(MAX_LINE - 1));
+    Placement {
+        start,
+        span: placement.span.clamp(1, MAX_LINE - start.unwrap_or(0)),
+    }
+}
+
+/// Cells taken by placed items, indexed by major then minor track
+#[derive(Default)]
+struct Occupancy {
+    cells: Vec<Vec<bool>>,
+}
+
+impl Occupancy {
+    fn is_free(&self, mut major: Range<usize>, minor: Range<usize>) -> bool {
+        major.all(|r| {
+            minor
+                .clone()
+                .all(|c| !self.cells.get(r).and_then(|row| row.get(c)).copied().unwrap_or(false))
+        })
+    }
+
+    fn occupy(&mut self, major: Range<usize>, minor: Range<usize>) {
+        for r in major {
+            if self.cells.len() <= r {
+                self.cells.resize(r + 1, vec![]);
+            }
+            let row = &mut self.cells[r];
+            if row.len() < minor.end {
+                row.resize(minor.end, false);
+            }
+            for c in minor.clone() {
+                row[c] = true;
+            }
+        }
+    }
+}
+
+/// Place every item on the grid (CSS Grid §8.5). Auto-placement works along the major axis
+/// given by `grid-auto-flow` (rows by default), filling the minor axis first.
+fn place_items<'n>(style: &Style, children: &[&'n Node]) -> Grid<'n> {
+    let areas = &style.grid_template_areas;
+    let explicit_rows = style.grid_template_rows.len().max(areas.len());
+    let explicit_columns = style.grid_template_columns.len().max(areas.first().map_or(0, Vec::len));
+    let column_flow = style.grid_auto_flow == GridAutoFlow::Column;
+
+    // (major, minor) placement of each item
+    let placements: Vec<(Placement, Placement)> = children
+        .iter()
+        .map(|child| {
+            let s = &child.style;
+            let rows = resolve_placement(&s.grid_row_start, &s.grid_row_end, explicit_rows, |name| {
+                find_area(areas, name).map(|area| area.0)
+            });
+            let columns = resolve_placement(&s.grid_column_start, &s.grid_column_end, explicit_columns, |name| {
+                find_area(areas, name).map(|area| area.1)
+            });
+            if column_flow {
+                (columns, rows)
+            } else {
+                (rows, columns)
+            }
+        })
+        .collect();
+    let explicit_minor = if column_flow { explicit_rows } else { explicit_columns };
+    let mut minor_count = placements
+        .iter()
+        .map(|(_, minor)| minor.start.unwrap_or(0) + minor.span)
+        .fold(explicit_minor.max(1), usize::max);
+
+    let mut occupied = Occupancy::default();
+    let mut placed: Vec<Option<(Range<usize>, Range<usize>)>> = vec![None; children.len()];
+    // Items with a definite position on both axes
+    for (i, (major, minor)) in placements.iter().enumerate() {
+        if let (Some(r), Some(c)) = (major.start, minor.start) {
+            occupied.occupy(r..r + major.span, c..c + minor.span);
+            placed[i] = Some((r..r + major.span, c..c + minor.span));
+        }
+    }
+    // Items locked to a major track take its first free slot
+    for (i, (major, minor)) in placements.iter().enumerate() {
+        if let (Some(r), None) = (major.start, minor.start) {
+            let majors = r..r + major.span;
+            let c = (0..=minor_count.saturating_sub(minor.span))
+                .find(|&c| occupied.is_free(majors.clone(), c..c + minor.span))
+                .unwrap_or(minor_count);
+            minor_count = minor_count.max(c + minor.span);
+            occupied.occupy(majors.clone(), c..c + minor.span);
+            placed[i] = Some((majors, c..c + minor.span));
+        }
+    }
+    // The rest follow a cursor through the grid
+    let (mut cursor_major, mut cursor_minor) = (0, 0);
+    for (i, (major, minor)) in placements.iter().enumerate() {
+        if placed[i].is_some() {
+            continue;
+        }
+        let minors = match minor.start {
+            Some(c) => {
+                if c < cursor_minor {
+                    cursor_major += 1;
+                }
+                while !occupied.is_free(cursor_major..cursor_major + major.span, c..c + minor.span) {
+                    cursor_major += 1;
+                }
+                c..c + minor.span
+            }
+            None => loop {
+                if cursor_minor + minor.span > minor_count {
+                    cursor_major += 1;
+                    cursor_minor = 0;
+                }
+                let minors = cursor_minor..cursor_minor + minor.span;
+                if occupied.is_free(cursor_major..cursor_major + major.span, minors.clone()) {
+                    break minors;
+                }
+                cursor_minor += 1;
+            },
+        };
+        cursor_minor = minors.end;
+        let majors = cursor_major..cursor_major + major.span;
+        occupied.occupy(majors.clone(), minors.clone());
+        placed[i] = Some((majors, minors));
+    }
+
+    let items: Vec<GridItem> = children
+        .iter()
+        .zip(placed.into_iter().flatten())
+        .map(|(&node, (major, minor))| {
+            let (rows, columns) = if column_flow { (minor, major) } else { (major, minor) };
+            GridItem { node, rows, columns }
+        })
+        .collect();
+    // The explicit grid, extended by implicit tracks to fit the items
+    Grid {
+        rows: items.iter().map(|i| i.rows.end).fold(explicit_rows, usize::max),
+        columns: items.iter().map(|i| i.columns.end).fold(explicit_columns, usize::max),
+        items,
+    }
+}
+
+/// Size the tracks of one axis (CSS Grid §11, simplified). `available` is the container's
+/// inner size on that axis, or None when the tracks size to their content.
+fn size_tracks(tracks: &[TrackSize], available: Option<f32>, gap: f32, contributions: &[Contribution]) -> Vec<f32> {
+    let count = tracks.len();
+    let flex = |i: usize| match tracks[i].max {
+        TrackBreadth::Fr(fr) => fr,
+        _ => 0.0,
+    };
+    let is_flexible = |i: usize| matches!(tracks[i].max, TrackBreadth::Fr(_));
+    let span_gaps = |span: &Range<usize>| gap * span.len().saturating_sub(1) as f32;
+    let mut base: Vec<f32> = tracks.iter().map(|t| t.min.fixed(available).unwrap_or(0.0)).collect();
+    // Growth limits; None until an item or a fixed maximum sets one
+    let mut limit: Vec<Option<f32>> = tracks.iter().map(|t| t.max.fixed(available)).collect();
+
+    // Intrinsic tracks grow to fit the items in them, single-track items first
+    let mut sorted: Vec<&Contribution> = contributions.iter().collect();
+    sorted.sort_by_key(|c| c.span.len());
+    for c in sorted {
+        let span = c.span.clone();
+        let flexible = span.clone().any(is_flexible);
+        // Items crossing a flexible track only raise the minimums of the flexible tracks
+        let grow_min: Vec<usize> = span
+            .clone()
+            .filter(|&i| tracks[i].min.fixed(available).is_none() && (!flexible || is_flexible(i)))
+            .collect();
+        let needed = if grow_min.iter().any(|&i| tracks[i].min == TrackBreadth::MaxContent) {
+            c.max
+        } else {
+            c.min
+        };
+        let extra = needed - span_gaps(&span) - span.clone().map(|i| base[i]).sum::<f32>();
+        if extra > 0.0 && !grow_min.is_empty() {
+            for &i in &grow_min {
+                base[i] += extra / grow_min.len() as f32;
+            }
+        }
+        if flexible {
+            continue;
+        }
+
+        let grow_max: Vec<usize> = span
+            .clone()
+            .filter(|&i| tracks[i].max.fixed(available).is_none())
+            .collect();
+        let needed = if grow_max.iter().all(|&i| tracks[i].max == TrackBreadth::MinContent) {
+            c.min
+        } else {
+            c.max
+        };
+        let current: f32 = span.clone().map(|i| limit[i].unwrap_or(base[i])).sum();
+        let extra = needed - span_gaps(&span) - current;
+        if extra > 0.0 && !grow_max.is_empty() {
+            for &i in &grow_max {
+                limit[i] = Some(limit[i].unwrap_or(base[i]) + extra / grow_max.len() as f32);
+            }
+        }
+    }
+
+    let limits: Vec<f32> = (0..count).map(|i| limit[i].unwrap_or(base[i]).max(base[i])).collect();
+    let total_gaps = gap * count.saturating_sub(1) as f32;
+    let mut sizes = base;
+    match available {
+        // Grow tracks towards their limits while space is left
+        Some(available) => {
+            let mut free = available - total_gaps - sizes.iter().sum::<f32>();
+            while free > 0.01 {
+                let growable: Vec<usize> = (0..count)
+                    .filter(|&i| !is_flexible(i) && limits[i] - sizes[i] > 0.01)
+                    .collect();
+                if growable.is_empty() {
+                    break;
+                }
+                let share = free / growable.len() as f32;
+                for i in growable {
+                    let grow = share.min(limits[i] - sizes[i]);
+                    sizes[i] += grow;
+                    free -= grow;
+                }
+            }
+        }
+        // Sized to content, every track reaches its limit
+        None => {
+            for i in (0..count).filter(|&i| !is_flexible(i)) {
+                sizes[i] = limits[i];
+            }
+        }
+    }
+
+    let flexible: Vec<usize> = (0..count).filter(|&i| is_flexible(i)).collect();
+    if !flexible.is_empty() {
+        let fr = match available {
+            Some(available) => fr_size(&sizes, &flexible, flex, available - total_gaps),
+            // Without a definite size, one fr is the largest share any track or item needs
+            None => {
+                let tracks_fr = flexible.iter().map(|&i| sizes[i] / flex(i).max(1.0));
+                let items_fr = contributions
+                    .iter()
+                    .filter(|c| c.span.clone().any(is_flexible))
+                    .map(|c| {
+                        let fixed: f32 = c.span.clone().filter(|&i| !is_flexible(i)).map(|i| sizes[i]).sum();
+                        let flex_sum: f32 = c.span.clone().map(flex).sum();
+                        (c.max - fixed - span_gaps(&c.span)) / flex_sum.max(1.0)
+                    });
+                tracks_fr.chain(items_fr).fold(0.0, f32::max)
+            }
+        };
+        for &i in &flexible {
+            sizes[i] = sizes[i].max(fr * flex(i));
+        }
+    } else if let Some(available) = available {
+        // Stretch `auto` tracks into the space that is left
+        let auto: Vec<usize> = (0..count).filter(|&i| tracks[i].max == TrackBreadth::Auto).collect();
+        let free = available - total_gaps - sizes.iter().sum::<f32>();
+        if free > 0.0 && !auto.is_empty() {
+            for &i in &auto {
+                sizes[i] += free / auto.len() as f32;
+            }
+        }
+    }
+    sizes
+}
+
+/// Size of one `fr` in `space` (CSS Grid §11.7.1): flexible tracks whose base size is larger
+/// than their share are treated as fixed and the share is computed again
+fn fr_size(sizes: &[f32], flexible: &[usize], flex: impl Fn(usize) -> f32, space: f32) -> f32 {
+    let mut fixed = vec![false; sizes.len()];
+    loop {
+        let is_flexible = |i: usize| flexible.contains(&i) && !fixed[i];
+        let leftover = space
+            - (0..sizes.len())
+                .filter(|&i| !is_flexible(i))
+                .map(|i| sizes[i])
+                .sum::<f32>();
+        let flex_sum: f32 = flexible.iter().filter(|&&i| !fixed[i]).map(|&i| flex(i)).sum();
+        let fr = leftover.max(0.0) / flex_sum.max(1.0);
+        let too_small: Vec<usize> = flexible
+            .iter()
+            .copied()
+            .filter(|&i| !fixed[i] && fr * flex(i) < sizes[i])
+            .collect();
+        if too_small.is_empty() {
+            return fr;
+        }
+        for i in too_small {
+            fixed[i] = true;
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! hit.rs — Hit testing: which DOM node is under a point of the viewport.
+//! Boxes are visited in the reverse of the renderer's paint order, so the first box that
+//! contains the point is the topmost one. Clipping hides what lies outside a box's clip rect,
+//! and boxes with `pointer-events: none` are passed through.
+
+use crate::browser::dom::Node as DomNode;
+use crate::browser::engine::{PointerEvents, Rect};
+use crate::browser::renderer::{layer_offset, stacking_layers, LayoutBox, TextNode};
+use std::rc::Rc;
+
+/// The topmost DOM node under a point
+#[derive(Debug, Clone)]
+pub struct HitTestResult {
+    pub node: Rc<DomNode>,
+    /// For a text node, the character offset in its text nearest the point, for placing a caret
+    pub text_offset: Option<usize>,
+}
+
+/// Find the DOM node under the viewport point (`x`, `y`) of `root` painted with the page
+/// scrolled to `scroll` in a viewport of size `viewport`
+pub fn hit_test(root: &LayoutBox, x: f32, y: f32, scroll: (f32, f32), viewport: (f32, f32)) -> Option<HitTestResult> {
+    let viewport = Rect {
+        x: 0.0,
+        y: 0.0,
+        width: viewport.0,
+        height: viewport.1,
+    };
+    HitTest { x, y }.stacking_context(root, (-scroll.0, -scroll.1), viewport)
+}
+
+struct HitTest {
+    x: f32,
+    y: f32,
+}
+
+impl HitTest {
+    /// A box and its descendants; `offset` maps its coordinates to the viewport, and sticky
+    /// descendants stick within `scrollport`
+    fn stacking_context(&self, root: &LayoutBox, offset: (f32, f32), scrollport: Rect) -> Option<HitTestResult> {
+        self.contents(root, offset, scrollport).or_else(|| self.own_box(root, offset))
+    }
+
+    /// A box's descendants, topmost first: the layers above in-flow content, in-flow content,
+    /// then the layers below it
+    fn contents(&self, root: &LayoutBox, offset: (f32, f32), scrollport: Rect) -> Option<HitTestResult> {
+        let (inner, scrollport) = match &root.clip {
+            Some(clip) if !clip.rect.contains(self.x - offset.0, self.y - offset.1) => return None,
+            Some(clip) => ((offset.0 - clip.scroll.0, offset.1 - clip.scroll.1), clip.scrollport(offset)),
+            None => (offset, scrollport),
+        };
+
+        let (layers, split) = stacking_layers(root);
+        let layer =
+            |layout: &&LayoutBox| self.stacking_context(layout, layer_offset(layout, inner, scrollport), scrollport);
+        layers[split..]
+            .iter()
+            .rev()
+            .find_map(layer)
+            .or_else(|| self.flow(&root.children, inner, scrollport))
+            .or_else(|| layers[..split].iter().rev().find_map(layer))
+    }
+
+    /// In-flow boxes, last painted first; a box's descendants are above the box itself
+    fn flow(&self, boxes: &[LayoutBox], offset: (f32, f32), scrollport: Rect) -> Option<HitTestResult> {
+        boxes.iter().rev().filter(|b| b.layer.is_none()).find_map(|layout| {
+            if layout.clip.is_some() {
+                self.contents(layout, offset, scrollport)
+            } else {
+                self.flow(&layout.children, offset, scrollport)
+            }
+            .or_else(|| self.own_box(layout, offset))
+        })
+    }
+
+    /// The box itself, if it contains the point and stands for a DOM node that takes pointer events
+    fn own_box(&self, layout: &LayoutBox, offset: (f32, f32)) -> Option<HitTestResult> {
+        let (x, y) = (self.x - offset.0, self.y - offset.1);
+        let border_box = Rect {
+            x: layout.x,
+            y: layout.y,
+            width: layout.width,
+            height: layout.height,
+        };
+        if !border_box.contains(x, y) || layout.pointer_events == PointerEvents::None {
+            return None;
+        }
+        let node = layout.dom.as_ref()?.upgrade()?;
+        // Generated text stands for its element, which has no caret positions
+        let text_offset = (layout.text.as_ref().filter(|_| node.is_text())).and_then(|text| {
+            let along = if text.vertical { y - layout.y } else { x - layout.x };
+            caret_offset(text, along)
+        });
+        Some(HitTestResult { node, text_offset })
+    }
+}
+
+/// Offset in the source text of the character boundary nearest `x` px from the start of `text`,
+/// its left edge or, in a vertical line, its top
+fn caret_offset(text: &TextNode, x: f32) -> Option<usize> {
+    if text.source_offsets.is_empty() {
+        return None;
+    }
+    let shaped = text.shape();
+    // Right-to-left glyphs come in visual order, so each cluster ends where the previous began
+    let (mut byte, mut cluster_end) = if text.rtl {
+        (0, text.content.len())
+    } else {
+        (text.content.len(), 0)
+    };
+    let mut start = 0.0;
+    let mut glyphs = shaped.glyphs.iter().peekable();
+    while let Some(glyph) = glyphs.next() {
+        // A cluster's glyphs are placed as one unit; the caret goes before or after it
+        let mut advance = glyph.x_advance;
+        while let Some(next) = glyphs.next_if(|g| g.cluster == glyph.cluster) {
+            advance += next.x_advance;
+        }
+        if x < start + advance / 2.0 {
+            byte = if text.rtl { cluster_end } else { glyph.cluster };
+            break;
+        }
+        cluster_end = glyph.cluster;
+        start += advance;
+    }
+    let index = text.content[..byte].chars().count();
+    text.source_offsets.get(index).copied()
+}
@@ -0,0 +1,194 @@
+//! images.rs — Images for replaced elements: fetching, decoding and a per-URL cache.
+//! Images are fetched before layout (`preload_images`); layout only reads the cache, sizing
+//! images that haven't arrived from their attributes. The renderer paints pixels from the
+//! same cache, so each image is fetched and decoded once.
+
+use crate::browser::dom::Node as DomNode;
+use crate::net::{fetch_resource, RequestType};
+use base64::{engine::general_purpose, Engine as _};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use url::Url;
+
+/// A decoded image in the renderer's pixel format: premultiplied ARGB, row by row
+#[derive(Clone)]
+pub struct DecodedImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Arc<Vec<u32>>,
+}
+
+impl fmt::Debug for DecodedImage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "DecodedImage({}x{})", self.width, self.height)
+    }
+}
+
+/// Errors while loading an image
+#[derive(Debug)]
+pub enum ImageLoadError {
+    BadUrl(String),
+    Fetch(String),
+    Decode(String),
+}
+
+/// What the cache knows about an image
+#[derive(Debug, Clone)]
+pub enum ImageState {
+    Loaded(DecodedImage),
+    Failed,
+    /// Not fetched yet
+    Pending,
+}
+
+/// Images by resolved URL; failed loads are cached as None
+#[derive(Default)]
+pub struct ImageCache {
+    /// URL of the document, which relative `src` attributes resolve against
+    base_url: Option<Url>,
+    images: HashMap<String, Option<DecodedImage>>,
+}
+
+thread_local! {
+    static IMAGE_CACHE: RefCell<ImageCache> = RefCell::new(ImageCache::default());
+}
+
+/// Run `f` with this thread's image cache
+pub fn with_image_cache<R>(f: impl FnOnce(&mut ImageCache) -> R) -> R {
+    IMAGE_CACHE.with(|cache| f(&mut cache.borrow_mut()))
+}
+
+/// Load the image at `src` (resolved against the document URL) through this thread's cache
+pub fn load_image(src: &str) -> Option<DecodedImage> {
+    with_image_cache(|cache| cache.load(src))
+}
+
+/// The image at `src` in this thread's cache, without fetching it
+pub fn image_state(src: &str) -> ImageState {
+    with_image_cache(|cache| cache.state(src))
+}
+
+/// Fetch every image a document shows (`<img src>`, `<video poster>`) into this thread's
+/// cache, so layout doesn't wait on the network
+pub fn preload_images(document: &DomNode) {
+    with_image_cache(|cache| cache.preload(document))
+}
+
+impl ImageCache {
+    /// Set the document URL that relative image URLs resolve against
+    pub fn set_base_url(&mut self, base_url: &str) {
+        self.base_url = Url::parse(base_url).ok();
+    }
+
+    /// Make an already decoded image available at `src`
+    pub fn insert(&mut self, src: &str, image: DecodedImage) {
+        let key = self.resolve(src).map_or_else(|_| src.to_string(), |url| url.to_string());
+        self.images.insert(key, Some(image));
+    }
+
+    /// Load an image (once)
+    pub fn load(&mut self, src: &str) -> Option<DecodedImage> {
+        let url = match self.resolve(src) {
+            Ok(url) => url,
+            Err(e) => {
+                log::warn!("Image '{}' has no usable URL: {:?}", src, e);
+                return None;
+            }
+        };
+        let key = url.to_string();
+        if let Some(image) = self.images.get(&key) {
+            return image.clone();
+        }
+
+        let image = match fetch_image(&url).and_then(|bytes| decode_image(&bytes)) {
+            Ok(image) => Some(image),
+            Err(e) => {
+                log::warn!("Image {} failed: {:?}", key, e);
+                None
+            }
+        };
+        self.images.insert(key, image.clone());
+        image
+    }
+
+    /// What is known about an image without fetching it. `data:` URLs need no network and
+    /// are decoded on first use.
+    pub fn state(&mut self, src: &str) -> ImageState {
+        let Ok(url) = self.resolve(src) else {
+            return ImageState::Failed;
+        };
+        if url.scheme() == "data" {
+            return self.load(src).map_or(ImageState::Failed, ImageState::Loaded);
+        }
+        match self.images.get(url.as_str()) {
+            Some(Some(image)) => ImageState::Loaded(image.clone()),
+            Some(None) => ImageState::Failed,
+            None => ImageState::Pending,
+        }
+    }
+
+    /// Load the images of `node` and its descendants
+    pub fn preload(&mut self, node: &DomNode) {
+        let src = match node.tag_name().as_deref() {
+            Some("img") => node.get_attr("src"),
+            Some("video") => node.get_attr("poster"),
+            _ => None,
+        };
+        if let Some(src) = src {
+            self.load(&src);
+        }
+        for child in node.children().iter() {
+            self.preload(child);
+        }
+    }
+
+    fn resolve(&self, src: &str) -> Result<Url, ImageLoadError> {
+        let src = src.trim();
+        match &self.base_url {
+            Some(base) => base.join(src),
+            None => Url::parse(src),
+        }
+        .map_err(|e| ImageLoadError::BadUrl(e.to_string()))
+    }
+}
+
+/// Decode PNG, JPEG, GIF (first frame), WebP or BMP data
+pub fn decode_image(bytes: &[u8]) -> Result<DecodedImage, ImageLoadError> {
+    let rgba = image::load_from_memory(bytes)
+        .map_err(|e| ImageLoadError::Decode(e.to_string()))?
+        .into_rgba8();
+    let (width, height) = rgba.dimensions();
+    let pixels = rgba
+        .pixels()
+        .map(|p| {
+            let [r, g, b, a] = p.0;
+            let premultiply = |c: u8| (c as u32 * a as u32 + 127) / 255;
+            (a as u32) << 24 | premultiply(r) << 16 | premultiply(g) << 8 | premultiply(b)
+        })
+        .collect();
+    Ok(DecodedImage {
+        width,
+        height,
+        pixels: Arc::new(pixels),
+    })
+}
+
+/// The bytes of an image: inline for `data:` URLs, fetched otherwise
+fn fetch_image(url: &Url) -> Result<Vec<u8>, ImageLoadError> {
+    if url.scheme() != "data" {
+        return fetch_resource(url.as_str(), RequestType::Image)
+            .map(|result| result.data)
+            .map_err(|e| ImageLoadError::Fetch(format!("{:?}", e)));
+    }
+    // data:[<mediatype>][;base64],<data>
+    let (header, data) = url.path().split_once(',').ok_or_else(|| ImageLoadError::BadUrl(url.to_string()))?;
+    if header.ends_with(";base64") {
+        general_purpose::STANDARD
+            .decode(data.trim())
+            .map_err(|e| ImageLoadError::Decode(e.to_string()))
+    } else {
+        Ok(data.as_bytes().to_vec())
+    }
+}
@@ -0,0 +1,904 @@
+//! inline.rs — Inline formatting context: white-space processing, line breaking and line boxes.
+//! Inline content of a block is flattened into one paragraph of styled text runs, broken into
+//! lines at Unicode line-break opportunities, then aligned horizontally and on a shared baseline.
+//! Lines are shortened to the space left beside floats. Bidirectional text gets its embedding
+//! levels from the Unicode bidi algorithm and each line is reordered visually.
+
+use crate::browser::engine::{
+    layout_atomic_inline, outer_widths, relative_offset, Direction, Display, FloatContext, Node, NodeType, Positioned,
+    Style, TextAlign, UnicodeBidi,
+};
+use crate::browser::fonts::FontQuery;
+use crate::browser::renderer::{LayoutBox, TextNode};
+use crate::browser::text::with_font_context;
+use std::ops::Range;
+use unicode_bidi::{bidi_class, BidiClass, BidiInfo, Level};
+use unicode_linebreak::{linebreaks, BreakOpportunity};
+
+// Bidi control characters (UAX #9 §2)
+const LRE: char = '\u{202A}';
+const RLE: char = '\u{202B}';
+const PDF: char = '\u{202C}';
+const LRO: char = '\u{202D}';
+const RLO: char = '\u{202E}';
+const LRI: char = '\u{2066}';
+const RLI: char = '\u{2067}';
+const FSI: char = '\u{2068}';
+const PDI: char = '\u{2069}';
+
+/// The text of an inline formatting context after white-space processing
+struct Paragraph<'a> {
+    text: String,
+    runs: Vec<TextRun<'a>>,
+    /// Inline elements with a background or border to paint behind their text
+    elements: Vec<&'a Node>,
+    /// `inline-block` boxes, each standing in the text as U+FFFC
+    atomics: Vec<Atomic<'a>>,
+    /// Floats, by the byte position in the text they are anchored at
+    floats: Vec<(usize, Atomic<'a>)>,
+    /// Absolutely positioned boxes, by the byte position of their static position
+    positioned: Vec<(usize, &'a Node)>,
+    /// The text ends in a collapsible space, so a following one collapses into it
+    ends_in_collapsible_space: bool,
+    /// Bidi controls for the `unicode-bidi` of inline elements, by the byte position they go before
+    controls: Vec<(usize, char)>,
+    /// Bidi embedding level of each byte of the text; empty if it is all left-to-right
+    levels: Vec<u8>,
+    /// Start and direction of each bidi paragraph, which the text's forced breaks separate
+    bases: Vec<(usize, bool)>,
+}
+
+/// A slice of the paragraph text in a single style
+struct TextRun<'a> {
+    range: Range<usize>,
+    /// The text node, `<br>` or atomic box the run comes from
+    node: &'a Node,
+    style: &'a Style,
+    /// Character offset in the source text at each character boundary of the run; empty if the
+    /// run has no source text
+    source: Vec<usize>,
+    /// Indices into `Paragraph::elements` of the painted elements containing this run
+    elements: Vec<usize>,
+    /// Index into `Paragraph::atomics` if the run is an `inline-block`
+    atomic: Option<usize>,
+}
+
+/// An `inline-block` placed on a line as a single unit, or a float
+struct Atomic<'a> {
+    node: &'a Node,
+    /// Laid out with its margin box at the origin
+    layout: LayoutBox,
+    /// Margin box size
+    width: f32,
+    height: f32,
+    /// Distance from the top of the margin box down to the box's baseline
+    baseline: f32,
+}
+
+impl<'a> Atomic<'a> {
+    fn new(node: &'a Node) -> Self {
+        Atomic {
+            node,
+            layout: LayoutBox::empty(),
+            width: 0.0,
+            height: 0.0,
+            baseline: 0.0,
+        }
+    }
+
+    /// Lay out the box so its size is known for line breaking
+    fn lay_out(&mut self, available: f32) {
+        let mut layout = layout_atomic_inline(self.node, available);
+        let margin = self.node.style.margin;
+        self.width = layout.width + margin.left + margin.right;
+        self.height = layout.height + margin.top + margin.bottom;
+        // The baseline of its last line, or the bottom margin edge if it has none
+        self.baseline = last_baseline(&layout).unwrap_or(self.height);
+        // Relative positioning moves the painted box, not the space it takes on the line
+        let (dx, dy) = relative_offset(&self.node.style, available, None);
+        layout.translate(dx, dy);
+        self.layout = layout;
+    }
+}
+
+/// Result of laying out a run of inline-level content
+pub struct InlineLayout<'a> {
+    /// One box per line
+    pub lines: Vec<LayoutBox>,
+    /// Floats anchored in the content, at their final position
+    pub floats: Vec<LayoutBox>,
+    /// Absolutely positioned boxes in the content, at their static position
+    pub positioned: Vec<Positioned<'a>>,
+    /// Height of the lines, including any space they skipped to get past floats
+    pub height: f32,
+}
+
+/// Text between two break opportunities
+struct Word {
+    range: Range<usize>,
+    /// The break after this word is mandatory (newline, `<br>`, end of text)
+    forced: bool,
+}
+
+/// A piece of a word within one text run
+#[derive(Debug, Clone)]
+struct Fragment {
+    run: usize,
+    range: Range<usize>,
+    /// Offset from the start of the line
+    x: f32,
+    width: f32,
+}
+
+#[derive(Debug, Default)]
+struct Line {
+    fragments: Vec<Fragment>,
+    width: f32,
+    /// Ended by a mandatory break; such lines are not justified
+    forced: bool,
+    /// Number of fragments after each word, for justification gaps
+    word_ends: Vec<usize>,
+}
+
+impl<'a> Paragraph<'a> {
+    fn build(nodes: &[&'a Node]) -> Self {
+        let mut para = Paragraph {
+            text: String::new(),
+            runs: vec![],
+            elements: vec![],
+            atomics: vec![],
+            floats: vec![],
+            positioned: vec![],
+            // Whitespace at the start of the paragraph collapses away
+            ends_in_collapsible_space: true,
+            controls: vec![],
+            levels: vec![],
+            bases: vec![],
+        };
+        let mut open = Vec::new();
+        for node in nodes {
+            para.collect(node, &mut open);
+        }
+        para
+    }
+
+    fn collect(&mut self, node: &'a Node, open: &mut Vec<usize>) {
+        if node.style.display == Display::None {
+            return;
+        }
+        match &node.node_type {
+            NodeType::Text(text) => self.push_text(text, node, open),
+            _ if node.is_out_of_flow() => self.positioned.push((self.text.len(), node)),
+            _ if node.is_floated() => self.floats.push((self.text.len(), Atomic::new(node))),
+            NodeType::Element(el) if el.tag_name == "br" => {
+                self.push_run("\n".into(), vec![], node, open);
+                self.ends_in_collapsible_space = true;
+            }
+            _ if node.is_replaced()
+                || matches!(
+                    node.style.display,
+                    Display::InlineBlock | Display::InlineFlex | Display::InlineGrid | Display::InlineTable
+                ) =>
+            {
+                self.atomics.push(Atomic::new(node));
+                self.push_run("\u{FFFC}".into(), vec![], node, open);
+                if let Some(run) = self.runs.last_mut() {
+                    run.atomic = Some(self.atomics.len() - 1);
+                }
+                self.ends_in_collapsible_space = false;
+            }
+            NodeType::Element(_) | NodeType::Anonymous => {
+                let style = &node.style;
+                let painted = style.background.is_some() || (style.border_color.is_some() && style.border_width > 0.0);
+                if painted {
+                    self.elements.push(node);
+                    open.push(self.elements.len() - 1);
+                }
+                let (opening, closing) = bidi_controls(style);
+                let start = self.text.len();
+                self.controls.extend(opening.iter().map(|&control| (start, control)));
+                for child in &node.children {
+                    self.collect(child, open);
+                }
+                let end = self.text.len();
+                self.controls.extend(closing.iter().map(|&control| (end, control)));
+                if painted {
+                    open.pop();
+                }
+            }
+        }
+    }
+
+    /// Apply the run's `white-space` rules (CSS Text 3 §4.1) and append it
+    fn push_text(&mut self, text: &str, node: &'a Node, open: &[usize]) {
+        let style = &node.style;
+        let collapse = style.white_space.collapses_spaces();
+        let keep_newlines = style.white_space.preserves_newlines();
+        let mut after_space = self.ends_in_collapsible_space;
+        let mut out = String::new();
+        // Source character offset of each character pushed to `out`
+        let mut source = Vec::new();
+
+        for (i, ch) in text.chars().enumerate() {
+            match ch {
+                '\n' if keep_newlines => {
+                    if collapse {
+                        // `pre-line` drops spaces around preserved newlines
+                        let kept = out.trim_end_matches(' ').len();
+                        source.truncate(source.len() - (out.len() - kept));
+                        out.truncate(kept);
+                    }
+                    out.push('\n');
+                    source.push(i);
+                    after_space = collapse;
+                }
+                ' ' | '\t' | '\n' | '\r' if collapse => {
+                    if !after_space {
+                        out.push(' ');
+                        source.push(i);
+                        after_space = true;
+                    }
+                }
+                '\t' => {
+                    out.push_str("        ");
+                    source.extend([i; 8]);
+                    after_space = false;
+                }
+                '\r' => {}
+                c => {
+                    out.push(c);
+                    source.push(i);
+                    after_space = false;
+                }
+            }
+        }
+        source.push(text.chars().count());
+
+        if !out.is_empty() {
+            self.ends_in_collapsible_space = after_space;
+        }
+        self.push_run(out, source, node, open);
+    }
+
+    fn push_run(&mut self, text: String, source: Vec<usize>, node: &'a Node, open: &[usize]) {
+        if text.is_empty() {
+            return;
+        }
+        let start = self.text.len();
+        self.text.push_str(&text);
+        self.runs.push(TextRun {
+            range: start..self.text.len(),
+            node,
+            style: &node.style,
+            source,
+            elements: open.to_vec(),
+            atomic: None,
+        });
+    }
+
+    /// Lay out the `inline-block`s and floats
+    fn layout_atomics(&mut self, available: f32) {
+        let floats = self.floats.iter_mut().map(|(_, float)| float);
+        for atomic in self.atomics.iter_mut().chain(floats) {
+            atomic.lay_out(available);
+        }
+    }
+
+    /// Resolve the bidi embedding level of every character (UAX #9) for a block container of
+    /// the given `direction` and `unicode-bidi`. Text that is all left-to-right is left as is.
+    fn resolve_bidi(&mut self, container: &Style) {
+        let rtl = container.direction == Direction::Rtl;
+        let overridden = matches!(container.unicode_bidi, UnicodeBidi::BidiOverride | UnicodeBidi::IsolateOverride);
+        let has_rtl = (self.text.chars()).any(|c| matches!(bidi_class(c), BidiClass::R | BidiClass::AL | BidiClass::AN));
+        if !rtl && !overridden && !has_rtl && self.controls.is_empty() {
+            return;
+        }
+
+        // The text with the controls in place, and where each of its bytes came from
+        let mut text = String::new();
+        let mut origin: Vec<Option<usize>> = Vec::new();
+        let mut push = |ch: char, at: Option<usize>| {
+            text.push(ch);
+            origin.extend((0..ch.len_utf8()).map(|i| at.map(|at| at + i)));
+        };
+        if overridden {
+            push(if rtl { RLO } else { LRO }, None);
+        }
+        let mut controls = self.controls.iter().peekable();
+        for (i, ch) in self.text.char_indices() {
+            while let Some(&(_, control)) = controls.next_if(|(pos, _)| *pos <= i) {
+                push(control, None);
+            }
+            push(ch, Some(i));
+        }
+        for &(_, control) in controls {
+            push(control, None);
+        }
+
+        let base = match container.unicode_bidi {
+            UnicodeBidi::Plaintext => None,
+            _ if rtl => Some(Level::rtl()),
+            _ => Some(Level::ltr()),
+        };
+        let info = BidiInfo::new(&text, base);
+        self.levels = vec![0; self.text.len()];
+        for (byte, at) in origin.iter().enumerate() {
+            if let Some(at) = at {
+                self.levels[*at] = info.levels[byte].number();
+            }
+        }
+        self.bases = (info.paragraphs.iter())
+            .map(|p| {
+                let start = origin[p.range.start..].iter().find_map(|at| *at).unwrap_or(self.text.len());
+                (start, p.level.is_rtl())
+            })
+            .collect();
+    }
+
+    /// The character at byte `pos` is laid out right to left
+    fn is_rtl(&self, pos: usize) -> bool {
+        self.levels.get(pos).is_some_and(|level| level % 2 == 1)
+    }
+
+    /// The bidi paragraph holding byte `pos` has a right-to-left base direction
+    fn base_rtl(&self, pos: usize) -> bool {
+        self.bases.iter().rev().find(|(start, _)| *start <= pos).is_some_and(|(_, rtl)| *rtl)
+    }
+
+    /// Split `range` where the embedding level changes
+    fn level_runs(&self, range: Range<usize>) -> Vec<Range<usize>> {
+        if self.levels.is_empty() {
+            return vec![range];
+        }
+        let mut runs = Vec::new();
+        let mut start = range.start;
+        for pos in range.clone().skip(1) {
+            if self.levels[pos] != self.levels[start] {
+                runs.push(start..pos);
+                start = pos;
+            }
+        }
+        runs.push(start..range.end);
+        runs
+    }
+
+    /// Indices of a line's fragments in visual order, left to right (UAX #9 rule L2): from the
+    /// highest level down to the lowest odd one, every sequence at that level or above reverses
+    fn visual_order(&self, fragments: &[Fragment]) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..fragments.len()).collect();
+        if self.levels.is_empty() {
+            return order;
+        }
+        let levels: Vec<u8> = (fragments.iter())
+            .map(|f| self.levels.get(f.range.start).copied().unwrap_or(0))
+            .collect();
+        let highest = levels.iter().copied().max().unwrap_or(0);
+        let lowest_odd = levels.iter().copied().min().unwrap_or(0) | 1;
+        for level in (lowest_odd..=highest).rev() {
+            let mut i = 0;
+            while i < order.len() {
+                let start = i;
+                while i < order.len() && levels[order[i]] >= level {
+                    i += 1;
+                }
+                order[start..i].reverse();
+                i += 1;
+            }
+        }
+        order
+    }
+
+    /// Index of the run containing byte `pos`
+    fn run_at(&self, pos: usize) -> usize {
+        self.runs.partition_point(|r| r.range.end <= pos)
+    }
+
+    /// Split the text at line-break opportunities (UAX #14); `nowrap` and `pre` text only
+    /// breaks at mandatory breaks
+    fn words(&self) -> Vec<Word> {
+        let mut words = Vec::new();
+        let mut start = 0;
+        for (pos, opportunity) in linebreaks(&self.text) {
+            if pos == 0 {
+                continue;
+            }
+            let forced = matches!(opportunity, BreakOpportunity::Mandatory);
+            if !forced && !self.runs[self.run_at(pos - 1)].style.white_space.wraps() {
+                continue;
+            }
+            words.push(Word {
+                range: start..pos,
+                forced,
+            });
+            start = pos;
+        }
+        words
+    }
+
+    /// The pieces of `range` in each run it covers, measured
+    fn fragments(&self, range: Range<usize>) -> Vec<Fragment> {
+        let mut fragments = Vec::new();
+        let mut index = self.run_at(range.start);
+        while let Some(run) = self.runs.get(index) {
+            if run.range.start >= range.end {
+                break;
+            }
+            let piece = run.range.start.max(range.start)..run.range.end.min(range.end);
+            for piece in self.level_runs(piece) {
+                let width = match run.atomic {
+                    Some(atomic) => self.atomics[atomic].width,
+                    None => self.measure(index, piece.clone()),
+                };
+                fragments.push(Fragment {
+                    run: index,
+                    width,
+                    range: piece,
+                    x: 0.0,
+                });
+            }
+            index += 1;
+        }
+        fragments
+    }
+
+    /// Width of the text `range` of run `run`, shaped in its direction as it will be painted
+    fn measure(&self, run: usize, range: Range<usize>) -> f32 {
+        let rtl = self.is_rtl(range.start);
+        measure_directed(&self.text[range], self.runs[run].style, rtl)
+    }
+
+    /// Width of the spaces ending `range`, which may hang past the end of a line
+    fn trailing_space_width(&self, range: &Range<usize>) -> f32 {
+        let text = &self.text[range.clone()];
+        let trimmed = text.trim_end_matches([' ', '\n']);
+        if trimmed.len() == text.len() {
+            return 0.0;
+        }
+        let style = self.runs[self.run_at(range.end - 1)].style;
+        measure_text(&text[trimmed.len()..], style)
+    }
+
+    /// True if the line holds nothing but collapsible spaces, which produce no line box
+    fn is_blank(&self, line: &Line) -> bool {
+        line.fragments.iter().all(|f| {
+            self.runs[f.run].style.white_space.collapses_spaces() && self.text[f.range.clone()].trim_matches(' ').is_empty()
+        })
+    }
+
+    /// Remove newlines and collapsible spaces from the end of the line. Returns the width used
+    /// for alignment, which excludes preserved (`pre-wrap`) spaces hanging at the end.
+    fn trim_line(&self, line: &mut Line) -> f32 {
+        for fragment in line.fragments.iter_mut().rev() {
+            let style = self.runs[fragment.run].style;
+            let text = &self.text[fragment.range.clone()];
+            let trimmed = if style.white_space.collapses_spaces() {
+                text.trim_end_matches([' ', '\n'])
+            } else {
+                text.trim_end_matches('\n')
+            };
+            if trimmed.len() != text.len() {
+                fragment.range.end = fragment.range.start + trimmed.len();
+                fragment.width = self.measure(fragment.run, fragment.range.clone());
+            }
+            if !trimmed.is_empty() {
+                break;
+            }
+        }
+
+        let Some(last) = line.fragments.iter().rev().find(|f| !f.range.is_empty()) else {
+            return 0.0;
+        };
+        let text = &self.text[last.range.clone()];
+        let hanging = measure_text(&text[text.trim_end_matches(' ').len()..], self.runs[last.run].style);
+        last.x + last.width - hanging
+    }
+}
+
+/// Lay out a run of inline-level content inside a block container whose content box starts
+/// at (`x`, `y`) and is `width` wide, wrapping around the floats of its formatting context
+pub fn layout_inline_run<'a>(
+    nodes: &[&'a Node],
+    container: &Style,
+    x: f32,
+    y: f32,
+    width: f32,
+    floats: &mut FloatContext,
+) -> InlineLayout<'a> {
+    let mut para = Paragraph::build(nodes);
+    para.resolve_bidi(container);
+    para.layout_atomics(width);
+    let words = para.words();
+    let strut = half_leading(container);
+    let strut_height = strut.0 + strut.1;
+
+    let mut result = InlineLayout {
+        lines: vec![],
+        floats: vec![],
+        positioned: vec![],
+        height: 0.0,
+    };
+    let mut anchored = para.floats.iter().peekable();
+    let mut statics = para.positioned.iter().peekable();
+    let mut line_y = y;
+    let mut next = 0;
+
+    // Greedy line filling: a word moves to the next line if it overflows a non-empty line
+    'lines: loop {
+        let (mut left, mut right) = floats.band(line_y, strut_height, x, x + width);
+        let mut line = Line::default();
+        let mut below = Vec::new();
+
+        loop {
+            // Floats anchored before the next word go at the top of this line if they fit
+            // beside what is already on it, otherwise below it (CSS 2.1 §9.5.1)
+            let anchor = words.get(next).map_or(usize::MAX, |w| w.range.start);
+            while let Some(&(_, node)) = statics.next_if(|(pos, _)| *pos <= anchor) {
+                result.positioned.push(Positioned {
+                    node,
+                    x: left + line.width,
+                    y: line_y,
+                });
+            }
+            while let Some((_, float)) = anchored.next_if(|(pos, _)| *pos <= anchor) {
+                if line.fragments.is_empty() || float.width <= right - left - line.width {
+                    result.floats.push(place_float(floats, float, line_y, x, width));
+                    (left, right) = floats.band(line_y, strut_height, x, x + width);
+                } else {
+                    below.push(float);
+                }
+            }
+
+            let Some(word) = words.get(next) else {
+                break;
+            };
+            let fragments = para.fragments(word.range.clone());
+            let word_width: f32 = fragments.iter().map(|f| f.width).sum();
+            let hanging = para.trailing_space_width(&word.range);
+            if line.width + word_width - hanging > right - left {
+                if !line.fragments.is_empty() {
+                    break;
+                }
+                // Too wide for the space beside the floats: try again below one of them
+                if left > x || right < x + width {
+                    if let Some(bottom) = floats.next_bottom(line_y, strut_height) {
+                        line_y = bottom;
+                        continue 'lines;
+                    }
+                }
+            }
+
+            for mut fragment in fragments {
+                fragment.x = line.width;
+                line.width += fragment.width;
+                line.fragments.push(fragment);
+            }
+            line.word_ends.push(line.fragments.len());
+            next += 1;
+            if word.forced {
+                line.forced = true;
+                break;
+            }
+        }
+
+        if line.fragments.is_empty() {
+            break;
+        }
+        if !para.is_blank(&line) {
+            let line_box = position_line(&para, &mut line, container, strut, left, line_y, right - left);
+            line_y += line_box.height;
+            result.lines.push(line_box);
+        }
+        for float in below {
+            result.floats.push(place_float(floats, float, line_y, x, width));
+        }
+    }
+    result.height = line_y - y;
+    result
+}
+
+/// Trim and align one line, put its boxes on a shared baseline and build its line box
+fn position_line(
+    para: &Paragraph,
+    line: &mut Line,
+    container: &Style,
+    strut: (f32, f32),
+    x: f32,
+    y: f32,
+    width: f32,
+) -> LayoutBox {
+    let used_width = para.trim_line(line);
+    let free = (width - used_width).max(0.0);
+
+    // Justified lines spread the free space over the gaps between words
+    let gaps = line.word_ends.len().saturating_sub(1);
+    let gap = if container.text_align == TextAlign::Justify && !line.forced && gaps > 0 {
+        free / gaps as f32
+    } else {
+        0.0
+    };
+    // `start`, `end` and the unjustified lines of justified text follow the paragraph's direction
+    let rtl = line.fragments.first().is_some_and(|f| para.base_rtl(f.range.start));
+    let start = if rtl { free } else { 0.0 };
+    let offset = match container.text_align {
+        TextAlign::Left => 0.0,
+        TextAlign::Right => free,
+        TextAlign::Center => free / 2.0,
+        TextAlign::Justify if gap > 0.0 => 0.0,
+        TextAlign::Start | TextAlign::Justify => start,
+        TextAlign::End => free - start,
+    };
+
+    // Every inline box on the line sits on a shared baseline (CSS 2.1 §10.8)
+    let (mut above, mut below) = strut;
+    for fragment in line.fragments.iter().filter(|f| !f.range.is_empty()) {
+        let run = &para.runs[fragment.run];
+        let (a, b) = match run.atomic {
+            Some(atomic) => {
+                let atomic = &para.atomics[atomic];
+                (atomic.baseline, atomic.height - atomic.baseline)
+            }
+            None => half_leading(run.style),
+        };
+        above = above.max(a);
+        below = below.max(b);
+    }
+    let baseline = y + above;
+
+    // A justification gap follows every word but the last
+    let mut gap_after = vec![false; line.fragments.len()];
+    for &end in &line.word_ends[..gaps] {
+        gap_after[end - 1] = true;
+    }
+
+    // Fragments are placed left to right in visual order; a gap goes on the side its word ends
+    let mut texts: Vec<(Fragment, f32)> = Vec::new();
+    let mut pen = x + offset;
+    for i in para.visual_order(&line.fragments) {
+        let fragment = &line.fragments[i];
+        let rtl = para.is_rtl(fragment.range.start);
+        let gap = if gap_after[i] { gap } else { 0.0 };
+        if rtl {
+            pen += gap;
+        }
+        if !fragment.range.is_empty() {
+            // Neighbouring pieces of one run in one direction are painted as a single text box
+            let follows = |prev: &Fragment| {
+                if rtl {
+                    fragment.range.end == prev.range.start
+                } else {
+                    prev.range.end == fragment.range.start
+                }
+            };
+            match texts.last_mut() {
+                Some((prev, prev_x))
+                    if prev.run == fragment.run
+                        && para.runs[prev.run].atomic.is_none()
+                        && para.is_rtl(prev.range.start) == rtl
+                        && follows(prev)
+                        && (*prev_x + prev.width - pen).abs() < 0.01 =>
+                {
+                    // Shaped as one piece, the text may kern across the word boundary; the box
+                    // and what follows it take the width the renderer will draw
+                    prev.range = prev.range.start.min(fragment.range.start)..prev.range.end.max(fragment.range.end);
+                    prev.width = para.measure(prev.run, prev.range.clone());
+                    pen = *prev_x + prev.width;
+                }
+                _ => {
+                    texts.push((fragment.clone(), pen));
+                    pen += fragment.width;
+                }
+            }
+        } else {
+            pen += fragment.width;
+        }
+        if !rtl {
+            pen += gap;
+        }
+    }
+
+    let mut children = Vec::new();
+    children.extend(element_boxes(para, &texts, baseline));
+    for (fragment, fx) in &texts {
+        if let Some(atomic) = para.runs[fragment.run].atomic {
+            let atomic = &para.atomics[atomic];
+            let mut layout = atomic.layout.clone();
+            layout.translate(*fx, baseline - atomic.baseline);
+            children.push(layout);
+            continue;
+        }
+        let run = &para.runs[fragment.run];
+        let style = run.style;
+        let (ascent, descent) = font_metrics(style);
+        let mut text = text_node(para.text[fragment.range.clone()].to_string(), style);
+        text.rtl = para.is_rtl(fragment.range.start);
+        if !run.source.is_empty() {
+            let start = para.text[run.range.start..fragment.range.start].chars().count();
+            text.source_offsets = run.source[start..=start + text.content.chars().count()].to_vec();
+        }
+        children.push(LayoutBox {
+            x: *fx,
+            y: baseline - ascent,
+            width: fragment.width,
+            height: ascent + descent,
+            text: Some(text),
+            dom: run.node.dom.clone(),
+            pointer_events: style.pointer_events,
+            ..LayoutBox::empty()
+        });
+    }
+
+    LayoutBox {
+        x,
+        y,
+        width,
+        height: above + below,
+        line: true,
+        children,
+        ..LayoutBox::empty()
+    }
+}
+
+/// Place a float at or below `y` in the container's content box and move its layout there
+fn place_float(floats: &mut FloatContext, float: &Atomic, y: f32, x: f32, width: f32) -> LayoutBox {
+    let (fx, fy) = floats.place(&float.node.style, float.width, float.height, y, x, x + width);
+    let mut layout = float.layout.clone();
+    layout.translate(fx, fy);
+    layout
+}
+
+/// Min-content and max-content widths of inline content: the widest unbreakable piece, and
+/// the widest line when only forced breaks are taken
+pub fn inline_content_widths(nodes: &[&Node]) -> (f32, f32) {
+    let mut para = Paragraph::build(nodes);
+    let words = para.words();
+
+    for atomic in &mut para.atomics {
+        atomic.width = outer_widths(atomic.node).0;
+    }
+    let min = words
+        .iter()
+        .map(|w| word_width(&para, w) - para.trailing_space_width(&w.range))
+        .fold(0.0, f32::max);
+
+    for atomic in &mut para.atomics {
+        atomic.width = outer_widths(atomic.node).1;
+    }
+    let (mut max, mut line) = (0.0f32, 0.0f32);
+    for word in &words {
+        line += word_width(&para, word);
+        if word.forced {
+            max = max.max(line - para.trailing_space_width(&word.range));
+            line = 0.0;
+        }
+    }
+
+    // Floats sit beside the text
+    let (float_min, float_max) = para
+        .floats
+        .iter()
+        .map(|(_, float)| outer_widths(float.node))
+        .fold((0.0f32, 0.0), |(min, max), (f_min, f_max)| (min.max(f_min), max + f_max));
+    (min.max(float_min), max.max(line) + float_max)
+}
+
+fn word_width(para: &Paragraph, word: &Word) -> f32 {
+    para.fragments(word.range.clone()).iter().map(|f| f.width).sum()
+}
+
+/// Baseline of the first line of text inside a laid out box
+pub fn first_baseline(layout: &LayoutBox) -> Option<f32> {
+    layout
+        .text
+        .as_ref()
+        .map(|text| layout.y + text.baseline)
+        .or_else(|| layout.children.iter().find_map(first_baseline))
+}
+
+/// Baseline of the last line of text inside a laid out box
+fn last_baseline(layout: &LayoutBox) -> Option<f32> {
+    layout
+        .children
+        .iter()
+        .rev()
+        .find_map(last_baseline)
+        .or_else(|| layout.text.as_ref().map(|text| layout.y + text.baseline))
+}
+
+/// Backgrounds and borders of inline elements: one box per line spanning the element's text
+fn element_boxes(para: &Paragraph, texts: &[(Fragment, f32)], baseline: f32) -> Vec<LayoutBox> {
+    let mut boxes = Vec::new();
+    for (index, element) in para.elements.iter().enumerate() {
+        let mut span: Option<(f32, f32)> = None;
+        for (fragment, fx) in texts {
+            if para.runs[fragment.run].elements.contains(&index) {
+                let (start, end) = span.unwrap_or((*fx, *fx));
+                span = Some((start.min(*fx), end.max(fx + fragment.width)));
+            }
+        }
+        let Some((start, end)) = span else {
+            continue;
+        };
+
+        let style = &element.style;
+        let (ascent, descent) = font_metrics(style);
+        boxes.push(LayoutBox {
+            x: start - style.padding.left - style.border_width,
+            y: baseline - ascent - style.padding.top - style.border_width,
+            width: end - start + style.padding.left + style.padding.right + 2.0 * style.border_width,
+            height: ascent + descent + style.padding.top + style.padding.bottom + 2.0 * style.border_width,
+            background: style.background,
+            border: style
+                .border_color
+                .filter(|_| style.border_width > 0.0)
+                .map(|color| (color, style.border_width)),
+            dom: element.dom.clone(),
+            pointer_events: style.pointer_events,
+            ..LayoutBox::empty()
+        });
+    }
+    boxes
+}
+
+/// Space an inline box takes above and below its baseline: its font's ascent and descent
+/// plus half the leading that `line-height` adds
+pub fn half_leading(style: &Style) -> (f32, f32) {
+    let (ascent, descent) = font_metrics(style);
+    let leading = style.used_line_height() - (ascent + descent);
+    (ascent + leading / 2.0, descent + leading / 2.0)
+}
+
+/// Advance width of `text` in the style's font, as the renderer will draw it
+pub fn measure_text(text: &str, style: &Style) -> f32 {
+    let query = FontQuery::from_style(style);
+    with_font_context(|fonts| fonts.shape(text, &query, style.font_size).width)
+}
+
+/// Advance width of `text` shaped in a known direction, as the renderer will draw it. Text
+/// at an even bidi level is shaped left to right even when its script runs the other way.
+fn measure_directed(text: &str, style: &Style, rtl: bool) -> f32 {
+    let query = FontQuery::from_style(style);
+    with_font_context(|fonts| fonts.shape_directed(text, &query, style.font_size, rtl).width)
+}
+
+/// Bidi controls standing in the text for an inline element's `unicode-bidi` and `direction`:
+/// those at its start and those at its end (CSS Writing Modes 3 §2.4.2)
+fn bidi_controls(style: &Style) -> (&'static [char], &'static [char]) {
+    let rtl = style.direction == Direction::Rtl;
+    match style.unicode_bidi {
+        UnicodeBidi::Normal => (&[], &[]),
+        UnicodeBidi::Embed if rtl => (&[RLE], &[PDF]),
+        UnicodeBidi::Embed => (&[LRE], &[PDF]),
+        UnicodeBidi::Isolate if rtl => (&[RLI], &[PDI]),
+        UnicodeBidi::Isolate => (&[LRI], &[PDI]),
+        UnicodeBidi::BidiOverride if rtl => (&[RLO], &[PDF]),
+        UnicodeBidi::BidiOverride => (&[LRO], &[PDF]),
+        UnicodeBidi::IsolateOverride if rtl => (&[RLI, RLO], &[PDF, PDI]),
+        UnicodeBidi::IsolateOverride => (&[LRI, LRO], &[PDF, PDI]),
+        UnicodeBidi::Plaintext => (&[FSI], &[PDI]),
+    }
+}
+
+/// (ascent, descent) of the style's first available font
+pub fn font_metrics(style: &Style) -> (f32, f32) {
+    let query = FontQuery::from_style(style);
+    let metrics = with_font_context(|fonts| fonts.metrics(&query, style.font_size));
+    (metrics.ascent, metrics.descent)
+}
+
+/// Text to paint, in the font of `style`, with its baseline at the font's ascent
+pub fn text_node(content: String, style: &Style) -> TextNode {
+    TextNode {
+        content,
+        font_size: style.font_size,
+        color: style.color,
+        font_family: style.font_family.clone(),
+        font_weight: style.font_weight,
+        font_style: style.font_style,
+        font_stretch: style.font_stretch,
+        baseline: font_metrics(style).0,
+        source_offsets: vec![],
+        rtl: false,
+        vertical: false,
+        orientation: style.text_orientation,
+    }
+}
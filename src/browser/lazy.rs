@@ -0,0 +1,190 @@
+//! lazy.rs — Viewport-lazy layout: only content near the viewport is laid out in full.
+//! Block-level boxes entirely outside the layout window get a placeholder of estimated height
+//! instead of a layout. Heights measured in earlier passes replace the estimates, so the page
+//! settles as the user scrolls and each region is laid out for real.
+
+use crate::browser::dom::Node as DomNode;
+use crate::browser::engine::{build_layout_tree, Length, Node, NodeType};
+use crate::browser::renderer::LayoutBox;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Weak;
+
+/// Lazy layout of one document, kept across passes so measured heights outlive a scroll
+pub struct LazyLayout {
+    /// Distance above and below the viewport that is laid out in full
+    pub overscan: f32,
+    /// Border-box heights of laid out blocks, by DOM node
+    heights: HashMap<*const DomNode, Measured>,
+    /// Viewport width the heights were measured at
+    width: f32,
+    stats: LayoutStats,
+}
+
+/// How much work a lazy layout pass did
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LayoutStats {
+    /// Block-level boxes laid out in full
+    pub laid_out: usize,
+    /// Off-screen blocks given a placeholder instead
+    pub deferred: usize,
+    /// Placeholders sized by estimate, not yet measured by an earlier pass
+    pub estimated: usize,
+}
+
+/// The measured height of a block
+struct Measured {
+    /// Holding the node keeps its address from being reused by a new one
+    dom: Weak<DomNode>,
+    height: f32,
+}
+
+/// State of the pass running on this thread
+struct Pass {
+    /// Top and bottom of the region laid out in full, in page coordinates
+    window: (f32, f32),
+    heights: HashMap<*const DomNode, Measured>,
+    stats: LayoutStats,
+    /// Number of live `Eager` guards; nothing is deferred while there are any
+    eager: usize,
+}
+
+/// Keeps deferral off while it lives (see `suspend_deferral`)
+pub struct Eager;
+
+impl Drop for Eager {
+    fn drop(&mut self) {
+        PASS.with(|current| {
+            if let Some(pass) = current.borrow_mut().as_mut() {
+                pass.eager -= 1;
+            }
+        });
+    }
+}
+
+thread_local! {
+    static PASS: RefCell<Option<Pass>> = const { RefCell::new(None) };
+}
+
+impl LazyLayout {
+    pub fn new(overscan: f32) -> Self {
+        LazyLayout {
+            overscan,
+            heights: HashMap::new(),
+            width: 0.0,
+            stats: LayoutStats::default(),
+        }
+    }
+
+    /// Lay out a box tree for a viewport scrolled down to `scroll_y`
+    pub fn layout(&mut self, root: &Node, viewport_width: f32, viewport_height: f32, scroll_y: f32) -> LayoutBox {
+        // Heights measured at another width no longer hold
+        if viewport_width != self.width {
+            self.heights.clear();
+            self.width = viewport_width;
+        }
+        // Nodes removed from the document take their heights with them
+        self.heights.retain(|_, measured| measured.dom.strong_count() > 0);
+        let pass = Pass {
+            window: (scroll_y - self.overscan, scroll_y + viewport_height + self.overscan),
+            heights: std::mem::take(&mut self.heights),
+            stats: LayoutStats::default(),
+            eager: 0,
+        };
+        PASS.with(|current| *current.borrow_mut() = Some(pass));
+        let layout = build_layout_tree(root, viewport_width, viewport_height);
+        if let Some(pass) = PASS.with(|current| current.borrow_mut().take()) {
+            self.heights = pass.heights;
+            self.stats = pass.stats;
+        }
+        layout
+    }
+
+    /// Work done by the last pass
+    pub fn stats(&self) -> LayoutStats {
+        self.stats
+    }
+
+    /// Forget measured heights, e.g. after the document or its styles change
+    pub fn invalidate(&mut self) {
+        self.heights.clear();
+    }
+}
+
+/// If a lazy pass is running and the block `node`, with its border edge at `y` in a container
+/// `width` px wide, lies outside the layout window: the height to reserve for it instead
+pub fn deferred_height(node: &Node, y: f32, width: f32) -> Option<f32> {
+    PASS.with(|current| {
+        let mut current = current.borrow_mut();
+        let pass = current.as_mut()?;
+        let (top, bottom) = pass.window;
+        if pass.eager > 0 || (y >= top && y < bottom) {
+            return None;
+        }
+        let measured = node
+            .dom
+            .as_ref()
+            .and_then(|dom| pass.heights.get(&dom.as_ptr()))
+            .filter(|measured| measured.dom.strong_count() > 0)
+            .map(|measured| measured.height);
+        let height = measured.unwrap_or_else(|| estimate_height(node, width));
+        if (y < bottom && y + height > top) || escapes(node) {
+            return None;
+        }
+        pass.stats.deferred += 1;
+        if measured.is_none() {
+            pass.stats.estimated += 1;
+        }
+        Some(height)
+    })
+}
+
+/// Lay out everything in full until the guard is dropped: the content of scroll containers
+/// and of fixed and sticky boxes does not move with the page, so the window does not apply
+pub fn suspend_deferral() -> Eager {
+    PASS.with(|current| {
+        if let Some(pass) = current.borrow_mut().as_mut() {
+            pass.eager += 1;
+        }
+    });
+    Eager
+}
+
+/// Note the border-box height of a block laid out in full, for later passes to reserve
+pub fn record_height(node: &Node, height: f32) {
+    PASS.with(|current| {
+        if let Some(pass) = current.borrow_mut().as_mut() {
+            pass.stats.laid_out += 1;
+            if let Some(dom) = &node.dom {
+                pass.heights.insert(dom.as_ptr(), Measured { dom: dom.clone(), height });
+            }
+        }
+    });
+}
+
+/// Rough border-box height of a block: its text set in lines of its font, or its fixed height
+fn estimate_height(node: &Node, width: f32) -> f32 {
+    let style = &node.style;
+    let edges = style.padding.top + style.padding.bottom + 2.0 * style.border_width;
+    if let Length::Px(height) = style.height {
+        return height + edges;
+    }
+    // An average glyph is about half an em wide
+    let per_line = (width / (style.font_size * 0.5)).max(1.0);
+    let lines = (text_length(node) as f32 / per_line).ceil();
+    lines * style.used_line_height() + edges
+}
+
+fn text_length(node: &Node) -> usize {
+    match &node.node_type {
+        NodeType::Text(text) => text.chars().count(),
+        _ => node.children.iter().map(text_length).sum(),
+    }
+}
+
+/// The block has floats or positioned boxes inside, which can reach outside it
+fn escapes(node: &Node) -> bool {
+    node.children
+        .iter()
+        .any(|child| child.is_floated() || child.is_out_of_flow() || escapes(child))
+}
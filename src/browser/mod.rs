@@ -4,31 +4,95 @@
 
 pub mod dom;
 pub mod parser;
+pub mod css;
 pub mod style;
 pub mod engine;
+pub mod inline;
+pub mod flex;
+pub mod grid;
+pub mod table;
+pub mod boxes;
 pub mod renderer;
+pub mod hit;
+pub mod dump;
+pub mod lazy;
+pub mod relayout;
+pub mod writing;
+pub mod paginate;
+pub mod pdf;
+pub mod fonts;
+pub mod images;
+pub mod woff;
+pub mod text;
 
 // Export key types and functions for external use
 // This acts like the browser's public API
 
 // === DOM Tree ===
 pub use dom::{
-    Node, NodeType, ElementData, AttrMap,
-    element, text, comment, print_tree,
+    Node, NodeType, ElementData, AttrMap, ElementState, StyleDirty, LayoutDirty, DomSnapshot,
+    element, text, comment, print_tree, snapshot,
 };
 
 // === HTML Parser ===
 pub use parser::parse_html;
 
+// === CSS ===
+pub use css::{
+    Stylesheet, Rule, Selector, CompoundSelector, Combinator, AttrSelector, AttrOp, Declaration, PseudoElement,
+    FontFaceRule, PageRule, MatchTarget, parse_stylesheet,
+};
+
 // === Style System ===
-pub use style::{StyledNode, compute_styles};
+pub use style::{
+    StyledNode, RestyleStats, ComputedStyle, ComputedProperty, StyleOrigin,
+    compute_styles, compute_styles_serial, restyle, format_counter, computed_style, dump_styles,
+};
 
 // === Layout & Engine ===
 pub use engine::{
-    Node as LayoutNode, Style, Display, Color,
+    Node as LayoutNode, Style, Display, Color, ContentItem, ListStyleType, ListStylePosition, FontStyle, Length,
+    WhiteSpace, TextAlign, LineHeight, Float, Clear, FloatContext, Position,
+    FlexDirection, FlexWrap, ContentDistribution, AlignItems, TrackBreadth, TrackSize, GridAutoFlow, GridLine,
+    BorderCollapse, TableLayout, CaptionSide, VerticalAlign, ObjectFit, Replaced, Overflow, PointerEvents,
+    Direction, UnicodeBidi, WritingMode, TextOrientation, BreakBetween, BreakInside,
     Dimensions, Rect, EdgeSizes,
     build_layout_tree, default_style, edges,
 };
+pub use boxes::build_box_tree;
+pub use flex::{FlexLayout, layout_flex};
+pub use grid::{GridLayout, layout_grid};
+pub use table::{TableBoxes, layout_table};
 
 // === Renderer ===
-pub use renderer::{Renderer, LayoutBox, TextNode};
+pub use renderer::{Renderer, LayoutBox, ListMarker, TextNode, Layer, Sticky, ImagePaint, Clip};
+
+// === Hit Testing ===
+pub use hit::{HitTestResult, hit_test};
+
+// === Layout Dump ===
+pub use dump::{dump_layout, node_path};
+
+// === Lazy Layout ===
+pub use lazy::{LazyLayout, LayoutStats};
+
+// === Incremental Relayout ===
+pub use relayout::{LayoutCache, RelayoutStats, relayout};
+
+// === Pagination ===
+pub use paginate::{PageSetup, Page, PaginatedLayout, Breaks, paginate};
+
+// === PDF ===
+pub use pdf::{render_pdf, write_pdf};
+
+// === Fonts ===
+pub use fonts::{FontFace, FontMatcher, FontQuery, FontSource, LoadedFont};
+
+// === Images ===
+pub use images::{
+    DecodedImage, ImageCache, ImageLoadError, ImageState, decode_image, image_state, load_image, preload_images,
+    with_image_cache,
+};
+
+// === Text ===
+pub use text::{FontContext, FontMetrics, ShapedGlyph, ShapedText, with_font_context};
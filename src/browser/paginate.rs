@@ -0,0 +1,263 @@
+//! paginate.rs — Paged layout for print (CSS Fragmentation 3, CSS Paged Media 3).
+//! The document is laid out once at the size of the page area, then cut into pages at break
+//! opportunities: between block boxes stacked in the flow and between lines. `break-before`
+//! and `break-after` force or avoid a break, `break-inside: avoid` keeps a box on one page when
+//! it fits, and `@page` rules set the page size and margins.
+
+use crate::browser::css::Stylesheet;
+use crate::browser::engine::{build_layout_tree, edges, BreakBetween, BreakInside, EdgeSizes, Length, Node, Style};
+use crate::browser::renderer::LayoutBox;
+use crate::browser::style::{box_shorthand, parse_length};
+
+/// Page break rules of a block, kept on its layout box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Breaks {
+    pub before: BreakBetween,
+    pub after: BreakBetween,
+    pub inside: BreakInside,
+}
+
+impl Breaks {
+    pub const AUTO: Breaks = Breaks {
+        before: BreakBetween::Auto,
+        after: BreakBetween::Auto,
+        inside: BreakInside::Auto,
+    };
+
+    pub fn of(style: &Style) -> Self {
+        Breaks {
+            before: style.break_before,
+            after: style.break_after,
+            inside: style.break_inside,
+        }
+    }
+}
+
+/// Size and margins of a page box, in px
+#[derive(Debug, Clone, Copy)]
+pub struct PageSetup {
+    pub width: f32,
+    pub height: f32,
+    pub margin: EdgeSizes,
+}
+
+impl Default for PageSetup {
+    /// A4 with 1cm margins
+    fn default() -> Self {
+        PageSetup {
+            width: mm(210.0),
+            height: mm(297.0),
+            margin: edges(mm(10.0)),
+        }
+    }
+}
+
+impl PageSetup {
+    /// The page box `@page` rules give, later rules winning. Rules for some pages only
+    /// (`:first`, `:left`, `:right`, named pages) are not supported and are skipped.
+    pub fn from_stylesheets(stylesheets: &[Stylesheet]) -> Self {
+        let mut setup = PageSetup::default();
+        let rules = stylesheets.iter().flat_map(|sheet| &sheet.pages);
+        for decl in rules
+            .filter(|rule| rule.selector.is_empty())
+            .flat_map(|rule| &rule.declarations)
+        {
+            let value = decl.value.as_str();
+            match decl.name.as_str() {
+                "size" => {
+                    if let Some((width, height)) = parse_page_size(value) {
+                        (setup.width, setup.height) = (width, height);
+                    }
+                }
+                "margin" => {
+                    if let Some([top, right, bottom, left]) = box_shorthand(value) {
+                        for (side, value) in [(0, top), (1, right), (2, bottom), (3, left)] {
+                            setup.set_margin(side, value);
+                        }
+                    }
+                }
+                "margin-top" => setup.set_margin(0, value),
+                "margin-right" => setup.set_margin(1, value),
+                "margin-bottom" => setup.set_margin(2, value),
+                "margin-left" => setup.set_margin(3, value),
+                _ => {}
+            }
+        }
+        setup
+    }
+
+    /// Size of the page area, the page box inside its margins, which content is laid out in
+    pub fn content_size(&self) -> (f32, f32) {
+        let margin = &self.margin;
+        (
+            (self.width - margin.left - margin.right).max(1.0),
+            (self.height - margin.top - margin.bottom).max(1.0),
+        )
+    }
+
+    /// Set the margin on the side numbered clockwise from the top; percentages are of the
+    /// page's width for all sides, as for margins of any box
+    fn set_margin(&mut self, side: usize, value: &str) {
+        let px = match parse_length(value, 16.0) {
+            Some(Length::Px(px)) => px,
+            Some(Length::Percent(pct)) => self.width * pct / 100.0,
+            Some(Length::Auto) => 0.0,
+            _ => return,
+        };
+        let margin = &mut self.margin;
+        *[&mut margin.top, &mut margin.right, &mut margin.bottom, &mut margin.left][side] = px;
+    }
+}
+
+/// One page: the band of the layout from `top` to `bottom`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Page {
+    pub top: f32,
+    pub bottom: f32,
+}
+
+/// A document laid out for print: the layout of the page area's width and the pages it is cut into
+#[derive(Debug, Clone)]
+pub struct PaginatedLayout {
+    pub setup: PageSetup,
+    pub layout: LayoutBox,
+    pub pages: Vec<Page>,
+}
+
+/// A place the flow may be cut
+struct Opportunity {
+    y: f32,
+    /// A break here is required
+    forced: bool,
+    /// A break here is only taken if nothing else fits
+    avoid: bool,
+}
+
+/// Lay out a box tree in pages of `setup`
+pub fn paginate(root: &Node, setup: PageSetup) -> PaginatedLayout {
+    let (width, height) = setup.content_size();
+    let layout = build_layout_tree(root, width, height);
+
+    let mut breaks = Vec::new();
+    collect_breaks(&layout, false, &mut breaks);
+    breaks.sort_by(|a, b| a.y.total_cmp(&b.y));
+    let extent = layout.overflow_extent();
+    let end = extent.y + extent.height;
+
+    let mut pages = Vec::new();
+    let mut top = 0.0;
+    loop {
+        let limit = top + height;
+        // A break must leave something on the page
+        let fits: Vec<&Opportunity> = (breaks.iter())
+            .filter(|b| b.y > top + 0.5 && b.y <= limit && b.y < end)
+            .collect();
+        let bottom = match fits.iter().find(|b| b.forced) {
+            Some(forced) => forced.y,
+            None if limit >= end => end,
+            // The last break that fits, one that is not avoided if possible; content with no
+            // break that fits is cut at the page's edge
+            None => (fits.iter().rev().find(|b| !b.avoid))
+                .or(fits.last())
+                .map_or(limit, |b| b.y),
+        };
+        pages.push(Page { top, bottom });
+        if bottom >= end {
+            break;
+        }
+        top = bottom;
+    }
+
+    PaginatedLayout { setup, layout, pages }
+}
+
+/// Break opportunities inside `layout`: before each in-flow child that starts below the previous
+/// one. Children laid side by side, like the pieces of one line, have none between them.
+fn collect_breaks(layout: &LayoutBox, avoid_inside: bool, out: &mut Vec<Opportunity>) {
+    let avoid_inside = avoid_inside || layout.breaks.inside == BreakInside::Avoid;
+    let flow: Vec<&LayoutBox> = layout.children.iter().filter(|child| child.layer.is_none()).collect();
+    for pair in flow.windows(2) {
+        let (prev, next) = (pair[0], pair[1]);
+        if next.y < prev.y + prev.height - 0.01 {
+            continue;
+        }
+        let rule = trailing_break(prev).stronger(leading_break(next));
+        out.push(Opportunity {
+            y: next.y,
+            forced: rule == BreakBetween::Page,
+            avoid: avoid_inside || rule == BreakBetween::Avoid,
+        });
+    }
+    // The content of a box that clips it is cut where the box is
+    for child in flow.into_iter().filter(|child| child.clip.is_none()) {
+        collect_breaks(child, avoid_inside, out);
+    }
+}
+
+/// `break-before` of a box and of the first boxes inside it, which share its top edge
+fn leading_break(layout: &LayoutBox) -> BreakBetween {
+    let first = layout.children.iter().find(|child| child.layer.is_none());
+    first
+        .map_or(BreakBetween::Auto, leading_break)
+        .stronger(layout.breaks.before)
+}
+
+/// `break-after` of a box and of the last boxes inside it
+fn trailing_break(layout: &LayoutBox) -> BreakBetween {
+    let last = layout.children.iter().rev().find(|child| child.layer.is_none());
+    last.map_or(BreakBetween::Auto, trailing_break)
+        .stronger(layout.breaks.after)
+}
+
+impl BreakBetween {
+    /// The stronger of two adjoining values
+    fn stronger(self, other: BreakBetween) -> BreakBetween {
+        if other > self {
+            other
+        } else {
+            self
+        }
+    }
+}
+
+/// Page size from `size`: `auto`, a named paper size, one or two lengths, and an orientation
+fn parse_page_size(value: &str) -> Option<(f32, f32)> {
+    let mut size = None;
+    let mut lengths = Vec::new();
+    let mut landscape = None;
+    for word in value.to_lowercase().split_whitespace() {
+        match word {
+            "auto" => size = Some((PageSetup::default().width, PageSetup::default().height)),
+            "portrait" => landscape = Some(false),
+            "landscape" => landscape = Some(true),
+            "a5" => size = Some((mm(148.0), mm(210.0))),
+            "a4" => size = Some((mm(210.0), mm(297.0))),
+            "a3" => size = Some((mm(297.0), mm(420.0))),
+            "b5" => size = Some((mm(176.0), mm(250.0))),
+            "b4" => size = Some((mm(250.0), mm(353.0))),
+            "jis-b5" => size = Some((mm(182.0), mm(257.0))),
+            "jis-b4" => size = Some((mm(257.0), mm(364.0))),
+            "letter" => size = Some((816.0, 1056.0)),
+            "legal" => size = Some((816.0, 1344.0)),
+            "ledger" => size = Some((1056.0, 1632.0)),
+            _ => match parse_length(word, 16.0)? {
+                Length::Px(px) if px > 0.0 => lengths.push(px),
+                _ => return None,
+            },
+        }
+    }
+    let (width, height) = match lengths[..] {
+        [] => size.unwrap_or((PageSetup::default().width, PageSetup::default().height)),
+        [side] => (side, side),
+        [width, height] => (width, height),
+        _ => return None,
+    };
+    Some(match landscape {
+        Some(landscape) if landscape == (width < height) => (height, width),
+        _ => (width, height),
+    })
+}
+
+fn mm(mm: f32) -> f32 {
+    mm * 96.0 / 25.4
+}
@@ -1,8 +1,14 @@
 //! A very basic HTML parser that turns raw HTML into a DOM tree.
 //! It builds a simplified `Node` tree based on HTML structure.
 
-use crate::browser::dom::{Node, NodeType, ElementData, element, text};
+use crate::browser::dom::{element, text, Node};
 use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Elements that have no content and no end tag (HTML §13.1.2)
+const VOID_ELEMENTS: [&str; 13] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr",
+];
 
 #[derive(Debug)]
 pub struct HTMLParser {
@@ -18,10 +24,10 @@ impl HTMLParser {
         }
     }
 
-    pub fn parse(&mut self) -> Node {
+    pub fn parse(&mut self) -> Rc<Node> {
         let mut nodes = self.parse_nodes();
         if nodes.len() == 1 {
-            Rc::try_unwrap(nodes.remove(0)).unwrap_or_else(|rc| (*rc).clone())
+            nodes.remove(0)
         } else {
             element("html", HashMap::new(), nodes)
         }
@@ -46,15 +52,22 @@ impl HTMLParser {
     }
 
     fn parse_text(&mut self) -> Rc<Node> {
-        let text = self.consume_while(|c| c != '<');
-        text(&text)
+        let content = self.consume_while(|c| c != '<');
+        text(&content)
     }
 
     fn parse_element(&mut self) -> Rc<Node> {
         assert!(self.consume_char() == '<');
         let tag_name = self.parse_tag_name();
         let attrs = self.parse_attributes();
+        // `<br/>` is allowed, but only void elements can leave out their end tag
+        if self.starts_with("/") {
+            self.consume_char();
+        }
         assert!(self.consume_char() == '>');
+        if VOID_ELEMENTS.contains(&tag_name.to_ascii_lowercase().as_str()) {
+            return element(&tag_name, attrs, Vec::new());
+        }
 
         let children = self.parse_nodes();
 
@@ -75,7 +88,7 @@ impl HTMLParser {
         let mut attributes = HashMap::new();
         loop {
             self.consume_whitespace();
-            if self.current_char() == '>' {
+            if self.current_char() == '>' || self.starts_with("/>") {
                 break;
             }
 
@@ -133,10 +146,32 @@ impl HTMLParser {
     }
 }
 
-use std::rc::Rc;
-
 /// Parse HTML into a DOM `Node` tree
 pub fn parse_html(input: &str) -> Rc<Node> {
     let mut parser = HTMLParser::new(input);
-    Rc::new(parser.parse())
+    parser.parse()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(node: &Node) -> Vec<String> {
+        node.children().iter().filter_map(|child| child.tag_name().map(|t| t.to_string())).collect()
+    }
+
+    #[test]
+    fn void_elements_have_no_end_tag() {
+        let document = parse_html(r#"<p>a<br>b<img src="x.png" width="4"><wbr/>c</p>"#);
+        assert_eq!(tags(&document), ["br", "img", "wbr"]);
+        assert_eq!(document.children()[3].get_attr("width").as_deref(), Some("4"));
+        assert!(document.children().iter().all(|child| child.children().is_empty()));
+    }
+
+    #[test]
+    fn void_elements_close_inside_tables() {
+        let document = parse_html(r#"<table><colgroup><col width="10"><col></colgroup></table>"#);
+        let colgroup = document.children()[0].clone();
+        assert_eq!(tags(&colgroup), ["col", "col"]);
+    }
 }
@@ -0,0 +1,616 @@
+//! pdf.rs — PDF output for paginated layouts.
+//! Each page paints the band of the layout it holds the way the screen renderer paints the
+//! viewport, in the same stacking order, but as PDF operators: boxes and borders as vector
+//! rectangles, text in embedded fonts so it stays selectable, images as compressed XObjects.
+//! Page content is in px with y pointing down; one CSS px is 0.75 pt. Fixed boxes repeat on
+//! every page, and scroll containers print what they show, without scrollbars.
+
+use crate::browser::engine::{ListStyleType, Rect};
+use crate::browser::fonts::LoadedFont;
+use crate::browser::images::DecodedImage;
+use crate::browser::paginate::{Page, PaginatedLayout};
+use crate::browser::renderer::{
+    glyph_path, marker_path, stacking_layers, Color, ImagePaint, Layer, LayoutBox, ListMarker, TextNode,
+};
+use ab_glyph::{Font, GlyphId};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use raqote::{Path, PathOp, Point};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::sync::Arc;
+
+/// PDF points per CSS px
+const PT_PER_PX: f32 = 0.75;
+
+/// Object numbers of the objects every document starts with
+const CATALOG: usize = 1;
+const PAGES: usize = 2;
+const RESOURCES: usize = 3;
+
+/// The document as PDF bytes, one page per page of the layout
+pub fn render_pdf(paginated: &PaginatedLayout) -> Vec<u8> {
+    let mut doc = Document::new();
+    let mut kids = Vec::new();
+    for page in &paginated.pages {
+        let ops = paint_page(&mut doc, paginated, page);
+        let contents = doc.reserve();
+        doc.stream(contents, "", ops.as_bytes());
+        let id = doc.reserve();
+        let setup = &paginated.setup;
+        let body = format!(
+            "<< /Type /Page /Parent {PAGES} 0 R /MediaBox [0 0 {} {}] \
+             /Resources {RESOURCES} 0 R /Contents {contents} 0 R >>",
+            num(setup.width * PT_PER_PX),
+            num(setup.height * PT_PER_PX),
+        );
+        doc.set(id, body.into_bytes());
+        kids.push(id);
+    }
+
+    let kids: Vec<String> = kids.iter().map(|id| format!("{id} 0 R")).collect();
+    let pages = format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), kids.len());
+    doc.set(PAGES, pages.into_bytes());
+    doc.set(CATALOG, format!("<< /Type /Catalog /Pages {PAGES} 0 R >>").into_bytes());
+    doc.finish()
+}
+
+/// Write the document as a PDF file
+pub fn write_pdf(paginated: &PaginatedLayout, path: &str) -> io::Result<()> {
+    std::fs::write(path, render_pdf(paginated))
+}
+
+/// The content stream of one page
+fn paint_page(doc: &mut Document, paginated: &PaginatedLayout, page: &Page) -> String {
+    let setup = &paginated.setup;
+    let (width, _) = setup.content_size();
+    let margin = &setup.margin;
+    let area = Rect {
+        x: margin.left,
+        y: margin.top,
+        width,
+        height: page.bottom - page.top,
+    };
+    let mut painter = PagePainter {
+        doc,
+        ops: String::new(),
+        origin: (margin.left, margin.top),
+        area,
+    };
+    // Flip to px with y down, then keep to the band of the layout this page holds
+    let _ = writeln!(
+        painter.ops,
+        "{} 0 0 {} 0 {} cm",
+        num(PT_PER_PX),
+        num(-PT_PER_PX),
+        num(setup.height * PT_PER_PX)
+    );
+    painter.push_clip(area);
+    painter.paint_stacking_context(&paginated.layout, (margin.left, margin.top - page.top));
+    painter.ops.push_str("Q\n");
+    painter.ops
+}
+
+/// Paints boxes onto one page as PDF operators
+struct PagePainter<'a> {
+    doc: &'a mut Document,
+    ops: String,
+    /// Top left of the page area, which fixed boxes are placed from
+    origin: (f32, f32),
+    /// The page area, cut off where the page's band of the layout ends
+    area: Rect,
+}
+
+impl PagePainter<'_> {
+    /// Paint a box and its descendants in stacking order
+    fn paint_stacking_context(&mut self, root: &LayoutBox, offset: (f32, f32)) {
+        if !self.visible(root, offset) {
+            return;
+        }
+        self.paint_box(root, offset);
+        self.paint_contents(root, offset);
+    }
+
+    /// Paint a box's descendants in the order the screen renderer does, clipped to the box if it
+    /// clips its overflow
+    fn paint_contents(&mut self, root: &LayoutBox, offset: (f32, f32)) {
+        let inner = match &root.clip {
+            Some(clip) => {
+                self.push_clip(Rect {
+                    x: clip.rect.x + offset.0,
+                    y: clip.rect.y + offset.1,
+                    ..clip.rect
+                });
+                (offset.0 - clip.scroll.0, offset.1 - clip.scroll.1)
+            }
+            None => offset,
+        };
+
+        let (layers, split) = stacking_layers(root);
+        for layer in &layers[..split] {
+            self.paint_layer(layer, inner);
+        }
+        self.paint_flow(&root.children, inner);
+        for layer in &layers[split..] {
+            self.paint_layer(layer, inner);
+        }
+
+        if root.clip.is_some() {
+            self.ops.push_str("Q\n");
+        }
+    }
+
+    /// Paint a positioned box. Sticky boxes stay where they are laid out on paper.
+    fn paint_layer(&mut self, layout: &LayoutBox, offset: (f32, f32)) {
+        let offset = match &layout.layer {
+            Some(Layer { fixed: true, .. }) => self.origin,
+            _ => offset,
+        };
+        self.paint_stacking_context(layout, offset);
+    }
+
+    /// Paint in-flow boxes in tree order, leaving positioned ones to their layer
+    fn paint_flow(&mut self, boxes: &[LayoutBox], offset: (f32, f32)) {
+        for layout in boxes.iter().filter(|b| b.layer.is_none()) {
+            if !self.visible(layout, offset) {
+                continue;
+            }
+            self.paint_box(layout, offset);
+            if layout.clip.is_some() {
+                self.paint_contents(layout, offset);
+            } else {
+                self.paint_flow(&layout.children, offset);
+            }
+        }
+    }
+
+    /// Returns true if the box or its overflow reaches into the page's band
+    fn visible(&self, layout: &LayoutBox, offset: (f32, f32)) -> bool {
+        let extent = layout.overflow_extent();
+        let (top, bottom) = (extent.y + offset.1, extent.y + extent.height + offset.1);
+        top < self.area.y + self.area.height && bottom > self.area.y
+    }
+
+    /// Paint a box's own background, border, image, text and marker
+    fn paint_box(&mut self, layout: &LayoutBox, offset: (f32, f32)) {
+        let paints = layout.background.is_some()
+            || layout.border.is_some()
+            || layout.image.is_some()
+            || layout.text.is_some()
+            || layout.marker.is_some();
+        if !paints {
+            return;
+        }
+        let _ = writeln!(self.ops, "q 1 0 0 1 {} {} cm", num(offset.0), num(offset.1));
+        let bounds = rect(layout.x, layout.y, layout.width, layout.height);
+        if let Some(background) = layout.background {
+            self.set_fill(background);
+            let _ = writeln!(self.ops, "{bounds} re f");
+        }
+        if let Some((color, thickness)) = layout.border {
+            self.set_stroke(color, thickness);
+            let _ = writeln!(self.ops, "{bounds} re S");
+        }
+        if let Some(image) = &layout.image {
+            self.draw_image(layout, image);
+        }
+        if let Some(text) = &layout.text {
+            self.draw_text(text, layout);
+        }
+        if let Some(marker) = &layout.marker {
+            self.draw_marker(layout, marker);
+        }
+        self.ops.push_str("Q\n");
+    }
+
+    /// Draw an image into its destination rectangle, clipped to the box
+    fn draw_image(&mut self, layout: &LayoutBox, paint: &ImagePaint) {
+        let name = self.doc.image(&paint.image);
+        let dest = paint.dest;
+        let _ = writeln!(
+            self.ops,
+            "q {} re W n {} 0 0 {} {} {} cm /{name} Do Q",
+            rect(layout.x, layout.y, layout.width, layout.height),
+            num(dest.width),
+            num(-dest.height),
+            num(dest.x),
+            num(dest.y + dest.height),
+        );
+    }
+
+    /// Draw a list marker's bullet
+    fn draw_marker(&mut self, layout: &LayoutBox, marker: &ListMarker) {
+        let path = path_ops(&marker_path(layout, marker));
+        if marker.kind == ListStyleType::Circle {
+            self.set_stroke(marker.color, (marker.font_size / 16.0).max(1.0));
+            let _ = writeln!(self.ops, "{path}S");
+        } else {
+            self.set_fill(marker.color);
+            let _ = writeln!(self.ops, "{path}f");
+        }
+    }
+
+    /// Draw shaped text filling its box, each glyph placed as on screen. Glyphs of fonts that
+    /// cannot be embedded are drawn as outlines.
+    fn draw_text(&mut self, text: &TextNode, layout: &LayoutBox) {
+        let (shaped, places) = text.place_glyphs(layout);
+        self.set_fill(text.color);
+        let fonts: Vec<Option<usize>> = shaped.fonts.iter().map(|font| self.doc.font(font)).collect();
+        for (glyph, place) in shaped.glyphs.iter().zip(&places) {
+            let Some(index) = glyph.font else {
+                continue;
+            };
+            let Some(font) = fonts[index] else {
+                if let Some(path) = glyph_path(&shaped.fonts[index], glyph.glyph_id, text.font_size, place) {
+                    let _ = writeln!(self.ops, "{}f", path_ops(&path));
+                }
+                continue;
+            };
+            // The cluster's text, so copying it out of the PDF gives the source text
+            let end = (shaped.glyphs.iter())
+                .map(|other| other.cluster)
+                .filter(|&cluster| cluster > glyph.cluster)
+                .min()
+                .unwrap_or(text.content.len());
+            self.doc.fonts[font]
+                .text
+                .entry(glyph.glyph_id)
+                .or_insert_with(|| text.content[glyph.cluster..end].to_string());
+            // Text space points up, the page down (see `glyph_path`)
+            let _ = writeln!(
+                self.ops,
+                "BT /F{font} {} Tf {} {} {} {} {} {} Tm <{:04X}> Tj ET",
+                num(text.font_size),
+                num(place.m11),
+                num(place.m12),
+                num(-place.m21),
+                num(-place.m22),
+                num(place.m31),
+                num(place.m32),
+                glyph.glyph_id,
+            );
+        }
+    }
+
+    fn set_fill(&mut self, color: Color) {
+        let alpha = self.doc.alpha(color.3);
+        let _ = writeln!(self.ops, "/{alpha} gs {} rg", rgb(color));
+    }
+
+    fn set_stroke(&mut self, color: Color, width: f32) {
+        let alpha = self.doc.alpha(color.3);
+        let _ = writeln!(self.ops, "/{alpha} gs {} RG {} w", rgb(color), num(width));
+    }
+
+    /// Clip to `area` until the matching `Q`
+    fn push_clip(&mut self, area: Rect) {
+        let _ = writeln!(self.ops, "q {} re W n", rect(area.x, area.y, area.width, area.height));
+    }
+}
+
+/// A font embedded in the document, addressed by glyph id (Identity-H)
+struct PdfFont {
+    font: LoadedFont,
+    /// Object number of its Type0 font dictionary
+    id: usize,
+    /// Compact Font Format outlines ("OTTO") rather than TrueType ones
+    cff: bool,
+    /// Text each glyph drawn stands for, for the ToUnicode map
+    text: BTreeMap<u16, String>,
+}
+
+/// PDF objects being written, and the fonts, images and opacities the pages share
+struct Document {
+    /// Object bodies by object number less one; None until written
+    objects: Vec<Option<Vec<u8>>>,
+    fonts: Vec<PdfFont>,
+    /// Images written, with their object numbers
+    images: Vec<(DecodedImage, usize)>,
+    /// Graphics states setting each opacity used, by alpha
+    alphas: BTreeMap<u8, usize>,
+}
+
+impl Document {
+    fn new() -> Self {
+        let mut doc = Document {
+            objects: Vec::new(),
+            fonts: Vec::new(),
+            images: Vec::new(),
+            alphas: BTreeMap::new(),
+        };
+        for _ in [CATALOG, PAGES, RESOURCES] {
+            doc.reserve();
+        }
+        doc
+    }
+
+    /// Allocate an object number
+    fn reserve(&mut self) -> usize {
+        self.objects.push(None);
+        self.objects.len()
+    }
+
+    fn set(&mut self, id: usize, body: Vec<u8>) {
+        self.objects[id - 1] = Some(body);
+    }
+
+    /// Write a Flate-compressed stream object; `dict` holds its other dictionary entries
+    fn stream(&mut self, id: usize, dict: &str, data: &[u8]) {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        let compressed = encoder
+            .write_all(data)
+            .and_then(|_| encoder.finish())
+            .unwrap_or_default();
+        let mut body = format!(
+            "<< {dict} /Filter /FlateDecode /Length {} >>\nstream\n",
+            compressed.len()
+        )
+        .into_bytes();
+        body.extend_from_slice(&compressed);
+        body.extend_from_slice(b"\nendstream");
+        self.set(id, body);
+    }
+
+    /// The index of the embedded copy of `font`, or None if its data cannot be embedded
+    /// (faces of a font collection)
+    fn font(&mut self, font: &LoadedFont) -> Option<usize> {
+        if let Some(index) = self.fonts.iter().position(|embedded| embedded.font.same(font)) {
+            return Some(index);
+        }
+        let cff = match font.data().get(..4)? {
+            &[0, 1, 0, 0] | b"true" => false,
+            b"OTTO" => true,
+            _ => return None,
+        };
+        let id = self.reserve();
+        self.fonts.push(PdfFont {
+            font: font.clone(),
+            id,
+            cff,
+            text: BTreeMap::new(),
+        });
+        Some(self.fonts.len() - 1)
+    }
+
+    /// The resource name of an image, written the first time it is drawn: RGB samples, with
+    /// the alpha channel as a soft mask
+    fn image(&mut self, image: &DecodedImage) -> String {
+        if let Some(index) = self
+            .images
+            .iter()
+            .position(|(other, _)| Arc::ptr_eq(&other.pixels, &image.pixels))
+        {
+            return format!("Im{index}");
+        }
+        let mut rgb = Vec::with_capacity(image.pixels.len() * 3);
+        let mut alpha = Vec::with_capacity(image.pixels.len());
+        for &pixel in image.pixels.iter() {
+            let a = (pixel >> 24) as u8;
+            // Pixels are premultiplied
+            let channel = |shift: u32| match a {
+                0 => 0,
+                _ => (((pixel >> shift) & 0xff) * 255 / a as u32).min(255) as u8,
+            };
+            rgb.extend([channel(16), channel(8), channel(0)]);
+            alpha.push(a);
+        }
+        let size = format!(
+            "/Type /XObject /Subtype /Image /Width {} /Height {}",
+            image.width, image.height
+        );
+        let mask = self.reserve();
+        self.stream(
+            mask,
+            &format!("{size} /ColorSpace /DeviceGray /BitsPerComponent 8"),
+            &alpha,
+        );
+        let id = self.reserve();
+        let dict = format!("{size} /ColorSpace /DeviceRGB /BitsPerComponent 8 /SMask {mask} 0 R");
+        self.stream(id, &dict, &rgb);
+        self.images.push((image.clone(), id));
+        format!("Im{}", self.images.len() - 1)
+    }
+
+    /// The resource name of a graphics state with fill and stroke opacity `alpha`
+    fn alpha(&mut self, alpha: u8) -> String {
+        if !self.alphas.contains_key(&alpha) {
+            let id = self.reserve();
+            let opacity = num(alpha as f32 / 255.0);
+            self.set(
+                id,
+                format!("<< /Type /ExtGState /ca {opacity} /CA {opacity} >>").into_bytes(),
+            );
+            self.alphas.insert(alpha, id);
+        }
+        format!("GA{alpha}")
+    }
+
+    /// Write the fonts and shared resources, then serialize every object
+    fn finish(mut self) -> Vec<u8> {
+        let fonts = std::mem::take(&mut self.fonts);
+        for font in &fonts {
+            self.write_font(font);
+        }
+
+        let mut resources = String::from("<< /ProcSet [/PDF /Text /ImageB /ImageC]");
+        let names: Vec<String> = (fonts.iter().enumerate())
+            .map(|(i, font)| format!("/F{i} {} 0 R", font.id))
+            .collect();
+        let _ = write!(resources, " /Font << {} >>", names.join(" "));
+        let names: Vec<String> = (self.images.iter().enumerate())
+            .map(|(i, (_, id))| format!("/Im{i} {id} 0 R"))
+            .collect();
+        let _ = write!(resources, " /XObject << {} >>", names.join(" "));
+        let names: Vec<String> = self
+            .alphas
+            .iter()
+            .map(|(alpha, id)| format!("/GA{alpha} {id} 0 R"))
+            .collect();
+        let _ = write!(resources, " /ExtGState << {} >> >>", names.join(" "));
+        self.set(RESOURCES, resources.into_bytes());
+
+        let mut out = b"%PDF-1.7\n%\xe2\xe3\xcf\xd3\n".to_vec();
+        let mut offsets = Vec::with_capacity(self.objects.len());
+        for (i, body) in self.objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+            out.extend_from_slice(body.as_deref().unwrap_or(b"null"));
+            out.extend_from_slice(b"\nendobj\n");
+        }
+        let xref = out.len();
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", offsets.len() + 1);
+        for offset in offsets {
+            let _ = writeln!(table, "{offset:010} 00000 n ");
+        }
+        let _ = write!(
+            table,
+            "trailer\n<< /Size {} /Root {CATALOG} 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+            self.objects.len() + 1
+        );
+        out.extend_from_slice(table.as_bytes());
+        out
+    }
+
+    /// Write a font as a Type0 font over a CID font holding the whole font file, with the
+    /// widths and text of the glyphs drawn
+    fn write_font(&mut self, embedded: &PdfFont) {
+        let font = &embedded.font.font;
+        let units = font.units_per_em().unwrap_or(1000.0);
+        let to_pdf = |value: f32| (value * 1000.0 / units).round() as i32;
+        let name = format!("F{}", embedded.id);
+
+        let file = self.reserve();
+        let data = embedded.font.data();
+        if embedded.cff {
+            self.stream(file, "/Subtype /OpenType", data);
+        } else {
+            self.stream(file, &format!("/Length1 {}", data.len()), data);
+        }
+        let descriptor = self.reserve();
+        let (ascent, descent) = (to_pdf(font.ascent_unscaled()), to_pdf(font.descent_unscaled()));
+        let body = format!(
+            "<< /Type /FontDescriptor /FontName /{name} /Flags 4 /FontBBox [0 {descent} 1000 {ascent}] \
+             /ItalicAngle 0 /Ascent {ascent} /Descent {descent} /CapHeight {ascent} /StemV 80 /{} {file} 0 R >>",
+            if embedded.cff { "FontFile3" } else { "FontFile2" },
+        );
+        self.set(descriptor, body.into_bytes());
+
+        let widths: Vec<String> = (embedded.text.keys())
+            .map(|&glyph| format!("{glyph} [{}]", to_pdf(font.h_advance_unscaled(GlyphId(glyph)))))
+            .collect();
+        let cid_font = self.reserve();
+        let body = format!(
+            "<< /Type /Font /Subtype /{} /BaseFont /{name} \
+             /CIDSystemInfo << /Registry (Adobe) /Ordering (Identity) /Supplement 0 >> \
+             /FontDescriptor {descriptor} 0 R /W [{}]{} >>",
+            if embedded.cff { "CIDFontType0" } else { "CIDFontType2" },
+            widths.join(" "),
+            if embedded.cff { "" } else { " /CIDToGIDMap /Identity" },
+        );
+        self.set(cid_font, body.into_bytes());
+
+        let to_unicode = self.reserve();
+        self.stream(to_unicode, "", to_unicode_cmap(&embedded.text).as_bytes());
+        let body = format!(
+            "<< /Type /Font /Subtype /Type0 /BaseFont /{name} /Encoding /Identity-H \
+             /DescendantFonts [{cid_font} 0 R] /ToUnicode {to_unicode} 0 R >>"
+        );
+        self.set(embedded.id, body.into_bytes());
+    }
+}
+
+/// A CMap from glyph ids to the text they stand for
+fn to_unicode_cmap(text: &BTreeMap<u16, String>) -> String {
+    let mut cmap = String::from(
+        "/CIDInit /ProcSet findresource begin\n12 dict begin\nbegincmap\n\
+         /CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n\
+         /CMapName /Adobe-Identity-UCS def\n/CMapType 2 def\n\
+         1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n",
+    );
+    let entries: Vec<(&u16, &String)> = text.iter().filter(|(_, text)| !text.is_empty()).collect();
+    // At most 100 mappings per block
+    for chunk in entries.chunks(100) {
+        let _ = writeln!(cmap, "{} beginbfchar", chunk.len());
+        for (glyph, text) in chunk {
+            let units: String = text.encode_utf16().map(|unit| format!("{unit:04X}")).collect();
+            let _ = writeln!(cmap, "<{glyph:04X}> <{units}>");
+        }
+        cmap.push_str("endbfchar\n");
+    }
+    cmap.push_str("endcmap\nCMapName currentdict /CMap defineresource pop\nend\nend\n");
+    cmap
+}
+
+/// Path construction operators for a path; quadratic curves become cubic ones
+fn path_ops(path: &Path) -> String {
+    let mut ops = String::new();
+    let mut current = Point::new(0.0, 0.0);
+    let mut start = current;
+    for op in &path.ops {
+        match *op {
+            PathOp::MoveTo(p) => {
+                let _ = writeln!(ops, "{} {} m", num(p.x), num(p.y));
+                (current, start) = (p, p);
+            }
+            PathOp::LineTo(p) => {
+                let _ = writeln!(ops, "{} {} l", num(p.x), num(p.y));
+                current = p;
+            }
+            PathOp::QuadTo(c, p) => {
+                let c1 = current + (c - current) * (2.0 / 3.0);
+                let c2 = p + (c - p) * (2.0 / 3.0);
+                let _ = writeln!(
+                    ops,
+                    "{} {} {} {} {} {} c",
+                    num(c1.x),
+                    num(c1.y),
+                    num(c2.x),
+                    num(c2.y),
+                    num(p.x),
+                    num(p.y)
+                );
+                current = p;
+            }
+            PathOp::CubicTo(c1, c2, p) => {
+                let _ = writeln!(
+                    ops,
+                    "{} {} {} {} {} {} c",
+                    num(c1.x),
+                    num(c1.y),
+                    num(c2.x),
+                    num(c2.y),
+                    num(p.x),
+                    num(p.y)
+                );
+                current = p;
+            }
+            PathOp::Close => {
+                ops.push_str("h\n");
+                current = start;
+            }
+        }
+    }
+    ops
+}
+
+/// Operands of `re`
+fn rect(x: f32, y: f32, width: f32, height: f32) -> String {
+    format!("{} {} {} {}", num(x), num(y), num(width), num(height))
+}
+
+/// Operands of `rg` and `RG`
+fn rgb(color: Color) -> String {
+    let channel = |value: u8| num(value as f32 / 255.0);
+    format!("{} {} {}", channel(color.0), channel(color.1), channel(color.2))
+}
+
+/// A number as PDF writes it: no exponent, at most three decimals
+fn num(value: f32) -> String {
+    let text = format!("{:.3}", if value.is_finite() { value } else { 0.0 });
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    match text {
+        "-0" | "" => "0".to_string(),
+        _ => text.to_string(),
+    }
+}
@@ -0,0 +1,155 @@
+//! relayout.rs — Incremental relayout: a block whose DOM subtree is clean and whose containing
+//! block is unchanged reuses its boxes from the last pass, moved to where it now starts.
+//! DOM nodes carry layout-dirty bits (see `dom::LayoutDirty`): text changes and explicit resizes
+//! set them directly, and restyle sets them on every node whose style it recomputes. A change of
+//! window width shows up as a different containing block width.
+
+use crate::browser::dom::Node as DomNode;
+use crate::browser::engine::{build_layout_tree, CollapsedMargin, Dimensions, Node};
+use crate::browser::renderer::LayoutBox;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Weak;
+
+/// Boxes of the last layout pass, by the DOM node of each block
+#[derive(Default)]
+pub struct LayoutCache {
+    blocks: HashMap<*const DomNode, CachedBlock>,
+}
+
+/// A laid out block and what its layout depended on
+struct CachedBlock {
+    /// Holding the node keeps its address from being reused by a new one
+    dom: Weak<DomNode>,
+    /// Left content edge and width of the containing block
+    container_x: f32,
+    container_width: f32,
+    container_height: Option<f32>,
+    /// Top border edge the block was laid out at
+    y: f32,
+    layout: LayoutBox,
+    bottom_margin: CollapsedMargin,
+    collapsed_through: bool,
+}
+
+/// How much of the tree a relayout pass laid out again
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RelayoutStats {
+    /// Blocks laid out again
+    pub relaid: usize,
+    /// Blocks whose previous boxes were reused, with everything inside them
+    pub reused: usize,
+}
+
+struct Pass {
+    cache: LayoutCache,
+    stats: RelayoutStats,
+}
+
+thread_local! {
+    static PASS: RefCell<Option<Pass>> = const { RefCell::new(None) };
+}
+
+impl LayoutCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forget every block, e.g. after stylesheets change without a restyle
+    pub fn clear(&mut self) {
+        self.blocks.clear();
+    }
+}
+
+/// Lay out a box tree like `build_layout_tree`, reusing the blocks in `cache` that are still
+/// valid, and keep the new boxes in `cache` for the next pass
+pub fn relayout(
+    root: &Node,
+    viewport_width: f32,
+    viewport_height: f32,
+    cache: &mut LayoutCache,
+) -> (LayoutBox, RelayoutStats) {
+    let pass = Pass {
+        cache: std::mem::take(cache),
+        stats: RelayoutStats::default(),
+    };
+    PASS.with(|current| *current.borrow_mut() = Some(pass));
+    let layout = build_layout_tree(root, viewport_width, viewport_height);
+    let Some(mut pass) = PASS.with(|current| current.borrow_mut().take()) else {
+        return (layout, RelayoutStats::default());
+    };
+
+    // Nodes that left the document can never be reused
+    pass.cache.blocks.retain(|_, block| block.dom.strong_count() > 0);
+    *cache = pass.cache;
+    if let Some(document) = root.dom.as_ref().and_then(Weak::upgrade) {
+        clear_dirty(&document);
+    }
+    (layout, pass.stats)
+}
+
+/// The previous layout of the block `node`, moved to start at `y` in `container`, if neither
+/// the block nor its containing block changed since
+pub fn cached_block(
+    node: &Node,
+    container: &Dimensions,
+    container_height: Option<f32>,
+    y: f32,
+) -> Option<(LayoutBox, CollapsedMargin, bool)> {
+    PASS.with(|current| {
+        let mut current = current.borrow_mut();
+        let pass = current.as_mut()?;
+        let dom = node.dom.as_ref()?;
+        if dom.upgrade()?.layout_dirty().needs_relayout() {
+            return None;
+        }
+        let block = pass.cache.blocks.get(&dom.as_ptr())?;
+        if block.container_width != container.content.width || block.container_height != container_height {
+            return None;
+        }
+        let mut layout = block.layout.clone();
+        layout.translate(container.content.x - block.container_x, y - block.y);
+        pass.stats.reused += 1;
+        Some((layout, block.bottom_margin, block.collapsed_through))
+    })
+}
+
+/// Keep a block just laid out at `y` in `container` for the next pass
+pub fn cache_block(
+    node: &Node,
+    container: &Dimensions,
+    container_height: Option<f32>,
+    y: f32,
+    layout: &LayoutBox,
+    bottom_margin: CollapsedMargin,
+    collapsed_through: bool,
+) {
+    PASS.with(|current| {
+        let mut current = current.borrow_mut();
+        let Some(pass) = current.as_mut() else {
+            return;
+        };
+        pass.stats.relaid += 1;
+        let Some(dom) = &node.dom else {
+            return;
+        };
+        let block = CachedBlock {
+            dom: dom.clone(),
+            container_x: container.content.x,
+            container_width: container.content.width,
+            container_height,
+            y,
+            layout: layout.clone(),
+            bottom_margin,
+            collapsed_through,
+        };
+        pass.cache.blocks.insert(dom.as_ptr(), block);
+    });
+}
+
+/// Layout of the document is up to date again. The DOM is walked rather than the box tree,
+/// so nodes without boxes (`display: none` or `contents`) are cleared too.
+fn clear_dirty(node: &DomNode) {
+    node.clear_layout_dirty();
+    node.children().iter().for_each(|child| clear_dirty(child));
+}
@@ -2,14 +2,20 @@
 //! This module handles drawing layout boxes (rectangles with style) and text.
 
 use raqote::*;
-use font_kit::source::SystemSource;
-use font_kit::properties::Properties;
-use font_kit::handle::Handle;
-use ab_glyph::{FontArc, PxScale, GlyphId, point, Glyph};
-use std::collections::HashMap;
+use ab_glyph::{Font, GlyphId, OutlineCurve};
+use crate::browser::css::Stylesheet;
+use crate::browser::dom::Node as DomNode;
+use crate::browser::engine::{FontStyle, ListStyleType, Overflow, PointerEvents, Rect, TextOrientation};
+use crate::browser::fonts::{FontQuery, LoadedFont};
+use crate::browser::hit::{hit_test, HitTestResult};
+use crate::browser::images::{with_image_cache, DecodedImage};
+use crate::browser::paginate::Breaks;
+use crate::browser::text::{with_font_context, ShapedGlyph, ShapedText};
+use std::rc::Weak;
+use unicode_vo::{char_orientation, Orientation};
 
 /// A simplified color struct.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Color(pub u8, pub u8, pub u8, pub u8);
 
 impl Color {
@@ -33,7 +39,196 @@ pub struct LayoutBox {
     pub background: Option<Color>,
     pub border: Option<(Color, f32)>,
     pub text: Option<TextNode>,
+    /// A list bullet painted as a shape (disc, circle, square)
+    pub marker: Option<ListMarker>,
+    /// The image of a replaced element, clipped to the box
+    pub image: Option<ImagePaint>,
+    /// Set on positioned boxes, which are painted above in-flow content in `z-index` order
+    pub layer: Option<Layer>,
+    /// Set on boxes that clip their overflow; scroll containers also scroll it
+    pub clip: Option<Clip>,
+    pub pointer_events: PointerEvents,
+    /// Page break rules of a block, for pagination
+    pub breaks: Breaks,
+    /// Set on line boxes, which hold the inline content of one line of a block
+    pub line: bool,
     pub children: Vec<LayoutBox>,
+    /// The DOM node the box was generated from
+    pub dom: Option<Weak<DomNode>>,
+}
+
+impl LayoutBox {
+    /// A zero-sized box with nothing to paint
+    pub fn empty() -> Self {
+        LayoutBox {
+            x: 0.0,
+            y: 0.0,
+            width: 0.0,
+            height: 0.0,
+            background: None,
+            border: None,
+            text: None,
+            marker: None,
+            image: None,
+            layer: None,
+            clip: None,
+            pointer_events: PointerEvents::Auto,
+            breaks: Breaks::AUTO,
+            line: false,
+            children: vec![],
+            dom: None,
+        }
+    }
+
+    /// Move the box and everything inside it
+    pub fn translate(&mut self, dx: f32, dy: f32) {
+        self.x += dx;
+        self.y += dy;
+        if let Some(image) = &mut self.image {
+            image.dest.x += dx;
+            image.dest.y += dy;
+        }
+        if let Some(clip) = &mut self.clip {
+            clip.rect.x += dx;
+            clip.rect.y += dy;
+            clip.content.x += dx;
+            clip.content.y += dy;
+        }
+        if let Some(Layer { sticky: Some(sticky), .. }) = &mut self.layer {
+            sticky.bounds.x += dx;
+            sticky.bounds.y += dy;
+        }
+        for child in &mut self.children {
+            child.translate(dx, dy);
+        }
+    }
+
+    /// The area the box and its visible overflow cover: its own rectangle, and its
+    /// descendants' unless it clips them
+    pub fn overflow_extent(&self) -> Rect {
+        let own = Rect {
+            x: self.x,
+            y: self.y,
+            width: self.width,
+            height: self.height,
+        };
+        if self.clip.is_some() {
+            return own;
+        }
+        self.children.iter().fold(own, |area, child| area.union(child.overflow_extent()))
+    }
+
+    /// Scroll the innermost scroll container under (`x`, `y`) that can still move by
+    /// (`dx`, `dy`). The point is in this box's coordinates, before any of its scrolling.
+    /// Returns false if none could, so the caller can scroll further out instead.
+    pub fn scroll_at(&mut self, x: f32, y: f32, dx: f32, dy: f32) -> bool {
+        let (inner_x, inner_y) = match &self.clip {
+            Some(clip) if !clip.rect.contains(x, y) => return false,
+            Some(clip) => (x + clip.scroll.0, y + clip.scroll.1),
+            None => (x, y),
+        };
+        if self.children.iter_mut().rev().any(|child| child.scroll_at(inner_x, inner_y, dx, dy)) {
+            return true;
+        }
+        match &mut self.clip {
+            Some(clip) if clip.overflow.is_scrollable() => clip.scroll_by(dx, dy),
+            _ => false,
+        }
+    }
+
+    /// Keep the scroll offsets of `previous`, an earlier layout of the same tree, on the scroll
+    /// containers at the same place in this one
+    pub fn restore_scroll(&mut self, previous: &LayoutBox) {
+        if let (Some(clip), Some(old)) = (&mut self.clip, &previous.clip) {
+            clip.scroll_to(old.scroll.0, old.scroll.1);
+        }
+        for (child, old) in self.children.iter_mut().zip(&previous.children) {
+            child.restore_scroll(old);
+        }
+    }
+}
+
+/// Overflow clipping of a box whose `overflow` is not `visible`
+#[derive(Debug, Clone)]
+pub struct Clip {
+    /// The padding box, which content is clipped to
+    pub rect: Rect,
+    /// Scrollable overflow: the area the content covers when not scrolled
+    pub content: Rect,
+    pub overflow: Overflow,
+    /// How far the content is scrolled, from the top left
+    pub scroll: (f32, f32),
+}
+
+impl Clip {
+    /// Largest scroll offset on each axis
+    pub fn max_scroll(&self) -> (f32, f32) {
+        (
+            (self.content.x + self.content.width - self.rect.x - self.rect.width).max(0.0),
+            (self.content.y + self.content.height - self.rect.y - self.rect.height).max(0.0),
+        )
+    }
+
+    /// Scroll to (`x`, `y`), kept within the content
+    pub fn scroll_to(&mut self, x: f32, y: f32) {
+        let (max_x, max_y) = self.max_scroll();
+        self.scroll = (x.clamp(0.0, max_x), y.clamp(0.0, max_y));
+    }
+
+    /// The clip rect in the coordinates of a translation `offset` of its box: the area sticky
+    /// descendants stick within
+    pub fn scrollport(&self, offset: (f32, f32)) -> Rect {
+        Rect {
+            x: self.rect.x + offset.0,
+            y: self.rect.y + offset.1,
+            ..self.rect
+        }
+    }
+
+    /// Scroll by (`dx`, `dy`); returns false if the content was already at that edge
+    pub fn scroll_by(&mut self, dx: f32, dy: f32) -> bool {
+        let before = self.scroll;
+        self.scroll_to(before.0 + dx, before.1 + dy);
+        self.scroll != before
+    }
+}
+
+/// Painting information for a positioned box. Each one is painted as a unit with its
+/// descendants; `z-index: auto` paints at level 0.
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub z_index: Option<i32>,
+    /// `position: fixed`: laid out against the viewport and not moved by scrolling
+    pub fixed: bool,
+    pub sticky: Option<Sticky>,
+}
+
+/// Constraints of a `position: sticky` box
+#[derive(Debug, Clone)]
+pub struct Sticky {
+    /// Distances from the edges of the scrollport (the viewport, or the clip rect of the
+    /// nearest box that clips its overflow) the box sticks at; None for `auto`
+    pub top: Option<f32>,
+    pub right: Option<f32>,
+    pub bottom: Option<f32>,
+    pub left: Option<f32>,
+    /// Content box of its containing block, which the box never leaves
+    pub bounds: Rect,
+}
+
+/// Bullet shape for a list marker box
+#[derive(Debug, Clone)]
+pub struct ListMarker {
+    pub kind: ListStyleType,
+    pub color: Color,
+    pub font_size: f32,
+}
+
+/// An image drawn scaled into `dest`, which `object-fit` may make larger than the box
+#[derive(Debug, Clone)]
+pub struct ImagePaint {
+    pub image: DecodedImage,
+    pub dest: Rect,
 }
 
 /// Text to render inside a box
@@ -43,14 +238,85 @@ pub struct TextNode {
     pub font_size: f32,
     pub color: Color,
     pub font_family: String,
+    pub font_weight: u16,
+    pub font_style: FontStyle,
+    pub font_stretch: f32,
+    /// Distance from the top of the text's box down to its baseline
+    pub baseline: f32,
+    /// Character offset in the DOM text node at each character boundary of `content`
+    /// (one more than its characters); empty for generated text
+    pub source_offsets: Vec<usize>,
+    /// Right-to-left text, with `content` in logical order; it is drawn reversed
+    pub rtl: bool,
+    /// Set in a vertical line: the text runs down its box, `baseline` is measured from the box's
+    /// right edge and `orientation` says which glyphs stand upright
+    pub vertical: bool,
+    pub orientation: TextOrientation,
+}
+
+impl TextNode {
+    /// The font properties to match for this text
+    pub fn font_query(&self) -> FontQuery {
+        FontQuery::new(&self.font_family, self.font_weight, self.font_style, self.font_stretch)
+    }
+
+    /// The glyphs of the text in the order they are drawn, left to right. The text is shaped
+    /// in its resolved direction, not the one its script suggests, so overrides hold.
+    pub fn shape(&self) -> ShapedText {
+        let query = self.font_query();
+        with_font_context(|fonts| fonts.shape_directed(&self.content, &query, self.font_size, self.rtl))
+    }
+
+    /// The text shaped, with where each glyph goes when the text fills `layout`: a transform
+    /// from the glyph's coordinates (in px, origin on its baseline, y down) to the page's.
+    /// In a vertical line, sideways glyphs are turned a quarter turn clockwise and upright ones
+    /// stand centred in the line, each in the space of its advance.
+    pub fn place_glyphs(&self, layout: &LayoutBox) -> (ShapedText, Vec<Transform>) {
+        let shaped = self.shape();
+        let mut pen = if self.vertical { layout.y } else { layout.x };
+        let (ascent, descent) = (self.baseline, layout.width - self.baseline);
+        let places = (shaped.glyphs.iter())
+            .map(|glyph| {
+                let place = if !self.vertical {
+                    Transform::translation(pen + glyph.x_offset, layout.y + self.baseline - glyph.y_offset)
+                } else if self.is_upright(glyph) {
+                    let x = layout.x + (layout.width - glyph.x_advance) / 2.0 + glyph.x_offset;
+                    let y = pen + (glyph.x_advance + ascent - descent) / 2.0 - glyph.y_offset;
+                    Transform::translation(x, y)
+                } else {
+                    // `baseline` is measured from the right edge of a vertical line
+                    let baseline = layout.x + layout.width - self.baseline;
+                    Transform::new(0.0, 1.0, -1.0, 0.0, baseline + glyph.y_offset, pen + glyph.x_offset)
+                };
+                pen += glyph.x_advance;
+                place
+            })
+            .collect();
+        (shaped, places)
+    }
+
+    /// In a vertical line, the glyph stands upright instead of sideways. With
+    /// `text-orientation: mixed` that depends on its character (UAX #50).
+    pub fn is_upright(&self, glyph: &ShapedGlyph) -> bool {
+        match self.orientation {
+            TextOrientation::Upright => true,
+            TextOrientation::Sideways => false,
+            TextOrientation::Mixed => {
+                let orientation = self.content[glyph.cluster..].chars().next().map(char_orientation);
+                matches!(orientation, Some(Orientation::Upright | Orientation::TransformedOrUpright))
+            }
+        }
+    }
 }
 
-/// The main renderer struct, which contains its DrawTarget and font cache.
+/// The main renderer struct, which contains its DrawTarget.
+/// Fonts live in the shared font context (see `text`), so layout measures what is drawn.
 pub struct Renderer {
     pub width: i32,
     pub height: i32,
     target: DrawTarget,
-    font_cache: HashMap<String, FontArc>,
+    /// Scroll position of the page
+    scroll: (f32, f32),
 }
 
 impl Renderer {
@@ -60,41 +326,159 @@ impl Renderer {
             width,
             height,
             target,
-            font_cache: HashMap::new(),
+            scroll: (0.0, 0.0),
         }
     }
 
+    /// Scroll the page to (`x`, `y`); fixed boxes stay in place and sticky boxes stick
+    pub fn scroll_to(&mut self, x: f32, y: f32) {
+        self.scroll = (x, y);
+    }
+
     /// Clear the canvas with a solid color
     pub fn clear(&mut self, color: Color) {
         self.target.clear(color.to_solid());
     }
 
-    /// Load a font from system or cache
-    fn load_font(&mut self, family: &str) -> Option<FontArc> {
-        if let Some(font) = self.font_cache.get(family) {
-            return Some(font.clone());
+    /// Register the `@font-face` rules of a stylesheet loaded from `base_url`
+    pub fn add_web_fonts(&mut self, sheet: &Stylesheet, base_url: &str) {
+        with_font_context(|fonts| fonts.add_stylesheet(sheet, base_url));
+    }
+
+    /// Set the document URL that relative image URLs resolve against
+    pub fn set_base_url(&mut self, base_url: &str) {
+        with_image_cache(|images| images.set_base_url(base_url));
+    }
+
+    /// Render a layout tree at the current scroll position
+    pub fn render_box(&mut self, layout: &LayoutBox) {
+        let (x, y) = self.scroll;
+        let viewport = Rect {
+            x: 0.0,
+            y: 0.0,
+            width: self.width as f32,
+            height: self.height as f32,
+        };
+        self.paint_stacking_context(layout, (-x, -y), viewport);
+        self.target.set_transform(&Transform::identity());
+    }
+
+    /// Scroll the page by (`dx`, `dy`), staying within the area `layout` covers
+    pub fn scroll_by(&mut self, layout: &LayoutBox, dx: f32, dy: f32) {
+        let page = layout.overflow_extent();
+        let max_x = (page.x + page.width - self.width as f32).max(0.0);
+        let max_y = (page.y + page.height - self.height as f32).max(0.0);
+        self.scroll = (
+            (self.scroll.0 + dx).clamp(0.0, max_x),
+            (self.scroll.1 + dy).clamp(0.0, max_y),
+        );
+    }
+
+    /// Scroll what is under the viewport point (`x`, `y`), e.g. for a mouse wheel: the
+    /// innermost scroll container there that can still move, otherwise the page
+    pub fn scroll_at(&mut self, layout: &mut LayoutBox, x: f32, y: f32, dx: f32, dy: f32) {
+        if !layout.scroll_at(x + self.scroll.0, y + self.scroll.1, dx, dy) {
+            self.scroll_by(layout, dx, dy);
         }
+    }
+
+    /// The DOM node under the viewport point (`x`, `y`), as the page is painted at the current
+    /// scroll position
+    pub fn hit_test(&self, layout: &LayoutBox, x: f32, y: f32) -> Option<HitTestResult> {
+        hit_test(layout, x, y, self.scroll, (self.width as f32, self.height as f32))
+    }
 
-        let source = SystemSource::new();
-        if let Ok(fonts) = source.select_family_by_name(family) {
-            if let Ok(handle) = fonts.fonts().first().cloned().unwrap().load() {
-                if let Handle::Memory { bytes, .. } = handle {
-                    if let Ok(font) = FontArc::try_from_vec(bytes.to_vec()) {
-                        self.font_cache.insert(family.to_string(), font.clone());
-                        return Some(font);
-                    }
-                }
+    /// Paint a box and its descendants in stacking order. Sticky descendants stick within
+    /// `scrollport`, in viewport coordinates.
+    fn paint_stacking_context(&mut self, root: &LayoutBox, offset: (f32, f32), scrollport: Rect) {
+        self.target.set_transform(&Transform::translation(offset.0, offset.1));
+        self.paint_box(root);
+        self.paint_contents(root, offset, scrollport);
+    }
+
+    /// Paint a box's descendants (CSS 2.1 Appendix E): negative `z-index` layers, in-flow
+    /// content, then the remaining layers. A box that clips its overflow clips all of them and
+    /// moves them by its scroll offset; positioned descendants do not escape it, and sticky
+    /// ones stick within its clip rect.
+    fn paint_contents(&mut self, root: &LayoutBox, offset: (f32, f32), scrollport: Rect) {
+        let (inner, scrollport) = match &root.clip {
+            Some(clip) => {
+                let mut pb = PathBuilder::new();
+                pb.rect(clip.rect.x, clip.rect.y, clip.rect.width, clip.rect.height);
+                self.target.set_transform(&Transform::translation(offset.0, offset.1));
+                self.target.push_clip(&pb.finish());
+                ((offset.0 - clip.scroll.0, offset.1 - clip.scroll.1), clip.scrollport(offset))
             }
+            None => (offset, scrollport),
+        };
+
+        let (layers, split) = stacking_layers(root);
+        for layer in &layers[..split] {
+            self.paint_layer(layer, inner, scrollport);
+        }
+        self.target.set_transform(&Transform::translation(inner.0, inner.1));
+        self.paint_flow(&root.children, inner, scrollport);
+        for layer in &layers[split..] {
+            self.paint_layer(layer, inner, scrollport);
         }
 
-        None
+        if let Some(clip) = &root.clip {
+            self.target.set_transform(&Transform::translation(offset.0, offset.1));
+            self.draw_scrollbars(clip);
+            self.target.pop_clip();
+        }
     }
 
-    /// Render a single layout box recursively
-    pub fn render_box(&mut self, layout: &LayoutBox) {
+    /// Paint a positioned box; `offset` is the translation of the layer it sits in
+    fn paint_layer(&mut self, layout: &LayoutBox, offset: (f32, f32), scrollport: Rect) {
+        let offset = layer_offset(layout, offset, scrollport);
+        self.paint_stacking_context(layout, offset, scrollport);
+    }
+
+    /// Paint in-flow boxes in tree order, leaving positioned ones to their layer.
+    /// `offset` is the translation already set on the target.
+    fn paint_flow(&mut self, boxes: &[LayoutBox], offset: (f32, f32), scrollport: Rect) {
+        for layout in boxes.iter().filter(|b| b.layer.is_none()) {
+            self.paint_box(layout);
+            if layout.clip.is_some() {
+                self.paint_contents(layout, offset, scrollport);
+                self.target.set_transform(&Transform::translation(offset.0, offset.1));
+            } else {
+                self.paint_flow(&layout.children, offset, scrollport);
+            }
+        }
+    }
+
+    /// Overlay scrollbars along the right and bottom of a scroll container: always for
+    /// `overflow: scroll`, and for `auto` on the axes that overflow
+    fn draw_scrollbars(&mut self, clip: &Clip) {
+        const THICKNESS: f32 = 6.0;
+        let (max_x, max_y) = clip.max_scroll();
+        let always = clip.overflow == Overflow::Scroll;
+        let rect = clip.rect;
+        let track = Source::Solid(Color(0, 0, 0, 24).to_solid());
+        let thumb = Source::Solid(Color(0, 0, 0, 110).to_solid());
+
+        if always || (clip.overflow == Overflow::Auto && max_y > 0.0) {
+            let x = rect.x + rect.width - THICKNESS;
+            let length = rect.height * rect.height / (rect.height + max_y);
+            let y = rect.y + (rect.height - length) * if max_y > 0.0 { clip.scroll.1 / max_y } else { 0.0 };
+            self.target.fill_rect(x, rect.y, THICKNESS, rect.height, &track, &DrawOptions::new());
+            self.target.fill_rect(x, y, THICKNESS, length, &thumb, &DrawOptions::new());
+        }
+        if always || (clip.overflow == Overflow::Auto && max_x > 0.0) {
+            let y = rect.y + rect.height - THICKNESS;
+            let length = rect.width * rect.width / (rect.width + max_x);
+            let x = rect.x + (rect.width - length) * if max_x > 0.0 { clip.scroll.0 / max_x } else { 0.0 };
+            self.target.fill_rect(rect.x, y, rect.width, THICKNESS, &track, &DrawOptions::new());
+            self.target.fill_rect(x, y, length, THICKNESS, &thumb, &DrawOptions::new());
+        }
+    }
+
+    /// Paint a box's own background, border, text and marker
+    fn paint_box(&mut self, layout: &LayoutBox) {
         // Draw background
         if let Some(bg) = layout.background {
-            let rect = DrawTarget::new(self.width, self.height);
             self.target.fill_rect(
                 layout.x,
                 layout.y,
@@ -127,87 +511,73 @@ impl Renderer {
             );
         }
 
+        if let Some(ref image) = layout.image {
+            self.draw_image(layout, image);
+        }
+
         // Render text
         if let Some(ref text) = layout.text {
-            self.draw_text(
-                &text.content,
-                layout.x + 4.0,
-                layout.y + text.font_size + 4.0,
-                &text.font_family,
-                text.font_size,
-                text.color,
-            );
+            self.draw_text(text, layout);
         }
 
-        // Recursively render children
-        for child in &layout.children {
-            self.render_box(child);
+        if let Some(ref marker) = layout.marker {
+            self.draw_marker(layout, marker);
         }
     }
 
-    /// Draw text using ab_glyph and raqote
-    fn draw_text(
-        &mut self,
-        text: &str,
-        x: f32,
-        y: f32,
-        font_family: &str,
-        font_size: f32,
-        color: Color,
-    ) {
-        let Some(font) = self.load_font(font_family) else {
-            eprintln!("Font '{}' not found", font_family);
-            return;
+    /// Draw an image into its destination rectangle, clipped to the box
+    fn draw_image(&mut self, layout: &LayoutBox, paint: &ImagePaint) {
+        let image = Image {
+            width: paint.image.width as i32,
+            height: paint.image.height as i32,
+            data: &paint.image.pixels,
         };
+        let mut pb = PathBuilder::new();
+        pb.rect(layout.x, layout.y, layout.width, layout.height);
+        self.target.push_clip(&pb.finish());
+        self.target.draw_image_with_size_at(
+            paint.dest.width,
+            paint.dest.height,
+            paint.dest.x,
+            paint.dest.y,
+            &image,
+            &DrawOptions::new(),
+        );
+        self.target.pop_clip();
+    }
 
-        let scale = PxScale::from(font_size);
-        let mut dx = x;
-
-        for ch in text.chars() {
-            if let Some(glyph_id) = font.glyph_id(ch) {
-                let glyph = font.outline_glyph(glyph_id.with_scale(scale)).unwrap();
-                if let Some(outline) = glyph.clone().into_outline() {
-                    let mut pb = PathBuilder::new();
-                    for segment in outline.segments() {
-                        match segment {
-                            ab_glyph::Segment::Line(a, b) => {
-                                pb.move_to(a.x + dx, a.y + y);
-                                pb.line_to(b.x + dx, b.y + y);
-                            }
-                            ab_glyph::Segment::Curve(a, b, c) => {
-                                pb.move_to(a.x + dx, a.y + y);
-                                pb.cubic_to(
-                                    b.x + dx,
-                                    b.y + y,
-                                    c.x + dx,
-                                    c.y + y,
-                                    a.x + dx,
-                                    a.y + y,
-                                );
-                            }
-                        }
-                    }
-
-                    let path = pb.finish();
-                    self.target.fill(
-                        &path,
-                        &Source::Solid(color.to_solid()),
-                        &DrawOptions::new(),
-                    );
-                }
-
-                dx += font.h_advance(glyph_id).unwrap_or(10.0) * font_size / 1000.0;
+    /// Draw a bullet centered on the marker box's line, against its right edge
+    fn draw_marker(&mut self, layout: &LayoutBox, marker: &ListMarker) {
+        let source = Source::Solid(marker.color.to_solid());
+        let path = marker_path(layout, marker);
+        if marker.kind == ListStyleType::Circle {
+            let stroke = StrokeStyle {
+                width: (marker.font_size / 16.0).max(1.0),
+                ..StrokeStyle::default()
+            };
+            self.target.stroke(&path, &source, &stroke, &DrawOptions::new());
+        } else {
+            self.target.fill(&path, &source, &DrawOptions::new());
+        }
+    }
+
+    /// Draw shaped text filling its box
+    fn draw_text(&mut self, text_node: &TextNode, layout: &LayoutBox) {
+        let (shaped, places) = text_node.place_glyphs(layout);
+        let source = Source::Solid(text_node.color.to_solid());
+        for (glyph, place) in shaped.glyphs.iter().zip(&places) {
+            let Some(font) = glyph.font.map(|i| &shaped.fonts[i]) else {
+                continue;
+            };
+            if let Some(path) = glyph_path(font, glyph.glyph_id, text_node.font_size, place) {
+                self.target.fill(&path, &source, &DrawOptions::new());
             }
         }
     }
 
     /// Export the current frame to a PNG image (debug/dev)
     pub fn save_png(&self, path: &str) {
-        use std::fs::File;
-        use std::io::BufWriter;
-        self.target
-            .write_png(BufWriter::new(File::create(path).unwrap()))
-            .unwrap();
+        self.target.write_png(path).unwrap();
     }
 
     /// Get raw pixel buffer (e.g. for passing to GPU texture)
@@ -215,3 +585,133 @@ impl Renderer {
         self.target.get_data()
     }
 }
+
+/// The layers of the stacking context `root` in paint order, and how many of them come
+/// before its in-flow content (those with a negative `z-index`)
+pub fn stacking_layers(root: &LayoutBox) -> (Vec<&LayoutBox>, usize) {
+    let mut layers = Vec::new();
+    collect_layers(&root.children, &mut layers);
+    let z_index = |l: &&LayoutBox| l.layer.as_ref().and_then(|layer| layer.z_index).unwrap_or(0);
+    // A stable sort keeps boxes of one level in tree order
+    layers.sort_by_key(z_index);
+    let split = layers.partition_point(|l| z_index(l) < 0);
+    (layers, split)
+}
+
+/// Translation of a positioned box painted in a layer whose translation is `offset`: fixed
+/// boxes ignore scrolling and sticky ones stick within `scrollport`, in viewport coordinates
+pub fn layer_offset(layout: &LayoutBox, offset: (f32, f32), scrollport: Rect) -> (f32, f32) {
+    match &layout.layer {
+        Some(Layer { fixed: true, .. }) => (0.0, 0.0),
+        Some(Layer { sticky: Some(sticky), .. }) => {
+            let dx = stick(
+                (layout.x, layout.x + layout.width),
+                (sticky.bounds.x, sticky.bounds.x + sticky.bounds.width),
+                (sticky.left, sticky.right),
+                offset.0,
+                (scrollport.x, scrollport.x + scrollport.width),
+            );
+            let dy = stick(
+                (layout.y, layout.y + layout.height),
+                (sticky.bounds.y, sticky.bounds.y + sticky.bounds.height),
+                (sticky.top, sticky.bottom),
+                offset.1,
+                (scrollport.y, scrollport.y + scrollport.height),
+            );
+            (offset.0 + dx, offset.1 + dy)
+        }
+        _ => offset,
+    }
+}
+
+/// Positioned descendants painted as layers of the stacking context that holds `boxes`.
+/// Those inside a box that clips its overflow are painted with that box instead.
+fn collect_layers<'a>(boxes: &'a [LayoutBox], out: &mut Vec<&'a LayoutBox>) {
+    for layout in boxes {
+        if layout.layer.is_some() {
+            out.push(layout);
+        } else if layout.clip.is_none() {
+            collect_layers(&layout.children, out);
+        }
+    }
+}
+
+/// How far a sticky box moves along one axis: it keeps `insets` away from the edges of the
+/// scrollport `port` while staying inside `bounds`. `span` and `bounds` are in page
+/// coordinates, `offset` maps them to the viewport, where `port` is.
+fn stick(span: (f32, f32), bounds: (f32, f32), insets: (Option<f32>, Option<f32>), offset: f32, port: (f32, f32)) -> f32 {
+    let (start, end) = (span.0 + offset, span.1 + offset);
+    let mut shift = 0.0;
+    if let Some(limit) = insets.0.map(|inset| port.0 + inset).filter(|limit| start < *limit) {
+        shift = (limit - start).min(bounds.1 - span.1).max(0.0);
+    }
+    if let Some(limit) = insets.1.map(|inset| port.1 - inset).filter(|limit| end > *limit) {
+        shift = (limit - end).max(bounds.0 - span.0).min(0.0);
+    }
+    shift
+}
+
+/// The bullet of a list marker box, centered on its line against its right edge. `circle` is
+/// stroked, the others filled.
+pub fn marker_path(layout: &LayoutBox, marker: &ListMarker) -> Path {
+    let size = marker.font_size * 0.35;
+    let cx = layout.x + layout.width - marker.font_size * 0.5 - size / 2.0;
+    let cy = layout.y + layout.height / 2.0;
+    let mut pb = PathBuilder::new();
+    match marker.kind {
+        ListStyleType::Square => pb.rect(cx - size / 2.0, cy - size / 2.0, size, size),
+        _ => pb.arc(cx, cy, size / 2.0, 0.0, 2.0 * std::f32::consts::PI),
+    }
+    pb.finish()
+}
+
+/// A glyph's outline as a path, scaled to `size` px and moved by `place` (see `place_glyphs`)
+pub fn glyph_path(font: &LoadedFont, glyph_id: u16, size: f32, place: &Transform) -> Option<Path> {
+    let outline = font.font.outline(GlyphId(glyph_id))?;
+    let scale = font.scale(size);
+    // Font units point up, the canvas points down
+    let to_canvas = |p: ab_glyph::Point| {
+        let point = place.transform_point(Point::new(p.x * scale, -p.y * scale));
+        (point.x, point.y)
+    };
+
+    let mut pb = PathBuilder::new();
+    let mut last = None;
+    for curve in &outline.curves {
+        let (start, end) = match *curve {
+            OutlineCurve::Line(a, b) => (a, b),
+            OutlineCurve::Quad(a, _, b) => (a, b),
+            OutlineCurve::Cubic(a, _, _, b) => (a, b),
+        };
+        // Each contour is a chain of curves; a jump starts the next one
+        if last != Some(start) {
+            if last.is_some() {
+                pb.close();
+            }
+            let (sx, sy) = to_canvas(start);
+            pb.move_to(sx, sy);
+        }
+        match *curve {
+            OutlineCurve::Line(_, b) => {
+                let (bx, by) = to_canvas(b);
+                pb.line_to(bx, by);
+            }
+            OutlineCurve::Quad(_, c, b) => {
+                let (cx, cy) = to_canvas(c);
+                let (bx, by) = to_canvas(b);
+                pb.quad_to(cx, cy, bx, by);
+            }
+            OutlineCurve::Cubic(_, c1, c2, b) => {
+                let (c1x, c1y) = to_canvas(c1);
+                let (c2x, c2y) = to_canvas(c2);
+                let (bx, by) = to_canvas(b);
+                pb.cubic_to(c1x, c1y, c2x, c2y, bx, by);
+            }
+        }
+        last = Some(end);
+    }
+    if last.is_some() {
+        pb.close();
+    }
+    Some(pb.finish())
+}
@@ -1,53 +1,667 @@
 //! style.rs — Responsible for applying visual styles to the DOM.
-//! This includes default tag styles, inline styles, and eventually selector-based styles.
+//! This includes default tag styles, stylesheet rules, inline styles and generated content.
 
-use crate::browser::dom::{Node, NodeType, ElementData};
-use crate::browser::engine::{Style, Display, Color, edges};
+use crate::browser::css::{
+    parse_declarations, split_top_level, unquote, Declaration, MatchTarget, PseudoElement, Rule, Selector,
+    Stylesheet,
+};
+use crate::browser::dom::{Node, NodeType, ElementData, ElementState, LayoutDirty, StyleDirty};
+use crate::browser::engine::{
+    Style, Display, AlignItems, BorderCollapse, CaptionSide, Clear, Color, ContentDistribution, ContentItem, EdgeSizes, FlexDirection, FlexWrap,
+    Float, FontStyle, GridAutoFlow, GridLine, Length, LineHeight, ListStylePosition, ListStyleType, Position,
+    ObjectFit, Overflow, PointerEvents, TableLayout, TextAlign, TrackBreadth, TrackSize, VerticalAlign, WhiteSpace, edges, default_style,
+    Direction, UnicodeBidi, WritingMode, TextOrientation, BreakBetween, BreakInside,
+};
 use std::collections::HashMap;
-use std::rc::Rc;
+use std::fmt::{self, Write};
+use std::rc::{Rc, Weak};
+use std::sync::Arc;
 
 /// Struct representing a styled DOM node with computed visual style
 #[derive(Debug, Clone)]
 pub struct StyledNode {
     pub node_type: NodeType,
-    pub style: Style,
+    /// Shared between structurally identical siblings (see `ShareKey`)
+    pub style: Arc<Style>,
     pub children: Vec<StyledNode>,
+    /// Set for anonymous boxes generated by `::before`, `::after` and `::marker`
+    pub pseudo: Option<PseudoElement>,
+    /// The DOM node this was styled from; None for pseudo-elements
+    pub dom: Option<Weak<Node>>,
 }
 
-/// Main entry point: Compute a styled tree from a DOM node
-pub fn compute_styles(node: &Rc<Node>) -> StyledNode {
-    let style = match &node.node_type {
-        NodeType::Element(el) => compute_style_for_element(el),
-        NodeType::Text(_) => default_text_style(),
-        NodeType::Comment(_) => none_style(),
-    };
+/// Counts from a style pass, so tests can assert how much work a restyle did
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RestyleStats {
+    /// DOM nodes whose style was recomputed by the cascade
+    pub restyled: usize,
+    /// DOM nodes whose previous style was reused
+    pub reused: usize,
+    /// DOM nodes that took a sibling's style from the sharing cache
+    pub shared: usize,
+}
+
+/// What an element's cascaded style depends on. Elements with equal keys get the same
+/// `Arc<Style>`: the parent style is compared by identity, so sharers are siblings or have
+/// ancestors that shared the same way, and therefore match the same selectors.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ShareKey {
+    /// Address of the parent's `Arc<Style>` (0 for the root)
+    parent: usize,
+    tag: String,
+    /// Sorted, deduplicated classes
+    classes: Vec<String>,
+    /// Values of the attributes selectors, inline styles or tag defaults look at
+    attrs: Vec<(String, String)>,
+    state: u8,
+}
+
+impl ShareKey {
+    /// The key of a text node or element under `parent`, given the attributes that matter
+    fn new(relevant_attrs: &[String], parent: Option<&Arc<Style>>, node_type: &NodeType, state: ElementState) -> Self {
+        let parent = parent.map_or(0, |p| Arc::as_ptr(p) as usize);
+        let NodeType::Element(el) = node_type else {
+            return ShareKey {
+                parent,
+                tag: "#text".into(),
+                classes: vec![],
+                attrs: vec![],
+                state: 0,
+            };
+        };
+
+        let mut classes: Vec<String> = el
+            .attrs
+            .get("class")
+            .map(|c| c.split_whitespace().map(String::from).collect())
+            .unwrap_or_default();
+        classes.sort();
+        classes.dedup();
 
-    let children = node
-        .get_children()
+        let attrs = relevant_attrs
+            .iter()
+            .filter_map(|name| el.attrs.get(name).map(|v| (name.clone(), v.clone())))
+            .collect();
+
+        ShareKey {
+            parent,
+            tag: el.tag_name.clone(),
+            classes,
+            attrs,
+            state: state.0,
+        }
+    }
+}
+
+/// Attributes `compute_style_for_element` reads for inline styles, tag defaults and `dir`
+const PRESENTATIONAL_ATTRS: [&str; 7] = ["style", "start", "width", "height", "border", "cellspacing", "dir"];
+
+/// Attributes whose values can change an element's style: the presentational ones, and
+/// those looked at by selectors
+fn relevant_attrs(stylesheets: &[Stylesheet]) -> Vec<String> {
+    let mut attrs: Vec<String> = PRESENTATIONAL_ATTRS.iter().map(|name| name.to_string()).collect();
+    for compound in stylesheets
         .iter()
-        .map(|child| compute_styles(child))
-        .collect();
+        .flat_map(|sheet| sheet.rules.iter())
+        .flat_map(|rule| rule.selectors.iter())
+        .flat_map(|selector| selector.compounds.iter())
+    {
+        if compound.id.is_some() {
+            attrs.push("id".into());
+        }
+        attrs.extend(compound.attrs.iter().map(|attr| attr.name.clone()));
+    }
+    attrs.sort();
+    attrs.dedup();
+    attrs
+}
+
+/// State threaded through one style pass
+struct StyleContext<'a> {
+    stylesheets: &'a [Stylesheet],
+    counters: Counters,
+    stats: RestyleStats,
+    /// With descendant selectors, restyling an element can change what its descendants match
+    has_descendant_selectors: bool,
+    /// Attributes whose values can change an element's style
+    relevant_attrs: Vec<String>,
+    share_cache: HashMap<ShareKey, Arc<Style>>,
+}
+
+impl<'a> StyleContext<'a> {
+    fn new(stylesheets: &'a [Stylesheet]) -> Self {
+        let has_descendant_selectors = stylesheets
+            .iter()
+            .flat_map(|sheet| sheet.rules.iter())
+            .flat_map(|rule| rule.selectors.iter())
+            .any(|selector| selector.compounds.len() > 1);
+
+        StyleContext {
+            stylesheets,
+            counters: Counters::default(),
+            stats: RestyleStats::default(),
+            has_descendant_selectors,
+            relevant_attrs: relevant_attrs(stylesheets),
+            share_cache: HashMap::new(),
+        }
+    }
+
+    /// The sharing key for a text node or element under `parent`
+    fn share_key(&self, parent: Option<&Arc<Style>>, node: &Node) -> ShareKey {
+        ShareKey::new(&self.relevant_attrs, parent, &node.node_type(), node.state())
+    }
+
+    /// Take a structurally identical sibling's style, or cascade and remember the result
+    fn shared_style(&mut self, key: ShareKey, cascade: impl FnOnce(&Self) -> Style) -> Arc<Style> {
+        if let Some(style) = self.share_cache.get(&key) {
+            self.stats.shared += 1;
+            return style.clone();
+        }
+        self.stats.restyled += 1;
+        let style = Arc::new(cascade(self));
+        self.share_cache.insert(key, style.clone());
+        style
+    }
+}
+
+/// Main entry point: Compute a styled tree from a DOM node and the page's stylesheets.
+/// With the `parallel-style` feature, independent subtrees are cascaded on a worker pool.
+pub fn compute_styles(node: &Rc<Node>, stylesheets: &[Stylesheet]) -> StyledNode {
+    #[cfg(feature = "parallel-style")]
+    {
+        parallel::compute_styles_parallel(node, stylesheets)
+    }
+    #[cfg(not(feature = "parallel-style"))]
+    {
+        compute_styles_serial(node, stylesheets)
+    }
+}
+
+/// Style the whole tree on the current thread
+pub fn compute_styles_serial(node: &Rc<Node>, stylesheets: &[Stylesheet]) -> StyledNode {
+    let mut ctx = StyleContext::new(stylesheets);
+    let mut scope = Vec::new();
+    let styled = style_node(node, None, None, &mut ctx, &mut scope);
+    ctx.counters.leave_scope(scope);
+    styled
+}
+
+/// Bring `previous` up to date after DOM changes, cascading only dirty elements.
+/// Descendants of a restyled element follow when inherited properties changed or
+/// descendant selectors may now match differently; clean subtrees are reused as-is.
+pub fn restyle(node: &Rc<Node>, previous: &StyledNode, stylesheets: &[Stylesheet]) -> (StyledNode, RestyleStats) {
+    let mut ctx = StyleContext::new(stylesheets);
+    let mut scope = Vec::new();
+    let styled = style_node(node, None, Some(previous), &mut ctx, &mut scope);
+    ctx.counters.leave_scope(scope);
+    (styled, ctx.stats)
+}
+
+/// Style one node and its subtree. `previous` is its result from the last pass (None forces
+/// a full cascade); `scope` collects the counters created at this sibling level.
+fn style_node(
+    node: &Rc<Node>,
+    parent: Option<&Arc<Style>>,
+    previous: Option<&StyledNode>,
+    ctx: &mut StyleContext,
+    scope: &mut Vec<String>,
+) -> StyledNode {
+    let dirty = node.style_dirty();
+    node.clear_style_dirty();
+
+    let node_type = node.node_type();
+    let el = match &*node_type {
+        NodeType::Element(el) => el,
+        NodeType::Text(_) | NodeType::Comment(_) => {
+            let style = match previous {
+                Some(prev) => {
+                    ctx.stats.reused += 1;
+                    prev.style.clone()
+                }
+                None if node.is_text() => {
+                    node.mark_layout_dirty(LayoutDirty::STYLE);
+                    let key = ctx.share_key(parent, node);
+                    ctx.shared_style(key, |_| inherited_style(parent.map(|p| p.as_ref())))
+                }
+                None => {
+                    ctx.stats.restyled += 1;
+                    Arc::new(none_style())
+                }
+            };
+            return leaf(node_type.clone(), style, Some(node));
+        }
+    };
+
+    let restyle_self = previous.is_none() || dirty.needs_restyle();
+    let style = match previous {
+        Some(prev) if !restyle_self => {
+            ctx.stats.reused += 1;
+            prev.style.clone()
+        }
+        _ => {
+            // Boxes laid out with the old style cannot be reused
+            node.mark_layout_dirty(LayoutDirty::STYLE);
+            let key = ctx.share_key(parent, node);
+            ctx.shared_style(key, |ctx| {
+                compute_style_for_element(node, el, parent.map(|p| p.as_ref()), ctx.stylesheets)
+            })
+        }
+    };
+
+    // Nothing changed in this subtree: keep the previous result, replaying its counters.
+    // Generated content is refreshed, since counters before it in the document may differ.
+    if let Some(prev) = previous {
+        if !restyle_self && !dirty.intersects(StyleDirty::DESCENDANT) {
+            let mut walked = 0;
+            let styled = ctx.counters.replay(prev, scope, &mut walked);
+            // The element itself was counted above
+            ctx.stats.reused += walked - 1;
+            return styled;
+        }
+    }
+
+    if style.display == Display::None {
+        return leaf(node_type.clone(), style, Some(node));
+    }
+
+    ctx.counters.apply(&style, el, scope);
+
+    // Children can only reuse their previous styles if what they inherit and match is unchanged
+    let previous_children: Vec<&StyledNode> = match previous {
+        Some(prev)
+            if !dirty.intersects(StyleDirty::CHILDREN)
+                && !(restyle_self
+                    && (ctx.has_descendant_selectors || inherited_changed(&prev.style, &style))) =>
+        {
+            prev.children.iter().filter(|c| c.pseudo.is_none()).collect()
+        }
+        _ => vec![],
+    };
+
+    let mut children = Vec::new();
+    if style.display == Display::ListItem {
+        children.extend(generate_pseudo(node, el, PseudoElement::Marker, &style, ctx));
+    }
+    children.extend(generate_pseudo(node, el, PseudoElement::Before, &style, ctx));
+
+    let mut child_scope = Vec::new();
+    for (i, child) in node.children().iter().enumerate() {
+        let previous_child = previous_children.get(i).copied();
+        children.push(style_node(child, Some(&style), previous_child, ctx, &mut child_scope));
+    }
+
+    children.extend(generate_pseudo(node, el, PseudoElement::After, &style, ctx));
+    ctx.counters.leave_scope(child_scope);
+
+    // Generated content of an element that kept its style may still read changed counters
+    if let Some(prev) = previous.filter(|_| !restyle_self) {
+        let pseudos = |children: &[StyledNode]| -> Vec<NodeType> {
+            (children.iter().filter(|c| c.pseudo.is_some()))
+                .flat_map(|c| c.children.iter().map(|text| text.node_type.clone()))
+                .collect()
+        };
+        if pseudos(&prev.children) != pseudos(&children) {
+            node.mark_layout_dirty(LayoutDirty::STYLE);
+        }
+    }
 
     StyledNode {
-        node_type: node.node_type.clone(),
+        node_type: node_type.clone(),
         style,
         children,
+        pseudo: None,
+        dom: Some(Rc::downgrade(node)),
+    }
+}
+
+/// Returns true if any inherited property differs
+fn inherited_changed(old: &Style, new: &Style) -> bool {
+    old.font_size != new.font_size
+        || old.font_family != new.font_family
+        || old.font_weight != new.font_weight
+        || old.font_style != new.font_style
+        || old.font_stretch != new.font_stretch
+        || old.color != new.color
+        || old.list_style_type != new.list_style_type
+        || old.list_style_position != new.list_style_position
+        || old.white_space != new.white_space
+        || old.text_align != new.text_align
+        || old.line_height != new.line_height
+        || old.pointer_events != new.pointer_events
+        || old.direction != new.direction
+        || old.writing_mode != new.writing_mode
+        || old.text_orientation != new.text_orientation
+}
+
+/// A styled node without children
+fn leaf(node_type: NodeType, style: Arc<Style>, dom: Option<&Rc<Node>>) -> StyledNode {
+    StyledNode {
+        node_type,
+        style,
+        children: vec![],
+        pseudo: None,
+        dom: dom.map(Rc::downgrade),
+    }
+}
+
+/// Build the anonymous box for a pseudo-element, if its `content` generates one
+fn generate_pseudo(
+    node: &Rc<Node>,
+    el: &ElementData,
+    pseudo: PseudoElement,
+    parent: &Style,
+    ctx: &StyleContext,
+) -> Option<StyledNode> {
+    let style = pseudo_style(node, pseudo, parent, ctx.stylesheets);
+    build_pseudo(el, pseudo, style, &ctx.counters)
+}
+
+/// Cascade a pseudo-element's style. This does not depend on counters, so it can run ahead
+/// of the document-order pass.
+fn pseudo_style<T: MatchTarget>(
+    node: &T,
+    pseudo: PseudoElement,
+    parent: &Style,
+    stylesheets: &[Stylesheet],
+) -> Style {
+    cascade_pseudo(node, pseudo, parent, stylesheets, &mut |_, _| {})
+}
+
+/// `pseudo_style`, reporting each declaration as it is applied
+fn cascade_pseudo<T: MatchTarget>(
+    node: &T,
+    pseudo: PseudoElement,
+    parent: &Style,
+    stylesheets: &[Stylesheet],
+    applied: &mut dyn FnMut(&Declaration, DeclarationSource),
+) -> Style {
+    let mut style = inherited_style(Some(parent));
+    for (decl, source) in matching_declarations(node, stylesheets, Some(pseudo)) {
+        apply_declaration(&mut style, decl);
+        applied(decl, source);
+    }
+    style
+}
+
+/// Turn a pseudo-element's style into its box, resolving `content` against the counters
+fn build_pseudo(el: &ElementData, pseudo: PseudoElement, style: Style, counters: &Counters) -> Option<StyledNode> {
+    if style.display == Display::None {
+        return None;
+    }
+    let content = pseudo_content(el, pseudo, &style, counters)?;
+    let tag_name = match pseudo {
+        PseudoElement::Before => "::before",
+        PseudoElement::After => "::after",
+        PseudoElement::Marker => "::marker",
+    };
+    let text = leaf(NodeType::Text(content), Arc::new(inherited_style(Some(&style))), None);
+
+    Some(StyledNode {
+        node_type: NodeType::Element(ElementData {
+            tag_name: tag_name.into(),
+            attrs: HashMap::new(),
+        }),
+        style: Arc::new(style),
+        children: vec![text],
+        pseudo: Some(pseudo),
+        dom: None,
+    })
+}
+
+/// The text a pseudo-element generates, or None if it generates no box
+fn pseudo_content(el: &ElementData, pseudo: PseudoElement, style: &Style, counters: &Counters) -> Option<String> {
+    match (&style.content, pseudo) {
+        (Some(items), _) => Some(resolve_content(items, el, counters)),
+        // Markers without `content` fall back to `list-style-type`
+        (None, PseudoElement::Marker) => marker_text(style.list_style_type, counters.value("list-item")),
+        (None, _) => None,
+    }
+}
+
+/// Evaluate `content` items to the text of the generated box
+fn resolve_content(items: &[ContentItem], el: &ElementData, counters: &Counters) -> String {
+    let mut out = String::new();
+    for item in items {
+        match item {
+            ContentItem::String(s) => out.push_str(s),
+            ContentItem::Attr(name) => {
+                if let Some(value) = el.attrs.get(name) {
+                    out.push_str(value);
+                }
+            }
+            ContentItem::Counter(name, kind) => out.push_str(&format_counter(counters.value(name), *kind)),
+            ContentItem::Counters(name, separator, kind) => {
+                let values: Vec<String> = counters
+                    .values(name)
+                    .iter()
+                    .map(|v| format_counter(*v, *kind))
+                    .collect();
+                out.push_str(&values.join(separator));
+            }
+        }
+    }
+    out
+}
+
+/// CSS counters in scope while walking the tree in document order
+#[derive(Debug, Default)]
+struct Counters {
+    /// Each counter name maps to its nested instances (innermost last)
+    instances: HashMap<String, Vec<i32>>,
+}
+
+impl Counters {
+    /// `counter-reset`: a new instance, or replace one created by an earlier sibling
+    fn reset(&mut self, name: &str, value: i32, scope: &mut Vec<String>) {
+        let instances = self.instances.entry(name.to_string()).or_default();
+        match instances.last_mut() {
+            Some(last) if scope.iter().any(|n| n == name) => *last = value,
+            _ => {
+                instances.push(value);
+                scope.push(name.to_string());
+            }
+        }
+    }
+
+    /// `counter-increment`: step the innermost instance (creating one if none is in scope)
+    fn increment(&mut self, name: &str, by: i32, scope: &mut Vec<String>) {
+        if self.values(name).is_empty() {
+            self.reset(name, 0, scope);
+        }
+        if let Some(last) = self.instances.get_mut(name).and_then(|v| v.last_mut()) {
+            *last = last.saturating_add(by);
+        }
+    }
+
+    /// Apply an element's counter properties: reset, then increment, then set (by `<li value>`)
+    fn apply(&mut self, style: &Style, el: &ElementData, scope: &mut Vec<String>) {
+        if style.display == Display::None {
+            return;
+        }
+        for (name, value) in &style.counter_reset {
+            self.reset(name, *value, scope);
+        }
+        for (name, by) in &style.counter_increment {
+            self.increment(name, *by, scope);
+        }
+        if style.display == Display::ListItem {
+            // List items implicitly step `list-item` unless it is incremented explicitly
+            if !style.counter_increment.iter().any(|(name, _)| name == "list-item") {
+                self.increment("list-item", 1, scope);
+            }
+            if let Some(value) = el.attrs.get("value").and_then(|v| v.trim().parse().ok()) {
+                self.set("list-item", value, scope);
+            }
+        }
+    }
+
+    /// Re-apply the counter operations of an already styled subtree, returning it with the
+    /// text of its generated content brought up to date. Adds the DOM nodes walked to `walked`.
+    fn replay(&mut self, styled: &StyledNode, scope: &mut Vec<String>, walked: &mut usize) -> StyledNode {
+        *walked += 1;
+        let NodeType::Element(el) = &styled.node_type else {
+            return styled.clone();
+        };
+        self.apply(&styled.style, el, scope);
+
+        let mut changed = false;
+        let mut children = Vec::with_capacity(styled.children.len());
+        let mut child_scope = Vec::new();
+        for child in &styled.children {
+            let Some(pseudo) = child.pseudo else {
+                children.push(self.replay(child, &mut child_scope, walked));
+                continue;
+            };
+            // `::marker` and `::before` see the counters after the element's own operations,
+            // `::after` those after its children's
+            let text = pseudo_content(el, pseudo, &child.style, self).map(NodeType::Text);
+            let old = child.children.first().map(|text| &text.node_type);
+            if text.as_ref() == old {
+                children.push(child.clone());
+                continue;
+            }
+            changed = true;
+            if let Some(text) = text {
+                let mut refreshed = child.clone();
+                if let Some(old) = refreshed.children.first_mut() {
+                    old.node_type = text;
+                }
+                children.push(refreshed);
+            }
+        }
+        self.leave_scope(child_scope);
+
+        if changed {
+            if let Some(node) = styled.dom.as_ref().and_then(Weak::upgrade) {
+                node.mark_layout_dirty(LayoutDirty::STYLE);
+            }
+        }
+        StyledNode {
+            node_type: styled.node_type.clone(),
+            style: styled.style.clone(),
+            children,
+            pseudo: None,
+            dom: styled.dom.clone(),
+        }
+    }
+
+    /// `<li value>`: overwrite the innermost instance
+    fn set(&mut self, name: &str, value: i32, scope: &mut Vec<String>) {
+        match self.instances.get_mut(name).and_then(|v| v.last_mut()) {
+            Some(last) => *last = value,
+            None => self.reset(name, value, scope),
+        }
     }
+
+    /// Drop the instances created at a sibling level once its parent is finished
+    fn leave_scope(&mut self, scope: Vec<String>) {
+        for name in scope {
+            if let Some(instances) = self.instances.get_mut(&name) {
+                instances.pop();
+            }
+        }
+    }
+
+    fn value(&self, name: &str) -> i32 {
+        self.values(name).last().copied().unwrap_or(0)
+    }
+
+    fn values(&self, name: &str) -> &[i32] {
+        self.instances.get(name).map(Vec::as_slice).unwrap_or(&[])
+    }
+}
+
+/// Default marker text for a list item, or None for `list-style-type: none`
+fn marker_text(kind: ListStyleType, ordinal: i32) -> Option<String> {
+    match kind {
+        ListStyleType::None => None,
+        ListStyleType::Disc | ListStyleType::Circle | ListStyleType::Square => {
+            Some(format!("{} ", format_counter(ordinal, kind)))
+        }
+        _ => Some(format!("{}. ", format_counter(ordinal, kind))),
+    }
+}
+
+/// Format a counter value in a counter style
+pub fn format_counter(value: i32, kind: ListStyleType) -> String {
+    match kind {
+        ListStyleType::Disc => "•".into(),
+        ListStyleType::Circle => "◦".into(),
+        ListStyleType::Square => "▪".into(),
+        ListStyleType::None => String::new(),
+        ListStyleType::Decimal => value.to_string(),
+        ListStyleType::LowerAlpha => to_alpha(value).unwrap_or_else(|| value.to_string()),
+        ListStyleType::UpperAlpha => to_alpha(value).map_or_else(|| value.to_string(), |s| s.to_uppercase()),
+        ListStyleType::LowerRoman => to_roman(value).map_or_else(|| value.to_string(), |s| s.to_lowercase()),
+        ListStyleType::UpperRoman => to_roman(value).unwrap_or_else(|| value.to_string()),
+    }
+}
+
+/// 1 → a, 26 → z, 27 → aa (positive values only)
+fn to_alpha(value: i32) -> Option<String> {
+    if value < 1 {
+        return None;
+    }
+    let mut n = value as u32;
+    let mut out = Vec::new();
+    while n > 0 {
+        n -= 1;
+        out.push(char::from(b'a' + (n % 26) as u8));
+        n /= 26;
+    }
+    Some(out.iter().rev().collect())
+}
+
+/// 1..=3999 in upper-case roman numerals
+fn to_roman(value: i32) -> Option<String> {
+    const NUMERALS: [(i32, &str); 13] = [
+        (1000, "M"), (900, "CM"), (500, "D"), (400, "CD"),
+        (100, "C"), (90, "XC"), (50, "L"), (40, "XL"),
+        (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I"),
+    ];
+    if !(1..=3999).contains(&value) {
+        return None;
+    }
+    let mut n = value;
+    let mut out = String::new();
+    for (weight, numeral) in NUMERALS {
+        while n >= weight {
+            out.push_str(numeral);
+            n -= weight;
+        }
+    }
+    Some(out)
 }
 
 /// Default text style
 fn default_text_style() -> Style {
-    Style {
-        display: Display::Inline,
-        font_size: 16.0,
-        font_family: "Arial".into(),
-        color: Color(0, 0, 0, 255),
-        margin: edges(0.0),
-        padding: edges(0.0),
-        border_color: None,
-        border_width: 0.0,
-        background: None,
+    default_style()
+}
+
+/// Text and unknown elements inherit font and color from their parent
+pub fn inherited_style(parent: Option<&Style>) -> Style {
+    let mut style = default_text_style();
+    if let Some(parent) = parent {
+        style.font_size = parent.font_size;
+        style.font_family = parent.font_family.clone();
+        style.font_weight = parent.font_weight;
+        style.font_style = parent.font_style;
+        style.font_stretch = parent.font_stretch;
+        style.color = parent.color;
+        style.list_style_type = parent.list_style_type;
+        style.list_style_position = parent.list_style_position;
+        style.white_space = parent.white_space;
+        style.text_align = parent.text_align;
+        style.direction = parent.direction;
+        style.writing_mode = parent.writing_mode;
+        style.text_orientation = parent.text_orientation;
+        style.line_height = parent.line_height;
+        style.border_collapse = parent.border_collapse;
+        style.border_spacing = parent.border_spacing;
+        style.caption_side = parent.caption_side;
+        style.pointer_events = parent.pointer_events;
     }
+    style
 }
 
 /// Style for comments and unrendered content
@@ -58,8 +672,24 @@ fn none_style() -> Style {
     }
 }
 
-/// Assign default styles based on tag, and parse inline `style=""` attributes.
-fn compute_style_for_element(el: &ElementData) -> Style {
+/// Cascade for an element: tag defaults, then stylesheet rules, then the inline `style=""`.
+fn compute_style_for_element<T: MatchTarget>(
+    node: &T,
+    el: &ElementData,
+    parent: Option<&Style>,
+    stylesheets: &[Stylesheet],
+) -> Style {
+    cascade_element(node, el, parent, stylesheets, &mut |_, _| {})
+}
+
+/// `compute_style_for_element`, reporting each declaration as it is applied
+fn cascade_element<T: MatchTarget>(
+    node: &T,
+    el: &ElementData,
+    parent: Option<&Style>,
+    stylesheets: &[Stylesheet],
+    applied: &mut dyn FnMut(&Declaration, DeclarationSource),
+) -> Style {
     let mut style = match el.tag_name.as_str() {
         "body" => Style {
             display: Display::Block,
@@ -71,17 +701,20 @@ fn compute_style_for_element(el: &ElementData) -> Style {
             background: Some(Color(255, 255, 255, 255)),
             border_color: None,
             border_width: 0.0,
+            ..inherited_style(parent)
         },
         "h1" => Style {
             display: Display::Block,
             font_size: 32.0,
             font_family: "Georgia".into(),
+            font_weight: 700,
             color: Color(50, 50, 50, 255),
             margin: edges(12.0),
             padding: edges(6.0),
             background: None,
             border_color: None,
             border_width: 0.0,
+            ..inherited_style(parent)
         },
         "p" => Style {
             display: Display::Block,
@@ -93,6 +726,7 @@ fn compute_style_for_element(el: &ElementData) -> Style {
             background: None,
             border_color: None,
             border_width: 0.0,
+            ..inherited_style(parent)
         },
         "div" => Style {
             display: Display::Block,
@@ -104,91 +738,2259 @@ fn compute_style_for_element(el: &ElementData) -> Style {
             background: None,
             border_color: None,
             border_width: 0.0,
+            ..inherited_style(parent)
+        },
+        "ul" | "ol" => Style {
+            display: Display::Block,
+            margin: edges(8.0),
+            padding: EdgeSizes {
+                left: 40.0,
+                ..edges(0.0)
+            },
+            // `<ol start="5">` numbers its first item 5
+            counter_reset: vec![(
+                "list-item".into(),
+                el.attrs.get("start").and_then(|v| v.trim().parse::<i32>().ok()).unwrap_or(1).saturating_sub(1),
+            )],
+            list_style_type: if el.tag_name == "ol" {
+                ListStyleType::Decimal
+            } else {
+                ListStyleType::Disc
+            },
+            ..inherited_style(parent)
+        },
+        "li" => Style {
+            display: Display::ListItem,
+            ..inherited_style(parent)
+        },
+        "table" => {
+            // Presentational attributes: `<table border="1" cellspacing="0" width="100%">`
+            let border = el.attrs.get("border").and_then(|v| v.trim().parse::<f32>().ok());
+            let spacing = el.attrs.get("cellspacing").and_then(|v| v.trim().parse::<f32>().ok());
+            Style {
+                display: Display::Table,
+                width: dimension_attribute(el, "width", parent),
+                border_width: border.unwrap_or(0.0),
+                border_color: border.map(|_| Color(128, 128, 128, 255)),
+                border_spacing: spacing.map_or((2.0, 2.0), |s| (s, s)),
+                ..inherited_style(parent)
+            }
+        }
+        "caption" => Style {
+            display: Display::TableCaption,
+            text_align: TextAlign::Center,
+            ..inherited_style(parent)
+        },
+        "thead" => Style {
+            display: Display::TableHeaderGroup,
+            ..inherited_style(parent)
+        },
+        "tbody" => Style {
+            display: Display::TableRowGroup,
+            ..inherited_style(parent)
+        },
+        "tfoot" => Style {
+            display: Display::TableFooterGroup,
+            ..inherited_style(parent)
         },
-        _ => default_text_style(),
+        "tr" => Style {
+            display: Display::TableRow,
+            ..inherited_style(parent)
+        },
+        "td" | "th" => {
+            let inherited = inherited_style(parent);
+            let header = el.tag_name == "th";
+            Style {
+                display: Display::TableCell,
+                width: dimension_attribute(el, "width", parent),
+                padding: edges(1.0),
+                vertical_align: VerticalAlign::Middle,
+                font_weight: if header { 700 } else { inherited.font_weight },
+                text_align: if header { TextAlign::Center } else { inherited.text_align },
+                ..inherited
+            }
+        }
+        "col" => Style {
+            display: Display::TableColumn,
+            width: dimension_attribute(el, "width", parent),
+            ..inherited_style(parent)
+        },
+        "colgroup" => Style {
+            display: Display::TableColumnGroup,
+            width: dimension_attribute(el, "width", parent),
+            ..inherited_style(parent)
+        },
+        "img" | "video" | "canvas" => Style {
+            width: dimension_attribute(el, "width", parent),
+            height: dimension_attribute(el, "height", parent),
+            ..inherited_style(parent)
+        },
+        _ => inherited_style(parent),
     };
+    bidi_attributes(el, &mut style);
+
+    for (decl, source) in matching_declarations(node, stylesheets, None) {
+        apply_declaration(&mut style, decl);
+        applied(decl, source);
+    }
 
     // Apply inline styles (e.g., <p style="color:red; background:#eee">)
     if let Some(inline_style) = el.attrs.get("style") {
-        apply_inline_styles(&mut style, inline_style);
+        for decl in parse_declarations(inline_style) {
+            apply_declaration(&mut style, &decl);
+            applied(&decl, DeclarationSource::Inline);
+        }
     }
 
     style
 }
 
-/// Parses and applies inline CSS from `style` attributes
-fn apply_inline_styles(style: &mut Style, inline: &str) {
-    for rule in inline.split(';') {
-        if let Some((key, value)) = rule.split_once(':') {
-            let key = key.trim().to_lowercase();
-            let value = value.trim();
-
-            match key.as_str() {
-                "color" => {
-                    if let Some(c) = parse_color(value) {
-                        style.color = c;
-                    }
-                }
-                "background" | "background-color" => {
-                    if let Some(c) = parse_color(value) {
-                        style.background = Some(c);
-                    }
-                }
-                "font-size" => {
-                    if let Ok(px) = value.trim_end_matches("px").parse::<f32>() {
-                        style.font_size = px;
-                    }
-                }
-                "font-family" => {
-                    style.font_family = value.to_string();
-                }
-                "border" => {
-                    if let Some((width, color)) = parse_border(value) {
-                        style.border_width = width;
-                        style.border_color = Some(color);
-                    }
-                }
-                _ => {}
-            }
+/// The `dir` attribute and the `bdi` and `bdo` elements (HTML §15.3.5). `dir="auto"` and
+/// `bdi` take their direction from their own text, which `unicode-bidi: plaintext` does.
+fn bidi_attributes(el: &ElementData, style: &mut Style) {
+    match el.attrs.get("dir").map(|dir| dir.trim().to_lowercase()).as_deref() {
+        Some("ltr") => {
+            style.direction = Direction::Ltr;
+            style.unicode_bidi = UnicodeBidi::Isolate;
+        }
+        Some("rtl") => {
+            style.direction = Direction::Rtl;
+            style.unicode_bidi = UnicodeBidi::Isolate;
         }
+        Some("auto") => style.unicode_bidi = UnicodeBidi::Plaintext,
+        _ if el.tag_name == "bdi" => style.unicode_bidi = UnicodeBidi::Plaintext,
+        _ => {}
+    }
+    if el.tag_name == "bdo" {
+        style.unicode_bidi = UnicodeBidi::IsolateOverride;
     }
 }
 
-/// Parses a basic border string: "1px solid red"
-fn parse_border(value: &str) -> Option<(f32, Color)> {
-    let parts: Vec<&str> = value.split_whitespace().collect();
-    if parts.len() == 3 {
-        if let Ok(width) = parts[0].trim_end_matches("px").parse::<f32>() {
-            let color = parse_color(parts[2])?;
-            return Some((width, color));
-        }
-    }
-    None
+/// An HTML `width` or `height` attribute: pixels or a percentage
+fn dimension_attribute(el: &ElementData, name: &str, parent: Option<&Style>) -> Length {
+    let font_size = parent.map_or(16.0, |p| p.font_size);
+    el.attrs
+        .get(name)
+        .and_then(|v| parse_length(v, font_size))
+        .unwrap_or(Length::Auto)
 }
 
-/// Parses color names or hex
-fn parse_color(value: &str) -> Option<Color> {
-    let value = value.trim().to_lowercase();
+/// Where a declaration applied by the cascade was written
+#[derive(Debug, Clone, Copy)]
+enum DeclarationSource<'a> {
+    /// A stylesheet rule, with the selector that matched most specifically
+    Rule {
+        sheet: usize,
+        rule: &'a Rule,
+        selector: &'a Selector,
+    },
+    /// The element's `style` attribute
+    Inline,
+}
 
-    match value.as_str() {
-        "black" => Some(Color(0, 0, 0, 255)),
-        "white" => Some(Color(255, 255, 255, 255)),
-        "red" => Some(Color(255, 0, 0, 255)),
-        "green" => Some(Color(0, 255, 0, 255)),
-        "blue" => Some(Color(0, 0, 255, 255)),
-        _ if value.starts_with('#') => hex_to_color(&value[1..]),
-        _ => None,
+/// Declarations of every rule matching `node` (or one of its pseudo-elements),
+/// in cascade order: ascending specificity, then source order.
+fn matching_declarations<'a, T: MatchTarget>(
+    node: &T,
+    stylesheets: &'a [Stylesheet],
+    pseudo: Option<PseudoElement>,
+) -> Vec<(&'a Declaration, DeclarationSource<'a>)> {
+    let mut matched = Vec::new();
+    for (sheet, stylesheet) in stylesheets.iter().enumerate() {
+        for rule in &stylesheet.rules {
+            let selector = rule
+                .selectors
+                .iter()
+                .filter(|s| s.pseudo_element == pseudo && s.matches(node))
+                .max_by_key(|s| s.specificity());
+            if let Some(selector) = selector {
+                matched.push((selector.specificity(), sheet, rule, selector));
+            }
+        }
     }
+
+    // Stable sort keeps source order between equal specificities
+    matched.sort_by_key(|(specificity, ..)| *specificity);
+    matched
+        .into_iter()
+        .flat_map(|(_, sheet, rule, selector)| {
+            let source = DeclarationSource::Rule { sheet, rule, selector };
+            rule.declarations.iter().map(move |decl| (decl, source))
+        })
+        .collect()
 }
 
-/// Convert hex color (e.g. "ff0000") to RGB
-fn hex_to_color(hex: &str) -> Option<Color> {
-    if hex.len() == 6 {
-        let r = u8::from_str_radix(&hex[0..2], 16).ok()?;
-        let g = u8::from_str_radix(&hex[2..4], 16).ok()?;
-        let b = u8::from_str_radix(&hex[4..6], 16).ok()?;
-        Some(Color(r, g, b, 255))
-    } else {
-        None
+/// Apply a single declaration to a style
+fn apply_declaration(style: &mut Style, decl: &Declaration) {
+    let value = decl.value.trim();
+
+    match decl.name.as_str() {
+        "color" => {
+            if let Some(c) = parse_color(value) {
+                style.color = c;
+            }
+        }
+        "background" | "background-color" => {
+            if let Some(c) = parse_color(value) {
+                style.background = Some(c);
+            }
+        }
+        "font-size" => {
+            if let Ok(px) = value.trim_end_matches("px").parse::<f32>() {
+                style.font_size = px;
+            }
+        }
+        "font-family" => {
+            style.font_family = value.to_string();
+        }
+        "font-weight" => {
+            if let Some(weight) = parse_font_weight(value, style.font_weight) {
+                style.font_weight = weight;
+            }
+        }
+        "font-style" => {
+            if let Some(font_style) = parse_font_style(value) {
+                style.font_style = font_style;
+            }
+        }
+        "font-stretch" => {
+            if let Some(stretch) = parse_font_stretch(value) {
+                style.font_stretch = stretch;
+            }
+        }
+        "border" => {
+            if let Some((width, color)) = parse_border(value) {
+                style.border_width = width;
+                style.border_color = Some(color);
+            }
+        }
+        "width" => {
+            if let Some(width) = parse_length(value, style.font_size) {
+                style.width = width;
+            }
+        }
+        "height" => {
+            if let Some(height) = parse_length(value, style.font_size) {
+                style.height = height;
+            }
+        }
+        "float" => {
+            if let Some(float) = parse_float(value) {
+                style.float = float;
+            }
+        }
+        "clear" => {
+            if let Some(clear) = parse_clear(value) {
+                style.clear = clear;
+            }
+        }
+        "position" => {
+            if let Some(position) = parse_position(value) {
+                style.position = position;
+            }
+        }
+        "inset" => {
+            if let Some(values) = box_shorthand(value) {
+                for (side, value) in SIDES.into_iter().zip(values) {
+                    set_inset(style, side, value);
+                }
+            }
+        }
+        "top" => set_inset(style, Side::Top, value),
+        "right" => set_inset(style, Side::Right, value),
+        "bottom" => set_inset(style, Side::Bottom, value),
+        "left" => set_inset(style, Side::Left, value),
+        "z-index" => {
+            if value.eq_ignore_ascii_case("auto") {
+                style.z_index = None;
+            } else if let Ok(z) = value.parse::<i32>() {
+                style.z_index = Some(z);
+            }
+        }
+        "flex-direction" => {
+            if let Some(direction) = parse_flex_direction(value) {
+                style.flex_direction = direction;
+            }
+        }
+        "flex-wrap" => {
+            if let Some(wrap) = parse_flex_wrap(value) {
+                style.flex_wrap = wrap;
+            }
+        }
+        "flex-flow" => {
+            for part in value.split_whitespace() {
+                if let Some(direction) = parse_flex_direction(part) {
+                    style.flex_direction = direction;
+                } else if let Some(wrap) = parse_flex_wrap(part) {
+                    style.flex_wrap = wrap;
+                }
+            }
+        }
+        "justify-content" => {
+            if let Some(justify) = parse_content_distribution(value, ContentDistribution::FlexStart) {
+                style.justify_content = justify;
+            }
+        }
+        "align-content" => {
+            if let Some(align) = parse_content_distribution(value, ContentDistribution::Stretch) {
+                style.align_content = align;
+            }
+        }
+        "align-items" => {
+            if let Some(align) = parse_align_items(value) {
+                style.align_items = align;
+            }
+        }
+        "align-self" => {
+            if value.eq_ignore_ascii_case("auto") {
+                style.align_self = None;
+            } else if let Some(align) = parse_align_items(value) {
+                style.align_self = Some(align);
+            }
+        }
+        "gap" | "grid-gap" => {
+            let gaps: Vec<f32> = value.split_whitespace().filter_map(|v| parse_gap(v, style.font_size)).collect();
+            match gaps[..] {
+                [gap] => (style.row_gap, style.column_gap) = (gap, gap),
+                [row, column] => (style.row_gap, style.column_gap) = (row, column),
+                _ => {}
+            }
+        }
+        "row-gap" | "grid-row-gap" => {
+            if let Some(gap) = parse_gap(value, style.font_size) {
+                style.row_gap = gap;
+            }
+        }
+        "column-gap" | "grid-column-gap" => {
+            if let Some(gap) = parse_gap(value, style.font_size) {
+                style.column_gap = gap;
+            }
+        }
+        "flex" => {
+            if let Some((grow, shrink, basis)) = parse_flex(value, style.font_size) {
+                style.flex_grow = grow;
+                style.flex_shrink = shrink;
+                style.flex_basis = basis;
+            }
+        }
+        "flex-grow" => {
+            if let Some(grow) = parse_flex_factor(value) {
+                style.flex_grow = grow;
+            }
+        }
+        "flex-shrink" => {
+            if let Some(shrink) = parse_flex_factor(value) {
+                style.flex_shrink = shrink;
+            }
+        }
+        "flex-basis" => {
+            if let Some(basis) = parse_flex_basis(value, style.font_size) {
+                style.flex_basis = basis;
+            }
+        }
+        "order" => {
+            if let Ok(order) = value.parse::<i32>() {
+                style.order = order;
+            }
+        }
+        "grid-template-columns" => {
+            if let Some(tracks) = parse_track_list(value, style.font_size) {
+                style.grid_template_columns = tracks;
+            }
+        }
+        "grid-template-rows" => {
+            if let Some(tracks) = parse_track_list(value, style.font_size) {
+                style.grid_template_rows = tracks;
+            }
+        }
+        "grid-template-areas" => {
+            if let Some(areas) = parse_template_areas(value) {
+                style.grid_template_areas = areas;
+            }
+        }
+        "grid-auto-columns" => {
+            if let Some(size) = parse_track_size(value, style.font_size) {
+                style.grid_auto_columns = size;
+            }
+        }
+        "grid-auto-rows" => {
+            if let Some(size) = parse_track_size(value, style.font_size) {
+                style.grid_auto_rows = size;
+            }
+        }
+        "grid-auto-flow" => {
+            if let Some(flow) = parse_grid_auto_flow(value) {
+                style.grid_auto_flow = flow;
+            }
+        }
+        "border-collapse" => {
+            if let Some(collapse) = parse_border_collapse(value) {
+                style.border_collapse = collapse;
+            }
+        }
+        "border-spacing" => {
+            let spacing: Vec<f32> = value
+                .split_whitespace()
+                .filter_map(|v| match parse_length(v, style.font_size) {
+                    Some(Length::Px(px)) if px >= 0.0 => Some(px),
+                    _ => None,
+                })
+                .collect();
+            match spacing[..] {
+                [both] => style.border_spacing = (both, both),
+                [horizontal, vertical] => style.border_spacing = (horizontal, vertical),
+                _ => {}
+            }
+        }
+        "table-layout" => {
+            if let Some(layout) = parse_table_layout(value) {
+                style.table_layout = layout;
+            }
+        }
+        "caption-side" => {
+            if let Some(side) = parse_caption_side(value) {
+                style.caption_side = side;
+            }
+        }
+        "vertical-align" => {
+            if let Some(align) = parse_vertical_align(value) {
+                style.vertical_align = align;
+            }
+        }
+        "object-fit" => {
+            if let Some(fit) = parse_object_fit(value) {
+                style.object_fit = fit;
+            }
+        }
+        "overflow" => {
+            if let Some(overflow) = parse_overflow(value) {
+                style.overflow = overflow;
+            }
+        }
+        "pointer-events" => match value.to_lowercase().as_str() {
+            "none" => style.pointer_events = PointerEvents::None,
+            // The SVG-only values behave like `auto` on HTML boxes
+            "auto" | "visiblepainted" | "visiblefill" | "visiblestroke" | "visible" | "painted" | "fill"
+            | "stroke" | "all" => style.pointer_events = PointerEvents::Auto,
+            _ => {}
+        },
+        "break-before" | "page-break-before" => {
+            if let Some(value) = parse_break_between(value) {
+                style.break_before = value;
+            }
+        }
+        "break-after" | "page-break-after" => {
+            if let Some(value) = parse_break_between(value) {
+                style.break_after = value;
+            }
+        }
+        "break-inside" | "page-break-inside" => match value.to_lowercase().as_str() {
+            "auto" | "avoid-column" | "avoid-region" => style.break_inside = BreakInside::Auto,
+            "avoid" | "avoid-page" => style.break_inside = BreakInside::Avoid,
+            _ => {}
+        },
+        "grid-row-start" => set_grid_line(&mut style.grid_row_start, value),
+        "grid-row-end" => set_grid_line(&mut style.grid_row_end, value),
+        "grid-column-start" => set_grid_line(&mut style.grid_column_start, value),
+        "grid-column-end" => set_grid_line(&mut style.grid_column_end, value),
+        "grid-row" => {
+            if let Some((start, end)) = parse_grid_span(value) {
+                (style.grid_row_start, style.grid_row_end) = (start, end);
+            }
+        }
+        "grid-column" => {
+            if let Some((start, end)) = parse_grid_span(value) {
+                (style.grid_column_start, style.grid_column_end) = (start, end);
+            }
+        }
+        "grid-area" => {
+            if let Some([row_start, column_start, row_end, column_end]) = parse_grid_area(value) {
+                style.grid_row_start = row_start;
+                style.grid_column_start = column_start;
+                style.grid_row_end = row_end;
+                style.grid_column_end = column_end;
+            }
+        }
+        "margin" => {
+            if let Some(values) = box_shorthand(value) {
+                for (side, value) in SIDES.into_iter().zip(values) {
+                    set_margin(style, side, value);
+                }
+            }
+        }
+        "margin-top" => set_margin(style, Side::Top, value),
+        "margin-right" => set_margin(style, Side::Right, value),
+        "margin-bottom" => set_margin(style, Side::Bottom, value),
+        "margin-left" => set_margin(style, Side::Left, value),
+        "padding" => {
+            if let Some(values) = box_shorthand(value) {
+                for (side, value) in SIDES.into_iter().zip(values) {
+                    set_padding(style, side, value);
+                }
+            }
+        }
+        "padding-top" => set_padding(style, Side::Top, value),
+        "padding-right" => set_padding(style, Side::Right, value),
+        "padding-bottom" => set_padding(style, Side::Bottom, value),
+        "padding-left" => set_padding(style, Side::Left, value),
+        "white-space" => {
+            if let Some(white_space) = parse_white_space(value) {
+                style.white_space = white_space;
+            }
+        }
+        "text-align" => {
+            if let Some(align) = parse_text_align(value) {
+                style.text_align = align;
+            }
+        }
+        "direction" => match value.to_lowercase().as_str() {
+            "ltr" => style.direction = Direction::Ltr,
+            "rtl" => style.direction = Direction::Rtl,
+            _ => {}
+        },
+        "unicode-bidi" => {
+            if let Some(unicode_bidi) = parse_unicode_bidi(value) {
+                style.unicode_bidi = unicode_bidi;
+            }
+        }
+        "writing-mode" => {
+            if let Some(writing_mode) = parse_writing_mode(value) {
+                style.writing_mode = writing_mode;
+            }
+        }
+        "text-orientation" => match value.to_lowercase().as_str() {
+            "mixed" => style.text_orientation = TextOrientation::Mixed,
+            "upright" => style.text_orientation = TextOrientation::Upright,
+            // `sideways-right` is the older name
+            "sideways" | "sideways-right" => style.text_orientation = TextOrientation::Sideways,
+            _ => {}
+        },
+        "line-height" => {
+            if let Some(line_height) = parse_line_height(value, style.font_size) {
+                style.line_height = line_height;
+            }
+        }
+        "display" => {
+            if let Some(display) = parse_display(value) {
+                style.display = display;
+            }
+        }
+        "content" => {
+            style.content = parse_content(value);
+        }
+        "counter-reset" => {
+            style.counter_reset = parse_counter_list(value, 0);
+        }
+        "counter-increment" => {
+            style.counter_increment = parse_counter_list(value, 1);
+        }
+        "list-style-type" => {
+            if let Some(kind) = parse_list_style_type(value) {
+                style.list_style_type = kind;
+            }
+        }
+        "list-style-position" => {
+            if let Some(position) = parse_list_style_position(value) {
+                style.list_style_position = position;
+            }
+        }
+        "list-style" => {
+            for part in value.split_whitespace() {
+                if let Some(kind) = parse_list_style_type(part) {
+                    style.list_style_type = kind;
+                } else if let Some(position) = parse_list_style_position(part) {
+                    style.list_style_position = position;
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Parses `float`; `inline-start`/`inline-end` stay logical until the box tree is built
+fn parse_float(value: &str) -> Option<Float> {
+    match value.to_lowercase().as_str() {
+        "none" => Some(Float::None),
+        "left" => Some(Float::Left),
+        "right" => Some(Float::Right),
+        "inline-start" => Some(Float::InlineStart),
+        "inline-end" => Some(Float::InlineEnd),
+        _ => None,
+    }
+}
+
+fn parse_clear(value: &str) -> Option<Clear> {
+    match value.to_lowercase().as_str() {
+        "none" => Some(Clear::None),
+        "left" => Some(Clear::Left),
+        "right" => Some(Clear::Right),
+        "inline-start" => Some(Clear::InlineStart),
+        "inline-end" => Some(Clear::InlineEnd),
+        "both" => Some(Clear::Both),
+        _ => None,
+    }
+}
+
+fn parse_position(value: &str) -> Option<Position> {
+    match value.to_lowercase().as_str() {
+        "static" => Some(Position::Static),
+        "relative" => Some(Position::Relative),
+        "absolute" => Some(Position::Absolute),
+        "fixed" => Some(Position::Fixed),
+        "sticky" | "-webkit-sticky" => Some(Position::Sticky),
+        _ => None,
+    }
+}
+
+fn parse_flex_direction(value: &str) -> Option<FlexDirection> {
+    match value.to_lowercase().as_str() {
+        "row" => Some(FlexDirection::Row),
+        "row-reverse" => Some(FlexDirection::RowReverse),
+        "column" => Some(FlexDirection::Column),
+        "column-reverse" => Some(FlexDirection::ColumnReverse),
+        _ => None,
+    }
+}
+
+fn parse_flex_wrap(value: &str) -> Option<FlexWrap> {
+    match value.to_lowercase().as_str() {
+        "nowrap" => Some(FlexWrap::Nowrap),
+        "wrap" => Some(FlexWrap::Wrap),
+        "wrap-reverse" => Some(FlexWrap::WrapReverse),
+        _ => None,
+    }
+}
+
+/// Parses `justify-content`/`align-content`; `normal` resolves to the property's own default
+fn parse_content_distribution(value: &str, normal: ContentDistribution) -> Option<ContentDistribution> {
+    match value.to_lowercase().as_str() {
+        "normal" => Some(normal),
+        "flex-start" | "start" | "left" => Some(ContentDistribution::FlexStart),
+        "flex-end" | "end" | "right" => Some(ContentDistribution::FlexEnd),
+        "center" => Some(ContentDistribution::Center),
+        "space-between" => Some(ContentDistribution::SpaceBetween),
+        "space-around" => Some(ContentDistribution::SpaceAround),
+        "space-evenly" => Some(ContentDistribution::SpaceEvenly),
+        "stretch" => Some(ContentDistribution::Stretch),
+        _ => None,
+    }
+}
+
+/// Parses `align-items`/`align-self` (without `auto`)
+fn parse_align_items(value: &str) -> Option<AlignItems> {
+    match value.to_lowercase().as_str() {
+        "normal" | "stretch" => Some(AlignItems::Stretch),
+        "flex-start" | "start" | "self-start" => Some(AlignItems::FlexStart),
+        "flex-end" | "end" | "self-end" => Some(AlignItems::FlexEnd),
+        "center" => Some(AlignItems::Center),
+        "baseline" | "first baseline" => Some(AlignItems::Baseline),
+        _ => None,
+    }
+}
+
+/// Parses a gap: `normal` or a non-negative length (percentages are not supported)
+fn parse_gap(value: &str, font_size: f32) -> Option<f32> {
+    if value.eq_ignore_ascii_case("normal") {
+        return Some(0.0);
+    }
+    match parse_length(value, font_size)? {
+        Length::Px(px) if px >= 0.0 => Some(px),
+        _ => None,
+    }
+}
+
+fn parse_flex_factor(value: &str) -> Option<f32> {
+    value.trim().parse::<f32>().ok().filter(|f| f.is_finite() && *f >= 0.0)
+}
+
+/// Parses `flex-basis`; `content` sizes the item from its content, like `auto` with no width
+fn parse_flex_basis(value: &str, font_size: f32) -> Option<Length> {
+    if value.eq_ignore_ascii_case("content") {
+        return Some(Length::Auto);
+    }
+    parse_length(value, font_size)
+}
+
+/// Parses the `flex` shorthand into grow, shrink and basis. A bare number sets the basis to 0.
+fn parse_flex(value: &str, font_size: f32) -> Option<(f32, f32, Length)> {
+    match value.trim().to_lowercase().as_str() {
+        "none" => return Some((0.0, 0.0, Length::Auto)),
+        "auto" => return Some((1.0, 1.0, Length::Auto)),
+        "initial" => return Some((0.0, 1.0, Length::Auto)),
+        _ => {}
+    }
+    let mut factors = Vec::new();
+    let mut basis = None;
+    for part in value.split_whitespace() {
+        match parse_flex_factor(part) {
+            Some(factor) if factors.len() < 2 => factors.push(factor),
+            _ if basis.is_none() => basis = Some(parse_flex_basis(part, font_size)?),
+            _ => return None,
+        }
+    }
+    let grow = *factors.first()?;
+    let shrink = factors.get(1).copied().unwrap_or(1.0);
+    Some((grow, shrink, basis.unwrap_or(Length::Px(0.0))))
+}
+
+/// Explicit grids stop at this many tracks, the number of lines grid placement reaches
+const MAX_TRACKS: usize = 10000;
+
+/// Parses a grid track list: track sizes and `repeat(<count>, <tracks>)`. Line names in
+/// brackets are skipped; `none` is the empty list.
+fn parse_track_list(value: &str, font_size: f32) -> Option<Vec<TrackSize>> {
+    if value.trim().eq_ignore_ascii_case("none") {
+        return Some(vec![]);
+    }
+    let mut tracks = parse_tracks(value, font_size, true)?;
+    tracks.truncate(MAX_TRACKS);
+    Some(tracks)
+}
+
+/// Track sizes and, outside a `repeat()`, repeats. Repeats stop at [`MAX_TRACKS`] tracks.
+fn parse_tracks(value: &str, font_size: f32, allow_repeat: bool) -> Option<Vec<TrackSize>> {
+    let value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut tracks = Vec::new();
+    let mut in_names = false;
+    for token in split_top_level(&value, ' ').into_iter().filter(|t| !t.is_empty()) {
+        if in_names || token.starts_with('[') {
+            in_names = !token.ends_with(']');
+            continue;
+        }
+        if let Some(args) = function_args(token, "repeat") {
+            if !allow_repeat {
+                return None;
+            }
+            let (count, list) = args.split_once(',')?;
+            let count = count.trim().parse::<u64>().ok().filter(|n| *n > 0)?.min(MAX_TRACKS as u64) as usize;
+            let list = parse_tracks(list, font_size, false).filter(|l| !l.is_empty())?;
+            let room = MAX_TRACKS.saturating_sub(tracks.len());
+            tracks.extend(list.iter().cycle().take(count.saturating_mul(list.len()).min(room)));
+        } else {
+            tracks.push(parse_track_size(token, font_size)?);
+        }
+    }
+    Some(tracks)
+}
+
+/// Parses one track size: a breadth or `minmax(min, max)`
+fn parse_track_size(value: &str, font_size: f32) -> Option<TrackSize> {
+    if let Some(args) = function_args(value.trim(), "minmax") {
+        let (min, max) = args.split_once(',')?;
+        let min = parse_track_breadth(min, font_size).filter(|b| !matches!(b, TrackBreadth::Fr(_)))?;
+        let max = parse_track_breadth(max, font_size)?;
+        return Some(TrackSize { min, max });
+    }
+    match parse_track_breadth(value, font_size)? {
+        fr @ TrackBreadth::Fr(_) => Some(TrackSize {
+            min: TrackBreadth::Auto,
+            max: fr,
+        }),
+        breadth => Some(TrackSize {
+            min: breadth,
+            max: breadth,
+        }),
+    }
+}
+
+fn parse_track_breadth(value: &str, font_size: f32) -> Option<TrackBreadth> {
+    let value = value.trim().to_lowercase();
+    match value.as_str() {
+        "auto" => return Some(TrackBreadth::Auto),
+        "min-content" => return Some(TrackBreadth::MinContent),
+        "max-content" => return Some(TrackBreadth::MaxContent),
+        _ => {}
+    }
+    if let Some(fr) = value.strip_suffix("fr") {
+        return fr.trim().parse::<f32>().ok().filter(|f| *f >= 0.0).map(TrackBreadth::Fr);
+    }
+    match parse_length(&value, font_size)? {
+        Length::Px(px) if px >= 0.0 => Some(TrackBreadth::Px(px)),
+        Length::Percent(pct) if pct >= 0.0 => Some(TrackBreadth::Percent(pct)),
+        _ => None,
+    }
+}
+
+/// The arguments of `name(...)`, if `value` is a call to that function
+fn function_args<'a>(value: &'a str, name: &str) -> Option<&'a str> {
+    let rest = value.get(..name.len()).filter(|n| n.eq_ignore_ascii_case(name)).map(|_| &value[name.len()..])?;
+    rest.strip_prefix('(')?.strip_suffix(')')
+}
+
+/// Parses `grid-template-areas`: one quoted string per row, one name or `.` per cell.
+/// Every row must have the same number of cells.
+fn parse_template_areas(value: &str) -> Option<Vec<Vec<Option<String>>>> {
+    if value.trim().eq_ignore_ascii_case("none") {
+        return Some(vec![]);
+    }
+    let mut rows = Vec::new();
+    for (i, part) in value.split('"').enumerate() {
+        if i % 2 == 0 {
+            if !part.trim().is_empty() {
+                return None;
+            }
+            continue;
+        }
+        let row: Vec<Option<String>> = part
+            .split_whitespace()
+            .map(|cell| (!cell.chars().all(|ch| ch == '.')).then(|| cell.to_string()))
+            .collect();
+        rows.push(row);
+    }
+    let columns = rows.first()?.len();
+    (columns > 0 && rows.iter().all(|r| r.len() == columns)).then_some(rows)
+}
+
+fn parse_grid_auto_flow(value: &str) -> Option<GridAutoFlow> {
+    match value.to_lowercase().as_str() {
+        "row" => Some(GridAutoFlow::Row),
+        "column" => Some(GridAutoFlow::Column),
+        _ => None,
+    }
+}
+
+/// Parses one grid line: `auto`, a line number, `span <n>` or an area name. Numbers are
+/// clamped to ±10000, as browsers do.
+fn parse_grid_line(value: &str) -> Option<GridLine> {
+    let value = value.trim();
+    let lower = value.to_lowercase();
+    match lower.split_whitespace().collect::<Vec<_>>()[..] {
+        ["auto"] => Some(GridLine::Auto),
+        ["span"] => Some(GridLine::Span(1)),
+        ["span", n] => n.parse::<u64>().ok().filter(|n| *n > 0).map(|n| GridLine::Span(n.min(10000) as u32)),
+        [n] if n.starts_with(|ch: char| ch.is_ascii_digit() || ch == '-' || ch == '+') => {
+            n.parse::<i64>().ok().filter(|n| *n != 0).map(|n| GridLine::Line(n.clamp(-10000, 10000) as i32))
+        }
+        [_] => Some(GridLine::Area(value.to_string())),
+        _ => None,
+    }
+}
+
+fn set_grid_line(line: &mut GridLine, value: &str) {
+    if let Some(parsed) = parse_grid_line(value) {
+        *line = parsed;
+    }
+}
+
+/// The end line a `grid-row`/`grid-column`/`grid-area` shorthand gives an omitted edge:
+/// the same area name, otherwise `auto`
+fn omitted_line(start: &GridLine) -> GridLine {
+    match start {
+        GridLine::Area(name) => GridLine::Area(name.clone()),
+        _ => GridLine::Auto,
+    }
+}
+
+/// Parses `grid-row`/`grid-column`: `<start> [/ <end>]`
+fn parse_grid_span(value: &str) -> Option<(GridLine, GridLine)> {
+    let mut parts = value.split('/');
+    let start = parse_grid_line(parts.next()?)?;
+    let end = match parts.next() {
+        Some(end) => parse_grid_line(end)?,
+        None => omitted_line(&start),
+    };
+    parts.next().is_none().then_some((start, end))
+}
+
+/// Parses `grid-area`: `<row-start> [/ <column-start> [/ <row-end> [/ <column-end>]]]`
+fn parse_grid_area(value: &str) -> Option<[GridLine; 4]> {
+    let lines = value.split('/').map(parse_grid_line).collect::<Option<Vec<_>>>()?;
+    let [row_start, rest @ ..] = &lines[..] else {
+        return None;
+    };
+    if rest.len() > 3 {
+        return None;
+    }
+    let column_start = rest.first().cloned().unwrap_or_else(|| omitted_line(row_start));
+    let row_end = rest.get(1).cloned().unwrap_or_else(|| omitted_line(row_start));
+    let column_end = rest.get(2).cloned().unwrap_or_else(|| omitted_line(&column_start));
+    Some([row_start.clone(), column_start, row_end, column_end])
+}
+
+fn parse_border_collapse(value: &str) -> Option<BorderCollapse> {
+    match value.to_lowercase().as_str() {
+        "separate" => Some(BorderCollapse::Separate),
+        "collapse" => Some(BorderCollapse::Collapse),
+        _ => None,
+    }
+}
+
+fn parse_table_layout(value: &str) -> Option<TableLayout> {
+    match value.to_lowercase().as_str() {
+        "auto" => Some(TableLayout::Auto),
+        "fixed" => Some(TableLayout::Fixed),
+        _ => None,
+    }
+}
+
+fn parse_caption_side(value: &str) -> Option<CaptionSide> {
+    match value.to_lowercase().as_str() {
+        "top" => Some(CaptionSide::Top),
+        "bottom" => Some(CaptionSide::Bottom),
+        _ => None,
+    }
+}
+
+/// Parses the `vertical-align` keywords table cells use
+fn parse_vertical_align(value: &str) -> Option<VerticalAlign> {
+    match value.to_lowercase().as_str() {
+        "baseline" => Some(VerticalAlign::Baseline),
+        "top" => Some(VerticalAlign::Top),
+        "middle" => Some(VerticalAlign::Middle),
+        "bottom" => Some(VerticalAlign::Bottom),
+        _ => None,
+    }
+}
+
+fn parse_object_fit(value: &str) -> Option<ObjectFit> {
+    match value.to_lowercase().as_str() {
+        "fill" => Some(ObjectFit::Fill),
+        "contain" => Some(ObjectFit::Contain),
+        "cover" => Some(ObjectFit::Cover),
+        "none" => Some(ObjectFit::None),
+        "scale-down" => Some(ObjectFit::ScaleDown),
+        _ => None,
+    }
+}
+
+/// Parses `overflow`; with separate values for the two axes, the box clips both if either clips
+fn parse_overflow(value: &str) -> Option<Overflow> {
+    let mut result = None;
+    for word in value.to_lowercase().split_whitespace() {
+        let overflow = match word {
+            "visible" => Overflow::Visible,
+            "hidden" | "clip" => Overflow::Hidden,
+            "scroll" => Overflow::Scroll,
+            "auto" => Overflow::Auto,
+            _ => return None,
+        };
+        result = match result {
+            None | Some(Overflow::Visible) => Some(overflow),
+            // `visible` cannot stay visible next to a clipping value, and an axis that
+            // scrolls makes the box a scroll container
+            Some(first) if first.is_scrollable() || overflow == Overflow::Visible => Some(first),
+            Some(_) => Some(overflow),
+        };
+    }
+    result
+}
+
+fn parse_white_space(value: &str) -> Option<WhiteSpace> {
+    match value.to_lowercase().as_str() {
+        "normal" => Some(WhiteSpace::Normal),
+        "nowrap" => Some(WhiteSpace::Nowrap),
+        "pre" => Some(WhiteSpace::Pre),
+        "pre-wrap" => Some(WhiteSpace::PreWrap),
+        "pre-line" => Some(WhiteSpace::PreLine),
+        _ => None,
+    }
+}
+
+/// Parses `unicode-bidi`
+fn parse_unicode_bidi(value: &str) -> Option<UnicodeBidi> {
+    match value.to_lowercase().as_str() {
+        "normal" => Some(UnicodeBidi::Normal),
+        "embed" => Some(UnicodeBidi::Embed),
+        "isolate" => Some(UnicodeBidi::Isolate),
+        "bidi-override" => Some(UnicodeBidi::BidiOverride),
+        "isolate-override" => Some(UnicodeBidi::IsolateOverride),
+        "plaintext" => Some(UnicodeBidi::Plaintext),
+        _ => None,
+    }
+}
+
+/// Parses `break-before`/`break-after` and their legacy `page-break-*` forms. Only pages are
+/// fragmented, so column and region breaks are `auto`.
+fn parse_break_between(value: &str) -> Option<BreakBetween> {
+    match value.to_lowercase().as_str() {
+        "auto" | "column" | "avoid-column" | "region" | "avoid-region" => Some(BreakBetween::Auto),
+        "avoid" | "avoid-page" => Some(BreakBetween::Avoid),
+        "page" | "always" | "left" | "right" | "recto" | "verso" => Some(BreakBetween::Page),
+        _ => None,
+    }
+}
+
+/// Parses `writing-mode`, including the SVG 1.1 values still found on older pages
+fn parse_writing_mode(value: &str) -> Option<WritingMode> {
+    match value.to_lowercase().as_str() {
+        "horizontal-tb" | "lr" | "lr-tb" | "rl" | "rl-tb" => Some(WritingMode::HorizontalTb),
+        "vertical-rl" | "tb" | "tb-rl" => Some(WritingMode::VerticalRl),
+        "vertical-lr" => Some(WritingMode::VerticalLr),
+        _ => None,
+    }
+}
+
+/// Parses `text-align`
+fn parse_text_align(value: &str) -> Option<TextAlign> {
+    match value.to_lowercase().as_str() {
+        "start" => Some(TextAlign::Start),
+        "end" => Some(TextAlign::End),
+        "left" => Some(TextAlign::Left),
+        "right" => Some(TextAlign::Right),
+        "center" => Some(TextAlign::Center),
+        "justify" => Some(TextAlign::Justify),
+        _ => None,
+    }
+}
+
+/// Parses `line-height`: `normal`, a unitless multiplier, a length or a percentage.
+/// Percentages and `em` are relative to the element's own font size.
+fn parse_line_height(value: &str, font_size: f32) -> Option<LineHeight> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("normal") {
+        return Some(LineHeight::Normal);
+    }
+    if let Ok(n) = value.parse::<f32>() {
+        return (n >= 0.0).then_some(LineHeight::Number(n));
+    }
+    match parse_length(value, font_size)? {
+        Length::Px(px) if px >= 0.0 => Some(LineHeight::Px(px)),
+        Length::Percent(pct) if pct >= 0.0 => Some(LineHeight::Px(font_size * pct / 100.0)),
+        _ => None,
+    }
+}
+
+/// A side of the box, for `margin-*` and `padding-*`
+#[derive(Debug, Clone, Copy)]
+enum Side {
+    Top,
+    Right,
+    Bottom,
+    Left,
+}
+
+const SIDES: [Side; 4] = [Side::Top, Side::Right, Side::Bottom, Side::Left];
+
+fn side_mut(edges: &mut EdgeSizes, side: Side) -> &mut f32 {
+    match side {
+        Side::Top => &mut edges.top,
+        Side::Right => &mut edges.right,
+        Side::Bottom => &mut edges.bottom,
+        Side::Left => &mut edges.left,
+    }
+}
+
+/// `auto` is kept as a flag for the horizontal sides; vertical `auto` margins are 0 in block flow
+fn set_margin(style: &mut Style, side: Side, value: &str) {
+    let auto = value.eq_ignore_ascii_case("auto");
+    let px = match parse_length(value, style.font_size) {
+        Some(Length::Px(px)) => px,
+        Some(Length::Auto) => 0.0,
+        _ => return,
+    };
+    *side_mut(&mut style.margin, side) = px;
+    match side {
+        Side::Left => style.margin_left_auto = auto,
+        Side::Right => style.margin_right_auto = auto,
+        _ => {}
+    }
+}
+
+fn set_inset(style: &mut Style, side: Side, value: &str) {
+    if let Some(length) = parse_length(value, style.font_size) {
+        match side {
+            Side::Top => style.top = length,
+            Side::Right => style.right = length,
+            Side::Bottom => style.bottom = length,
+            Side::Left => style.left = length,
+        }
+    }
+}
+
+/// Padding cannot be negative or `auto`
+fn set_padding(style: &mut Style, side: Side, value: &str) {
+    if let Some(Length::Px(px)) = parse_length(value, style.font_size) {
+        if px >= 0.0 {
+            *side_mut(&mut style.padding, side) = px;
+        }
+    }
+}
+
+/// Expands a 1–4 value box shorthand (`margin: 0 auto`) to top, right, bottom, left
+pub fn box_shorthand(value: &str) -> Option<[&str; 4]> {
+    match value.split_whitespace().collect::<Vec<_>>()[..] {
+        [all] => Some([all, all, all, all]),
+        [vertical, horizontal] => Some([vertical, horizontal, vertical, horizontal]),
+        [top, horizontal, bottom] => Some([top, horizontal, bottom, horizontal]),
+        [top, right, bottom, left] => Some([top, right, bottom, left]),
+        _ => None,
+    }
+}
+
+/// Parses a length: `auto`, `12px`, `0`, `1.5em` (of `font_size`), an absolute length such as
+/// `2cm` or `10pt`, or `50%`
+pub fn parse_length(value: &str, font_size: f32) -> Option<Length> {
+    let value = value.trim().to_lowercase();
+    if value == "auto" {
+        return Some(Length::Auto);
+    }
+    if let Some(pct) = value.strip_suffix('%') {
+        return pct.trim().parse().ok().map(Length::Percent);
+    }
+    if let Some(em) = value.strip_suffix("em").filter(|v| !v.ends_with('r')) {
+        return em.trim().parse::<f32>().ok().map(|em| Length::Px(em * font_size));
+    }
+    // Absolute units, at 96px to the inch
+    for (unit, px) in [("cm", 96.0 / 2.54), ("mm", 96.0 / 25.4), ("in", 96.0), ("pt", 96.0 / 72.0), ("pc", 16.0)] {
+        if let Some(number) = value.strip_suffix(unit) {
+            return number.trim().parse::<f32>().ok().map(|n| Length::Px(n * px));
+        }
+    }
+    value.strip_suffix("px").unwrap_or(&value).trim().parse().ok().map(Length::Px)
+}
+
+/// Parses `font-weight`; `bolder`/`lighter` are relative to the inherited weight
+pub fn parse_font_weight(value: &str, inherited: u16) -> Option<u16> {
+    match value.to_lowercase().as_str() {
+        "normal" => Some(400),
+        "bold" => Some(700),
+        "bolder" => Some(match inherited {
+            0..=349 => 400,
+            350..=549 => 700,
+            _ => 900,
+        }),
+        "lighter" => Some(match inherited {
+            0..=549 => 100,
+            550..=749 => 400,
+            _ => 700,
+        }),
+        n => n.parse::<u16>().ok().filter(|w| (1..=1000).contains(w)),
+    }
+}
+
+/// Parses `font-style` (an oblique angle is accepted and ignored)
+pub fn parse_font_style(value: &str) -> Option<FontStyle> {
+    match value.split_whitespace().next()?.to_lowercase().as_str() {
+        "normal" => Some(FontStyle::Normal),
+        "italic" => Some(FontStyle::Italic),
+        "oblique" => Some(FontStyle::Oblique),
+        _ => None,
+    }
+}
+
+/// Parses `font-stretch` keywords and percentages
+pub fn parse_font_stretch(value: &str) -> Option<f32> {
+    match value.to_lowercase().as_str() {
+        "ultra-condensed" => Some(50.0),
+        "extra-condensed" => Some(62.5),
+        "condensed" => Some(75.0),
+        "semi-condensed" => Some(87.5),
+        "normal" => Some(100.0),
+        "semi-expanded" => Some(112.5),
+        "expanded" => Some(125.0),
+        "extra-expanded" => Some(150.0),
+        "ultra-expanded" => Some(200.0),
+        v => v.strip_suffix('%')?.trim().parse::<f32>().ok().filter(|p| *p >= 0.0),
+    }
+}
+
+/// Parses a `display` keyword
+fn parse_display(value: &str) -> Option<Display> {
+    match value.to_lowercase().as_str() {
+        "block" => Some(Display::Block),
+        "inline" => Some(Display::Inline),
+        "inline-block" => Some(Display::InlineBlock),
+        "list-item" => Some(Display::ListItem),
+        "flow-root" => Some(Display::FlowRoot),
+        "flex" => Some(Display::Flex),
+        "inline-flex" => Some(Display::InlineFlex),
+        "grid" => Some(Display::Grid),
+        "inline-grid" => Some(Display::InlineGrid),
+        "table" => Some(Display::Table),
+        "inline-table" => Some(Display::InlineTable),
+        "table-row-group" => Some(Display::TableRowGroup),
+        "table-header-group" => Some(Display::TableHeaderGroup),
+        "table-footer-group" => Some(Display::TableFooterGroup),
+        "table-row" => Some(Display::TableRow),
+        "table-cell" => Some(Display::TableCell),
+        "table-caption" => Some(Display::TableCaption),
+        "table-column" => Some(Display::TableColumn),
+        "table-column-group" => Some(Display::TableColumnGroup),
+        "contents" => Some(Display::Contents),
+        "none" => Some(Display::None),
+        _ => None,
+    }
+}
+
+/// Parses `counter-reset`/`counter-increment`: `name [int] name [int] ...` or `none`
+fn parse_counter_list(value: &str, default: i32) -> Vec<(String, i32)> {
+    let mut counters: Vec<(String, i32)> = Vec::new();
+    for token in value.split_whitespace() {
+        if token.eq_ignore_ascii_case("none") {
+            return vec![];
+        }
+        match (token.parse::<i32>(), counters.last_mut()) {
+            (Ok(n), Some(last)) => last.1 = n,
+            (Ok(_), None) => return vec![],
+            (Err(_), _) => counters.push((token.to_string(), default)),
+        }
+    }
+    counters
+}
+
+/// Parses a `list-style-type` keyword
+fn parse_list_style_type(value: &str) -> Option<ListStyleType> {
+    match value.to_lowercase().as_str() {
+        "disc" => Some(ListStyleType::Disc),
+        "circle" => Some(ListStyleType::Circle),
+        "square" => Some(ListStyleType::Square),
+        "decimal" => Some(ListStyleType::Decimal),
+        "lower-alpha" | "lower-latin" => Some(ListStyleType::LowerAlpha),
+        "upper-alpha" | "upper-latin" => Some(ListStyleType::UpperAlpha),
+        "lower-roman" => Some(ListStyleType::LowerRoman),
+        "upper-roman" => Some(ListStyleType::UpperRoman),
+        "none" => Some(ListStyleType::None),
+        _ => None,
+    }
+}
+
+/// Parses a `list-style-position` keyword
+fn parse_list_style_position(value: &str) -> Option<ListStylePosition> {
+    match value.to_lowercase().as_str() {
+        "outside" => Some(ListStylePosition::Outside),
+        "inside" => Some(ListStylePosition::Inside),
+        _ => None,
+    }
+}
+
+/// Parses `content`: strings, `attr()`, `counter()` and `counters()`.
+/// `normal` and `none` generate no box.
+fn parse_content(value: &str) -> Option<Vec<ContentItem>> {
+    if value.eq_ignore_ascii_case("none") || value.eq_ignore_ascii_case("normal") {
+        return None;
+    }
+
+    let mut items = Vec::new();
+    for token in split_top_level(value, ' ').into_iter().filter(|t| !t.is_empty()) {
+        if let Some(s) = unquote(token) {
+            items.push(ContentItem::String(s));
+            continue;
+        }
+
+        let (func, args) = token.strip_suffix(')')?.split_once('(')?;
+        let args: Vec<&str> = split_top_level(args, ',').into_iter().map(str::trim).collect();
+        let name = args.first()?.to_string();
+        items.push(match func.to_lowercase().as_str() {
+            "attr" => ContentItem::Attr(name.to_lowercase()),
+            "counter" => ContentItem::Counter(name, counter_style_arg(args.get(1))?),
+            "counters" => ContentItem::Counters(name, unquote(args.get(1)?)?, counter_style_arg(args.get(2))?),
+            _ => return None,
+        });
+    }
+    Some(items)
+}
+
+/// Optional counter style argument of `counter()`/`counters()`, defaulting to decimal
+fn counter_style_arg(arg: Option<&&str>) -> Option<ListStyleType> {
+    match arg {
+        Some(name) => parse_list_style_type(name),
+        None => Some(ListStyleType::Decimal),
+    }
+}
+
+/// Parses a basic border string: "1px solid red"
+fn parse_border(value: &str) -> Option<(f32, Color)> {
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    if parts.len() == 3 {
+        if let Ok(width) = parts[0].trim_end_matches("px").parse::<f32>() {
+            let color = parse_color(parts[2])?;
+            return Some((width, color));
+        }
+    }
+    None
+}
+
+/// Parses color names or hex
+fn parse_color(value: &str) -> Option<Color> {
+    let value = value.trim().to_lowercase();
+
+    match value.as_str() {
+        "black" => Some(Color(0, 0, 0, 255)),
+        "white" => Some(Color(255, 255, 255, 255)),
+        "red" => Some(Color(255, 0, 0, 255)),
+        "green" => Some(Color(0, 255, 0, 255)),
+        "blue" => Some(Color(0, 0, 255, 255)),
+        _ if value.starts_with('#') => hex_to_color(&value[1..]),
+        _ => None,
+    }
+}
+
+/// Convert hex color (e.g. "ff0000") to RGB
+fn hex_to_color(hex: &str) -> Option<Color> {
+    if hex.len() == 6 {
+        let r = u8::from_str_radix(&hex[0..2], 16).ok()?;
+        let g = u8::from_str_radix(&hex[2..4], 16).ok()?;
+        let b = u8::from_str_radix(&hex[4..6], 16).ok()?;
+        Some(Color(r, g, b, 255))
+    } else {
+        None
+    }
+}
+
+/// Where a computed property's value came from
+#[derive(Debug, Clone, PartialEq)]
+pub enum StyleOrigin {
+    /// The tag's default style or the property's initial value
+    Default,
+    /// Inherited from the parent
+    Inherited,
+    /// A stylesheet rule: index into the stylesheet list, the rule's line and its winning selector
+    Rule { sheet: usize, line: usize, selector: String },
+    /// The element's `style` attribute
+    Inline,
+}
+
+impl fmt::Display for StyleOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StyleOrigin::Default => write!(f, "default"),
+            StyleOrigin::Inherited => write!(f, "inherited"),
+            StyleOrigin::Rule { sheet, line, selector } => {
+                write!(f, "stylesheet {}, line {}: {}", sheet, line, selector)
+            }
+            StyleOrigin::Inline => write!(f, "inline style"),
+        }
+    }
+}
+
+impl DeclarationSource<'_> {
+    fn origin(&self) -> StyleOrigin {
+        match self {
+            DeclarationSource::Rule { sheet, rule, selector } => StyleOrigin::Rule {
+                sheet: *sheet,
+                line: rule.line,
+                selector: selector.to_string(),
+            },
+            DeclarationSource::Inline => StyleOrigin::Inline,
+        }
+    }
+}
+
+/// One computed property, serialized as CSS, and where it came from
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComputedProperty {
+    pub name: &'static str,
+    pub value: String,
+    pub origin: StyleOrigin,
+}
+
+/// An element's computed style with the origin of every property, like a devtools Styles pane
+#[derive(Debug, Clone)]
+pub struct ComputedStyle {
+    pub style: Style,
+    pub properties: Vec<ComputedProperty>,
+}
+
+impl ComputedStyle {
+    fn new(style: Style, parent: Option<&Style>, mut origins: HashMap<&'static str, StyleOrigin>) -> Self {
+        let parent_values = parent.map(style_properties).unwrap_or_default();
+        let properties = style_properties(&style)
+            .into_iter()
+            .map(|(name, value)| {
+                let origin = origins.remove(name).unwrap_or_else(|| {
+                    let inherited = INHERITED_PROPERTIES.contains(&name)
+                        && parent_values.iter().any(|(n, v)| *n == name && *v == value);
+                    if inherited {
+                        StyleOrigin::Inherited
+                    } else {
+                        StyleOrigin::Default
+                    }
+                });
+                ComputedProperty { name, value, origin }
+            })
+            .collect();
+        ComputedStyle { style, properties }
+    }
+
+    /// Look up one property by CSS name
+    pub fn property(&self, name: &str) -> Option<&ComputedProperty> {
+        self.properties.iter().find(|p| p.name == name)
+    }
+}
+
+/// Every property `Style` holds, by CSS name
+static PROPERTY_NAMES: [&str; 67] = [
+    "display",
+    "width",
+    "height",
+    "float",
+    "clear",
+    "position",
+    "top",
+    "right",
+    "bottom",
+    "left",
+    "z-index",
+    "flex-direction",
+    "flex-wrap",
+    "justify-content",
+    "align-content",
+    "align-items",
+    "align-self",
+    "row-gap",
+    "column-gap",
+    "flex-grow",
+    "flex-shrink",
+    "flex-basis",
+    "order",
+    "grid-template-columns",
+    "grid-template-rows",
+    "grid-template-areas",
+    "grid-auto-columns",
+    "grid-auto-rows",
+    "grid-auto-flow",
+    "grid-row-start",
+    "grid-row-end",
+    "grid-column-start",
+    "grid-column-end",
+    "border-collapse",
+    "border-spacing",
+    "table-layout",
+    "caption-side",
+    "vertical-align",
+    "object-fit",
+    "overflow",
+    "pointer-events",
+    "break-before",
+    "break-after",
+    "break-inside",
+    "direction",
+    "unicode-bidi",
+    "writing-mode",
+    "text-orientation",
+    "color",
+    "background",
+    "border-width",
+    "border-color",
+    "margin",
+    "padding",
+    "font-size",
+    "font-family",
+    "font-weight",
+    "font-style",
+    "font-stretch",
+    "content",
+    "counter-reset",
+    "counter-increment",
+    "list-style-type",
+    "list-style-position",
+    "white-space",
+    "text-align",
+    "line-height",
+];
+
+/// Properties children take from their parent (see `inherited_style`)
+const INHERITED_PROPERTIES: [&str; 18] = [
+    "color",
+    "font-size",
+    "font-family",
+    "font-weight",
+    "font-style",
+    "font-stretch",
+    "list-style-type",
+    "list-style-position",
+    "white-space",
+    "text-align",
+    "line-height",
+    "border-collapse",
+    "border-spacing",
+    "caption-side",
+    "pointer-events",
+    "direction",
+    "writing-mode",
+    "text-orientation",
+];
+
+/// Compute the style of a single DOM node, recording which declaration won each property.
+/// Ancestors are cascaded first so inheritance matches a full style pass.
+pub fn computed_style(node: &Rc<Node>, stylesheets: &[Stylesheet]) -> ComputedStyle {
+    let mut ancestors = Vec::new();
+    let mut current = node.parent();
+    while let Some(ancestor) = current {
+        current = ancestor.parent();
+        ancestors.push(ancestor);
+    }
+
+    let mut parent: Option<Style> = None;
+    for ancestor in ancestors.iter().rev() {
+        let style = match &*ancestor.node_type() {
+            NodeType::Element(el) => compute_style_for_element(ancestor, el, parent.as_ref(), stylesheets),
+            _ => inherited_style(parent.as_ref()),
+        };
+        parent = Some(style);
+    }
+    trace_style(node, parent.as_ref(), stylesheets)
+}
+
+/// Render a styled tree as text: every element with its computed properties and their origins.
+/// `root` is the DOM node `styled` was computed from; values are taken from `styled`.
+pub fn dump_styles(root: &Rc<Node>, styled: &StyledNode, stylesheets: &[Stylesheet]) -> String {
+    let parent = root.parent().map(|p| computed_style(&p, stylesheets).style);
+    let mut out = String::new();
+    dump_node(&mut out, root, styled, parent.as_ref(), stylesheets, 0);
+    out
+}
+
+fn dump_node(
+    out: &mut String,
+    node: &Rc<Node>,
+    styled: &StyledNode,
+    parent: Option<&Style>,
+    stylesheets: &[Stylesheet],
+    depth: usize,
+) {
+    let indent = "  ".repeat(depth);
+    let el = match &styled.node_type {
+        NodeType::Element(el) => el,
+        NodeType::Text(text) => {
+            let _ = writeln!(out, "{}{:?}", indent, text);
+            return;
+        }
+        NodeType::Comment(_) => return,
+    };
+
+    let mut attrs: Vec<_> = el.attrs.iter().collect();
+    attrs.sort();
+    let _ = write!(out, "{}<{}", indent, el.tag_name);
+    for (name, value) in attrs {
+        let _ = write!(out, " {}=\"{}\"", name, value);
+    }
+    let _ = writeln!(out, ">");
+    write_properties(out, &styled.style, &trace_style(node, parent, stylesheets), depth + 1);
+
+    let children = node.children();
+    let mut dom_children = children.iter();
+    for child in &styled.children {
+        if let Some(pseudo) = child.pseudo {
+            let name = match pseudo {
+                PseudoElement::Before => "::before",
+                PseudoElement::After => "::after",
+                PseudoElement::Marker => "::marker",
+            };
+            let _ = writeln!(out, "{}  {}", indent, name);
+            let mut origins = HashMap::new();
+            let traced = cascade_pseudo(node, pseudo, &styled.style, stylesheets, &mut |decl, source| {
+                record_origin(&mut origins, decl, source)
+            });
+            let traced = ComputedStyle::new(traced, Some(&styled.style), origins);
+            write_properties(out, &child.style, &traced, depth + 2);
+        } else if let Some(dom_child) = dom_children.next() {
+            dump_node(out, dom_child, child, Some(&styled.style), stylesheets, depth + 1);
+        }
+    }
+}
+
+/// One `name: value; /* origin */` line per property
+fn write_properties(out: &mut String, style: &Style, traced: &ComputedStyle, depth: usize) {
+    let indent = "  ".repeat(depth);
+    for (name, value) in style_properties(style) {
+        let origin = traced.property(name).map(|p| &p.origin).unwrap_or(&StyleOrigin::Default);
+        let _ = writeln!(out, "{}{}: {}; /* {} */", indent, name, value, origin);
+    }
+}
+
+/// Cascade one node again, recording the last declaration applied to each property
+fn trace_style(node: &Rc<Node>, parent: Option<&Style>, stylesheets: &[Stylesheet]) -> ComputedStyle {
+    let mut origins = HashMap::new();
+    let style = match &*node.node_type() {
+        NodeType::Element(el) => cascade_element(node, el, parent, stylesheets, &mut |decl, source| {
+            record_origin(&mut origins, decl, source)
+        }),
+        NodeType::Text(_) => inherited_style(parent),
+        NodeType::Comment(_) => none_style(),
+    };
+    ComputedStyle::new(style, parent, origins)
+}
+
+fn record_origin(origins: &mut HashMap<&'static str, StyleOrigin>, decl: &Declaration, source: DeclarationSource) {
+    for &property in declaration_properties(&decl.name) {
+        origins.insert(property, source.origin());
+    }
+}
+
+/// The computed properties a declaration sets (shorthands set several)
+fn declaration_properties(name: &str) -> &'static [&'static str] {
+    match name {
+        "background" | "background-color" => &["background"],
+        "border" => &["border-width", "border-color"],
+        "list-style" => &["list-style-type", "list-style-position"],
+        "margin-top" | "margin-right" | "margin-bottom" | "margin-left" => &["margin"],
+        "padding-top" | "padding-right" | "padding-bottom" | "padding-left" => &["padding"],
+        "inset" => &["top", "right", "bottom", "left"],
+        "page-break-before" => &["break-before"],
+        "page-break-after" => &["break-after"],
+        "page-break-inside" => &["break-inside"],
+        "flex" => &["flex-grow", "flex-shrink", "flex-basis"],
+        "flex-flow" => &["flex-direction", "flex-wrap"],
+        "gap" | "grid-gap" => &["row-gap", "column-gap"],
+        "grid-row-gap" => &["row-gap"],
+        "grid-column-gap" => &["column-gap"],
+        "grid-row" => &["grid-row-start", "grid-row-end"],
+        "grid-column" => &["grid-column-start", "grid-column-end"],
+        "grid-area" => &["grid-row-start", "grid-column-start", "grid-row-end", "grid-column-end"],
+        _ => PROPERTY_NAMES
+            .iter()
+            .find(|p| **p == name)
+            .map(std::slice::from_ref)
+            .unwrap_or(&[]),
+    }
+}
+
+/// Serialize every property of a style as (CSS name, CSS value)
+fn style_properties(style: &Style) -> Vec<(&'static str, String)> {
+    let color = |c: Color| format!("rgba({}, {}, {}, {})", c.0, c.1, c.2, c.3 as f32 / 255.0);
+    let optional_color = |c: Option<Color>| c.map(color).unwrap_or_else(|| "none".into());
+    let sides = |e: EdgeSizes| format!("{}px {}px {}px {}px", e.top, e.right, e.bottom, e.left);
+    let length = |l: Length| match l {
+        Length::Auto => "auto".to_string(),
+        Length::Px(px) => format!("{}px", px),
+        Length::Percent(pct) => format!("{}%", pct),
+    };
+    let margin_side = |px: f32, auto: bool| if auto { "auto".to_string() } else { format!("{}px", px) };
+    let margin = format!(
+        "{}px {} {}px {}",
+        style.margin.top,
+        margin_side(style.margin.right, style.margin_right_auto),
+        style.margin.bottom,
+        margin_side(style.margin.left, style.margin_left_auto),
+    );
+    let counter_list = |list: &[(String, i32)]| {
+        if list.is_empty() {
+            "none".to_string()
+        } else {
+            list.iter().map(|(name, n)| format!("{} {}", name, n)).collect::<Vec<_>>().join(" ")
+        }
+    };
+    let content = match &style.content {
+        None => "normal".to_string(),
+        Some(items) => items
+            .iter()
+            .map(|item| match item {
+                ContentItem::String(s) => format!("{:?}", s),
+                ContentItem::Attr(name) => format!("attr({})", name),
+                ContentItem::Counter(name, kind) => format!("counter({}, {})", name, keyword(kind)),
+                ContentItem::Counters(name, sep, kind) => {
+                    format!("counters({}, {:?}, {})", name, sep, keyword(kind))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" "),
+    };
+
+    let values = [
+        keyword(&style.display),
+        length(style.width),
+        length(style.height),
+        keyword(&style.float),
+        keyword(&style.clear),
+        keyword(&style.position),
+        length(style.top),
+        length(style.right),
+        length(style.bottom),
+        length(style.left),
+        style.z_index.map_or_else(|| "auto".to_string(), |z| z.to_string()),
+        keyword(&style.flex_direction),
+        keyword(&style.flex_wrap),
+        keyword(&style.justify_content),
+        keyword(&style.align_content),
+        keyword(&style.align_items),
+        style.align_self.map_or_else(|| "auto".to_string(), |a| keyword(&a)),
+        format!("{}px", style.row_gap),
+        format!("{}px", style.column_gap),
+        style.flex_grow.to_string(),
+        style.flex_shrink.to_string(),
+        length(style.flex_basis),
+        style.order.to_string(),
+        track_list(&style.grid_template_columns),
+        track_list(&style.grid_template_rows),
+        if style.grid_template_areas.is_empty() {
+            "none".to_string()
+        } else {
+            style
+                .grid_template_areas
+                .iter()
+                .map(|row| {
+                    let cells: Vec<&str> = row.iter().map(|cell| cell.as_deref().unwrap_or(".")).collect();
+                    format!("\"{}\"", cells.join(" "))
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        },
+        track_size(style.grid_auto_columns),
+        track_size(style.grid_auto_rows),
+        keyword(&style.grid_auto_flow),
+        grid_line(&style.grid_row_start),
+        grid_line(&style.grid_row_end),
+        grid_line(&style.grid_column_start),
+        grid_line(&style.grid_column_end),
+        keyword(&style.border_collapse),
+        format!("{}px {}px", style.border_spacing.0, style.border_spacing.1),
+        keyword(&style.table_layout),
+        keyword(&style.caption_side),
+        keyword(&style.vertical_align),
+        keyword(&style.object_fit),
+        keyword(&style.overflow),
+        keyword(&style.pointer_events),
+        keyword(&style.break_before),
+        keyword(&style.break_after),
+        keyword(&style.break_inside),
+        keyword(&style.direction),
+        keyword(&style.unicode_bidi),
+        keyword(&style.writing_mode),
+        keyword(&style.text_orientation),
+        color(style.color),
+        optional_color(style.background),
+        format!("{}px", style.border_width),
+        optional_color(style.border_color),
+        margin,
+        sides(style.padding),
+        format!("{}px", style.font_size),
+        style.font_family.clone(),
+        style.font_weight.to_string(),
+        keyword(&style.font_style),
+        format!("{}%", style.font_stretch),
+        content,
+        counter_list(&style.counter_reset),
+        counter_list(&style.counter_increment),
+        keyword(&style.list_style_type),
+        keyword(&style.list_style_position),
+        keyword(&style.white_space),
+        keyword(&style.text_align),
+        match style.line_height {
+            LineHeight::Normal => "normal".to_string(),
+            LineHeight::Number(n) => n.to_string(),
+            LineHeight::Px(px) => format!("{}px", px),
+        },
+    ];
+    PROPERTY_NAMES.iter().copied().zip(values).collect()
+}
+
+/// CSS value of a grid track size: `100px`, `1fr`, `minmax(100px, 1fr)`
+fn track_size(size: TrackSize) -> String {
+    let breadth = |b: TrackBreadth| match b {
+        TrackBreadth::Px(px) => format!("{}px", px),
+        TrackBreadth::Percent(pct) => format!("{}%", pct),
+        TrackBreadth::Fr(fr) => format!("{}fr", fr),
+        other => keyword(&other),
+    };
+    match (size.min, size.max) {
+        (TrackBreadth::Auto, TrackBreadth::Fr(_)) => breadth(size.max),
+        (min, max) if min == max => breadth(min),
+        (min, max) => format!("minmax({}, {})", breadth(min), breadth(max)),
+    }
+}
+
+fn track_list(tracks: &[TrackSize]) -> String {
+    if tracks.is_empty() {
+        return "none".to_string();
+    }
+    tracks.iter().map(|t| track_size(*t)).collect::<Vec<_>>().join(" ")
+}
+
+fn grid_line(line: &GridLine) -> String {
+    match line {
+        GridLine::Auto => "auto".to_string(),
+        GridLine::Line(n) => n.to_string(),
+        GridLine::Span(n) => format!("span {}", n),
+        GridLine::Area(name) => name.clone(),
+    }
+}
+
+/// CSS keyword for a style enum: `ListItem` → `list-item`
+fn keyword(value: &impl fmt::Debug) -> String {
+    let mut out = String::new();
+    for (i, ch) in format!("{:?}", value).chars().enumerate() {
+        if ch.is_ascii_uppercase() && i > 0 {
+            out.push('-');
+        }
+        out.push(ch.to_ascii_lowercase());
+    }
+    out
+}
+
+/// Parallel styling: the cascade runs on worker threads over a `DomSnapshot`, then a serial
+/// pass in document order applies counters and builds generated content. Counters are the
+/// only state that flows between subtrees, so the result equals `compute_styles_serial`.
+#[cfg(feature = "parallel-style")]
+mod parallel {
+    use super::*;
+    use crate::browser::dom::{snapshot, DomSnapshot};
+    use rayon::prelude::*;
+    use std::sync::Mutex;
+
+    /// Subtrees with fewer nodes than this are cascaded on the current thread
+    const PARALLEL_THRESHOLD: usize = 64;
+
+    /// A snapshot node plus its ancestor chain, for selector matching
+    #[derive(Clone, Copy)]
+    struct SnapshotPath<'a> {
+        node: &'a DomSnapshot,
+        parent: Option<&'a SnapshotPath<'a>>,
+    }
+
+    impl MatchTarget for SnapshotPath<'_> {
+        fn match_element(&self, f: &dyn Fn(&ElementData, ElementState) -> bool) -> bool {
+            match &self.node.node_type {
+                NodeType::Element(el) => f(el, self.node.state),
+                _ => false,
+            }
+        }
+
+        fn parent_node(&self) -> Option<Self> {
+            self.parent.copied()
+        }
+    }
+
+    /// Cascaded styles of one snapshot node, before counters are applied
+    struct Cascaded {
+        style: Arc<Style>,
+        marker: Option<Style>,
+        before: Option<Style>,
+        after: Option<Style>,
+        children: Vec<Cascaded>,
+    }
+
+    impl Cascaded {
+        fn leaf(style: Arc<Style>) -> Self {
+            Cascaded {
+                style,
+                marker: None,
+                before: None,
+                after: None,
+                children: vec![],
+            }
+        }
+    }
+
+    /// The style-sharing cache of the serial pass, shared by the workers
+    struct SharedStyles<'a> {
+        stylesheets: &'a [Stylesheet],
+        relevant_attrs: Vec<String>,
+        cache: Mutex<HashMap<ShareKey, Arc<Style>>>,
+    }
+
+    impl SharedStyles<'_> {
+        /// Take a structurally identical node's style, or cascade and remember the result.
+        /// Two workers cascading equal keys at once both keep the first stored style.
+        fn get(&self, key: ShareKey, cascade: impl FnOnce() -> Style) -> Arc<Style> {
+            if let Some(style) = self.cache.lock().unwrap().get(&key) {
+                return style.clone();
+            }
+            let style = Arc::new(cascade());
+            self.cache.lock().unwrap().entry(key).or_insert(style).clone()
+        }
+    }
+
+    pub fn compute_styles_parallel(node: &Rc<Node>, stylesheets: &[Stylesheet]) -> StyledNode {
+        let snap = snapshot(node);
+        clear_dirty(node);
+
+        // Ancestors of the root take part in matching just like in the serial path
+        let mut ancestors = Vec::new();
+        let mut current = node.parent();
+        while let Some(ancestor) = current {
+            ancestors.push(DomSnapshot {
+                node_type: ancestor.node_type().clone(),
+                state: ancestor.state(),
+                children: vec![],
+                size: 1,
+            });
+            current = ancestor.parent();
+        }
+        ancestors.reverse();
+
+        let shared = SharedStyles {
+            stylesheets,
+            relevant_attrs: relevant_attrs(stylesheets),
+            cache: Mutex::new(HashMap::new()),
+        };
+        let cascaded = with_ancestors(&ancestors, None, &|parent| {
+            cascade(SnapshotPath { node: &snap, parent }, None, &shared)
+        });
+
+        let mut counters = Counters::default();
+        let mut scope = Vec::new();
+        let styled = assemble(node, &snap, cascaded, &mut counters, &mut scope);
+        counters.leave_scope(scope);
+        styled
+    }
+
+    /// Build the ancestor chain on the stack, then run `f` with the innermost link
+    fn with_ancestors<R>(
+        ancestors: &[DomSnapshot],
+        parent: Option<&SnapshotPath>,
+        f: &dyn Fn(Option<&SnapshotPath>) -> R,
+    ) -> R {
+        match ancestors.split_first() {
+            Some((first, rest)) => {
+                let path = SnapshotPath { node: first, parent };
+                with_ancestors(rest, Some(&path), f)
+            }
+            None => f(parent),
+        }
+    }
+
+    /// The serial pass clears dirty bits as it goes; do the same for the live tree
+    fn clear_dirty(node: &Rc<Node>) {
+        node.clear_style_dirty();
+        for child in node.children().iter() {
+            clear_dirty(child);
+        }
+    }
+
+    /// Cascade a snapshot subtree, splitting large subtrees across the worker pool
+    fn cascade(path: SnapshotPath, parent: Option<&Arc<Style>>, shared: &SharedStyles) -> Cascaded {
+        let node = path.node;
+        let stylesheets = shared.stylesheets;
+        let key = || ShareKey::new(&shared.relevant_attrs, parent, &node.node_type, node.state);
+        let el = match &node.node_type {
+            NodeType::Element(el) => el,
+            NodeType::Text(_) => {
+                return Cascaded::leaf(shared.get(key(), || inherited_style(parent.map(|p| p.as_ref()))))
+            }
+            NodeType::Comment(_) => return Cascaded::leaf(Arc::new(none_style())),
+        };
+
+        let style = shared.get(key(), || {
+            compute_style_for_element(&path, el, parent.map(|p| p.as_ref()), stylesheets)
+        });
+        if style.display == Display::None {
+            return Cascaded::leaf(style);
+        }
+
+        let marker = (style.display == Display::ListItem)
+            .then(|| pseudo_style(&path, PseudoElement::Marker, &style, stylesheets));
+        let before = Some(pseudo_style(&path, PseudoElement::Before, &style, stylesheets));
+        let after = Some(pseudo_style(&path, PseudoElement::After, &style, stylesheets));
+
+        let style_child = |child: &DomSnapshot| {
+            cascade(SnapshotPath { node: child, parent: Some(&path) }, Some(&style), shared)
+        };
+        let children = if node.size >= PARALLEL_THRESHOLD {
+            node.children.par_iter().map(style_child).collect()
+        } else {
+            node.children.iter().map(style_child).collect()
+        };
+
+        Cascaded {
+            style,
+            marker,
+            before,
+            after,
+            children,
+        }
+    }
+
+    /// Document-order pass: apply counters and build the styled tree with generated content
+    fn assemble(
+        dom: &Rc<Node>,
+        node: &DomSnapshot,
+        cascaded: Cascaded,
+        counters: &mut Counters,
+        scope: &mut Vec<String>,
+    ) -> StyledNode {
+        dom.mark_layout_dirty(LayoutDirty::STYLE);
+        let NodeType::Element(el) = &node.node_type else {
+            return leaf(node.node_type.clone(), cascaded.style, Some(dom));
+        };
+        let style = cascaded.style;
+        if style.display == Display::None {
+            return leaf(node.node_type.clone(), style, Some(dom));
+        }
+
+        counters.apply(&style, el, scope);
+
+        let mut children = Vec::new();
+        if let Some(marker) = cascaded.marker {
+            children.extend(build_pseudo(el, PseudoElement::Marker, marker, counters));
+        }
+        if let Some(before) = cascaded.before {
+            children.extend(build_pseudo(el, PseudoElement::Before, before, counters));
+        }
+
+        let mut child_scope = Vec::new();
+        let dom_children = dom.children();
+        for ((dom_child, child), child_cascaded) in dom_children.iter().zip(&node.children).zip(cascaded.children) {
+            children.push(assemble(dom_child, child, child_cascaded, counters, &mut child_scope));
+        }
+
+        if let Some(after) = cascaded.after {
+            children.extend(build_pseudo(el, PseudoElement::After, after, counters));
+        }
+        counters.leave_scope(child_scope);
+
+        StyledNode {
+            node_type: node.node_type.clone(),
+            style,
+            children,
+            pseudo: None,
+            dom: Some(Rc::downgrade(dom)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::browser::css::parse_stylesheet;
+    use crate::browser::parser::parse_html;
+
+    /// The text of every `::marker` box, in document order
+    fn markers(styled: &StyledNode, out: &mut Vec<String>) {
+        if styled.pseudo == Some(PseudoElement::Marker) {
+            if let Some(NodeType::Text(text)) = styled.children.first().map(|c| &c.node_type) {
+                out.push(text.clone());
+            }
+        }
+        for child in &styled.children {
+            markers(child, out);
+        }
+    }
+
+    fn list_markers(html: &str, css: &str) -> Vec<String> {
+        let document = parse_html(html);
+        let styled = compute_styles(&document, &[parse_stylesheet(css)]);
+        let mut out = Vec::new();
+        markers(&styled, &mut out);
+        out
+    }
+
+    fn find(node: &Rc<Node>, tag: &str) -> Vec<Rc<Node>> {
+        let mut out = Vec::new();
+        if node.tag_name().is_some_and(|name| &*name == tag) {
+            out.push(node.clone());
+        }
+        for child in node.children().iter() {
+            out.extend(find(child, tag));
+        }
+        out
+    }
+
+    fn all_markers(styled: &StyledNode) -> Vec<String> {
+        let mut out = Vec::new();
+        markers(styled, &mut out);
+        out
+    }
+
+    /// The text of every `::before` box, in document order
+    fn befores(styled: &StyledNode, out: &mut Vec<String>) {
+        if styled.pseudo == Some(PseudoElement::Before) {
+            if let Some(NodeType::Text(text)) = styled.children.first().map(|c| &c.node_type) {
+                out.push(text.clone());
+            }
+        }
+        for child in &styled.children {
+            befores(child, out);
+        }
+    }
+
+    /// Whether the first two children of the root share one `Arc<Style>`
+    fn siblings_share(html: &str) -> bool {
+        let document = parse_html(html);
+        let styled = compute_styles(&document, &[]);
+        Arc::ptr_eq(&styled.children[0].style, &styled.children[1].style)
+    }
+
+    #[test]
+    fn siblings_differing_in_presentational_attributes_do_not_share() {
+        assert!(siblings_share(r#"<div><ol start="3"></ol><ol start="3"></ol></div>"#));
+        assert!(!siblings_share(r#"<div><ol start="3"></ol><ol></ol></div>"#));
+        assert!(!siblings_share(r#"<div><table border="1"></table><table></table></div>"#));
+        assert!(!siblings_share(r#"<div><table cellspacing="0"></table><table></table></div>"#));
+        assert!(!siblings_share(r#"<tr><td width="20"></td><td></td></tr>"#));
+        assert!(!siblings_share(r#"<div><img width="20"><img></div>"#));
+        assert!(!siblings_share(r#"<div><img height="20"><img></div>"#));
+        assert!(!siblings_share(r#"<div><span dir="rtl"></span><span></span></div>"#));
+    }
+
+    #[test]
+    fn restyle_of_an_unchanged_tree_reuses_everything() {
+        let document = parse_html("<ol><li>a</li><li>b</li></ol>");
+        let sheets = [parse_stylesheet("li { color: red }")];
+        let styled = compute_styles(&document, &sheets);
+        let (restyled, stats) = restyle(&document, &styled, &sheets);
+        assert_eq!(stats.restyled, 0);
+        assert_eq!(all_markers(&restyled), ["1. ", "2. "]);
+    }
+
+    #[test]
+    fn markers_after_a_changed_item_are_renumbered() {
+        let document = parse_html("<ol><li>a</li><li>b</li><li>c</li></ol>");
+        let styled = compute_styles(&document, &[]);
+        let items = find(&document, "li");
+        for item in &items {
+            item.clear_layout_dirty();
+        }
+
+        items[0].set_attr("value", "10");
+        let (restyled, stats) = restyle(&document, &styled, &[]);
+        assert_eq!(all_markers(&restyled), ["10. ", "11. ", "12. "]);
+        // Only the item whose attribute changed was cascaded again
+        assert_eq!(stats.restyled, 1);
+        assert!(items[2].layout_dirty().needs_relayout());
+    }
+
+    #[test]
+    fn generated_counters_follow_earlier_changes() {
+        let document = parse_html(r#"<div><h2 class="a">x</h2><p>y</p><h2>z</h2></div>"#);
+        let sheets = [parse_stylesheet(
+            "h2 { counter-increment: section } .big { counter-increment: section 5 }
+             h2::before { content: \"S\" counter(section) \" \" }",
+        )];
+        let styled = compute_styles(&document, &sheets);
+        let mut before = Vec::new();
+        befores(&styled, &mut before);
+        assert_eq!(before, ["S1 ", "S2 "]);
+
+        find(&document, "h2")[0].set_attr("class", "big");
+        let (restyled, _) = restyle(&document, &styled, &sheets);
+        let mut after = Vec::new();
+        befores(&restyled, &mut after);
+        assert_eq!(after, ["S5 ", "S6 "]);
+    }
+
+    /// Everything a styled tree holds except its DOM links, one node per line
+    #[cfg(feature = "parallel-style")]
+    fn describe(styled: &StyledNode, depth: usize, out: &mut String) {
+        out.push_str(&format!(
+            "{}{:?} {:?} {:?}\n",
+            "  ".repeat(depth),
+            styled.node_type,
+            styled.pseudo,
+            styled.style
+        ));
+        for child in &styled.children {
+            describe(child, depth + 1, out);
+        }
+    }
+
+    #[cfg(feature = "parallel-style")]
+    #[test]
+    fn parallel_styles_match_serial() {
+        let items: String = (0..300)
+            .map(|i| format!(r#"<li class="c{}" title="t{}"><b>x</b> y</li>"#, i % 3, i % 5))
+            .collect();
+        let html = format!(r#"<div><ol start="3">{}</ol><p id="last">end</p></div>"#, items);
+        let sheets = [parse_stylesheet(
+            r#"li { color: blue } .c1 { font-size: 20px } ol > .c2 b { color: red }
+               [title="t4"]::after { content: counter(list-item) } #last::before { content: "*" }"#,
+        )];
+
+        let document = parse_html(&html);
+        let serial = compute_styles_serial(&document, &sheets);
+        let parallel = parallel::compute_styles_parallel(&document, &sheets);
+        let (mut a, mut b) = (String::new(), String::new());
+        describe(&serial, 0, &mut a);
+        describe(&parallel, 0, &mut b);
+        assert_eq!(a, b);
+
+        // Items with equal classes and titles share one style, as in the serial pass
+        let ol = &parallel.children[0];
+        let items: Vec<&StyledNode> = ol.children.iter().filter(|c| c.pseudo.is_none()).collect();
+        assert!(Arc::ptr_eq(&items[0].style, &items[15].style));
+        assert!(!Arc::ptr_eq(&items[0].style, &items[1].style));
+    }
+
+    #[test]
+    fn state_changes_restyle_only_the_changed_element() {
+        let document = parse_html("<div><p>a</p><p>b</p></div>");
+        let sheets = [parse_stylesheet("p:hover { background: red }")];
+        let styled = compute_styles(&document, &sheets);
+
+        let paragraphs = find(&document, "p");
+        paragraphs[1].set_state(ElementState::HOVER, true);
+        assert!(paragraphs[1].style_dirty().intersects(StyleDirty::STATE));
+        assert!(document.style_dirty().intersects(StyleDirty::DESCENDANT));
+
+        let (restyled, stats) = restyle(&document, &styled, &sheets);
+        assert_eq!(stats.restyled, 1);
+        assert_eq!(restyled.children[0].style.background, None);
+        assert_eq!(restyled.children[1].style.background, Some(Color(255, 0, 0, 255)));
+        assert_eq!(document.style_dirty(), StyleDirty::NONE);
+    }
+
+    #[test]
+    fn computed_style_reports_the_winning_rule() {
+        let document = parse_html(r#"<div><p class="note" id="x">a<span>b</span></p><p style="color: green">c</p></div>"#);
+        let sheets = [parse_stylesheet("p { color: red }\n.note { color: blue; margin: 4px }\n")];
+        let ps = find(&document, "p");
+
+        let note = computed_style(&ps[0], &sheets);
+        let color = note.property("color").unwrap();
+        assert_eq!(color.value, "rgba(0, 0, 255, 1)");
+        assert_eq!(
+            color.origin,
+            StyleOrigin::Rule { sheet: 0, line: 2, selector: ".note".into() }
+        );
+        assert_eq!(note.property("display").unwrap().origin, StyleOrigin::Default);
+
+        let inline = computed_style(&ps[1], &sheets);
+        assert_eq!(inline.property("color").unwrap().origin, StyleOrigin::Inline);
+
+        let span = computed_style(&find(&document, "span")[0], &sheets);
+        let color = span.property("color").unwrap();
+        assert_eq!((color.value.as_str(), &color.origin), ("rgba(0, 0, 255, 1)", &StyleOrigin::Inherited));
+    }
+
+    #[test]
+    fn style_dump_lists_properties_with_origins() {
+        let document = parse_html(r#"<ul><li class="done">a</li></ul>"#);
+        let sheets = [parse_stylesheet(".done { color: red }\nli::before { content: \"x\" }")];
+        let styled = compute_styles(&document, &sheets);
+        let dump = dump_styles(&document, &styled, &sheets);
+
+        assert!(dump.starts_with("<ul>\n"));
+        assert!(dump.contains("  <li class=\"done\">\n"));
+        assert!(dump.contains("    color: rgba(255, 0, 0, 1); /* stylesheet 0, line 1: .done */\n"));
+        assert!(dump.contains("    ::before\n"));
+        assert!(dump.contains("      content: \"x\"; /* stylesheet 0, line 2: li::before */\n"));
+        assert!(dump.contains("    \"a\"\n"));
+    }
+
+    #[test]
+    fn list_start_saturates() {
+        let html = r#"<ol start="-2147483648"><li>a</li><li>b</li></ol>"#;
+        assert_eq!(list_markers(html, ""), ["-2147483647. ", "-2147483646. "]);
+    }
+
+    #[test]
+    fn counter_increment_saturates() {
+        let html = "<ol><li>a</li><li>b</li></ol>";
+        let css = "li { counter-increment: list-item 2147483647 }";
+        assert_eq!(list_markers(html, css), ["2147483647. ", "2147483647. "]);
+    }
+
+    #[test]
+    fn counters_nest_per_list_and_format_by_list_style_type() {
+        let html = "<div><ol><li>a<ol><li>b</li><li>c</li></ol></li><li>d</li></ol><ol><li>e</li></ol></div>";
+        let css = r#"ol ol li::marker { content: counters(list-item, ".") " " } ol ol { list-style-type: lower-alpha }"#;
+        assert_eq!(list_markers(html, css), ["1. ", "1.1 ", "1.2 ", "2. ", "1. "]);
+        let css = "li { list-style-type: upper-roman }";
+        assert_eq!(list_markers(html, css), ["I. ", "I. ", "II. ", "II. ", "I. "]);
+    }
+
+    /// The pseudo-element and text of every generated box, in document order
+    fn generated(styled: &StyledNode, out: &mut Vec<(PseudoElement, String)>) {
+        if let Some(pseudo) = styled.pseudo {
+            if let Some(NodeType::Text(text)) = styled.children.first().map(|c| &c.node_type) {
+                out.push((pseudo, text.clone()));
+            }
+        }
+        for child in &styled.children {
+            generated(child, out);
+        }
+    }
+
+    #[test]
+    fn content_generates_before_after_and_marker_boxes() {
+        let document = parse_html(r#"<div><ul><li title="a">x</li><li>y</li></ul><p>z</p></div>"#);
+        let css = r#"li::before { content: "[" attr(title) "]" } p::after { content: "!" } p::before { content: none }"#;
+        let styled = compute_styles(&document, &[parse_stylesheet(css)]);
+        let mut out = Vec::new();
+        generated(&styled, &mut out);
+
+        use PseudoElement::*;
+        let expected = [(Marker, "• "), (Before, "[a]"), (Marker, "• "), (Before, "[]"), (After, "!")];
+        assert_eq!(out, expected.map(|(pseudo, text)| (pseudo, text.to_string())));
     }
 }
@@ -0,0 +1,574 @@
+//! table.rs — Table layout (CSS 2.1 §17, simplified).
+//! Rows and cells are slotted into a grid honouring `colspan` and `rowspan`, columns are sized
+//! by the automatic or fixed table layout algorithm, and rows grow to fit their cells. Captions
+//! sit above or below the table, in its margin area. With `border-collapse: collapse` adjacent
+//! cell borders overlap instead of being resolved edge by edge: there is no conflict
+//! resolution by style, width and origin (CSS 2.1 §17.6.2.1), as borders have no style and
+//! are the same on every side. Where borders of different cells meet, the cell later in
+//! document order paints over the earlier one, even when its border is narrower.
+
+use crate::browser::engine::{
+    layout_sized, outer_widths, BorderCollapse, CaptionSide, Dimensions, Display, EdgeSizes, Length, Node,
+    PointerEvents, Positioned, Style, TableLayout, VerticalAlign,
+};
+use crate::browser::inline::first_baseline;
+use crate::browser::renderer::LayoutBox;
+use std::ops::Range;
+
+/// Laid out contents of a table: its row groups, rows and cells, and its captions
+pub struct TableBoxes<'n> {
+    pub boxes: Vec<LayoutBox>,
+    /// Content height: the rows, or the table's own height if that is larger
+    pub height: f32,
+    pub positioned: Vec<Positioned<'n>>,
+}
+
+/// A cell and the grid slots it covers
+struct Cell<'n> {
+    node: &'n Node,
+    row: usize,
+    column: usize,
+    rows: usize,
+    columns: usize,
+}
+
+/// Consecutive rows painted together: a row group element, or rows placed directly in the table
+struct Group<'n> {
+    node: Option<&'n Node>,
+    rows: Range<usize>,
+}
+
+/// A table's rows and cells slotted into columns
+struct TableGrid<'n> {
+    captions: Vec<&'n Node>,
+    /// Widths set by `<col>` and `<colgroup>`, one per column they cover
+    column_widths: Vec<Length>,
+    groups: Vec<Group<'n>>,
+    rows: Vec<&'n Node>,
+    cells: Vec<Cell<'n>>,
+    columns: usize,
+}
+
+/// Space between adjacent cells, and between the outer cells and the table's border
+struct Spacing {
+    between: (f32, f32),
+    edge: (f32, f32),
+}
+
+impl Spacing {
+    fn of(style: &Style, grid: &TableGrid) -> Spacing {
+        match style.border_collapse {
+            BorderCollapse::Separate => Spacing {
+                between: style.border_spacing,
+                edge: style.border_spacing,
+            },
+            BorderCollapse::Collapse => {
+                // Neighbouring borders overlap by the widest cell border, and the outer ones
+                // overlap the table's own. Narrower borders are not widened to match.
+                let overlap = grid
+                    .cells
+                    .iter()
+                    .map(|cell| cell.node.style.border_width)
+                    .fold(0.0, f32::max);
+                let edge = overlap.min(style.border_width);
+                Spacing {
+                    between: (-overlap, -overlap),
+                    edge: (-edge, -edge),
+                }
+            }
+        }
+    }
+
+    /// Total spacing across `count` columns (or rows, with `axis` 1)
+    fn total(&self, count: usize, axis: usize) -> f32 {
+        let (between, edge) = if axis == 0 {
+            (self.between.0, self.edge.0)
+        } else {
+            (self.between.1, self.edge.1)
+        };
+        match count {
+            0 => 0.0,
+            n => (n - 1) as f32 * between + 2.0 * edge,
+        }
+    }
+}
+
+/// Lay out a table's contents inside `d`, whose width is the table's available (or specified)
+/// width on entry. Shrinks `d` to the width the columns use, drops its padding when borders
+/// collapse, and grows its margins to make room for captions.
+pub fn layout_table<'n>(node: &'n Node, style: &Style, d: &mut Dimensions, height: Option<f32>) -> TableBoxes<'n> {
+    let grid = table_grid(node);
+    let spacing = Spacing::of(style, &grid);
+    let auto_width = style.width == Length::Auto;
+
+    // Tables in the collapsing border model have no padding
+    if style.border_collapse == BorderCollapse::Collapse {
+        d.content.x -= d.padding.left;
+        d.content.y -= d.padding.top;
+        if auto_width {
+            d.content.width += d.padding.left + d.padding.right;
+        }
+        d.padding = EdgeSizes::default();
+    }
+
+    let gaps = spacing.total(grid.columns, 0);
+    let widths = if style.table_layout == TableLayout::Fixed && !auto_width {
+        fixed_columns(&grid, &spacing, d.content.width - gaps)
+    } else {
+        let (min, max) = column_bounds(&grid, &spacing);
+        let min_total = min.iter().sum::<f32>() + gaps;
+        let max_total = max.iter().sum::<f32>() + gaps;
+        let used = if auto_width {
+            max_total.min(d.content.width)
+        } else {
+            d.content.width
+        };
+        auto_columns(&min, &max, used.max(min_total) - gaps)
+    };
+    let used = widths.iter().sum::<f32>() + gaps;
+    if auto_width {
+        // An auto-width table shrinks to fit its columns; `auto` margins center it in the rest
+        let free = (d.content.width - used).max(0.0);
+        let shift = match (style.margin_left_auto, style.margin_right_auto) {
+            (true, true) => free / 2.0,
+            (true, false) => free,
+            (false, _) => 0.0,
+        };
+        d.content.x += shift;
+        d.margin.left += shift;
+        d.margin.right += free - shift;
+        d.content.width = used;
+    } else {
+        d.content.width = d.content.width.max(used);
+    }
+    let column_x = offsets(&widths, spacing.between.0, spacing.edge.0);
+
+    // Captions are as wide as the table's border box and stack outside it
+    let caption_width = d.border_box().width;
+    let captions: Vec<_> = grid
+        .captions
+        .iter()
+        .map(|caption| {
+            let margin = caption.style.margin;
+            let (layout, positioned) =
+                layout_sized(caption, (caption_width - margin.left - margin.right).max(0.0), None);
+            (
+                caption.style.caption_side == CaptionSide::Top,
+                margin,
+                layout,
+                positioned,
+            )
+        })
+        .collect();
+    let caption_height = |top: bool| -> f32 {
+        captions
+            .iter()
+            .filter(|(side, ..)| *side == top)
+            .map(|(_, margin, layout, _)| margin.top + layout.height + margin.bottom)
+            .sum()
+    };
+    let above = caption_height(true);
+    d.margin.top += above;
+    d.content.y += above;
+
+    // Each cell is laid out at the width of the columns it spans
+    let mut cells: Vec<(LayoutBox, Vec<Positioned<'n>>)> = grid
+        .cells
+        .iter()
+        .map(|cell| {
+            let span = cell.column..cell.column + cell.columns;
+            let width = widths[span].iter().sum::<f32>() + (cell.columns - 1) as f32 * spacing.between.0;
+            layout_sized(cell.node, width.max(0.0), None)
+        })
+        .collect();
+    let baselines: Vec<f32> = cells
+        .iter()
+        .map(|(layout, _)| first_baseline(layout).unwrap_or(layout.height))
+        .collect();
+
+    let mut row_heights: Vec<f32> = grid
+        .rows
+        .iter()
+        .map(|row| row.style.height.resolve(None).unwrap_or(0.0).max(0.0))
+        .collect();
+    let mut row_baselines = vec![0.0f32; grid.rows.len()];
+    let mut below_baselines = vec![0.0f32; grid.rows.len()];
+    for (index, cell) in grid.cells.iter().enumerate() {
+        let cell_height = cells[index].0.height;
+        if cell.rows != 1 {
+            continue;
+        }
+        if cell.node.style.vertical_align == VerticalAlign::Baseline {
+            row_baselines[cell.row] = row_baselines[cell.row].max(baselines[index]);
+            below_baselines[cell.row] = below_baselines[cell.row].max(cell_height - baselines[index]);
+        }
+        row_heights[cell.row] = row_heights[cell.row].max(cell_height);
+    }
+    for (row, height) in row_heights.iter_mut().enumerate() {
+        *height = height.max(row_baselines[row] + below_baselines[row]);
+    }
+
+    // Cells spanning several rows share what they need beyond those rows between them
+    let mut spanning: Vec<usize> = (0..grid.cells.len())
+        .filter(|&index| grid.cells[index].rows > 1)
+        .collect();
+    spanning.sort_by_key(|&index| grid.cells[index].rows);
+    for index in spanning {
+        let cell = &grid.cells[index];
+        let span = cell.row..cell.row + cell.rows;
+        let available = row_heights[span.clone()].iter().sum::<f32>() + (cell.rows - 1) as f32 * spacing.between.1;
+        let extra = cells[index].0.height - available;
+        if extra > 0.0 {
+            for height in &mut row_heights[span] {
+                *height += extra / cell.rows as f32;
+            }
+        }
+    }
+
+    // A table height larger than its rows is shared between them
+    let rows_height = row_heights.iter().sum::<f32>() + spacing.total(grid.rows.len(), 1);
+    if let Some(height) = height {
+        if height > rows_height && !row_heights.is_empty() {
+            let extra = (height - rows_height) / row_heights.len() as f32;
+            row_heights.iter_mut().for_each(|row| *row += extra);
+        }
+    }
+    let row_y = offsets(&row_heights, spacing.between.1, spacing.edge.1);
+    let content_height =
+        (row_heights.iter().sum::<f32>() + spacing.total(grid.rows.len(), 1)).max(height.unwrap_or(0.0));
+
+    // Rows span every column; each holds the cells that start in it
+    let columns_width = widths.iter().sum::<f32>() + grid.columns.saturating_sub(1) as f32 * spacing.between.0;
+    let row_x = d.content.x + spacing.edge.0;
+    let mut row_boxes: Vec<LayoutBox> = grid
+        .rows
+        .iter()
+        .enumerate()
+        .map(|(row, node)| LayoutBox {
+            x: row_x,
+            y: d.content.y + row_y[row],
+            width: columns_width,
+            height: row_heights[row],
+            background: node.style.background,
+            dom: node.dom.clone(),
+            pointer_events: node.style.pointer_events,
+            ..LayoutBox::empty()
+        })
+        .collect();
+
+    let mut positioned = Vec::new();
+    for child in node.children.iter().filter(|child| child.is_out_of_flow()) {
+        positioned.push(Positioned {
+            node: child,
+            x: d.content.x,
+            y: d.content.y,
+        });
+    }
+    for (index, cell) in grid.cells.iter().enumerate() {
+        let (mut layout, mut cell_positioned) = std::mem::replace(&mut cells[index], (LayoutBox::empty(), Vec::new()));
+        let span = cell.row..cell.row + cell.rows;
+        let area = row_heights[span].iter().sum::<f32>() + (cell.rows - 1) as f32 * spacing.between.1;
+        let offset = match cell.node.style.vertical_align {
+            VerticalAlign::Top => 0.0,
+            VerticalAlign::Middle => (area - layout.height) / 2.0,
+            VerticalAlign::Bottom => area - layout.height,
+            VerticalAlign::Baseline if cell.rows == 1 => row_baselines[cell.row] - baselines[index],
+            VerticalAlign::Baseline => 0.0,
+        }
+        .max(0.0);
+        for child in &mut layout.children {
+            child.translate(0.0, offset);
+        }
+        layout.height = layout.height.max(area);
+
+        let (x, y) = (d.content.x + column_x[cell.column], d.content.y + row_y[cell.row]);
+        layout.translate(x, y);
+        for entry in &mut cell_positioned {
+            entry.x += x;
+            entry.y += y + offset;
+        }
+        positioned.extend(cell_positioned);
+        row_boxes[cell.row].children.push(layout);
+    }
+
+    let mut boxes = Vec::new();
+    let mut rows = row_boxes.into_iter();
+    for group in &grid.groups {
+        let children: Vec<LayoutBox> = rows.by_ref().take(group.rows.len()).collect();
+        let (Some(first), Some(last)) = (children.first(), children.last()) else {
+            continue;
+        };
+        let (y, height) = (first.y, last.y + last.height - first.y);
+        boxes.push(LayoutBox {
+            x: row_x,
+            y,
+            width: columns_width,
+            height,
+            background: group.node.and_then(|group| group.style.background),
+            dom: group.node.and_then(|group| group.dom.clone()),
+            pointer_events: group.node.map_or(PointerEvents::Auto, |group| group.style.pointer_events),
+            children,
+            ..LayoutBox::empty()
+        });
+    }
+
+    // Place the captions now the table's border box is known
+    let border_box = d.border_box();
+    let mut top_y = border_box.y - above;
+    let mut bottom_y = d.content.y + content_height + d.padding.bottom + d.border.bottom;
+    let below = caption_height(false);
+    for (top, margin, mut layout, caption_positioned) in captions {
+        let y = if top { &mut top_y } else { &mut bottom_y };
+        let (x, caption_y) = (border_box.x + margin.left, *y + margin.top);
+        *y += margin.top + layout.height + margin.bottom;
+        layout.translate(x, caption_y);
+        positioned.extend(caption_positioned.into_iter().map(|mut entry| {
+            entry.x += x;
+            entry.y += caption_y;
+            entry
+        }));
+        boxes.push(layout);
+    }
+    d.margin.bottom += below;
+
+    TableBoxes {
+        boxes,
+        height: content_height,
+        positioned,
+    }
+}
+
+/// Minimum and maximum content widths of a table's content box (CSS 2.1 §17.5.2.2)
+pub fn table_content_widths(node: &Node) -> (f32, f32) {
+    let grid = table_grid(node);
+    let spacing = Spacing::of(&node.style, &grid);
+    let (min, max) = column_bounds(&grid, &spacing);
+    let gaps = spacing.total(grid.columns, 0);
+    let (mut min_total, mut max_total) = (min.iter().sum::<f32>() + gaps, max.iter().sum::<f32>() + gaps);
+    for caption in &grid.captions {
+        let (caption_min, caption_max) = outer_widths(caption);
+        min_total = min_total.max(caption_min);
+        max_total = max_total.max(caption_max);
+    }
+    (min_total, max_total.max(min_total))
+}
+
+/// Slot a table's rows and cells into a grid. Header groups come first and footer groups
+/// last; rows placed directly in the table form anonymous groups between the others.
+fn table_grid(node: &Node) -> TableGrid<'_> {
+    let mut captions = Vec::new();
+    let mut column_widths = Vec::new();
+    let (mut headers, mut bodies, mut footers) = (Vec::new(), Vec::new(), Vec::new());
+    let mut loose = Vec::new();
+    for child in node.children.iter().filter(|child| !child.is_out_of_flow()) {
+        if child.style.display == Display::TableRow {
+            loose.push(child);
+            continue;
+        }
+        if !loose.is_empty() {
+            bodies.push((None, std::mem::take(&mut loose)));
+        }
+        match child.style.display {
+            Display::TableCaption => captions.push(child),
+            Display::TableColumn => push_columns(&mut column_widths, child),
+            Display::TableColumnGroup if child.children.is_empty() => push_columns(&mut column_widths, child),
+            Display::TableColumnGroup => {
+                for column in &child.children {
+                    push_columns(&mut column_widths, column);
+                }
+            }
+            Display::TableHeaderGroup => headers.push((Some(child), group_rows(child))),
+            Display::TableFooterGroup => footers.push((Some(child), group_rows(child))),
+            Display::TableRowGroup => bodies.push((Some(child), group_rows(child))),
+            _ => {}
+        }
+    }
+    if !loose.is_empty() {
+        bodies.push((None, loose));
+    }
+
+    let mut groups = Vec::new();
+    let mut rows = Vec::new();
+    let mut cells = Vec::new();
+    let mut occupied: Vec<Vec<bool>> = Vec::new();
+    for (group, group_rows) in headers.into_iter().chain(bodies).chain(footers) {
+        let start = rows.len();
+        let end = start + group_rows.len();
+        occupied.resize(end, Vec::new());
+        for row_node in group_rows {
+            let row = rows.len();
+            rows.push(row_node);
+            let mut column = 0;
+            for cell in row_node
+                .children
+                .iter()
+                .filter(|cell| cell.style.display == Display::TableCell)
+            {
+                while occupied[row].get(column) == Some(&true) {
+                    column += 1;
+                }
+                let columns = span_attribute(cell, "colspan").filter(|&span| span > 0).unwrap_or(1);
+                // `rowspan="0"` spans the rest of the group; no span reaches past it
+                let rows_spanned = match span_attribute(cell, "rowspan") {
+                    Some(0) => end - row,
+                    Some(span) => span.min(end - row),
+                    None => 1,
+                };
+                for slots in &mut occupied[row..row + rows_spanned] {
+                    if slots.len() < column + columns {
+                        slots.resize(column + columns, false);
+                    }
+                    slots[column..column + columns].iter_mut().for_each(|slot| *slot = true);
+                }
+                cells.push(Cell {
+                    node: cell,
+                    row,
+                    column,
+                    rows: rows_spanned,
+                    columns,
+                });
+                column += columns;
+            }
+        }
+        groups.push(Group {
+            node: group,
+            rows: start..end,
+        });
+    }
+    let columns = cells
+        .iter()
+        .map(|cell| cell.column + cell.columns)
+        .fold(column_widths.len(), usize::max);
+    TableGrid {
+        captions,
+        column_widths,
+        groups,
+        rows,
+        cells,
+        columns,
+    }
+}
+
+/// The rows of a row group
+fn group_rows(group: &Node) -> Vec<&Node> {
+    group
+        .children
+        .iter()
+        .filter(|row| row.style.display == Display::TableRow)
+        .collect()
+}
+
+/// Record the width a `<col>` (or childless `<colgroup>`) gives each column it spans
+fn push_columns(widths: &mut Vec<Length>, column: &Node) {
+    let span = span_attribute(column, "span").filter(|&span| span > 0).unwrap_or(1);
+    widths.extend(std::iter::repeat_n(column.style.width, span));
+}
+
+/// A `colspan`, `rowspan` or `span` attribute, capped like HTML caps them
+fn span_attribute(node: &Node, name: &str) -> Option<usize> {
+    let span = node.attribute(name)?.trim().parse::<usize>().ok()?;
+    Some(span.min(if name == "rowspan" { 65534 } else { 1000 }))
+}
+
+/// Minimum and maximum widths of each column, from the cells in it (automatic table layout).
+/// Cells spanning several columns widen those columns equally by whatever more they need.
+fn column_bounds(grid: &TableGrid, spacing: &Spacing) -> (Vec<f32>, Vec<f32>) {
+    let mut min = vec![0.0f32; grid.columns];
+    let mut max = vec![0.0f32; grid.columns];
+    for (index, width) in grid.column_widths.iter().enumerate() {
+        if let Length::Px(width) = *width {
+            min[index] = width;
+            max[index] = width;
+        }
+    }
+    let mut cells: Vec<&Cell> = grid.cells.iter().collect();
+    cells.sort_by_key(|cell| cell.columns);
+    for cell in cells {
+        let (cell_min, cell_max) = outer_widths(cell.node);
+        let span = cell.column..cell.column + cell.columns;
+        let gaps = (cell.columns - 1) as f32 * spacing.between.0;
+        for (bounds, needed) in [(&mut min, cell_min), (&mut max, cell_max)] {
+            let extra = needed - gaps - bounds[span.clone()].iter().sum::<f32>();
+            if extra > 0.0 {
+                bounds[span.clone()]
+                    .iter_mut()
+                    .for_each(|width| *width += extra / cell.columns as f32);
+            }
+        }
+    }
+    for (max, min) in max.iter_mut().zip(&min) {
+        *max = max.max(*min);
+    }
+    (min, max)
+}
+
+/// Share `space` between columns given their minimum and maximum widths: columns grow from
+/// their minimum towards their maximum together, and past it in proportion to it.
+fn auto_columns(min: &[f32], max: &[f32], space: f32) -> Vec<f32> {
+    let (min_total, max_total) = (min.iter().sum::<f32>(), max.iter().sum::<f32>());
+    if space >= max_total {
+        let extra = space - max_total;
+        if max_total > 0.0 {
+            max.iter().map(|width| width + extra * width / max_total).collect()
+        } else {
+            max.iter().map(|width| width + extra / max.len() as f32).collect()
+        }
+    } else if max_total > min_total {
+        let ratio = ((space - min_total) / (max_total - min_total)).max(0.0);
+        min.iter()
+            .zip(max)
+            .map(|(min, max)| min + (max - min) * ratio)
+            .collect()
+    } else {
+        min.to_vec()
+    }
+}
+
+/// Column widths in the fixed table layout (CSS 2.1 §17.5.2.1): from column elements, then
+/// the first row's cells, with the columns neither sizes sharing what is left of `space`
+fn fixed_columns(grid: &TableGrid, spacing: &Spacing, space: f32) -> Vec<f32> {
+    let mut widths: Vec<Option<f32>> = (0..grid.columns)
+        .map(|index| match grid.column_widths.get(index) {
+            Some(Length::Px(width)) => Some(*width),
+            _ => None,
+        })
+        .collect();
+    for cell in grid.cells.iter().filter(|cell| cell.row == 0) {
+        let style = &cell.node.style;
+        if let Length::Px(width) = style.width {
+            let outer = width + style.padding.left + style.padding.right + 2.0 * style.border_width;
+            let share = (outer - (cell.columns - 1) as f32 * spacing.between.0) / cell.columns as f32;
+            for width in &mut widths[cell.column..cell.column + cell.columns] {
+                if width.is_none() {
+                    *width = Some(share);
+                }
+            }
+        }
+    }
+    let fixed: f32 = widths.iter().flatten().sum();
+    let auto = widths.iter().filter(|width| width.is_none()).count();
+    let share = if auto > 0 {
+        ((space - fixed) / auto as f32).max(0.0)
+    } else {
+        0.0
+    };
+    let mut widths: Vec<f32> = widths.into_iter().map(|width| width.unwrap_or(share)).collect();
+    // With every column sized, a wider table widens them all
+    if auto == 0 && fixed > 0.0 && fixed < space {
+        widths.iter_mut().for_each(|width| *width *= space / fixed);
+    }
+    widths
+}
+
+/// Start of each track from the content edge, given the tracks' sizes and the spacing
+fn offsets(sizes: &[f32], between: f32, edge: f32) -> Vec<f32> {
+    let mut position = edge;
+    sizes
+        .iter()
+        .map(|size| {
+            let start = position;
+            position += size + between;
+            start
+        })
+        .collect()
+}
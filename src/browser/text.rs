@@ -0,0 +1,261 @@
+//! text.rs — Font selection, text shaping and font metrics.
+//! Layout measures text and the renderer paints it through the same shaping results,
+//! so the width layout reserves is exactly the width that gets drawn.
+
+use crate::browser::css::Stylesheet;
+use crate::browser::engine::FontStyle;
+use crate::browser::fonts::{font_from_handle, FontMatcher, FontQuery, LoadedFont};
+use ab_glyph::Font;
+use font_kit::family_name::FamilyName;
+use font_kit::properties::{Properties, Stretch, Style as FontKitStyle, Weight};
+use font_kit::source::SystemSource;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Advance of a character no font can draw, in em
+const MISSING_GLYPH_ADVANCE: f32 = 0.5;
+
+/// One glyph positioned relative to the pen, in px
+#[derive(Debug, Clone, Copy)]
+pub struct ShapedGlyph {
+    /// Index into `ShapedText::fonts`; None for a character no font covers (drawn as a gap)
+    pub font: Option<usize>,
+    pub glyph_id: u16,
+    /// Byte offset of the glyph's cluster in the shaped text
+    pub cluster: usize,
+    pub x_advance: f32,
+    pub x_offset: f32,
+    pub y_offset: f32,
+}
+
+/// Text shaped at one font size
+#[derive(Clone, Default)]
+pub struct ShapedText {
+    pub glyphs: Vec<ShapedGlyph>,
+    pub fonts: Vec<LoadedFont>,
+    /// Sum of the advances, in px
+    pub width: f32,
+}
+
+/// Vertical metrics of a font at one size, in px
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FontMetrics {
+    pub ascent: f32,
+    /// Distance below the baseline (positive)
+    pub descent: f32,
+    pub line_gap: f32,
+}
+
+/// Installed and `@font-face` fonts, shared by layout and the renderer
+#[derive(Default)]
+pub struct FontContext {
+    /// Installed fonts by family and style; failed lookups are cached as None
+    system: HashMap<String, Option<LoadedFont>>,
+    web_fonts: FontMatcher,
+}
+
+thread_local! {
+    static FONT_CONTEXT: RefCell<FontContext> = RefCell::new(FontContext::default());
+}
+
+/// Run `f` with this thread's font context
+pub fn with_font_context<R>(f: impl FnOnce(&mut FontContext) -> R) -> R {
+    FONT_CONTEXT.with(|ctx| f(&mut ctx.borrow_mut()))
+}
+
+impl FontContext {
+    /// Register the `@font-face` rules of a stylesheet loaded from `base_url`
+    pub fn add_stylesheet(&mut self, sheet: &Stylesheet, base_url: &str) {
+        self.web_fonts.add_stylesheet(sheet, base_url);
+    }
+
+    /// Load an installed font from system or cache
+    fn load_system_font(&mut self, family: &str, query: &FontQuery) -> Option<LoadedFont> {
+        let key = format!("{}|{}|{:?}|{}", family, query.weight, query.style, query.stretch);
+        if let Some(font) = self.system.get(&key) {
+            return font.clone();
+        }
+
+        let family_name = match family.to_lowercase().as_str() {
+            "serif" => FamilyName::Serif,
+            "sans-serif" => FamilyName::SansSerif,
+            "monospace" => FamilyName::Monospace,
+            "cursive" => FamilyName::Cursive,
+            "fantasy" => FamilyName::Fantasy,
+            _ => FamilyName::Title(family.to_string()),
+        };
+        let properties = Properties {
+            weight: Weight(query.weight as f32),
+            style: match query.style {
+                FontStyle::Normal => FontKitStyle::Normal,
+                FontStyle::Italic => FontKitStyle::Italic,
+                FontStyle::Oblique => FontKitStyle::Oblique,
+            },
+            stretch: Stretch(query.stretch / 100.0),
+        };
+
+        let font = SystemSource::new()
+            .select_best_match(&[family_name], &properties)
+            .ok()
+            .and_then(|handle| font_from_handle(&handle));
+        self.system.insert(key, font.clone());
+        font
+    }
+
+    /// Pick the font to draw `ch` with: walk the family list, preferring `@font-face`
+    /// faces over installed fonts, and fall back to the default sans-serif font.
+    pub fn font_for(&mut self, query: &FontQuery, ch: char) -> Option<LoadedFont> {
+        for family in &query.families {
+            if let Some(index) = self.web_fonts.select(family, query, ch) {
+                if let Some(font) = self.web_fonts.load(index) {
+                    return Some(font);
+                }
+            }
+            // A family declared with @font-face shadows installed fonts of that name
+            if self.web_fonts.has_family(family) {
+                continue;
+            }
+            if let Some(font) = self.load_system_font(family, query) {
+                if font.font.glyph_id(ch).0 != 0 {
+                    return Some(font);
+                }
+            }
+        }
+        self.load_system_font("sans-serif", query)
+    }
+
+    /// Ascent, descent and line gap of the first available font (the one drawing a space)
+    pub fn metrics(&mut self, query: &FontQuery, size: f32) -> FontMetrics {
+        match self.font_for(query, ' ') {
+            Some(loaded) => {
+                let scale = loaded.scale(size);
+                let font = &loaded.font;
+                FontMetrics {
+                    ascent: font.ascent_unscaled() * scale,
+                    descent: -font.descent_unscaled() * scale,
+                    line_gap: font.line_gap_unscaled() * scale,
+                }
+            }
+            None => FontMetrics {
+                ascent: size * 0.8,
+                descent: size * 0.2,
+                line_gap: 0.0,
+            },
+        }
+    }
+
+    /// Shape `text` at `size` px: split it into runs by the font each character falls back to,
+    /// then shape every run with its font (kerning, ligatures, marks). Each run's direction is
+    /// guessed from its script.
+    pub fn shape(&mut self, text: &str, query: &FontQuery, size: f32) -> ShapedText {
+        self.shape_in(text, query, size, None)
+    }
+
+    /// `shape` text of a known direction. Right-to-left text comes out in visual order: its runs
+    /// and glyphs reversed, and characters such as brackets mirrored.
+    pub fn shape_directed(&mut self, text: &str, query: &FontQuery, size: f32, rtl: bool) -> ShapedText {
+        self.shape_in(text, query, size, Some(rtl))
+    }
+
+    fn shape_in(&mut self, text: &str, query: &FontQuery, size: f32, rtl: Option<bool>) -> ShapedText {
+        let mut shaped = ShapedText::default();
+        let mut runs: Vec<(Option<usize>, Range<usize>)> = Vec::new();
+
+        for (i, ch) in text.char_indices() {
+            if ch.is_control() {
+                continue;
+            }
+            let end = i + ch.len_utf8();
+            // Spaces stay in the current run so shaping across them is not interrupted
+            if ch.is_whitespace() {
+                if let Some((_, range)) = runs.last_mut().filter(|(_, r)| r.end == i) {
+                    range.end = end;
+                    continue;
+                }
+            }
+
+            let font = self.font_for(query, ch).map(|font| {
+                shaped.fonts.iter().position(|f| f.same(&font)).unwrap_or_else(|| {
+                    shaped.fonts.push(font);
+                    shaped.fonts.len() - 1
+                })
+            });
+            match runs.last_mut() {
+                Some((run_font, range)) if *run_font == font && range.end == i => range.end = end,
+                _ => runs.push((font, i..end)),
+            }
+        }
+
+        if rtl == Some(true) {
+            runs.reverse();
+        }
+        for (font, range) in runs {
+            match font {
+                Some(index) => {
+                    let loaded = shaped.fonts[index].clone();
+                    shape_run(&loaded, index, &text[range.clone()], range.start, size, rtl, &mut shaped);
+                }
+                None => {
+                    let mut chars: Vec<usize> = text[range.clone()].char_indices().map(|(i, _)| i).collect();
+                    if rtl == Some(true) {
+                        chars.reverse();
+                    }
+                    for i in chars {
+                        let advance = size * MISSING_GLYPH_ADVANCE;
+                        shaped.glyphs.push(ShapedGlyph {
+                            font: None,
+                            glyph_id: 0,
+                            cluster: range.start + i,
+                            x_advance: advance,
+                            x_offset: 0.0,
+                            y_offset: 0.0,
+                        });
+                        shaped.width += advance;
+                    }
+                }
+            }
+        }
+        shaped
+    }
+}
+
+/// Shape one single-font run with rustybuzz, in the given direction or the one its script
+/// suggests, and append its glyphs
+fn shape_run(
+    loaded: &LoadedFont,
+    index: usize,
+    text: &str,
+    offset: usize,
+    size: f32,
+    rtl: Option<bool>,
+    out: &mut ShapedText,
+) {
+    let Some(face) = rustybuzz::Face::from_slice(loaded.data(), loaded.index) else {
+        return;
+    };
+    let scale = size / face.units_per_em() as f32;
+
+    let mut buffer = rustybuzz::UnicodeBuffer::new();
+    buffer.push_str(text);
+    buffer.guess_segment_properties();
+    match rtl {
+        Some(true) => buffer.set_direction(rustybuzz::Direction::RightToLeft),
+        Some(false) => buffer.set_direction(rustybuzz::Direction::LeftToRight),
+        None => {}
+    }
+    let output = rustybuzz::shape(&face, &[], buffer);
+
+    for (info, pos) in output.glyph_infos().iter().zip(output.glyph_positions()) {
+        let glyph = ShapedGlyph {
+            font: Some(index),
+            glyph_id: info.glyph_id as u16,
+            cluster: offset + info.cluster as usize,
+            x_advance: pos.x_advance as f32 * scale,
+            x_offset: pos.x_offset as f32 * scale,
+            y_offset: pos.y_offset as f32 * scale,
+        };
+        out.width += glyph.x_advance;
+        out.glyphs.push(glyph);
+    }
+}
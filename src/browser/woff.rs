@@ -0,0 +1,697 @@
+//! woff.rs — Unwraps WOFF and WOFF2 web font containers into plain sfnt (TTF/OTF) data
+//! that the glyph rasterizer can read.
+
+use std::io::Read;
+
+/// Errors while decoding a font container
+#[derive(Debug)]
+pub enum WoffError {
+    Truncated,
+    BadSignature,
+    Unsupported(String),
+    Decompress(String),
+    Malformed(String),
+}
+
+/// Decode font data in any supported container (sfnt, WOFF, WOFF2) into sfnt bytes
+pub fn decode_font(data: &[u8]) -> Result<Vec<u8>, WoffError> {
+    match data.get(0..4) {
+        Some(b"wOFF") => decode_woff(data),
+        Some(b"wOF2") => decode_woff2(data),
+        Some(&[0, 1, 0, 0]) | Some(b"OTTO") | Some(b"true") | Some(b"ttcf") => Ok(data.to_vec()),
+        Some(_) => Err(WoffError::BadSignature),
+        None => Err(WoffError::Truncated),
+    }
+}
+
+/// A table of the output font
+struct Table {
+    tag: [u8; 4],
+    data: Vec<u8>,
+}
+
+/// WOFF 1.0: a table directory of individually zlib-compressed tables
+fn decode_woff(data: &[u8]) -> Result<Vec<u8>, WoffError> {
+    let mut r = Reader::new(data);
+    r.skip(4)?;
+    let flavor = r.u32()?;
+    r.skip(4)?; // length
+    let num_tables = r.u16()?;
+    r.skip(2)?; // reserved
+    // The lengths in the directory are not trusted further than the font's stated size
+    let mut budget = r.u32()? as usize;
+    r.seek(44)?;
+
+    let mut tables = Vec::with_capacity(num_tables as usize);
+    for _ in 0..num_tables {
+        let tag = r.tag()?;
+        let offset = r.u32()? as usize;
+        let comp_length = r.u32()? as usize;
+        let orig_length = r.u32()? as usize;
+        r.skip(4)?; // origChecksum
+
+        let raw = data
+            .get(offset..offset.saturating_add(comp_length))
+            .ok_or(WoffError::Truncated)?;
+        budget = budget
+            .checked_sub(orig_length)
+            .ok_or_else(|| WoffError::Malformed("tables larger than totalSfntSize".into()))?;
+        let table = if comp_length < orig_length {
+            let mut out = Vec::new();
+            flate2::read::ZlibDecoder::new(raw)
+                .take(orig_length as u64)
+                .read_to_end(&mut out)
+                .map_err(|e| WoffError::Decompress(e.to_string()))?;
+            out
+        } else {
+            raw.to_vec()
+        };
+
+        if table.len() != orig_length {
+            return Err(WoffError::Malformed("table length mismatch".into()));
+        }
+        tables.push(Table { tag, data: table });
+    }
+
+    build_sfnt(flavor, tables)
+}
+
+/// Tags addressed by index in the WOFF2 table directory
+const KNOWN_TAGS: [&[u8; 4]; 63] = [
+    b"cmap", b"head", b"hhea", b"hmtx", b"maxp", b"name", b"OS/2", b"post",
+    b"cvt ", b"fpgm", b"glyf", b"loca", b"prep", b"CFF ", b"VORG", b"EBDT",
+    b"EBLC", b"gasp", b"hdmx", b"kern", b"LTSH", b"PCLT", b"VDMX", b"vhea",
+    b"vmtx", b"BASE", b"GDEF", b"GPOS", b"GSUB", b"EBSC", b"JSTF", b"MATH",
+    b"CBDT", b"CBLC", b"COLR", b"CPAL", b"SVG ", b"sbix", b"acnt", b"avar",
+    b"bdat", b"bloc", b"bsln", b"cvar", b"fdsc", b"feat", b"fmtx", b"fvar",
+    b"gvar", b"hsty", b"just", b"lcar", b"mort", b"morx", b"opbd", b"prop",
+    b"trak", b"Zapf", b"Silf", b"Glat", b"Gloc", b"Feat", b"Sill",
+];
+
+/// A WOFF2 table directory entry
+struct Woff2Entry {
+    tag: [u8; 4],
+    stored_length: usize,
+    transformed: bool,
+}
+
+/// WOFF 2.0: one brotli stream holding every table, with `glyf`/`loca` transformed
+fn decode_woff2(data: &[u8]) -> Result<Vec<u8>, WoffError> {
+    let mut r = Reader::new(data);
+    r.skip(4)?;
+    let flavor = r.u32()?;
+    if &flavor.to_be_bytes() == b"ttcf" {
+        return Err(WoffError::Unsupported("font collections".into()));
+    }
+    r.skip(4)?; // length
+    let num_tables = r.u16()?;
+    r.skip(2)?; // reserved
+    let total_sfnt_size = r.u32()? as usize;
+    let total_compressed = r.u32()? as usize;
+    r.seek(48)?;
+
+    let mut entries = Vec::with_capacity(num_tables as usize);
+    for _ in 0..num_tables {
+        let flags = r.u8()?;
+        let tag = match flags & 0x3f {
+            63 => r.tag()?,
+            index => *KNOWN_TAGS[index as usize],
+        };
+        let version = flags >> 6;
+        let orig_length = r.base128()? as usize;
+
+        // For glyf/loca version 0 means "transformed"; for everything else it is the null transform
+        let transformed = if &tag == b"glyf" || &tag == b"loca" {
+            version == 0
+        } else {
+            version != 0
+        };
+        let stored_length = if transformed {
+            r.base128()? as usize
+        } else {
+            orig_length
+        };
+        entries.push(Woff2Entry {
+            tag,
+            stored_length,
+            transformed,
+        });
+    }
+
+    // The stream holds the stored tables back to back; they can't outgrow the font's stated size
+    let stream_length = entries.iter().map(|entry| entry.stored_length).sum::<usize>();
+    if stream_length > total_sfnt_size {
+        return Err(WoffError::Malformed("tables larger than totalSfntSize".into()));
+    }
+    let compressed = r.bytes(total_compressed)?;
+    let mut stream = Vec::new();
+    brotli_decompressor::Decompressor::new(compressed, 4096)
+        .take(stream_length as u64)
+        .read_to_end(&mut stream)
+        .map_err(|e| WoffError::Decompress(e.to_string()))?;
+
+    let mut tables = Vec::with_capacity(entries.len());
+    let mut long_loca = None;
+    let mut offset = 0usize;
+    for entry in &entries {
+        let table = stream
+            .get(offset..offset.saturating_add(entry.stored_length))
+            .ok_or(WoffError::Truncated)?;
+        offset += entry.stored_length;
+
+        match (&entry.tag, entry.transformed) {
+            (_, false) => tables.push(Table {
+                tag: entry.tag,
+                data: table.to_vec(),
+            }),
+            (b"glyf", true) => {
+                let glyf = reconstruct_glyf(table)?;
+                long_loca = Some(glyf.long_loca);
+                tables.push(Table { tag: *b"glyf", data: glyf.glyf });
+                tables.push(Table { tag: *b"loca", data: glyf.loca });
+            }
+            // Rebuilt together with glyf
+            (b"loca", true) => {}
+            (tag, true) => {
+                return Err(WoffError::Unsupported(format!(
+                    "transformed {} table",
+                    String::from_utf8_lossy(tag)
+                )))
+            }
+        }
+    }
+
+    // The rebuilt loca may need a different offset size than the original
+    if let Some(long_loca) = long_loca {
+        let head = tables
+            .iter_mut()
+            .find(|t| &t.tag == b"head")
+            .ok_or_else(|| WoffError::Malformed("missing head table".into()))?;
+        let index_to_loc = head
+            .data
+            .get_mut(50..52)
+            .ok_or(WoffError::Truncated)?;
+        index_to_loc.copy_from_slice(&(long_loca as u16).to_be_bytes());
+    }
+
+    build_sfnt(flavor, tables)
+}
+
+/// Output of the WOFF2 glyf transform reversal
+struct RebuiltGlyf {
+    glyf: Vec<u8>,
+    loca: Vec<u8>,
+    long_loca: bool,
+}
+
+/// Undo the WOFF2 `glyf` transform, rebuilding standard `glyf` and `loca` tables
+fn reconstruct_glyf(data: &[u8]) -> Result<RebuiltGlyf, WoffError> {
+    let mut r = Reader::new(data);
+    r.skip(2)?; // reserved
+    let _option_flags = r.u16()?;
+    let num_glyphs = r.u16()? as usize;
+    let index_format = r.u16()?;
+
+    let mut sizes = [0usize; 7];
+    for size in sizes.iter_mut() {
+        *size = r.u32()? as usize;
+    }
+    let mut offset = r.pos;
+    let mut streams = Vec::with_capacity(7);
+    for size in sizes {
+        streams.push(Reader::new(
+            data.get(offset..offset.saturating_add(size)).ok_or(WoffError::Truncated)?,
+        ));
+        offset += size;
+    }
+    let [mut n_contours, mut n_points, mut flags, mut glyphs, mut composites, mut bboxes, mut instructions]: [Reader; 7] =
+        streams.try_into().map_err(|_| WoffError::Truncated)?;
+
+    let bitmap_len = ((num_glyphs + 31) >> 5) << 2;
+    let bbox_bitmap = bboxes.bytes(bitmap_len)?;
+
+    let mut glyf = Vec::new();
+    let mut offsets = Vec::with_capacity(num_glyphs + 1);
+
+    for i in 0..num_glyphs {
+        offsets.push(glyf.len());
+        let has_bbox = bbox_bitmap[i >> 3] & (0x80 >> (i & 7)) != 0;
+        let contours = n_contours.i16()?;
+
+        if contours == 0 {
+            if has_bbox {
+                return Err(WoffError::Malformed("empty glyph with bbox".into()));
+            }
+            continue;
+        }
+
+        if contours < 0 {
+            // Composite: component records are stored verbatim
+            if !has_bbox {
+                return Err(WoffError::Malformed("composite glyph without bbox".into()));
+            }
+            let start = composites.pos;
+            let mut have_instructions = false;
+            loop {
+                let component_flags = composites.u16()?;
+                have_instructions |= component_flags & 0x0100 != 0;
+                let mut skip = 2 + if component_flags & 0x0001 != 0 { 4 } else { 2 };
+                if component_flags & 0x0008 != 0 {
+                    skip += 2;
+                } else if component_flags & 0x0040 != 0 {
+                    skip += 4;
+                } else if component_flags & 0x0080 != 0 {
+                    skip += 8;
+                }
+                composites.skip(skip)?;
+                if component_flags & 0x0020 == 0 {
+                    break;
+                }
+            }
+
+            glyf.extend_from_slice(&(-1i16).to_be_bytes());
+            glyf.extend_from_slice(bboxes.bytes(8)?);
+            glyf.extend_from_slice(&composites.data[start..composites.pos]);
+            if have_instructions {
+                let len = glyphs.u255_16()? as usize;
+                glyf.extend_from_slice(&(len as u16).to_be_bytes());
+                glyf.extend_from_slice(instructions.bytes(len)?);
+            }
+        } else {
+            // Simple: point counts, flags and triplet-encoded coordinates come from separate streams
+            let mut end_points = Vec::with_capacity(contours as usize);
+            let mut total = 0usize;
+            for _ in 0..contours {
+                total += n_points.u255_16()? as usize;
+                let end = total
+                    .checked_sub(1)
+                    .ok_or_else(|| WoffError::Malformed("contour without points".into()))?;
+                end_points.push(end as u16);
+            }
+
+            let mut points = Vec::with_capacity(total);
+            let (mut x, mut y) = (0i32, 0i32);
+            for _ in 0..total {
+                let flag = flags.u8()?;
+                let (dx, dy) = decode_triplet(flag & 0x7f, &mut glyphs)?;
+                x += dx;
+                y += dy;
+                points.push((x, y, flag & 0x80 == 0));
+            }
+            let instruction_len = glyphs.u255_16()? as usize;
+
+            glyf.extend_from_slice(&(contours).to_be_bytes());
+            if has_bbox {
+                glyf.extend_from_slice(bboxes.bytes(8)?);
+            } else {
+                let min_x = points.iter().map(|p| p.0).min().unwrap_or(0);
+                let min_y = points.iter().map(|p| p.1).min().unwrap_or(0);
+                let max_x = points.iter().map(|p| p.0).max().unwrap_or(0);
+                let max_y = points.iter().map(|p| p.1).max().unwrap_or(0);
+                for v in [min_x, min_y, max_x, max_y] {
+                    glyf.extend_from_slice(&(v as i16).to_be_bytes());
+                }
+            }
+            for end in end_points {
+                glyf.extend_from_slice(&end.to_be_bytes());
+            }
+            glyf.extend_from_slice(&(instruction_len as u16).to_be_bytes());
+            glyf.extend_from_slice(instructions.bytes(instruction_len)?);
+            encode_points(&points, &mut glyf);
+        }
+
+        while glyf.len() % 4 != 0 {
+            glyf.push(0);
+        }
+    }
+    offsets.push(glyf.len());
+
+    // Short offsets store offset/2 and top out at 0x1FFFE
+    let long_loca = index_format != 0 || glyf.len() > 0x1FFFE;
+    let mut loca = Vec::with_capacity(offsets.len() * 4);
+    for off in offsets {
+        if long_loca {
+            loca.extend_from_slice(&(off as u32).to_be_bytes());
+        } else {
+            loca.extend_from_slice(&((off / 2) as u16).to_be_bytes());
+        }
+    }
+
+    Ok(RebuiltGlyf {
+        glyf,
+        loca,
+        long_loca,
+    })
+}
+
+/// Decode one WOFF2 coordinate triplet into a (dx, dy) delta
+fn decode_triplet(flag: u8, r: &mut Reader) -> Result<(i32, i32), WoffError> {
+    let with_sign = |flag: u8, base: i32| if flag & 1 != 0 { base } else { -base };
+    let flag_i = flag as i32;
+
+    Ok(match flag {
+        0..=9 => {
+            let b0 = r.u8()? as i32;
+            (0, with_sign(flag, ((flag_i & 14) << 7) + b0))
+        }
+        10..=19 => {
+            let b0 = r.u8()? as i32;
+            (with_sign(flag, (((flag_i - 10) & 14) << 7) + b0), 0)
+        }
+        20..=83 => {
+            let b0 = flag_i - 20;
+            let b1 = r.u8()? as i32;
+            (
+                with_sign(flag, 1 + (b0 & 0x30) + (b1 >> 4)),
+                with_sign(flag >> 1, 1 + ((b0 & 0x0c) << 2) + (b1 & 0x0f)),
+            )
+        }
+        84..=119 => {
+            let b0 = flag_i - 84;
+            let b1 = r.u8()? as i32;
+            let b2 = r.u8()? as i32;
+            (
+                with_sign(flag, 1 + ((b0 / 12) << 8) + b1),
+                with_sign(flag >> 1, 1 + (((b0 % 12) >> 2) << 8) + b2),
+            )
+        }
+        120..=123 => {
+            let b1 = r.u8()? as i32;
+            let b2 = r.u8()? as i32;
+            let b3 = r.u8()? as i32;
+            (
+                with_sign(flag, (b1 << 4) + (b2 >> 4)),
+                with_sign(flag >> 1, ((b2 & 0x0f) << 8) + b3),
+            )
+        }
+        _ => {
+            let dx = r.u16()? as i32;
+            let dy = r.u16()? as i32;
+            (with_sign(flag, dx), with_sign(flag >> 1, dy))
+        }
+    })
+}
+
+/// Write TrueType simple-glyph flags and coordinate deltas (no flag repetition)
+fn encode_points(points: &[(i32, i32, bool)], out: &mut Vec<u8>) {
+    const ON_CURVE: u8 = 0x01;
+    const X_SHORT: u8 = 0x02;
+    const Y_SHORT: u8 = 0x04;
+    const X_SAME_OR_POSITIVE: u8 = 0x10;
+    const Y_SAME_OR_POSITIVE: u8 = 0x20;
+
+    let mut flags = Vec::with_capacity(points.len());
+    let mut xs = Vec::new();
+    let mut ys = Vec::new();
+    let (mut last_x, mut last_y) = (0, 0);
+
+    for &(x, y, on_curve) in points {
+        let mut flag = if on_curve { ON_CURVE } else { 0 };
+        let (dx, dy) = (x - last_x, y - last_y);
+        (last_x, last_y) = (x, y);
+
+        if dx == 0 {
+            flag |= X_SAME_OR_POSITIVE;
+        } else if dx.abs() < 256 {
+            flag |= X_SHORT | if dx > 0 { X_SAME_OR_POSITIVE } else { 0 };
+            xs.push(dx.unsigned_abs() as u8);
+        } else {
+            xs.extend_from_slice(&(dx as i16).to_be_bytes());
+        }
+
+        if dy == 0 {
+            flag |= Y_SAME_OR_POSITIVE;
+        } else if dy.abs() < 256 {
+            flag |= Y_SHORT | if dy > 0 { Y_SAME_OR_POSITIVE } else { 0 };
+            ys.push(dy.unsigned_abs() as u8);
+        } else {
+            ys.extend_from_slice(&(dy as i16).to_be_bytes());
+        }
+        flags.push(flag);
+    }
+
+    out.extend_from_slice(&flags);
+    out.extend_from_slice(&xs);
+    out.extend_from_slice(&ys);
+}
+
+/// Assemble an sfnt file: offset table, sorted table records, 4-byte aligned table data
+fn build_sfnt(flavor: u32, mut tables: Vec<Table>) -> Result<Vec<u8>, WoffError> {
+    if tables.is_empty() {
+        return Err(WoffError::Malformed("font has no tables".into()));
+    }
+    tables.sort_by_key(|t| t.tag);
+    let num_tables = u16::try_from(tables.len()).map_err(|_| WoffError::Malformed("too many tables".into()))?;
+
+    // Binary search hints; fonts with more than 4095 tables cannot state them in 16 bits,
+    // so they saturate
+    let entry_selector = num_tables.ilog2() as u16;
+    let search_range = (1u32 << entry_selector) * 16;
+    let range_shift = u32::from(num_tables) * 16 - search_range;
+    let saturate = |v: u32| u16::try_from(v).unwrap_or(u16::MAX);
+    let (search_range, range_shift) = (saturate(search_range), saturate(range_shift));
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&flavor.to_be_bytes());
+    for v in [num_tables, search_range, entry_selector, range_shift] {
+        out.extend_from_slice(&v.to_be_bytes());
+    }
+
+    let mut offset = 12 + 16 * tables.len();
+    for table in &tables {
+        out.extend_from_slice(&table.tag);
+        out.extend_from_slice(&checksum(&table.data).to_be_bytes());
+        out.extend_from_slice(&(offset as u32).to_be_bytes());
+        out.extend_from_slice(&(table.data.len() as u32).to_be_bytes());
+        offset += (table.data.len() + 3) & !3;
+    }
+
+    for table in &tables {
+        out.extend_from_slice(&table.data);
+        while out.len() % 4 != 0 {
+            out.push(0);
+        }
+    }
+    Ok(out)
+}
+
+/// sfnt table checksum: wrapping sum of big-endian u32 words, zero padded
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+/// Big-endian cursor over font data
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], WoffError> {
+        let slice = self
+            .data
+            .get(self.pos..self.pos + n)
+            .ok_or(WoffError::Truncated)?;
+        self.pos += n;
+        Ok(slice)
+    }
+
+    fn skip(&mut self, n: usize) -> Result<(), WoffError> {
+        self.bytes(n).map(|_| ())
+    }
+
+    fn seek(&mut self, pos: usize) -> Result<(), WoffError> {
+        if pos > self.data.len() {
+            return Err(WoffError::Truncated);
+        }
+        self.pos = pos;
+        Ok(())
+    }
+
+    fn u8(&mut self) -> Result<u8, WoffError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, WoffError> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn i16(&mut self) -> Result<i16, WoffError> {
+        self.u16().map(|v| v as i16)
+    }
+
+    fn u32(&mut self) -> Result<u32, WoffError> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn tag(&mut self) -> Result<[u8; 4], WoffError> {
+        let b = self.bytes(4)?;
+        Ok([b[0], b[1], b[2], b[3]])
+    }
+
+    /// WOFF2 UIntBase128: up to five 7-bit groups, most significant first
+    fn base128(&mut self) -> Result<u32, WoffError> {
+        let mut value: u32 = 0;
+        for i in 0..5 {
+            let byte = self.u8()?;
+            if i == 0 && byte == 0x80 {
+                return Err(WoffError::Malformed("leading zero in UIntBase128".into()));
+            }
+            if value & 0xFE00_0000 != 0 {
+                return Err(WoffError::Malformed("UIntBase128 overflow".into()));
+            }
+            value = (value << 7) | (byte & 0x7f) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(WoffError::Malformed("UIntBase128 too long".into()))
+    }
+
+    /// WOFF2 255UInt16 variable-length encoding
+    fn u255_16(&mut self) -> Result<u16, WoffError> {
+        Ok(match self.u8()? {
+            253 => self.u16()?,
+            254 => self.u8()? as u16 + 253 * 2,
+            255 => self.u8()? as u16 + 253,
+            code => code as u16,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u16_at(data: &[u8], at: usize) -> u16 {
+        u16::from_be_bytes([data[at], data[at + 1]])
+    }
+
+    fn u32_at(data: &[u8], at: usize) -> u32 {
+        u32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+    }
+
+    /// The data of each table of an sfnt, by tag
+    fn sfnt_tables(sfnt: &[u8]) -> Vec<([u8; 4], &[u8])> {
+        (0..u16_at(sfnt, 4) as usize)
+            .map(|i| {
+                let record = 12 + 16 * i;
+                let (offset, length) = (u32_at(sfnt, record + 8) as usize, u32_at(sfnt, record + 12) as usize);
+                (sfnt[record..record + 4].try_into().unwrap(), &sfnt[offset..offset + length])
+            })
+            .collect()
+    }
+
+    /// The decoded font parses, and has an outline for "a"
+    fn assert_usable(sfnt: &[u8]) {
+        use ab_glyph::Font;
+        let font = ab_glyph::FontRef::try_from_slice(sfnt).expect("decoded font parses");
+        assert!(font.glyph_count() > 1);
+        assert!(font.outline(font.glyph_id('a')).is_some());
+    }
+
+    #[test]
+    fn decodes_a_woff_font() {
+        let woff = include_bytes!("../../tests/fonts/Cantarell-Regular.woff");
+        let sfnt = decode_font(woff).unwrap();
+        assert_eq!(&sfnt[0..4], &woff[4..8], "flavor");
+
+        // Every table comes back with the length and checksum of the original font
+        let tables = sfnt_tables(&sfnt);
+        assert_eq!(tables.len(), u16_at(woff, 12) as usize);
+        for entry in woff[44..].chunks(20).take(tables.len()) {
+            let (_, data) = tables.iter().find(|(tag, _)| tag[..] == entry[0..4]).unwrap();
+            assert_eq!(data.len(), u32_at(entry, 12) as usize);
+            let mut data = data.to_vec();
+            if &entry[0..4] == b"head" {
+                // The checksum adjustment is left out of the head table's checksum
+                data[8..12].fill(0);
+            }
+            assert_eq!(checksum(&data), u32_at(entry, 16));
+        }
+        assert_usable(&sfnt);
+    }
+
+    #[test]
+    fn decodes_a_woff2_font() {
+        let woff2 = include_bytes!("../../tests/fonts/OpenSans-Regular.woff2");
+        let sfnt = decode_font(woff2).unwrap();
+        let tables = sfnt_tables(&sfnt);
+        assert_eq!(tables.len(), u16_at(woff2, 12) as usize);
+        // The rebuilt loca has an offset for every glyph, and one past the last
+        let (_, maxp) = tables.iter().find(|(tag, _)| tag == b"maxp").unwrap();
+        let (_, head) = tables.iter().find(|(tag, _)| tag == b"head").unwrap();
+        let (_, loca) = tables.iter().find(|(tag, _)| tag == b"loca").unwrap();
+        let offset_size = if u16_at(head, 50) == 0 { 2 } else { 4 };
+        assert_eq!(loca.len(), (u16_at(maxp, 4) as usize + 1) * offset_size);
+        assert_usable(&sfnt);
+    }
+
+    #[test]
+    fn tables_larger_than_the_font_are_rejected() {
+        let mut woff = include_bytes!("../../tests/fonts/Cantarell-Regular.woff").to_vec();
+        // Claim the first table decompresses to far more than the whole font
+        woff[44 + 12..44 + 16].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(matches!(decode_font(&woff), Err(WoffError::Malformed(_))));
+
+        let mut woff2 = include_bytes!("../../tests/fonts/OpenSans-Regular.woff2").to_vec();
+        woff2[16..20].copy_from_slice(&1000u32.to_be_bytes());
+        assert!(matches!(decode_font(&woff2), Err(WoffError::Malformed(_))));
+    }
+
+    #[test]
+    fn rejects_fonts_without_tables() {
+        let mut woff = Vec::new();
+        woff.extend_from_slice(b"wOFF");
+        woff.extend_from_slice(&0x0001_0000u32.to_be_bytes());
+        woff.resize(44, 0);
+        assert!(matches!(decode_font(&woff), Err(WoffError::Malformed(_))));
+    }
+
+    #[test]
+    fn directory_search_hints() {
+        let tables = |n: usize| -> Vec<Table> {
+            (0..n)
+                .map(|i| Table {
+                    tag: (i as u32).to_be_bytes(),
+                    data: vec![1, 2, 3],
+                })
+                .collect()
+        };
+        // (numTables, searchRange, entrySelector, rangeShift)
+        for (n, search_range, entry_selector, range_shift) in
+            [(1, 16, 0, 0), (5, 64, 2, 16), (16, 256, 4, 0), (5000, u16::MAX, 12, 14464)]
+        {
+            let sfnt = build_sfnt(0x0001_0000, tables(n)).unwrap();
+            assert_eq!(u16_at(&sfnt, 4) as usize, n);
+            assert_eq!(u16_at(&sfnt, 6), search_range);
+            assert_eq!(u16_at(&sfnt, 8), entry_selector);
+            assert_eq!(u16_at(&sfnt, 10), range_shift);
+        }
+    }
+
+    #[test]
+    fn table_offsets_past_the_end_are_truncated() {
+        let mut woff = Vec::new();
+        woff.extend_from_slice(b"wOFF");
+        woff.extend_from_slice(&0x0001_0000u32.to_be_bytes());
+        woff.extend_from_slice(&0u32.to_be_bytes());
+        woff.extend_from_slice(&1u16.to_be_bytes());
+        woff.resize(44, 0);
+        woff.extend_from_slice(b"head");
+        for v in [u32::MAX, u32::MAX, 4, 0] {
+            woff.extend_from_slice(&v.to_be_bytes());
+        }
+        assert!(matches!(decode_font(&woff), Err(WoffError::Truncated)));
+    }
+}
@@ -0,0 +1,189 @@
+//! writing.rs — Vertical writing modes (CSS Writing Modes 3).
+//! Layout itself only knows lines running left to right and stacking downward. A box whose
+//! writing mode differs from its container's is laid out in the logical coordinates of its own
+//! mode, where that holds, and its boxes are then mapped back: in `vertical-rl` lines run down
+//! the page and stack leftward, in `vertical-lr` they stack rightward. Styles are mapped the
+//! other way first, once, when the box tree is built (`reorient_tree`), so physical margins,
+//! padding, sizes and insets land on the logical sides.
+
+use crate::browser::engine::{Display, EdgeSizes, Node, NodeType, Rect, Style, WritingMode};
+use crate::browser::renderer::{Layer, LayoutBox};
+use std::cell::Cell;
+use std::sync::Arc;
+
+thread_local! {
+    /// The writing mode whose logical coordinates layout is working in
+    static SPACE: Cell<WritingMode> = const { Cell::new(WritingMode::HorizontalTb) };
+    /// Size of the viewport being laid out
+    static VIEWPORT: Cell<(f32, f32)> = const { Cell::new((0.0, 0.0)) };
+}
+
+/// Keeps layout in the coordinates of a writing mode while it lives (see `enter_space`)
+pub struct Space(WritingMode);
+
+impl Drop for Space {
+    fn drop(&mut self) {
+        SPACE.with(|space| space.set(self.0));
+    }
+}
+
+/// Lay out in the logical coordinates of `mode` until the guard is dropped
+pub fn enter_space(mode: WritingMode) -> Space {
+    Space(SPACE.with(|space| space.replace(mode)))
+}
+
+/// The writing mode whose logical coordinates layout is working in
+pub fn current_space() -> WritingMode {
+    SPACE.with(Cell::get)
+}
+
+/// Note the size of the viewport a layout pass starts from
+pub fn set_viewport(width: f32, height: f32) {
+    VIEWPORT.with(|viewport| viewport.set((width, height)));
+}
+
+/// Size of the viewport of the running layout pass
+pub fn viewport() -> (f32, f32) {
+    VIEWPORT.with(Cell::get)
+}
+
+/// Restate the styles of the box tree `node`, laid out in the logical coordinates of `space`,
+/// in the coordinates each box is laid out in: those of the writing mode of the nearest block
+/// container around it. Styles in horizontal content are left alone (and stay shared).
+pub fn reorient_tree(node: &mut Node, space: WritingMode) {
+    if space != WritingMode::HorizontalTb {
+        node.style = Arc::new(reorient_style(&node.style, WritingMode::HorizontalTb, space));
+    }
+    // Inline boxes lay their content out on their container's lines
+    let inner = match node.node_type {
+        NodeType::Text(_) => space,
+        _ if node.style.display == Display::Inline => space,
+        _ => node.style.writing_mode,
+    };
+    for child in &mut node.children {
+        reorient_tree(child, inner);
+    }
+}
+
+/// `style` with its margins, padding, sizes and insets moved from the sides they have in the
+/// logical coordinates of `from` to those they have in `to`
+pub fn reorient_style(style: &Style, from: WritingMode, to: WritingMode) -> Style {
+    let mut style = style.clone();
+    if from == to {
+        return style;
+    }
+    style.margin = reorient_edges(style.margin, from, to);
+    style.padding = reorient_edges(style.padding, from, to);
+    let [top, right, bottom, left] = map_sides([style.top, style.right, style.bottom, style.left], from, to);
+    (style.top, style.right, style.bottom, style.left) = (top, right, bottom, left);
+    if from.is_vertical() != to.is_vertical() {
+        std::mem::swap(&mut style.width, &mut style.height);
+        // `auto` margins only center a box along its container's inline axis
+        style.margin_left_auto = false;
+        style.margin_right_auto = false;
+    }
+    style
+}
+
+/// Move a box tree laid out in the logical coordinates of `from`, within a frame of `size`
+/// there, to the logical coordinates of `to`. Text turns with it.
+pub fn reorient_box(layout: &mut LayoutBox, from: WritingMode, to: WritingMode, size: (f32, f32)) {
+    let physical = if from.is_vertical() { (size.1, size.0) } else { size };
+    let map = |rect: Rect| to_logical(to_physical(rect, from, size), to, physical);
+
+    let rect = map(Rect {
+        x: layout.x,
+        y: layout.y,
+        width: layout.width,
+        height: layout.height,
+    });
+    (layout.x, layout.y, layout.width, layout.height) = (rect.x, rect.y, rect.width, rect.height);
+    if let Some(image) = &mut layout.image {
+        image.dest = map(image.dest);
+    }
+    if let Some(clip) = &mut layout.clip {
+        clip.rect = map(clip.rect);
+        clip.content = map(clip.content);
+    }
+    if let Some(Layer { sticky: Some(sticky), .. }) = &mut layout.layer {
+        sticky.bounds = map(sticky.bounds);
+        let [top, right, bottom, left] = map_sides([sticky.top, sticky.right, sticky.bottom, sticky.left], from, to);
+        (sticky.top, sticky.right, sticky.bottom, sticky.left) = (top, right, bottom, left);
+    }
+    if let Some(text) = &mut layout.text {
+        text.vertical ^= from.is_vertical() != to.is_vertical();
+    }
+    for child in &mut layout.children {
+        reorient_box(child, from, to, size);
+    }
+}
+
+fn reorient_edges(edges: EdgeSizes, from: WritingMode, to: WritingMode) -> EdgeSizes {
+    let [top, right, bottom, left] = map_sides([edges.top, edges.right, edges.bottom, edges.left], from, to);
+    EdgeSizes {
+        top,
+        right,
+        bottom,
+        left,
+    }
+}
+
+/// Values for the top, right, bottom and left sides in the logical coordinates of `from`,
+/// reordered for those of `to`
+fn map_sides<T>(sides: [T; 4], from: WritingMode, to: WritingMode) -> [T; 4] {
+    // In logical coordinates the top is the block start and the left the inline start
+    let [top, right, bottom, left] = match from {
+        WritingMode::HorizontalTb => sides,
+        WritingMode::VerticalRl => {
+            let [block_start, inline_end, block_end, inline_start] = sides;
+            [inline_start, block_start, inline_end, block_end]
+        }
+        WritingMode::VerticalLr => {
+            let [block_start, inline_end, block_end, inline_start] = sides;
+            [inline_start, block_end, inline_end, block_start]
+        }
+    };
+    match to {
+        WritingMode::HorizontalTb => [top, right, bottom, left],
+        WritingMode::VerticalRl => [right, bottom, left, top],
+        WritingMode::VerticalLr => [left, bottom, right, top],
+    }
+}
+
+/// A rectangle in the logical coordinates of `mode`, in a frame of logical `size`, on the page
+fn to_physical(rect: Rect, mode: WritingMode, size: (f32, f32)) -> Rect {
+    match mode {
+        WritingMode::HorizontalTb => rect,
+        WritingMode::VerticalRl => Rect {
+            x: size.1 - rect.y - rect.height,
+            y: rect.x,
+            width: rect.height,
+            height: rect.width,
+        },
+        WritingMode::VerticalLr => Rect {
+            x: rect.y,
+            y: rect.x,
+            width: rect.height,
+            height: rect.width,
+        },
+    }
+}
+
+/// A rectangle on the page, in a frame of physical `size`, in the logical coordinates of `mode`
+fn to_logical(rect: Rect, mode: WritingMode, size: (f32, f32)) -> Rect {
+    match mode {
+        WritingMode::HorizontalTb => rect,
+        WritingMode::VerticalRl => Rect {
+            x: rect.y,
+            y: size.0 - rect.x - rect.width,
+            width: rect.height,
+            height: rect.width,
+        },
+        WritingMode::VerticalLr => Rect {
+            x: rect.y,
+            y: rect.x,
+            width: rect.height,
+            height: rect.width,
+        },
+    }
+}
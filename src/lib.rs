@@ -0,0 +1,3 @@
+pub mod browser;
+pub mod net;
+pub mod utils;
@@ -0,0 +1,67 @@
+//! RustyBrowser — render a local HTML file.
+//! `rusty-browser page.html` prints its layout tree; `rusty-browser page.html out.png` or
+//! `out.pdf` writes the page as an image or as printed pages. `<style>` elements are applied.
+
+use rusty_browser::browser::{
+    build_box_tree, build_layout_tree, compute_styles, dump_layout, paginate, parse_html, parse_stylesheet,
+    preload_images, write_pdf, Node, PageSetup, Renderer, Stylesheet,
+};
+use std::rc::Rc;
+
+const VIEWPORT: (i32, i32) = (800, 600);
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some(input) = args.first() else {
+        eprintln!("usage: rusty-browser <page.html> [out.png | out.pdf]");
+        std::process::exit(2);
+    };
+    let html = match std::fs::read_to_string(input) {
+        Ok(html) => html,
+        Err(err) => {
+            eprintln!("{}: {}", input, err);
+            std::process::exit(1);
+        }
+    };
+
+    let dom = parse_html(&html);
+    preload_images(&dom);
+    let stylesheets: Vec<Stylesheet> = style_elements(&dom).iter().map(|css| parse_stylesheet(css)).collect();
+    let styled = compute_styles(&dom, &stylesheets);
+    let Some(boxes) = build_box_tree(&styled) else {
+        return;
+    };
+
+    match args.get(1) {
+        Some(out) if out.ends_with(".pdf") => {
+            let pages = paginate(&boxes, PageSetup::from_stylesheets(&stylesheets));
+            if let Err(err) = write_pdf(&pages, out) {
+                eprintln!("{}: {}", out, err);
+                std::process::exit(1);
+            }
+        }
+        Some(out) => {
+            let layout = build_layout_tree(&boxes, VIEWPORT.0 as f32, VIEWPORT.1 as f32);
+            let mut renderer = Renderer::new(VIEWPORT.0, VIEWPORT.1);
+            renderer.clear(rusty_browser::browser::Color(255, 255, 255, 255));
+            renderer.render_box(&layout);
+            renderer.save_png(out);
+        }
+        None => {
+            let layout = build_layout_tree(&boxes, VIEWPORT.0 as f32, VIEWPORT.1 as f32);
+            print!("{}", dump_layout(&layout));
+        }
+    }
+}
+
+/// The text of every `<style>` element, in document order
+fn style_elements(node: &Rc<Node>) -> Vec<String> {
+    let mut sheets = Vec::new();
+    if node.tag_name().is_some_and(|tag| &*tag == "style") {
+        sheets.push(node.children().iter().filter_map(|child| child.text().map(|t| t.to_string())).collect());
+    }
+    for child in node.children().iter() {
+        sheets.extend(style_elements(child));
+    }
+    sheets
+}
@@ -1,8 +1,7 @@
 //! fetch.rs — Secure, resource-type aware fetching for HTML, CSS, images, etc.
 
-use crate::net::{http::fetch_url, requests::RequestType};
+use crate::net::{http::fetch_url, request::RequestType};
 use crate::utils::logger::log;
-use std::collections::HashMap;
 use std::time::Instant;
 
 /// Types of web resources
@@ -40,7 +39,7 @@ pub fn fetch_resource(url: &str, req_type: RequestType) -> Result<FetchResult, F
     log(&format!("Fetching {:?} from {}", req_type, url));
     let start = Instant::now();
 
-    let response = fetch_url(url, req_type).map_err(FetchError::Network)?;
+    let response = fetch_url(url, req_type, false).map_err(|e| FetchError::Network(format!("{:?}", e)))?;
 
     let content_type = response
         .headers
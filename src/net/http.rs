@@ -1,16 +1,15 @@
 use std::{
     collections::{HashMap, VecDeque},
-    io::Read,
     net::{IpAddr, ToSocketAddrs},
     sync::Mutex,
     time::{Duration, Instant},
 };
 
+use crate::net::request::RequestType;
 use lazy_static::lazy_static;
 use log::{info, warn};
-use sha2::{Digest, Sha256};
 use url::Url;
-use ureq::{Agent, AgentBuilder};
+use ureq::Agent;
 
 /// Error types
 #[derive(Debug)]
@@ -22,7 +21,6 @@ pub enum FetchError {
     NetworkError(String),
     ReadError(String),
     DangerousContentType(String),
-    CertificateMismatch,
     RateLimitExceeded,
 }
 
@@ -34,14 +32,11 @@ pub struct HttpResponse {
     pub body: Vec<u8>,
 }
 
-/// Optional certificate fingerprint to validate TLS cert
-const EXPECTED_CERT_SHA256: Option<&str> = None; // Example: Some("SHA256_HASH_BASE64")
-
 /// Only allow specific headers to pass through
 const ALLOWED_HEADERS: &[&str] = &["content-type", "content-length", "server"];
 
-/// In-memory rate limiter
 lazy_static! {
+    /// In-memory rate limiter
     static ref RATE_LIMITER: Mutex<VecDeque<Instant>> = Mutex::new(VecDeque::new());
 }
 
@@ -59,28 +54,28 @@ pub fn fetch_url(url: &str, req_type: RequestType, enforce_https: bool) -> Resul
         return Err(FetchError::DnsBlocked(parsed_url.to_string()));
     }
 
-    let agent = AgentBuilder::new()
-        .timeout(Duration::from_secs(10))
-        .redirects(0)
+    let config = Agent::config_builder()
+        .timeout_global(Some(Duration::from_secs(10)))
+        .max_redirects(0)
         .build();
+    let agent = Agent::new_with_config(config);
 
-    let mut request = agent.get(parsed_url.as_str());
-    request.set("User-Agent", "secure-fetch/2.0");
-    request.set("Accept", req_type.accept_header());
-
-    let response = request.call().map_err(|e| FetchError::NetworkError(e.to_string()))?;
+    let mut response = agent
+        .get(parsed_url.as_str())
+        .header("User-Agent", "secure-fetch/2.0")
+        .header("Accept", req_type.accept_header())
+        .call()
+        .map_err(|e| FetchError::NetworkError(e.to_string()))?;
 
     let post_ips = resolve_ips(&parsed_url)?;
     if initial_ips != post_ips {
         return Err(FetchError::DnsRebindingDetected);
     }
 
-    if let Some(cert_fingerprint) = EXPECTED_CERT_SHA256 {
-        validate_cert(&response, cert_fingerprint)?;
-    }
-
     let content_type = response
-        .header("Content-Type")
+        .headers()
+        .get("Content-Type")
+        .and_then(|v| v.to_str().ok())
         .unwrap_or("unknown")
         .to_lowercase();
     if is_dangerous_mime(&content_type) {
@@ -88,31 +83,23 @@ pub fn fetch_url(url: &str, req_type: RequestType, enforce_https: bool) -> Resul
     }
 
     let headers = response
-        .headers_names()
+        .headers()
         .iter()
-        .filter_map(|k| {
-            if ALLOWED_HEADERS.contains(&k.to_ascii_lowercase().as_str()) {
-                response.header(k).map(|v| (k.to_string(), v.to_string()))
-            } else {
-                None
-            }
-        })
+        .filter(|(k, _)| ALLOWED_HEADERS.contains(&k.as_str()))
+        .filter_map(|(k, v)| Some((k.to_string(), v.to_str().ok()?.to_string())))
         .collect::<HashMap<_, _>>();
 
-    let mut body = Vec::new();
-    response
-        .into_reader()
-        .take(1_048_576)
-        .read_to_end(&mut body)
+    let status = response.status().as_u16();
+    let body = response
+        .body_mut()
+        .with_config()
+        .limit(1_048_576)
+        .read_to_vec()
         .map_err(|e| FetchError::ReadError(e.to_string()))?;
 
-    info!("Fetched: {} -> Status: {}", url, response.status());
+    info!("Fetched: {} -> Status: {}", url, status);
 
-    Ok(HttpResponse {
-        status: response.status(),
-        headers,
-        body,
-    })
+    Ok(HttpResponse { status, headers, body })
 }
 
 /// Enforce a basic per-process rate limit
@@ -121,7 +108,7 @@ fn enforce_rate_limit() -> Result<(), FetchError> {
     let now = Instant::now();
 
     // Remove old entries
-    while limiter.front().map_or(false, |t| now.duration_since(*t) > RATE_LIMIT_WINDOW) {
+    while limiter.front().is_some_and(|t| now.duration_since(*t) > RATE_LIMIT_WINDOW) {
         limiter.pop_front();
     }
 
@@ -160,10 +147,16 @@ fn resolve_ips(url: &Url) -> Result<Vec<IpAddr>, FetchError> {
 
 /// Block dangerous IPs
 fn is_blocked_ip(ip: &IpAddr) -> bool {
-    ip.is_loopback()
-        || ip.is_private()
-        || ip.is_link_local()
-        || ip.is_multicast()
+    match ip {
+        IpAddr::V4(v4) => v4.is_loopback() || v4.is_private() || v4.is_link_local() || v4.is_multicast(),
+        // Unique local (fc00::/7) and link-local (fe80::/10) addresses
+        IpAddr::V6(v6) => {
+            v6.is_loopback()
+                || v6.is_multicast()
+                || (v6.segments()[0] & 0xfe00) == 0xfc00
+                || (v6.segments()[0] & 0xffc0) == 0xfe80
+        }
+    }
 }
 
 /// Detect dangerous MIME types
@@ -173,19 +166,3 @@ fn is_dangerous_mime(mime: &str) -> bool {
         || mime.contains("application/x-sh")
         || mime.contains("text/x-script")
 }
-
-/// Validate server TLS certificate fingerprint
-fn validate_cert(response: &ureq::Response, expected_fingerprint: &str) -> Result<(), FetchError> {
-    use base64::{engine::general_purpose, Engine as _};
-    if let Some(cert_der) = response.synthetic_certificate() {
-        let mut hasher = Sha256::new();
-        hasher.update(cert_der);
-        let result = hasher.finalize();
-        let actual = general_purpose::STANDARD.encode(result);
-
-        if actual != expected_fingerprint {
-            return Err(FetchError::CertificateMismatch);
-        }
-    }
-    Ok(())
-}
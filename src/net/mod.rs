@@ -3,9 +3,9 @@
 
 pub mod http;
 pub mod fetch;
-pub mod requests;
+pub mod request;
 
 // Re-export types for external convenience
 pub use http::{fetch_url, HttpResponse, FetchError};
 pub use fetch::{fetch_resource, fetch_html, FetchResult, ResourceType};
-pub use requests::{RequestType};
+pub use request::RequestType;
@@ -0,0 +1,6 @@
+//! logger.rs — Logging helpers on top of the `log` facade.
+
+/// Log an informational message
+pub fn log(message: &str) {
+    log::info!("{}", message);
+}
@@ -0,0 +1,103 @@
+//! Box tree generation
+
+mod common;
+
+use common::layout;
+use rusty_browser::browser::engine::NodeType;
+use rusty_browser::browser::{build_box_tree, compute_styles, hit_test, parse_html, parse_stylesheet, LayoutNode};
+use std::sync::Arc;
+
+fn box_tree(html: &str, css: &str) -> LayoutNode {
+    let document = parse_html(html);
+    let styled = compute_styles(&document, &[parse_stylesheet(css)]);
+    build_box_tree(&styled).expect("document generates boxes")
+}
+
+/// The first box generated by the element with `id`
+fn by_id<'a>(node: &'a LayoutNode, id: &str) -> Option<&'a LayoutNode> {
+    let matches = match &node.node_type {
+        NodeType::Element(el) => el.attributes.iter().any(|(k, v)| k == "id" && v == id),
+        _ => false,
+    };
+    if matches {
+        return Some(node);
+    }
+    node.children.iter().find_map(|child| by_id(child, id))
+}
+
+fn is_anonymous(node: &LayoutNode) -> bool {
+    matches!(node.node_type, NodeType::Anonymous)
+}
+
+#[test]
+fn boxes_share_the_computed_style() {
+    let document = parse_html(r#"<div><p>a</p><p>b</p></div>"#);
+    let styled = compute_styles(&document, &[parse_stylesheet("p { color: red }")]);
+    let boxes = build_box_tree(&styled).unwrap();
+    assert!(Arc::ptr_eq(&boxes.style, &styled.style));
+
+    fn all_shared(node: &LayoutNode, styled: &rusty_browser::browser::StyledNode) -> bool {
+        Arc::ptr_eq(&node.style, &styled.style)
+            && node.children.iter().zip(&styled.children).all(|(n, s)| all_shared(n, s))
+    }
+    assert!(all_shared(&boxes, &styled));
+}
+
+#[test]
+fn generated_content_of_contents_elements_hits_the_element() {
+    let page = layout(
+        r#"<div id="root"><span id="c">inside</span></div>"#,
+        r#"#c { display: contents } #c::before { content: "Generated " }"#,
+        800.0,
+        600.0,
+    );
+    let texts = page.text_of("root");
+    let generated = texts
+        .iter()
+        .find(|t| t.text.as_ref().unwrap().content.starts_with("Generated"))
+        .expect("generated text is laid out");
+    let (x, y) = (generated.x + 2.0, generated.y + generated.height / 2.0);
+    let hit = hit_test(&page.layout, x, y, (0.0, 0.0), (800.0, 600.0)).unwrap();
+    assert_eq!(hit.node.get_attr("id").as_deref(), Some("c"));
+    assert_eq!(hit.text_offset, None);
+}
+
+#[test]
+fn inline_content_beside_blocks_is_wrapped_in_anonymous_blocks() {
+    let tree = box_tree(r#"<div id="d">before<p>block</p>after <span>x</span></div>"#, "");
+    let kinds: Vec<bool> = by_id(&tree, "d").unwrap().children.iter().map(is_anonymous).collect();
+    assert_eq!(kinds, [true, false, true]);
+}
+
+#[test]
+fn blocks_inside_inlines_split_the_inline() {
+    let tree = box_tree(r#"<div id="d"><span id="s">a<p>b</p>c</span></div>"#, "");
+    let div = by_id(&tree, "d").unwrap();
+    // Each half of the span sits in its own anonymous block, with the paragraph between
+    assert_eq!(div.children.len(), 3);
+    assert!(is_anonymous(&div.children[0]) && is_anonymous(&div.children[2]));
+    assert!(by_id(&div.children[0], "s").is_some() && by_id(&div.children[2], "s").is_some());
+    assert!(!is_anonymous(&div.children[1]));
+}
+
+#[test]
+fn display_contents_is_replaced_by_its_children() {
+    let tree = box_tree(r#"<div id="d"><section id="c"><p id="p">a</p></section></div>"#, "#c { display: contents }");
+    assert!(by_id(&tree, "c").is_none());
+    let p = by_id(&tree, "p").unwrap();
+    assert!(by_id(&tree, "d").unwrap().children.iter().any(|child| std::ptr::eq(child, p)));
+}
+
+#[test]
+fn inline_blocks_sit_on_the_line() {
+    let page = layout(
+        r#"<div id="root"><span id="a">text</span><span id="b">box</span></div>"#,
+        "#b { display: inline-block; width: 50px; height: 30px }",
+        400.0,
+        300.0,
+    );
+    let (root, b) = (page.by_id("root"), page.by_id("b"));
+    assert_eq!((b.width, b.height), (50.0, 30.0));
+    assert!(b.x > root.x, "the inline-block follows the text on the same line");
+    assert!(root.height >= 30.0);
+}
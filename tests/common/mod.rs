@@ -0,0 +1,55 @@
+//! Helpers shared by the layout tests: run a page through the whole pipeline and find the
+//! boxes of elements by id.
+
+#![allow(dead_code)]
+
+use rusty_browser::browser::{
+    build_box_tree, build_layout_tree, compute_styles, parse_html, parse_stylesheet, LayoutBox, Node,
+};
+use std::rc::Rc;
+
+/// A page laid out in a viewport. The document is kept alive so boxes can reach their DOM nodes.
+pub struct Page {
+    pub document: Rc<Node>,
+    pub layout: LayoutBox,
+}
+
+/// Parse, style and lay out `html` with `css` in a `width`×`height` viewport
+pub fn layout(html: &str, css: &str, width: f32, height: f32) -> Page {
+    let document = parse_html(html);
+    let styled = compute_styles(&document, &[parse_stylesheet(css)]);
+    let boxes = build_box_tree(&styled).expect("document generates boxes");
+    let layout = build_layout_tree(&boxes, width, height);
+    Page { document, layout }
+}
+
+impl Page {
+    /// The first box generated by the element with `id`
+    pub fn by_id(&self, id: &str) -> &LayoutBox {
+        find(&self.layout, id).unwrap_or_else(|| panic!("no box for #{}", id))
+    }
+
+    /// Every text fragment of the element with `id`, in paint order
+    pub fn text_of(&self, id: &str) -> Vec<&LayoutBox> {
+        let mut out = Vec::new();
+        texts(self.by_id(id), &mut out);
+        out
+    }
+}
+
+fn find<'a>(layout: &'a LayoutBox, id: &str) -> Option<&'a LayoutBox> {
+    let dom = layout.dom.as_ref().and_then(|dom| dom.upgrade());
+    if dom.is_some_and(|node| node.get_attr("id").as_deref() == Some(id)) {
+        return Some(layout);
+    }
+    layout.children.iter().find_map(|child| find(child, id))
+}
+
+fn texts<'a>(layout: &'a LayoutBox, out: &mut Vec<&'a LayoutBox>) {
+    if layout.text.is_some() {
+        out.push(layout);
+    }
+    for child in &layout.children {
+        texts(child, out);
+    }
+}
@@ -0,0 +1,108 @@
+//! Layout dumps of small pages, compared as a whole
+
+mod common;
+
+use common::layout;
+use rusty_browser::browser::dump_layout;
+
+const BASE: &str = "div { margin: 0; padding: 0 } p { margin: 0; padding: 0 }";
+
+/// The dump of `html`, with the rectangles and baselines of text runs left out: they depend on
+/// the installed fonts
+fn dump(html: &str) -> String {
+    let dump = dump_layout(&layout(html, BASE, 400.0, 300.0).layout);
+    dump.lines().map(|line| without_text_metrics(line) + "\n").collect()
+}
+
+fn without_text_metrics(line: &str) -> String {
+    if !line.trim_start().starts_with("text ") {
+        return line.to_string();
+    }
+    let (head, rest) = line.split_once(" at (").unwrap();
+    let (_, baseline) = rest.split_once(" baseline ").unwrap();
+    let tail = baseline.split_once(' ').map_or("", |(_, tail)| tail);
+    format!("{} {}", head, tail)
+}
+
+#[test]
+fn blocks() {
+    assert_eq!(
+        dump(
+            r#"<div>
+                <div style="height: 20px; margin-bottom: 10px"></div>
+                <div style="padding: 5px; background-color: #ff0000"><div style="height: 10px"></div></div>
+            </div>"#
+        ),
+        "\
+box /div at (0,0) size 400x50
+  box /div/div[1] at (0,0) size 400x20
+  box /div/div[2] at (0,30) size 400x20 background #ff0000
+    box /div/div[2]/div at (5,35) size 390x10
+"
+    );
+}
+
+#[test]
+fn inline_content() {
+    assert_eq!(
+        dump(r#"<div><p>Hello <b>bold</b></p></div>"#),
+        "\
+box /div at (0,0) size 400x19.2
+  box /div/p at (0,0) size 400x19.2
+    line 1 at (0,0) size 400x19.2
+      text /div/p/#text \"Hello \"
+      text /div/p/b/#text \"bold\"
+"
+    );
+}
+
+#[test]
+fn lines_of_atomic_inlines_only() {
+    // Without a line height the strut adds nothing, so the line is as tall as the inline-block
+    assert_eq!(
+        dump(
+            r#"<div style="line-height: 0"><p style="height: 20px"></p><span style="display: inline-block; width: 30px; height: 10px"></span></div>"#
+        ),
+        "\
+box /div at (0,0) size 400x30
+  box /div/p at (0,0) size 400x20
+  anonymous at (0,20) size 400x10
+    line 1 at (0,20) size 400x10
+      inline /div/span at (0,20) size 30x10
+"
+    );
+}
+
+#[test]
+fn floats() {
+    assert_eq!(
+        dump(r#"<div style="width: 200px"><div style="float: left; width: 50px; height: 30px"></div><p>Wraps</p></div>"#),
+        "\
+box /div at (0,0) size 200x30
+  anonymous at (0,0) size 200x0
+    box /div/div at (0,0) size 50x30
+  box /div/p at (0,0) size 200x19.2
+    line 1 at (50,0) size 150x19.2
+      text /div/p/#text \"Wraps\"
+"
+    );
+}
+
+#[test]
+fn tables() {
+    assert_eq!(
+        dump(
+            r#"<div><table style="border-spacing: 2px"><tr>
+                <td style="width: 40px; height: 20px"></td><td style="width: 60px; height: 20px"></td>
+            </tr></table></div>"#
+        ),
+        "\
+box /div at (0,0) size 400x26
+  box /div/table at (0,0) size 110x26
+    anonymous at (2,2) size 106x22
+      box /div/table/tr at (2,2) size 106x22
+        box /div/table/tr/td[1] at (2,2) size 42x22
+        box /div/table/tr/td[2] at (46,2) size 62x22
+"
+    );
+}
@@ -0,0 +1,121 @@
+//! Flex layout: flexible lengths (CSS Flexbox §9.7), lines, alignment and order
+
+mod common;
+
+use common::layout;
+
+/// Blocks without the default margins and padding
+const BASE: &str = "div { margin: 0; padding: 0 } #root { display: flex; width: 300px }";
+
+fn widths(page: &common::Page, ids: &[&str]) -> Vec<f32> {
+    ids.iter().map(|id| page.by_id(id).width).collect()
+}
+
+#[test]
+fn free_space_is_shared_by_grow_factor() {
+    let page = layout(
+        r#"<div id="root">
+            <div id="a" style="flex: 1 1 0px"></div>
+            <div id="b" style="flex: 2 1 0px"></div>
+        </div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    assert_eq!(widths(&page, &["a", "b"]), [100.0, 200.0]);
+}
+
+#[test]
+fn overflow_is_taken_by_scaled_shrink_factor() {
+    let page = layout(
+        r#"<div id="root">
+            <div id="a" style="flex: 0 1 200px"></div>
+            <div id="b" style="flex: 0 1 200px"></div>
+        </div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    assert_eq!(widths(&page, &["a", "b"]), [150.0, 150.0]);
+}
+
+#[test]
+fn items_stop_shrinking_at_their_min_content_width() {
+    let page = layout(
+        r#"<div id="root">
+            <div id="a" style="flex: 0 1 200px"><div style="width: 180px"></div></div>
+            <div id="b" style="flex: 0 1 200px"></div>
+            <div id="c" style="flex: 0 1 200px"></div>
+        </div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    assert_eq!(widths(&page, &["a", "b", "c"]), [180.0, 60.0, 60.0]);
+}
+
+#[test]
+fn infinite_flex_factors_are_ignored() {
+    let page = layout(
+        r#"<div id="root">
+            <div id="a" style="flex-basis: 100px; flex-grow: 1e39"></div>
+            <div id="b" style="flex-basis: 100px"></div>
+        </div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    assert_eq!(widths(&page, &["a", "b"]), [100.0, 100.0]);
+}
+
+#[test]
+fn items_wrap_onto_lines_separated_by_gaps() {
+    let page = layout(
+        r#"<div id="root" style="flex-wrap: wrap; gap: 10px 20px">
+            <div id="a" style="width: 100px; height: 30px"></div>
+            <div id="b" style="width: 100px; height: 40px"></div>
+            <div id="c" style="width: 100px; height: 30px"></div>
+        </div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    let (root, a, b, c) = (page.by_id("root"), page.by_id("a"), page.by_id("b"), page.by_id("c"));
+    assert_eq!((b.x - a.x, b.y), (120.0, a.y));
+    // The first line is as tall as its tallest item
+    assert_eq!((c.x, c.y - root.y), (root.x, 50.0));
+    assert_eq!(root.height, 80.0);
+}
+
+#[test]
+fn justify_content_and_align_items_place_items() {
+    let page = layout(
+        r#"<div id="root" style="height: 100px; justify-content: space-between; align-items: center">
+            <div id="a" style="width: 50px; height: 20px"></div>
+            <div id="b" style="width: 50px; height: 40px"></div>
+        </div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    let (root, a, b) = (page.by_id("root"), page.by_id("a"), page.by_id("b"));
+    assert_eq!((a.x - root.x, a.y - root.y), (0.0, 40.0));
+    assert_eq!((b.x - root.x, b.y - root.y), (250.0, 30.0));
+}
+
+#[test]
+fn order_and_column_direction() {
+    let page = layout(
+        r#"<div id="root" style="flex-direction: column">
+            <div id="a" style="height: 10px; order: 2"></div>
+            <div id="b" style="height: 20px"></div>
+        </div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    let (root, a, b) = (page.by_id("root"), page.by_id("a"), page.by_id("b"));
+    assert_eq!((b.y - root.y, a.y - root.y), (0.0, 20.0));
+    // Items stretch across a column container
+    assert_eq!(a.width, 300.0);
+}
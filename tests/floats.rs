@@ -0,0 +1,94 @@
+//! Floats, clearance and line boxes shortened beside floats (CSS 2.1 §9.5)
+
+mod common;
+
+use common::layout;
+
+const BASE: &str = "body, div, p { margin: 0; padding: 0 } p { font-size: 10px; line-height: 10px }";
+
+#[test]
+fn floats_go_to_the_sides_of_their_container() {
+    let page = layout(
+        r#"<div id="root">
+            <div id="l" style="float: left; width: 50px; height: 20px"></div>
+            <div id="r" style="float: right; width: 60px; height: 20px"></div>
+        </div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    let root = page.by_id("root");
+    let (l, r) = (page.by_id("l"), page.by_id("r"));
+    assert_eq!((l.x, l.y), (root.x, root.y));
+    assert_eq!((r.x + r.width, r.y), (root.x + root.width, root.y));
+}
+
+#[test]
+fn left_floats_stack_beside_each_other_and_wrap_below() {
+    let page = layout(
+        r#"<div id="root">
+            <div id="a" style="float: left; width: 150px; height: 20px"></div>
+            <div id="b" style="float: left; width: 150px; height: 30px"></div>
+            <div id="c" style="float: left; width: 150px; height: 20px"></div>
+        </div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    let (a, b, c) = (page.by_id("a"), page.by_id("b"), page.by_id("c"));
+    assert_eq!((b.x - a.x, b.y), (150.0, a.y));
+    // `c` only fits at the left edge, and may not start beside `b` (CSS 2.1 §9.5.1 rule 2)
+    assert_eq!((c.x, c.y), (a.x, a.y + 30.0));
+}
+
+#[test]
+fn lines_are_shortened_beside_a_float() {
+    let page = layout(
+        r#"<div id="root">
+            <div id="f" style="float: left; width: 100px; height: 15px"></div>
+            <p id="p">one two three four five six seven eight nine ten eleven twelve thirteen fourteen fifteen sixteen seventeen eighteen nineteen twenty</p>
+        </div>"#,
+        BASE,
+        300.0,
+        300.0,
+    );
+    let f = page.by_id("f");
+    let texts = page.text_of("p");
+    let first_line = texts.iter().filter(|t| t.y < f.y + 10.0);
+    assert!(first_line.clone().count() > 0);
+    assert!(first_line.clone().all(|t| t.x >= f.x + f.width));
+    // Lines below the float start at the container's left edge again
+    assert!(texts.iter().any(|t| t.y >= f.y + f.height && (t.x - f.x).abs() < 0.01));
+}
+
+#[test]
+fn clear_moves_a_block_below_floats() {
+    let page = layout(
+        r#"<div id="root">
+            <div id="f" style="float: left; width: 50px; height: 40px"></div>
+            <div id="c" style="clear: left; height: 10px"></div>
+            <div id="n" style="clear: right; height: 10px"></div>
+        </div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    let f = page.by_id("f");
+    assert_eq!(page.by_id("c").y, f.y + 40.0);
+    assert_eq!(page.by_id("n").y, f.y + 50.0);
+}
+
+#[test]
+fn flow_roots_contain_their_floats() {
+    let page = layout(
+        r#"<div id="root">
+            <div id="plain"><div style="float: left; width: 10px; height: 30px"></div></div>
+            <div id="root2" style="display: flow-root"><div style="float: left; width: 10px; height: 30px"></div></div>
+        </div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    assert_eq!(page.by_id("plain").height, 0.0);
+    assert_eq!(page.by_id("root2").height, 30.0);
+}
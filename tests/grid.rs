@@ -0,0 +1,133 @@
+//! Grid layout: track sizing (CSS Grid §11) and item placement (CSS Grid §8)
+
+mod common;
+
+use common::layout;
+
+/// One-pixel implicit rows, so an item's top is its row index
+const BASE: &str = "div { margin: 0; padding: 0 } #root { display: grid; grid-auto-rows: 1px }";
+
+#[test]
+fn huge_line_numbers_are_clamped() {
+    let page = layout(
+        r#"<div id="root"><div id="a" style="grid-row: 99999"></div></div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    assert_eq!(page.by_id("a").y - page.by_id("root").y, 9999.0);
+    assert_eq!(page.by_id("root").height, 10000.0);
+}
+
+#[test]
+fn huge_negative_line_numbers_start_the_grid() {
+    let page = layout(
+        r#"<div id="root"><div id="a" style="grid-row: -99999"></div></div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    assert_eq!(page.by_id("a").y, page.by_id("root").y);
+}
+
+#[test]
+fn huge_spans_are_clamped() {
+    let page = layout(
+        r#"<div id="root"><div id="a" style="grid-row: 5 / span 99999"></div></div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    assert_eq!(page.by_id("a").height, 9996.0);
+}
+
+#[test]
+fn huge_repeat_counts_are_clamped() {
+    let page = layout(
+        r#"<div id="root" style="grid-template-columns: repeat(99999, 1px) 5px"><div id="a" style="grid-column: -2"></div></div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    // The trailing track is past the limit, so the last track is one of the repeated ones
+    assert_eq!(page.by_id("a").x - page.by_id("root").x, 9999.0);
+    assert_eq!(page.by_id("a").width, 1.0);
+}
+
+#[test]
+fn nested_repeats_are_invalid() {
+    let page = layout(
+        r#"<div id="root" style="grid-template-columns: 20px; grid-template-columns: repeat(2, repeat(2, 10px))">
+            <div id="a"></div>
+        </div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    assert_eq!(page.by_id("a").width, 20.0);
+}
+
+#[test]
+fn fr_tracks_share_the_space_left_by_fixed_tracks_and_gaps() {
+    let page = layout(
+        r#"<div id="root" style="width: 310px; grid-template-columns: 100px 1fr 2fr; column-gap: 5px">
+            <div id="a"></div><div id="b"></div><div id="c"></div>
+        </div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    let root = page.by_id("root").x;
+    let expected = [
+        (0.0, 100.0),
+        (105.0, 200.0 / 3.0),
+        (105.0 + 200.0 / 3.0 + 5.0, 400.0 / 3.0),
+    ];
+    for (id, (x, width)) in ["a", "b", "c"].into_iter().zip(expected) {
+        let item = page.by_id(id);
+        assert!(
+            (item.x - root - x).abs() < 0.01 && (item.width - width).abs() < 0.01,
+            "{id}: {} {}",
+            item.x - root,
+            item.width
+        );
+    }
+}
+
+#[test]
+fn auto_placement_fills_rows_and_skips_occupied_cells() {
+    let page = layout(
+        r#"<div id="root" style="grid-template-columns: 10px 10px">
+            <div id="fixed" style="grid-column: 2; grid-row: 1"></div>
+            <div id="a"></div><div id="b"></div><div id="c"></div>
+        </div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    let root = page.by_id("root");
+    let cell = |id: &str| (page.by_id(id).x - root.x, page.by_id(id).y - root.y);
+    assert_eq!([cell("a"), cell("b"), cell("c")], [(0.0, 0.0), (0.0, 1.0), (10.0, 1.0)]);
+}
+
+#[test]
+fn items_are_placed_in_named_areas() {
+    let page = layout(
+        r#"<div id="root"><div id="main"></div><div id="head"></div></div>"#,
+        &format!(
+            "{BASE} #root {{ grid-template-columns: 20px 30px; grid-template-rows: 5px 7px; grid-template-areas: \"head head\" \"side main\" }} #main {{ grid-area: main }} #head {{ grid-area: head }}"
+        ),
+        400.0,
+        300.0,
+    );
+    let root = page.by_id("root");
+    let (head, main) = (page.by_id("head"), page.by_id("main"));
+    assert_eq!(
+        (head.x - root.x, head.y - root.y, head.width, head.height),
+        (0.0, 0.0, 50.0, 5.0)
+    );
+    assert_eq!(
+        (main.x - root.x, main.y - root.y, main.width, main.height),
+        (20.0, 5.0, 30.0, 7.0)
+    );
+}
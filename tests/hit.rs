@@ -0,0 +1,117 @@
+//! Hit testing: stacking order, clipping, `pointer-events` and caret offsets in text
+
+mod common;
+
+use common::{layout, Page};
+use rusty_browser::browser::{hit_test, HitTestResult};
+
+const BASE: &str = "div, p { margin: 0; padding: 0 } .cover { position: absolute; top: 0; left: 0; width: 50px; height: 50px }";
+
+fn hit(page: &Page, x: f32, y: f32) -> HitTestResult {
+    hit_test(&page.layout, x, y, (0.0, 0.0), (800.0, 600.0)).expect("something is under the point")
+}
+
+fn hit_id(page: &Page, x: f32, y: f32) -> Option<String> {
+    hit(page, x, y).node.get_attr("id")
+}
+
+#[test]
+fn layers_above_flow_content_are_hit_first() {
+    let page = layout(
+        r#"<div id="root">
+            <div id="flow" style="height: 100px"></div>
+            <div id="above" class="cover" style="z-index: 1"></div>
+            <div id="below" class="cover" style="z-index: -1; left: 60px"></div>
+        </div>"#,
+        BASE,
+        800.0,
+        600.0,
+    );
+    assert_eq!(hit_id(&page, 10.0, 10.0).as_deref(), Some("above"));
+    // A negative z-index paints under in-flow blocks, so they take the hit
+    assert_eq!(hit_id(&page, 70.0, 10.0).as_deref(), Some("flow"));
+    assert_eq!(hit_id(&page, 70.0, 80.0).as_deref(), Some("flow"));
+}
+
+#[test]
+fn clipped_content_is_not_hit_outside_the_clip() {
+    let page = layout(
+        r#"<div id="root" style="height: 300px">
+            <div id="clip" style="overflow: hidden; width: 50px; height: 50px">
+                <div id="big" style="width: 200px; height: 200px"></div>
+            </div>
+        </div>"#,
+        BASE,
+        800.0,
+        600.0,
+    );
+    assert_eq!(hit_id(&page, 10.0, 10.0).as_deref(), Some("big"));
+    assert_eq!(hit_id(&page, 100.0, 10.0).as_deref(), Some("root"));
+    assert_eq!(hit_id(&page, 10.0, 100.0).as_deref(), Some("root"));
+}
+
+#[test]
+fn pointer_events_none_passes_through() {
+    let page = layout(
+        r#"<div id="root">
+            <div id="below" style="height: 100px"></div>
+            <div id="above" class="cover" style="z-index: 1; pointer-events: none"></div>
+        </div>"#,
+        BASE,
+        800.0,
+        600.0,
+    );
+    assert_eq!(hit_id(&page, 10.0, 10.0).as_deref(), Some("below"));
+}
+
+#[test]
+fn text_offsets_fall_on_the_nearest_character_boundary() {
+    let page = layout(r#"<div><p id="p">abcd</p></div>"#, BASE, 800.0, 600.0);
+    let text = page.text_of("p")[0];
+    let y = text.y + text.height / 2.0;
+    let first = text.text.as_ref().unwrap().shape().glyphs[0].x_advance;
+
+    let offset = |x: f32| hit(&page, x, y).text_offset;
+    assert_eq!(offset(text.x + 0.1), Some(0));
+    assert_eq!(offset(text.x + first * 0.4), Some(0));
+    assert_eq!(offset(text.x + first * 0.6), Some(1));
+    assert_eq!(offset(text.x + text.width - 0.1), Some(4));
+}
+
+#[test]
+fn scrolled_content_is_hit_where_it_is_painted() {
+    let mut page = layout(
+        r#"<div id="root">
+            <div id="spacer" style="height: 100px"></div>
+            <div id="scroller" style="overflow: auto; height: 100px">
+                <div id="sticky" style="position: sticky; top: 0; height: 20px"></div>
+                <div id="tall" style="height: 500px"></div>
+            </div>
+        </div>"#,
+        BASE,
+        800.0,
+        600.0,
+    );
+    assert!(page.layout.scroll_at(5.0, 150.0, 0.0, 50.0));
+    assert_eq!(hit_id(&page, 5.0, 105.0).as_deref(), Some("sticky"));
+    assert_eq!(hit_id(&page, 5.0, 125.0).as_deref(), Some("tall"));
+    // Scrolling the page moves the scroll container, and the sticky box with it
+    let scrolled = hit_test(&page.layout, 5.0, 55.0, (0.0, 50.0), (800.0, 600.0)).unwrap();
+    assert_eq!(scrolled.node.get_attr("id").as_deref(), Some("sticky"));
+}
+
+#[test]
+fn right_to_left_text_offsets_count_from_the_right() {
+    let page = layout(r#"<div><p id="p" dir="rtl">אבגד</p></div>"#, BASE, 800.0, 600.0);
+    let text = page.text_of("p")[0];
+    let y = text.y + text.height / 2.0;
+    let glyphs = text.text.as_ref().unwrap().shape().glyphs;
+    let first = glyphs.last().unwrap().x_advance;
+    let right = text.x + text.width;
+
+    let offset = |x: f32| hit(&page, x, y).text_offset;
+    assert_eq!(offset(right - 0.1), Some(0));
+    assert_eq!(offset(right - first * 0.4), Some(0));
+    assert_eq!(offset(right - first * 0.6), Some(1));
+    assert_eq!(offset(text.x + 0.1), Some(4));
+}
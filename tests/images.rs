@@ -0,0 +1,46 @@
+//! Replaced images: intrinsic sizing, and layout reads the image cache and never fetches
+
+mod common;
+
+use common::layout;
+use rusty_browser::browser::{image_state, preload_images, ImageState};
+
+const BASE: &str = "div { margin: 0; padding: 0 } img { display: block }";
+
+/// A 3×2 PNG
+const PNG: &str = "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAMAAAACCAIAAAASFvFNAAAAEElEQVR4nGP4z8AAQQxwFgBB0gX7h/C5SAAAAABJRU5ErkJggg==";
+
+#[test]
+fn unfetched_images_take_their_attribute_size() {
+    let src = "http://images.invalid/pending.png";
+    let page = layout(
+        &format!(r#"<div><img id="a" src="{}" width="40" height="30"></div>"#, src),
+        BASE,
+        400.0,
+        300.0,
+    );
+    let image = page.by_id("a");
+    assert_eq!((image.width, image.height), (40.0, 30.0));
+    assert!(matches!(image_state(src), ImageState::Pending));
+}
+
+#[test]
+fn data_urls_need_no_preload() {
+    let page = layout(&format!(r#"<div><img id="a" src="{}"></div>"#, PNG), BASE, 400.0, 300.0);
+    let image = page.by_id("a");
+    assert_eq!((image.width, image.height), (3.0, 2.0));
+}
+
+#[test]
+fn preloading_settles_every_image() {
+    let page = layout(r#"<div><img src="http://images.invalid/missing.png"></div>"#, BASE, 400.0, 300.0);
+    preload_images(&page.document);
+    assert!(matches!(image_state("http://images.invalid/missing.png"), ImageState::Failed));
+}
+
+#[test]
+fn one_size_attribute_keeps_the_intrinsic_ratio() {
+    let page = layout(&format!(r#"<div><img id="a" src="{}" width="30"></div>"#, PNG), BASE, 400.0, 300.0);
+    let image = page.by_id("a");
+    assert_eq!((image.width, image.height), (30.0, 20.0));
+}
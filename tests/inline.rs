@@ -0,0 +1,177 @@
+//! Inline formatting: line breaking under `white-space`, `text-align`, `line-height` and
+//! baseline alignment. Text widths depend on the installed fonts, so the geometry is checked
+//! against the widths the layout measured rather than against fixed numbers.
+
+mod common;
+
+use common::{layout, Page};
+
+/// Paragraphs without the default margins and padding
+const BASE: &str = "p { margin: 0; padding: 0; font-size: 16px; line-height: 20px }";
+
+/// The text of each line of the element with `id`, joined across fragments
+fn lines(page: &Page, id: &str) -> Vec<String> {
+    let mut lines: Vec<(f32, String)> = Vec::new();
+    for text in page.text_of(id) {
+        let content = &text.text.as_ref().unwrap().content;
+        match lines.last_mut() {
+            Some((y, line)) if *y == text.y => line.push_str(content),
+            _ => lines.push((text.y, content.clone())),
+        }
+    }
+    lines.into_iter().map(|(_, line)| line.trim_end().to_string()).collect()
+}
+
+/// Left and right edges of the text on the line at `index`
+fn line_extent(page: &Page, id: &str, index: usize) -> (f32, f32) {
+    let texts = page.text_of(id);
+    let mut ys: Vec<f32> = texts.iter().map(|t| t.y).collect();
+    ys.dedup();
+    let line = texts.iter().filter(|t| t.y == ys[index]);
+    line.fold((f32::MAX, f32::MIN), |(left, right), t| (left.min(t.x), right.max(t.x + t.width)))
+}
+
+fn assert_close(actual: f32, expected: f32) {
+    assert!((actual - expected).abs() < 0.01, "{} != {}", actual, expected);
+}
+
+#[test]
+fn normal_text_collapses_spaces_and_wraps() {
+    let page = layout("<p id=\"p\">one   two\nthree</p>", BASE, 800.0, 600.0);
+    assert_eq!(lines(&page, "p"), ["one two three"]);
+
+    let narrow = layout("<p id=\"p\" style=\"width: 1px\">one two three</p>", BASE, 800.0, 600.0);
+    assert_eq!(lines(&narrow, "p"), ["one", "two", "three"]);
+}
+
+#[test]
+fn nowrap_keeps_text_on_one_line() {
+    let page = layout(
+        "<p id=\"p\" style=\"width: 1px; white-space: nowrap\">one two\nthree</p>",
+        BASE,
+        800.0,
+        600.0,
+    );
+    assert_eq!(lines(&page, "p"), ["one two three"]);
+}
+
+#[test]
+fn pre_keeps_spaces_and_breaks_only_at_newlines() {
+    let page = layout(
+        "<p id=\"p\" style=\"width: 1px; white-space: pre\">one   two\nthree</p>",
+        BASE,
+        800.0,
+        600.0,
+    );
+    assert_eq!(lines(&page, "p"), ["one   two", "three"]);
+    assert_eq!(page.by_id("p").height, 40.0);
+}
+
+#[test]
+fn pre_wrap_keeps_spaces_and_wraps() {
+    let page = layout(
+        "<p id=\"p\" style=\"width: 1px; white-space: pre-wrap\">one   two\nthree</p>",
+        BASE,
+        800.0,
+        600.0,
+    );
+    assert_eq!(lines(&page, "p"), ["one", "two", "three"]);
+
+    let wide = layout("<p id=\"p\" style=\"white-space: pre-wrap\">a   b</p>", BASE, 800.0, 600.0);
+    let collapsed = layout("<p id=\"p\">a   b</p>", BASE, 800.0, 600.0);
+    assert!(line_extent(&wide, "p", 0).1 > line_extent(&collapsed, "p", 0).1);
+}
+
+#[test]
+fn pre_line_collapses_spaces_and_keeps_newlines() {
+    let page = layout(
+        "<p id=\"p\" style=\"white-space: pre-line\">one   two\nthree</p>",
+        BASE,
+        800.0,
+        600.0,
+    );
+    assert_eq!(lines(&page, "p"), ["one two", "three"]);
+}
+
+#[test]
+fn text_align_places_the_line_in_the_free_space() {
+    let css = format!("{} p {{ width: 200px }}", BASE);
+    let extent = |align: &str| {
+        let html = format!("<div><p id=\"p\" style=\"text-align: {}\">word</p></div>", align);
+        line_extent(&layout(&html, &css, 800.0, 600.0), "p", 0)
+    };
+    let (left, right) = extent("left");
+    let width = right - left;
+    assert_close(left, extent("start").0);
+    assert_close(extent("right").1, extent("end").1);
+    assert_close(extent("right").1 - left, 200.0);
+    assert_close(extent("center").0 - left, (200.0 - width) / 2.0);
+}
+
+#[test]
+fn justified_lines_fill_the_width_except_the_last() {
+    let page = layout(
+        "<div><p id=\"p\" style=\"width: 120px; text-align: justify\">aa bb cc dd ee ff gg hh ii jj kk ll mm nn oo pp qq rr ss tt uu vv ww xx yy z</p></div>",
+        BASE,
+        800.0,
+        600.0,
+    );
+    let count = lines(&page, "p").len();
+    assert!(count > 2);
+    let (left, _) = line_extent(&page, "p", 0);
+    for index in 0..count - 1 {
+        let (start, end) = line_extent(&page, "p", index);
+        assert_close(start, left);
+        assert_close(end - left, 120.0);
+    }
+    assert!(line_extent(&page, "p", count - 1).1 - left < 120.0 - 1.0);
+}
+
+#[test]
+fn line_height_sets_the_line_pitch() {
+    let page = layout(
+        "<p id=\"px\" style=\"width: 1px; line-height: 30px\">one two</p>\
+         <p id=\"number\" style=\"width: 1px; font-size: 10px; line-height: 2.5\">one two</p>",
+        BASE,
+        800.0,
+        600.0,
+    );
+    let px = page.text_of("px");
+    assert_eq!(px[1].y - px[0].y, 30.0);
+    assert_eq!(page.by_id("px").height, 60.0);
+    let number = page.text_of("number");
+    assert_eq!(number[1].y - number[0].y, 25.0);
+    assert_eq!(page.by_id("number").height, 50.0);
+}
+
+#[test]
+fn mixed_font_sizes_share_a_baseline() {
+    let page = layout(
+        "<p id=\"p\" style=\"line-height: normal\">small <span style=\"font-size: 40px\">Big</span> small</p>",
+        BASE,
+        800.0,
+        600.0,
+    );
+    let texts = page.text_of("p");
+    assert_eq!(texts.len(), 3);
+    let baselines: Vec<f32> = texts.iter().map(|t| t.y + t.text.as_ref().unwrap().baseline).collect();
+    assert_close(baselines[0], baselines[1]);
+    assert_close(baselines[2], baselines[1]);
+    // The big text sets the line's height, and the small text sits lower in it
+    assert!(texts[0].y > texts[1].y);
+    assert!(page.by_id("p").height >= texts[1].height);
+}
+
+#[test]
+fn tag_defaults_keep_inherited_text_properties() {
+    let page = layout(
+        "<div style=\"white-space: pre; text-align: right; line-height: 30px\"><p id=\"p\">a   b</p></div>",
+        BASE.replace("line-height: 20px", "").as_str(),
+        800.0,
+        600.0,
+    );
+    assert_eq!(lines(&page, "p"), ["a   b"]);
+    let p = page.by_id("p");
+    assert_eq!(p.height, 30.0);
+    assert_close(line_extent(&page, "p", 0).1, p.x + p.width);
+}
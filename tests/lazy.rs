@@ -0,0 +1,44 @@
+//! Viewport-lazy layout does work in proportion to the viewport, not the page
+
+use rusty_browser::browser::{
+    build_box_tree, build_layout_tree, compute_styles, parse_html, parse_stylesheet, LayoutBox, LazyLayout,
+};
+
+#[test]
+fn a_long_page_lays_out_only_near_the_viewport() {
+    let paragraphs = 100_000;
+    let html = format!("<div>{}</div>", "<p>Some words in a paragraph</p>".repeat(paragraphs));
+    let document = parse_html(&html);
+    let styled = compute_styles(&document, &[parse_stylesheet("")]);
+    let boxes = build_box_tree(&styled).expect("document generates boxes");
+
+    let mut lazy = LazyLayout::new(200.0);
+    let layout = lazy.layout(&boxes, 800.0, 600.0, 0.0);
+    let stats = lazy.stats();
+    assert!(stats.laid_out < 100, "{:?}", stats);
+    assert!(stats.deferred > paragraphs - 100, "{:?}", stats);
+    assert!(layout.height > 1_000_000.0);
+
+    // Scrolling far down lays out only the newly visible region
+    lazy.layout(&boxes, 800.0, 600.0, 2_000_000.0);
+    assert!(lazy.stats().laid_out < 100, "{:?}", lazy.stats());
+}
+
+#[test]
+fn visible_blocks_are_placed_as_in_a_full_layout() {
+    let html = format!("<div>{}</div>", "<p>Some words in a paragraph</p>".repeat(1_000));
+    let document = parse_html(&html);
+    let styled = compute_styles(&document, &[parse_stylesheet("")]);
+    let boxes = build_box_tree(&styled).expect("document generates boxes");
+
+    let full = build_layout_tree(&boxes, 800.0, 600.0);
+    let lazy = LazyLayout::new(200.0).layout(&boxes, 800.0, 600.0, 0.0);
+    // The paragraphs are the first boxes with siblings
+    let first = |mut layout: &LayoutBox| {
+        while layout.children.len() == 1 {
+            layout = &layout.children[0];
+        }
+        layout.children[..5].iter().map(|p| (p.y, p.height)).collect::<Vec<_>>()
+    };
+    assert_eq!(first(&lazy), first(&full));
+}
@@ -0,0 +1,124 @@
+//! Block layout margins: vertical margin collapsing (CSS 2.1 §8.3.1) and `auto` margins
+
+mod common;
+
+use common::layout;
+
+/// Blocks without the default margins and padding; the root's padding keeps its children's
+/// margins inside it
+const BASE: &str = "div { margin: 0; padding: 0 } #root { padding-top: 1px }";
+
+/// Top border edge of `id` relative to the top of `#root`'s content
+fn top(page: &common::Page, id: &str) -> f32 {
+    page.by_id(id).y - page.by_id("root").y
+}
+
+#[test]
+fn sibling_margins_collapse_to_the_larger() {
+    let page = layout(
+        r#"<div id="root">
+            <div id="a" style="height: 10px; margin-bottom: 20px"></div>
+            <div id="b" style="height: 10px; margin-top: 30px"></div>
+        </div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    assert_eq!(top(&page, "b") - top(&page, "a"), 40.0);
+}
+
+#[test]
+fn negative_margins_are_subtracted() {
+    let page = layout(
+        r#"<div id="root">
+            <div id="a" style="height: 10px; margin-bottom: 20px"></div>
+            <div id="b" style="height: 10px; margin-top: -5px"></div>
+        </div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    assert_eq!(top(&page, "b") - top(&page, "a"), 25.0);
+}
+
+#[test]
+fn first_child_margin_leaves_through_the_parent() {
+    let page = layout(
+        r#"<div id="root">
+            <div id="parent" style="margin-top: 10px">
+                <div id="child" style="height: 5px; margin-top: 25px"></div>
+            </div>
+        </div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    assert_eq!(top(&page, "parent"), 26.0);
+    assert_eq!(top(&page, "child"), 26.0);
+}
+
+#[test]
+fn padding_keeps_margins_apart() {
+    let page = layout(
+        r#"<div id="root">
+            <div id="parent" style="margin-top: 10px; padding-top: 2px">
+                <div id="child" style="height: 5px; margin-top: 25px"></div>
+            </div>
+        </div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    assert_eq!(top(&page, "parent"), 11.0);
+    assert_eq!(top(&page, "child"), 11.0 + 2.0 + 25.0);
+}
+
+#[test]
+fn empty_block_margins_collapse_through() {
+    let page = layout(
+        r#"<div id="root">
+            <div id="a" style="height: 10px; margin-bottom: 10px"></div>
+            <div id="empty" style="margin-top: 30px; margin-bottom: 5px"></div>
+            <div id="b" style="height: 10px; margin-top: 15px"></div>
+        </div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    assert_eq!(top(&page, "b") - top(&page, "a"), 40.0);
+}
+
+#[test]
+fn empty_first_child_margin_is_counted_once() {
+    // All margins adjoin the parent's top margin and collapse into one 20px margin
+    let page = layout(
+        r#"<div id="root">
+            <div id="parent">
+                <div id="empty" style="margin-top: 20px; margin-bottom: 20px"></div>
+                <div id="next" style="height: 10px; margin-top: 10px"></div>
+            </div>
+        </div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    assert_eq!(top(&page, "parent"), 21.0);
+    assert_eq!(top(&page, "next"), 21.0);
+    assert_eq!(page.by_id("parent").height, 10.0);
+}
+
+#[test]
+fn auto_margins_center_a_fixed_width_block() {
+    let page = layout(
+        r#"<div id="root">
+            <div id="centered" style="width: 200px; height: 10px; margin: 0 auto; padding: 0 10px"></div>
+            <div id="right" style="width: 50%; height: 10px; margin-left: auto"></div>
+        </div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    let root = page.by_id("root").x;
+    assert_eq!((page.by_id("centered").x - root, page.by_id("centered").width), (90.0, 220.0));
+    assert_eq!((page.by_id("right").x - root, page.by_id("right").width), (200.0, 200.0));
+}
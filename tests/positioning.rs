@@ -0,0 +1,84 @@
+//! Relative, absolute and fixed positioning, and painting in `z-index` order
+
+mod common;
+
+use common::layout;
+use rusty_browser::browser::{Color, Renderer};
+
+const BASE: &str = "body, div { margin: 0; padding: 0 }";
+
+#[test]
+fn relative_offsets_move_the_box_but_not_its_siblings() {
+    let page = layout(
+        r#"<div id="root">
+            <div id="a" style="position: relative; top: 5px; left: 7px; height: 10px"></div>
+            <div id="b" style="height: 10px"></div>
+        </div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    let (root, a, b) = (page.by_id("root"), page.by_id("a"), page.by_id("b"));
+    assert_eq!((a.x - root.x, a.y - root.y), (7.0, 5.0));
+    assert_eq!(b.y - root.y, 10.0);
+}
+
+#[test]
+fn absolute_boxes_use_the_nearest_positioned_ancestor() {
+    let page = layout(
+        r#"<div id="root">
+            <div id="cb" style="position: relative; margin-left: 20px; width: 200px; height: 100px">
+                <div style="height: 30px"></div>
+                <div id="abs" style="position: absolute; right: 10px; bottom: 5px; width: 50px; height: 20px"></div>
+                <div id="static" style="position: absolute; width: 10px; height: 10px"></div>
+            </div>
+        </div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    let (cb, abs) = (page.by_id("cb"), page.by_id("abs"));
+    assert_eq!((abs.x + abs.width, abs.y + abs.height), (cb.x + 190.0, cb.y + 95.0));
+    // Without offsets, an absolute box stays where it would have been in the flow
+    assert_eq!(page.by_id("static").y, cb.y + 30.0);
+    // Out-of-flow boxes take no space
+    assert_eq!(cb.height, 100.0);
+}
+
+#[test]
+fn fixed_boxes_are_placed_against_the_viewport() {
+    let page = layout(
+        r#"<div id="root" style="position: relative; margin: 40px">
+            <div id="fixed" style="position: fixed; right: 0; bottom: 0; width: 30px; height: 20px"></div>
+        </div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    let fixed = page.by_id("fixed");
+    assert_eq!((fixed.x, fixed.y), (370.0, 280.0));
+}
+
+#[test]
+fn higher_z_index_paints_on_top() {
+    let page = layout(
+        r#"<div>
+            <div style="position: absolute; top: 0; left: 0; width: 20px; height: 20px; background: blue; z-index: 2"></div>
+            <div style="position: absolute; top: 0; left: 0; width: 20px; height: 20px; background: red; z-index: 1"></div>
+            <div style="height: 40px; background: green"></div>
+            <div style="position: absolute; top: 30px; left: 0; width: 20px; height: 20px; background: red; z-index: -1"></div>
+        </div>"#,
+        BASE,
+        100.0,
+        100.0,
+    );
+    let mut renderer = Renderer::new(100, 100);
+    renderer.clear(Color(255, 255, 255, 255));
+    renderer.render_box(&page.layout);
+    // Pixels are premultiplied ARGB
+    let pixel = |x: usize, y: usize| renderer.get_data()[y * 100 + x] & 0xffffff;
+    assert_eq!(pixel(10, 10), 0x0000ff);
+    // A negative z-index paints below in-flow content
+    assert_eq!(pixel(10, 35), 0x00ff00);
+    assert_eq!(pixel(10, 45), 0xff0000);
+}
@@ -0,0 +1,99 @@
+//! Printing: the page box from `@page`, page breaks, and PDF output
+
+use rusty_browser::browser::{
+    build_box_tree, compute_styles, edges, paginate, parse_html, parse_stylesheet, render_pdf, write_pdf, Page,
+    PageSetup, PaginatedLayout,
+};
+
+const BASE: &str = "div { margin: 0; padding: 0 }";
+
+/// A 200×250 page with 25px margins: a 150×200 page area
+fn small_page() -> PageSetup {
+    PageSetup {
+        width: 200.0,
+        height: 250.0,
+        margin: edges(25.0),
+    }
+}
+
+fn print(html: &str, css: &str) -> PaginatedLayout {
+    let document = parse_html(html);
+    let styled = compute_styles(&document, &[parse_stylesheet(css)]);
+    paginate(&build_box_tree(&styled).expect("document generates boxes"), small_page())
+}
+
+fn bands(paginated: &PaginatedLayout) -> Vec<(f32, f32)> {
+    paginated.pages.iter().map(|&Page { top, bottom }| (top, bottom)).collect()
+}
+
+#[test]
+fn page_rules_set_size_and_margins() {
+    let sheet = parse_stylesheet("@page { size: 400px 300px; margin: 10px 20px } @page { margin-left: 5% }");
+    let setup = PageSetup::from_stylesheets(&[sheet]);
+    assert_eq!((setup.width, setup.height), (400.0, 300.0));
+    let margin = setup.margin;
+    assert_eq!((margin.top, margin.right, margin.bottom, margin.left), (10.0, 20.0, 10.0, 20.0));
+    assert_eq!(setup.content_size(), (360.0, 280.0));
+}
+
+#[test]
+fn named_page_sizes_turn_for_landscape() {
+    let setup = PageSetup::from_stylesheets(&[parse_stylesheet("@page { size: a5 landscape }")]);
+    let mm = 96.0 / 25.4;
+    assert!((setup.width - 210.0 * mm).abs() < 0.01);
+    assert!((setup.height - 148.0 * mm).abs() < 0.01);
+}
+
+#[test]
+fn pages_break_between_blocks() {
+    let block = r#"<div style="height: 90px"></div>"#;
+    let paginated = print(&format!(r#"<div>{}</div>"#, block.repeat(5)), BASE);
+    assert_eq!(bands(&paginated), [(0.0, 180.0), (180.0, 360.0), (360.0, 450.0)]);
+}
+
+#[test]
+fn forced_breaks_start_a_new_page() {
+    let paginated = print(
+        r#"<div>
+            <div style="height: 50px"></div>
+            <div style="height: 50px; break-before: page"></div>
+            <div style="height: 50px"></div>
+        </div>"#,
+        BASE,
+    );
+    assert_eq!(bands(&paginated), [(0.0, 50.0), (50.0, 150.0)]);
+}
+
+#[test]
+fn avoided_breaks_move_to_an_earlier_opportunity() {
+    let paginated = print(
+        r#"<div>
+            <div style="height: 90px"></div>
+            <div style="break-inside: avoid">
+                <div style="height: 60px"></div>
+                <div style="height: 60px"></div>
+            </div>
+        </div>"#,
+        BASE,
+    );
+    assert_eq!(bands(&paginated), [(0.0, 90.0), (90.0, 210.0)]);
+}
+
+#[test]
+fn pdf_has_a_page_per_page() {
+    let paginated = print(r#"<div><div style="height: 300px">Printed text</div></div>"#, BASE);
+    assert_eq!(paginated.pages.len(), 2);
+    let pdf = render_pdf(&paginated);
+    let text = String::from_utf8_lossy(&pdf);
+    assert!(text.starts_with("%PDF-"));
+    assert!(text.trim_end().ends_with("%%EOF"));
+    assert!(text.contains("/Count 2"));
+    // Page boxes are in points
+    assert_eq!(text.matches("/MediaBox [0 0 150 187.5]").count(), 2);
+    assert!(text.contains("/FontFile2"), "text is drawn in an embedded font");
+
+    let path = std::env::temp_dir().join(format!("rusty-browser-print-{}.pdf", std::process::id()));
+    write_pdf(&paginated, path.to_str().unwrap()).unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), pdf);
+    std::fs::remove_file(path).unwrap();
+}
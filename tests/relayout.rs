@@ -0,0 +1,91 @@
+//! Incremental relayout reuses clean blocks without losing what they contribute around them
+
+use rusty_browser::browser::{
+    build_box_tree, build_layout_tree, compute_styles, parse_html, parse_stylesheet, relayout, restyle, LayoutBox,
+    LayoutCache, Node,
+};
+use std::rc::Rc;
+
+const CSS: &str = "div { margin: 0; padding: 0 } p { margin: 0; padding: 0 }
+    #float { float: left; width: 100px; height: 100px }";
+
+fn find_id(node: &Rc<Node>, id: &str) -> Option<Rc<Node>> {
+    if node.get_attr("id").as_deref() == Some(id) {
+        return Some(node.clone());
+    }
+    node.children().iter().find_map(|child| find_id(child, id))
+}
+
+/// Left edges of the text fragments of the element with `id`
+fn text_left_edges(layout: &LayoutBox, id: &str) -> Vec<f32> {
+    fn walk(layout: &LayoutBox, inside: bool, id: &str, out: &mut Vec<f32>) {
+        let dom = layout.dom.as_ref().and_then(|dom| dom.upgrade());
+        let inside = inside || dom.is_some_and(|node| node.get_attr("id").as_deref() == Some(id));
+        if inside && layout.text.is_some() {
+            out.push(layout.x);
+        }
+        layout.children.iter().for_each(|child| walk(child, inside, id, out));
+    }
+    let mut out = Vec::new();
+    walk(layout, false, id, &mut out);
+    out
+}
+
+#[test]
+fn only_changed_blocks_are_laid_out_again() {
+    let paragraphs: String = (0..20)
+        .map(|i| format!(r#"<p id="p{}">Paragraph {}</p>"#, i, i))
+        .collect();
+    let document = parse_html(&format!(r#"<div id="root">{}</div>"#, paragraphs));
+    let sheets = [parse_stylesheet(CSS)];
+    let styled = compute_styles(&document, &sheets);
+    let mut cache = LayoutCache::new();
+    let (_, first) = relayout(&build_box_tree(&styled).unwrap(), 800.0, 600.0, &mut cache);
+    assert_eq!(first.reused, 0);
+
+    find_id(&document, "p7").unwrap().children()[0].set_text("Changed");
+    let (styled, _) = restyle(&document, &styled, &sheets);
+    let boxes = build_box_tree(&styled).unwrap();
+    let (second, stats) = relayout(&boxes, 800.0, 600.0, &mut cache);
+    assert!(stats.reused >= 19, "{:?}", stats);
+    assert!(stats.relaid < first.relaid, "{:?}", stats);
+    // The result is the same as laying the page out from scratch
+    let full = build_layout_tree(&boxes, 800.0, 600.0);
+    assert_eq!(text_left_edges(&second, "root"), text_left_edges(&full, "root"));
+    assert_eq!(second.height, full.height);
+}
+
+#[test]
+fn text_wraps_around_a_cached_blocks_float() {
+    let document = parse_html(
+        r#"<div id="root">
+            <div id="holder"><div id="float"></div>Beside</div>
+            <p id="wrapped">Text next to the float</p>
+            <p id="other">Unrelated</p>
+        </div>"#,
+    );
+    let sheets = [parse_stylesheet(CSS)];
+    let styled = compute_styles(&document, &sheets);
+    let mut cache = LayoutCache::new();
+    let (first, _) = relayout(&build_box_tree(&styled).unwrap(), 800.0, 600.0, &mut cache);
+    assert!(text_left_edges(&first, "wrapped").iter().all(|&x| x >= 100.0));
+
+    find_id(&document, "other").unwrap().children()[0].set_text("Changed");
+    let (styled, _) = restyle(&document, &styled, &sheets);
+    let (second, _) = relayout(&build_box_tree(&styled).unwrap(), 800.0, 600.0, &mut cache);
+    assert_eq!(text_left_edges(&second, "wrapped"), text_left_edges(&first, "wrapped"));
+}
+
+#[test]
+fn relayout_cleans_nodes_without_boxes() {
+    let document = parse_html(r#"<div id="root"><div id="hidden" style="display: none"><p>Gone</p></div></div>"#);
+    let sheets = [parse_stylesheet(CSS)];
+    let styled = compute_styles(&document, &sheets);
+    let hidden = find_id(&document, "hidden").unwrap();
+    hidden.children()[0].children()[0].set_text("Still gone");
+    assert!(hidden.layout_dirty().needs_relayout());
+
+    let (styled, _) = restyle(&document, &styled, &sheets);
+    relayout(&build_box_tree(&styled).unwrap(), 800.0, 600.0, &mut LayoutCache::new());
+    assert!(!hidden.layout_dirty().needs_relayout());
+}
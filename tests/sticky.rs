@@ -0,0 +1,70 @@
+//! Overflow clipping, scroll containers, and sticky positioning against the viewport and
+//! against scroll containers
+
+mod common;
+
+use common::{layout, Page};
+use rusty_browser::browser::{Color, Renderer};
+
+const BASE: &str = "div { margin: 0; padding: 0 }";
+
+/// Paint `page` scrolled down by `scroll` and return the color at viewport point (`x`, `y`)
+fn pixel(page: &Page, x: usize, y: usize, scroll: f32) -> u32 {
+    let mut renderer = Renderer::new(400, 300);
+    renderer.clear(Color(255, 255, 255, 255));
+    renderer.scroll_to(0.0, scroll);
+    renderer.render_box(&page.layout);
+    // Pixels are premultiplied ARGB
+    renderer.get_data()[y * 400 + x] & 0xffffff
+}
+
+#[test]
+fn overflow_is_clipped_to_the_padding_box() {
+    let page = layout(
+        r#"<div id="root">
+            <div style="overflow: hidden; height: 50px"><div style="height: 100px; background: red"></div></div>
+        </div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    assert_eq!(pixel(&page, 5, 45, 0.0), 0xff0000);
+    assert_eq!(pixel(&page, 5, 55, 0.0), 0xffffff);
+}
+
+#[test]
+fn sticky_boxes_stick_to_the_viewport() {
+    let page = layout(
+        r#"<div id="root">
+            <div style="height: 100px; background: red"></div>
+            <div style="position: sticky; top: 0; height: 20px; background: green"></div>
+            <div style="height: 1000px; background: blue"></div>
+        </div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    assert_eq!(pixel(&page, 5, 5, 0.0), 0xff0000);
+    assert_eq!(pixel(&page, 5, 5, 300.0), 0x00ff00);
+}
+
+#[test]
+fn sticky_boxes_stick_to_their_scroll_container() {
+    let mut page = layout(
+        r#"<div id="root">
+            <div style="height: 100px; background: red"></div>
+            <div id="scroller" style="overflow: auto; height: 100px">
+                <div style="position: sticky; top: 0; height: 20px; background: green"></div>
+                <div style="height: 500px; background: blue"></div>
+            </div>
+        </div>"#,
+        BASE,
+        400.0,
+        300.0,
+    );
+    assert!(page.layout.scroll_at(5.0, 150.0, 0.0, 50.0));
+    assert_eq!(pixel(&page, 5, 105, 0.0), 0x00ff00);
+    assert_eq!(pixel(&page, 5, 125, 0.0), 0x0000ff);
+    // Scrolling the page moves the scroll container, and the box with it
+    assert_eq!(pixel(&page, 5, 55, 50.0), 0x00ff00);
+}
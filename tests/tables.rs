@@ -0,0 +1,131 @@
+//! Table layout (CSS 2.1 §17): slotting cells into rows and columns, captions, the fixed and
+//! automatic column algorithms, border spacing and anonymous table boxes
+
+mod common;
+
+use common::layout;
+
+/// No spacing or padding, and cells holding blocks of a known size
+const BASE: &str = "div { margin: 0; padding: 0 } table { border-spacing: 0 } td { padding: 0 } \
+                    .box { width: 20px; height: 10px }";
+
+const BOX: &str = r#"<div class="box"></div>"#;
+
+/// Position of the element with `id` relative to the element with `origin`
+fn at(page: &common::Page, id: &str, origin: &str) -> (f32, f32) {
+    let (of, from) = (page.by_id(id), page.by_id(origin));
+    (of.x - from.x, of.y - from.y)
+}
+
+#[test]
+fn spanning_cells_take_the_slots_they_cover() {
+    let html = format!(
+        r#"<table id="t">
+            <tr><td id="a" colspan="2">{b}</td><td id="b" rowspan="2">{b}</td></tr>
+            <tr><td id="c">{b}</td><td id="d">{b}</td><td id="e">{b}</td></tr>
+        </table>"#,
+        b = BOX
+    );
+    let page = layout(&html, BASE, 800.0, 600.0);
+    assert_eq!(at(&page, "a", "t"), (0.0, 0.0));
+    assert_eq!(page.by_id("a").width, 40.0);
+    assert_eq!(at(&page, "b", "t"), (40.0, 0.0));
+    assert_eq!(page.by_id("b").height, 20.0);
+    assert_eq!(at(&page, "c", "t"), (0.0, 10.0));
+    assert_eq!(at(&page, "d", "t"), (20.0, 10.0));
+    // The slot under `b` is taken, so `e` moves past it
+    assert_eq!(at(&page, "e", "t"), (60.0, 10.0));
+}
+
+#[test]
+fn rowspan_zero_spans_the_rest_of_its_group() {
+    let html = format!(
+        r#"<table id="t">
+            <tbody>
+                <tr><td id="all" rowspan="0">{b}</td><td>{b}</td></tr>
+                <tr><td id="second">{b}</td></tr>
+                <tr><td id="third">{b}</td></tr>
+            </tbody>
+            <tbody><tr><td id="next">{b}</td></tr></tbody>
+        </table>"#,
+        b = BOX
+    );
+    let page = layout(&html, BASE, 800.0, 600.0);
+    assert_eq!(page.by_id("all").height, 30.0);
+    assert_eq!(at(&page, "second", "t"), (20.0, 10.0));
+    assert_eq!(at(&page, "third", "t"), (20.0, 20.0));
+    assert_eq!(at(&page, "next", "t"), (0.0, 30.0));
+}
+
+#[test]
+fn captions_sit_above_and_below_the_table() {
+    let html = format!(
+        r#"<div id="root"><table>
+            <caption id="bottom" style="caption-side: bottom">{b}</caption>
+            <caption id="top">{b}</caption>
+            <tr><td id="cell" style="width: 60px">{b}</td></tr>
+        </table></div>"#,
+        b = BOX
+    );
+    let page = layout(&html, BASE, 800.0, 600.0);
+    let (top, cell, bottom) = (page.by_id("top"), page.by_id("cell"), page.by_id("bottom"));
+    assert_eq!(top.y, page.by_id("root").y);
+    assert_eq!(cell.y, top.y + top.height);
+    assert_eq!(bottom.y, cell.y + cell.height);
+    // Captions are as wide as the table
+    assert_eq!((top.width, bottom.width), (60.0, 60.0));
+    assert_eq!(page.by_id("root").height, 30.0);
+}
+
+#[test]
+fn fixed_layout_sizes_columns_from_the_first_row() {
+    let html = r#"<table style="width: 200px">
+        <tr><td id="a" style="width: 50px"></td><td id="b"></td></tr>
+        <tr><td><div style="width: 120px"></div></td><td></td></tr>
+    </table>"#;
+    let fixed = layout(html, &format!("{} table {{ table-layout: fixed }}", BASE), 800.0, 600.0);
+    assert_eq!((fixed.by_id("a").width, fixed.by_id("b").width), (50.0, 150.0));
+
+    // The automatic layout makes room for the wide content of the second row
+    let auto = layout(html, BASE, 800.0, 600.0);
+    assert!(auto.by_id("a").width >= 120.0);
+    assert_eq!(auto.by_id("a").width + auto.by_id("b").width, 200.0);
+}
+
+#[test]
+fn border_spacing_separates_cells_unless_borders_collapse() {
+    let html = format!(
+        r#"<table id="t"><tr><td id="a">{b}</td><td id="b">{b}</td></tr></table>"#,
+        b = BOX
+    );
+    let css = format!("{} table {{ border-spacing: 10px 4px }} td {{ border: 2px solid black }}", BASE);
+    let separate = layout(&html, &css, 800.0, 600.0);
+    assert_eq!(at(&separate, "a", "t"), (10.0, 4.0));
+    assert_eq!(at(&separate, "b", "t"), (10.0 + 24.0 + 10.0, 4.0));
+
+    // Collapsed borders overlap and the spacing is ignored
+    let collapsed = layout(&html, &format!("{} table {{ border-collapse: collapse }}", css), 800.0, 600.0);
+    assert_eq!(at(&collapsed, "a", "t"), (0.0, 0.0));
+    assert_eq!(at(&collapsed, "b", "t"), (22.0, 0.0));
+}
+
+#[test]
+fn missing_table_boxes_are_generated() {
+    // Cells outside a row and table get an anonymous row and table around them
+    let cells = format!(
+        r#"<div id="root"><div id="a" style="display: table-cell">{b}</div><div id="b" style="display: table-cell">{b}</div></div>"#,
+        b = BOX
+    );
+    let page = layout(&cells, BASE, 800.0, 600.0);
+    assert_eq!(at(&page, "b", "a"), (20.0, 0.0));
+    assert_eq!(page.by_id("root").height, 10.0);
+
+    // Content directly in a row goes in an anonymous cell beside the real ones
+    let row = format!(
+        r#"<table><tr id="r"><td id="a">{b}</td><div id="loose" class="box"></div></tr></table>"#,
+        b = BOX
+    );
+    let page = layout(&row, BASE, 800.0, 600.0);
+    assert_eq!(at(&page, "loose", "a"), (20.0, 0.0));
+    assert_eq!(page.by_id("r").width, 40.0);
+}
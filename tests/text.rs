@@ -0,0 +1,94 @@
+//! Text measurement: layout reserves the width the renderer draws
+
+mod common;
+
+use common::layout;
+use rusty_browser::browser::{hit_test, LayoutBox};
+
+fn text_boxes<'a>(layout: &'a LayoutBox, out: &mut Vec<&'a LayoutBox>) {
+    if layout.text.is_some() {
+        out.push(layout);
+    }
+    for child in &layout.children {
+        text_boxes(child, out);
+    }
+}
+
+/// Every text box is as wide as its text shaped for painting
+fn assert_widths_match(layout: &LayoutBox) {
+    let mut boxes = Vec::new();
+    text_boxes(layout, &mut boxes);
+    assert!(!boxes.is_empty());
+    for text_box in boxes {
+        let text = text_box.text.as_ref().unwrap();
+        let painted = text.shape().width;
+        assert!(
+            (text_box.width - painted).abs() < 0.01,
+            "{:?}: laid out {} wide, painted {} wide",
+            text.content,
+            text_box.width,
+            painted
+        );
+    }
+}
+
+#[test]
+fn merged_words_take_their_painted_width() {
+    // Hyphens are break opportunities, and fonts kern them against the next letter
+    let page = layout(
+        r#"<div><p id="p">Ta T-To V-Vo Y-Yo A-T AV</p></div>"#,
+        "p { font-size: 37px; font-family: serif }",
+        2000.0,
+        600.0,
+    );
+    let texts = page.text_of("p");
+    assert_eq!(texts.len(), 1, "one line is painted as one text box");
+    assert_widths_match(&page.layout);
+}
+
+#[test]
+fn outside_markers_are_as_wide_as_their_text() {
+    let page = layout(
+        r#"<ol start="98"><li>a</li><li>b</li><li>c</li></ol>"#,
+        "li { font-size: 20px }",
+        800.0,
+        600.0,
+    );
+    assert_widths_match(&page.layout);
+}
+
+#[test]
+fn overridden_left_to_right_text_is_shaped_left_to_right() {
+    let page = layout(r#"<div><p id="p"><bdo dir="ltr">אבגד</bdo></p></div>"#, "", 800.0, 600.0);
+    let text = page.text_of("p")[0];
+    let clusters: Vec<usize> = text.text.as_ref().unwrap().shape().glyphs.iter().map(|g| g.cluster).collect();
+    assert!(clusters.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", clusters);
+
+    // The first letter is leftmost, so the caret goes before it
+    let hit = hit_test(&page.layout, text.x + 1.0, text.y + 1.0, (0.0, 0.0), (800.0, 600.0)).unwrap();
+    assert_eq!(hit.text_offset, Some(0));
+    assert_widths_match(&page.layout);
+}
+
+#[test]
+fn logical_floats_and_clearance_follow_the_direction() {
+    let css = "div { margin: 0; padding: 0; width: 200px } .f { width: 50px; height: 10px }";
+    let floats = r#"<div id="root">
+        <div id="start" class="f" style="float: inline-start"></div>
+        <div id="end" class="f" style="float: inline-end"></div>
+    </div>"#;
+    let ltr = layout(floats, css, 800.0, 600.0);
+    assert_eq!((ltr.by_id("start").x, ltr.by_id("end").x), (0.0, 150.0));
+    let rtl = layout(&floats.replace(r#"id="root""#, r#"id="root" dir="rtl""#), css, 800.0, 600.0);
+    assert_eq!((rtl.by_id("start").x, rtl.by_id("end").x), (150.0, 0.0));
+
+    // `inline-end` clears the right float in left-to-right content and the left one otherwise
+    let clear = r#"<div id="root">
+        <div class="f" style="float: left; height: 20px"></div>
+        <div class="f" style="float: right"></div>
+        <div id="clear" style="clear: inline-end"></div>
+    </div>"#;
+    assert_eq!(layout(clear, css, 800.0, 600.0).by_id("clear").y, 10.0);
+    let rtl = layout(&clear.replace(r#"id="root""#, r#"id="root" dir="rtl""#), css, 800.0, 600.0);
+    assert_eq!(rtl.by_id("clear").y, 20.0);
+}
@@ -0,0 +1,67 @@
+//! Vertical writing modes: block flow direction, and physical margins and padding land on their physical sides
+
+mod common;
+
+use common::layout;
+
+const BASE: &str = "div { margin: 0; padding: 0 } p { margin: 0; padding: 0 }";
+
+#[test]
+fn vertical_boxes_keep_physical_padding() {
+    let page = layout(
+        r#"<div id="root">
+            <div id="v" style="writing-mode: vertical-rl; height: 200px; padding-top: 3px; padding-right: 11px">
+                <p id="a">Some vertical text</p>
+            </div>
+        </div>"#,
+        BASE,
+        600.0,
+        400.0,
+    );
+    let (v, a) = (page.by_id("v"), page.by_id("a"));
+    assert_eq!(a.y - v.y, 3.0);
+    assert_eq!((v.x + v.width) - (a.x + a.width), 11.0);
+    assert_eq!(v.height, 203.0);
+}
+
+#[test]
+fn nested_orthogonal_boxes_keep_physical_padding() {
+    let page = layout(
+        r#"<div id="root">
+            <div id="v" style="writing-mode: vertical-rl; height: 200px">
+                <div id="h" style="writing-mode: horizontal-tb; width: 80px; padding-left: 5px; padding-top: 7px">
+                    <p id="a">Across</p>
+                    <div id="w" style="writing-mode: vertical-lr; height: 50px; padding-left: 2px">
+                        <p id="b">Down</p>
+                    </div>
+                </div>
+            </div>
+        </div>"#,
+        BASE,
+        600.0,
+        400.0,
+    );
+    let (h, a) = (page.by_id("h"), page.by_id("a"));
+    assert_eq!((a.x - h.x, a.y - h.y), (5.0, 7.0));
+    assert_eq!(page.by_id("b").x - page.by_id("w").x, 2.0);
+}
+
+#[test]
+fn vertical_rl_blocks_stack_leftward_and_vertical_lr_rightward() {
+    let html = r#"<div id="root">
+        <div id="v" style="height: 100px">
+            <div id="a" style="width: 10px"></div>
+            <div id="b" style="width: 20px"></div>
+        </div>
+    </div>"#;
+    let rl = layout(html, &format!("{} #v {{ writing-mode: vertical-rl }}", BASE), 600.0, 400.0);
+    let (v, a, b) = (rl.by_id("v"), rl.by_id("a"), rl.by_id("b"));
+    assert_eq!(v.width, 30.0);
+    assert_eq!((a.x + a.width, b.x + b.width), (v.x + v.width, v.x + v.width - 10.0));
+    // Blocks fill the line's extent, which is the container's height
+    assert_eq!((a.y, a.height), (v.y, 100.0));
+
+    let lr = layout(html, &format!("{} #v {{ writing-mode: vertical-lr }}", BASE), 600.0, 400.0);
+    let (v, a, b) = (lr.by_id("v"), lr.by_id("a"), lr.by_id("b"));
+    assert_eq!((a.x, b.x), (v.x, v.x + 10.0));
+}